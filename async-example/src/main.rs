@@ -0,0 +1,254 @@
+// Runs the Wayland queue inside a tokio runtime, the way an application that is async anyway
+// would integrate it.
+//
+// The connection fd is wrapped in an `AsyncFd` and a task reads, dispatches and flushes like the
+// poll loop of wayland-app-common, awaiting the socket instead of blocking in `poll`. The frame
+// callbacks pace the drawing as usual, a `tokio::time::interval` only redraws the windows that
+// have none in flight. An unrelated task sends a new clear color every second over a channel.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    io::ErrorKind,
+    os::fd::{AsFd, AsRawFd},
+    time::Duration,
+};
+
+use smithay_client_toolkit::shell::{xdg::window::WindowConfigure, WaylandSurface};
+use tokio::{
+    io::{unix::AsyncFd, Interest},
+    sync::mpsc,
+    time::{self, Instant, MissedTickBehavior},
+};
+use wayland_app_common::{App, AppHandler, BaseApp, ConnectionError, Options, ShmBuffers};
+use wayland_client::{
+    backend::{ObjectId, WaylandError},
+    EventQueue, QueueHandle,
+};
+
+/// The clear colors the color task goes through, one a second.
+const COLORS: [[f32; 3]; 4] = [[0.8, 0.2, 0.2], [0.2, 0.6, 0.3], [0.2, 0.3, 0.8], [0.8, 0.6, 0.1]];
+
+// One thread is enough, the Wayland task never blocks it
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let options = Options::from_args();
+    let mut logger = env_logger::Builder::from_default_env();
+    if let Some(level) = options.log_level {
+        logger.filter_level(level);
+    }
+    logger.init();
+
+    let (sender, colors) = mpsc::channel(1);
+    tokio::spawn(change_colors(sender));
+
+    // GitHub does not let projects use the `org.github` domain but the `io.github` domain is
+    // fine.
+    let (mut app, event_queue) = wayland_app_common::init_with_options(
+        "async wayland window",
+        "io.github.smithay.client-toolkit.AsyncExample",
+        AsyncApp {
+            windows: HashMap::new(),
+            color: COLORS[0],
+        },
+        options,
+    );
+    let result = run(&mut app, event_queue, colors).await;
+    app.finish();
+
+    // Buffers have to go before the pool and the window
+    drop(app.handler);
+    drop(app.windows);
+    if let Err(err) = result {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
+
+/// The unrelated task, it knows nothing of Wayland. Ends once the Wayland task is gone.
+async fn change_colors(sender: mpsc::Sender<[f32; 3]>) {
+    let mut seconds = time::interval(Duration::from_secs(1));
+    // The first tick is right away, the first color is already on screen
+    seconds.tick().await;
+    for color in COLORS.iter().cycle().skip(1) {
+        seconds.tick().await;
+        if sender.send(*color).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Dispatches the Wayland events until the app exits, waiting for the socket, the redraw
+/// interval, the colors and Ctrl-C all at once.
+async fn run(
+    app: &mut App<AsyncApp>,
+    mut event_queue: EventQueue<App<AsyncApp>>,
+    mut colors: mpsc::Receiver<[f32; 3]>,
+) -> Result<(), ConnectionError> {
+    let qh = event_queue.handle();
+    let conn = app.connection().clone();
+
+    // The fd stays owned by the connection, which outlives the AsyncFd
+    let fd = AsyncFd::with_interest(
+        conn.as_fd().as_raw_fd(),
+        Interest::READABLE | Interest::WRITABLE,
+    )
+    .map_err(|err| ConnectionError::wayland(&conn, WaylandError::Io(err)))?;
+
+    let mut redraws = time::interval(app.shared.options.redraw_interval);
+    redraws.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let exit_at = app.shared.options.exit_after_seconds.map(|after| Instant::now() + after);
+
+    loop {
+        // Events read on an earlier round may still be queued, they must be dispatched before
+        // waiting or they'd wait for the next unrelated wake up.
+        if let Err(err) = event_queue.dispatch_pending(app) {
+            return Err(ConnectionError::dispatch(&conn, err));
+        }
+        app.after_dispatch(&qh);
+        if app.shared.exit {
+            println!("exiting example");
+            return Ok(());
+        }
+
+        // With a full socket buffer the rest is sent once the socket is writable again
+        let mut wait_for_writable = false;
+        match conn.flush() {
+            Ok(()) => {}
+            Err(WaylandError::Io(err)) if err.kind() == ErrorKind::WouldBlock => {
+                wait_for_writable = true;
+            }
+            Err(err) => return Err(ConnectionError::wayland(&conn, err)),
+        }
+
+        // None means events arrived in the meantime, go back and dispatch them first
+        let Some(guard) = event_queue.prepare_read() else {
+            continue;
+        };
+
+        tokio::select! {
+            readable = fd.readable() => {
+                let mut readable = readable
+                    .map_err(|err| ConnectionError::wayland(&conn, WaylandError::Io(err)))?;
+                match guard.read() {
+                    Ok(_) => {}
+                    // Everything there was is read, wait for the socket to become readable again
+                    Err(WaylandError::Io(err)) if err.kind() == ErrorKind::WouldBlock => {
+                        readable.clear_ready();
+                    }
+                    Err(err) => return Err(ConnectionError::wayland(&conn, err)),
+                }
+            }
+            writable = fd.writable(), if wait_for_writable => {
+                // Flushed again on the next round
+                drop(guard);
+                if let Ok(mut writable) = writable {
+                    writable.clear_ready();
+                }
+            }
+            _ = redraws.tick() => {
+                drop(guard);
+                app.redraw_idle_windows(&qh);
+            }
+            Some(color) = colors.recv() => {
+                drop(guard);
+                log::debug!("clear color {color:?}");
+                app.handler.color = color;
+                // The windows draw it with their next frame callback
+            }
+            _ = tokio::signal::ctrl_c() => {
+                drop(guard);
+                println!("interrupted");
+                app.shared.exit = true;
+            }
+            _ = time::sleep_until(exit_at.unwrap_or_else(Instant::now)), if exit_at.is_some() => {
+                drop(guard);
+                app.shared.exit = true;
+            }
+        }
+    }
+}
+
+struct AsyncApp {
+    /// The buffers of each window, keyed by [`BaseApp::id`].
+    windows: HashMap<ObjectId, ShmBuffers>,
+    /// The clear color, the latest one the color task sent.
+    color: [f32; 3],
+}
+
+impl AppHandler for AsyncApp {
+    fn configure(
+        &mut self,
+        base: &mut BaseApp,
+        _qh: &QueueHandle<App<Self>>,
+        configure: WindowConfigure,
+        _serial: u32,
+    ) {
+        let (width, height) = base.configured_size(&configure);
+
+        // Create the pool on the first configure of the window
+        if let Entry::Vacant(entry) = self.windows.entry(base.id()) {
+            base.width = width;
+            base.height = height;
+            match ShmBuffers::new(base) {
+                Ok(buffers) => {
+                    entry.insert(buffers);
+                }
+                Err(err) => {
+                    eprintln!("no wl_shm pool: {err}");
+                    base.close = true;
+                }
+            }
+        } else if base.set_size(width, height) {
+            self.resize(base);
+        }
+    }
+
+    fn resize(&mut self, _base: &mut BaseApp) {
+        // The buffers of the old size are dropped with the next draw, their slots go back to
+        // the pool
+    }
+
+    fn draw(&mut self, base: &mut BaseApp) {
+        let (width, height) = base.buffer_size();
+        // The bar moves with the frame callbacks, the color changes with the color task
+        let bar_x = base.time / 4 % width;
+        // Dialogs and `--self-check` are all bar
+        let bar = if base.plain() { 0..width } else { bar_x..bar_x + (width / 10).max(1) };
+        let background = self.color.map(|c| (c * 255.0) as u8);
+        let [red, green, blue] = base.clear_color().map(|c| (c * 255.0) as u8);
+
+        let Some(buffers) = self.windows.get_mut(&base.id()) else {
+            return;
+        };
+        let surface = base.window().wl_surface();
+        let frame = match buffers.acquire(width, height) {
+            Ok(frame) => frame,
+            Err(err) => {
+                // Commit anyway, so the frame callback request goes out and the window keeps
+                // trying
+                log::warn!("no buffer for window {}: {err}", base.id());
+                surface.commit();
+                return;
+            }
+        };
+        for (offset, pixel) in frame.canvas.chunks_exact_mut(4).enumerate() {
+            let [r, g, b] = if bar.contains(&(offset as u32 % width)) {
+                [red, green, blue]
+            } else {
+                background
+            };
+            // Argb8888 is stored little endian, so the bytes are in BGRA order
+            pixel.copy_from_slice(&[b, g, r, 255]);
+        }
+
+        surface.damage_buffer(0, 0, width as i32, height as i32);
+        if let Err(err) = frame.buffer.attach_to(surface) {
+            log::warn!("failed to attach the buffer of window {}: {err}", base.id());
+        }
+        surface.commit();
+    }
+
+    fn window_closed(&mut self, base: &mut BaseApp) {
+        self.windows.remove(&base.id());
+    }
+}
@@ -0,0 +1,401 @@
+//! What a tablet draws, see [`BaseApp::take_strokes`]. The strokes go into a texture through a
+//! framebuffer object, so they stay without being drawn again, and the texture is blended over
+//! the scene every frame.
+//!
+//! The texture is upright like the content area and has as many pixels as the content area has
+//! in the buffer, so strokes are as sharp as the rest. When the content area changes size or
+//! scale the strokes move into a new texture at the same logical position from the top left,
+//! what no longer fits is cut off for good.
+
+use glow::{
+    Context, HasContext, NativeBuffer, NativeFramebuffer, NativeProgram, NativeTexture,
+    NativeUniformLocation, NativeVertexArray, PixelUnpackData, ARRAY_BUFFER, BLEND,
+    CLAMP_TO_EDGE, COLOR_ATTACHMENT0, COLOR_BUFFER_BIT, FLOAT, FRAMEBUFFER, FRAMEBUFFER_COMPLETE,
+    LINEAR, MAX_TEXTURE_SIZE, ONE, ONE_MINUS_SRC_ALPHA, RGBA, STATIC_DRAW, STREAM_DRAW, TEXTURE0,
+    TEXTURE_2D, TEXTURE_MAG_FILTER, TEXTURE_MIN_FILTER, TEXTURE_WRAP_S, TEXTURE_WRAP_T,
+    TRIANGLES, TRIANGLE_STRIP, UNSIGNED_BYTE,
+};
+use wayland_app_common::{BaseApp, GlInitError, StrokeSegment};
+
+use crate::{
+    shader::{link_program, Dialect},
+    srgb, View,
+};
+
+/// Color of the strokes.
+const STROKE_COLOR: [f32; 3] = [0.05, 0.05, 0.2];
+
+/// Corners of the unit quad as a triangle strip, it covers the viewport.
+#[rustfmt::skip]
+const VERTICES: [f32; 8] = [
+    -1.0, -1.0,
+     1.0, -1.0,
+    -1.0,  1.0,
+     1.0,  1.0,
+];
+
+/// Leaves the sheet upright, for copying one sheet into the next.
+const IDENTITY: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+
+const STROKE_VERTEX_SHADER_SOURCE: &str = r#"
+ATTRIBUTE vec2 position;
+
+void main() {
+    gl_Position = vec4(position, 0.0, 1.0);
+}
+"#;
+
+const STROKE_FRAGMENT_SHADER_SOURCE: &str = r#"
+uniform vec3 color;
+
+void main() {
+    FRAG_COLOR = vec4(color, 1.0);
+}
+"#;
+
+const SHEET_VERTEX_SHADER_SOURCE: &str = r#"
+ATTRIBUTE vec2 position;
+VARYING vec2 v_tex_coord;
+
+// Maps upright surface coordinates to the rotated buffer, see BaseApp::ndc_transform
+uniform mat2 transform;
+
+void main() {
+    gl_Position = vec4(transform * position, 0.0, 1.0);
+    // The strokes went in bottom row first like everything GL draws
+    v_tex_coord = position * 0.5 + 0.5;
+}
+"#;
+
+const SHEET_FRAGMENT_SHADER_SOURCE: &str = r#"
+VARYING vec2 v_tex_coord;
+
+uniform sampler2D sheet;
+
+void main() {
+    vec4 color = TEXTURE(sheet, v_tex_coord);
+    FRAG_COLOR = vec4(framebuffer_color(color.rgb), color.a);
+}
+"#;
+
+/// The programs and buffers the sheets of all windows of a context are drawn with.
+pub struct Canvas {
+    stroke_program: NativeProgram,
+    color: Option<NativeUniformLocation>,
+    sheet_program: NativeProgram,
+    transform: Option<NativeUniformLocation>,
+    srgb_framebuffer: Option<NativeUniformLocation>,
+    quad_buffer: NativeBuffer,
+    /// Refilled with the triangles of the new strokes on every update.
+    stroke_buffer: NativeBuffer,
+    /// Shared by both buffers, the position attribute is pointed at the one drawn.
+    vertex_array: Option<NativeVertexArray>,
+}
+
+/// The strokes of a window. Nothing until the first one, and nothing again once cleared.
+#[derive(Default)]
+pub struct Sheet {
+    target: Option<Target>,
+}
+
+/// The texture the strokes are in and the framebuffer object drawing into it.
+struct Target {
+    texture: NativeTexture,
+    framebuffer: NativeFramebuffer,
+    /// In texture pixels.
+    size: (u32, u32),
+    /// Texture pixels per logical pixel.
+    scale: f32,
+}
+
+impl Canvas {
+    /// Sets up the programs, the context must be current.
+    pub fn new(gl: &Context) -> Result<Canvas, GlInitError> {
+        let dialect = Dialect::of(gl);
+
+        unsafe {
+            let stroke_program = link_program(
+                gl,
+                &format!("{}{STROKE_VERTEX_SHADER_SOURCE}", dialect.vertex_header),
+                &format!("{}{STROKE_FRAGMENT_SHADER_SOURCE}", dialect.fragment_header),
+            )?;
+            let sheet_program = link_program(
+                gl,
+                &format!("{}{SHEET_VERTEX_SHADER_SOURCE}", dialect.vertex_header),
+                &format!(
+                    "{}{}{SHEET_FRAGMENT_SHADER_SOURCE}",
+                    dialect.fragment_header,
+                    srgb::SHADER_SOURCE
+                ),
+            )?;
+
+            let vertex_array = if dialect.needs_vertex_array {
+                Some(gl.create_vertex_array().map_err(GlInitError::Shader)?)
+            } else {
+                None
+            };
+
+            let quad_buffer = gl.create_buffer().map_err(GlInitError::Shader)?;
+            gl.bind_buffer(ARRAY_BUFFER, Some(quad_buffer));
+            let bytes: Vec<u8> = VERTICES.iter().flat_map(|v| v.to_ne_bytes()).collect();
+            gl.buffer_data_u8_slice(ARRAY_BUFFER, &bytes, STATIC_DRAW);
+            gl.bind_buffer(ARRAY_BUFFER, None);
+            let stroke_buffer = gl.create_buffer().map_err(GlInitError::Shader)?;
+
+            Ok(Canvas {
+                color: gl.get_uniform_location(stroke_program, "color"),
+                transform: gl.get_uniform_location(sheet_program, "transform"),
+                srgb_framebuffer: gl.get_uniform_location(sheet_program, "srgb_framebuffer"),
+                stroke_program,
+                sheet_program,
+                quad_buffer,
+                stroke_buffer,
+                vertex_array,
+            })
+        }
+    }
+
+    /// Binds the buffer and points the position attribute of the program at it.
+    unsafe fn bind_vertices(&self, gl: &Context, program: NativeProgram, buffer: NativeBuffer) {
+        unsafe {
+            if let Some(vertex_array) = self.vertex_array {
+                gl.bind_vertex_array(Some(vertex_array));
+            }
+            gl.bind_buffer(ARRAY_BUFFER, Some(buffer));
+            if let Some(location) = gl.get_attrib_location(program, "position") {
+                gl.enable_vertex_attrib_array(location);
+                gl.vertex_attrib_pointer_f32(location, 2, FLOAT, false, 0, 0);
+            }
+        }
+    }
+
+    unsafe fn unbind_vertices(&self, gl: &Context) {
+        unsafe {
+            if self.vertex_array.is_some() {
+                gl.bind_vertex_array(None);
+            }
+            gl.bind_buffer(ARRAY_BUFFER, None);
+        }
+    }
+
+    /// Draws what the tablet drew since the last frame into the sheet of the window, and
+    /// resizes it along with the content area. Call it at the start of a frame, before the
+    /// viewport and the scissor test are set up for the window: it leaves the default
+    /// framebuffer bound but the viewport anywhere.
+    pub fn update(&self, gl: &Context, sheet: &mut Sheet, base: &mut BaseApp) {
+        let (clear, segments) = base.take_strokes();
+        if clear && let Some(target) = sheet.target.take() {
+            target.destroy(gl);
+        }
+        if sheet.target.is_none() && segments.is_empty() {
+            return;
+        }
+
+        let [_, _, logical_width, _] = base.content_area();
+        let [_, _, width, height] = base.content_rect();
+        // The content rectangle is rotated along with the buffer, the sheet is upright
+        let (width, height) =
+            if base.ndc_transform()[0] == 0.0 { (height, width) } else { (width, height) };
+        let size = (width.max(1) as u32, height.max(1) as u32);
+        let scale = size.0 as f32 / logical_width.max(1) as f32;
+
+        let resized = sheet.target.as_ref().is_none_or(|t| t.size != size || t.scale != scale);
+        if resized {
+            match self.resize(gl, sheet.target.take(), size, scale) {
+                Ok(target) => sheet.target = Some(target),
+                Err(err) => {
+                    log::warn!("no canvas to draw on: {err}");
+                    return;
+                }
+            }
+        }
+        let Some(target) = &sheet.target else {
+            return;
+        };
+        if segments.is_empty() {
+            unsafe { gl.bind_framebuffer(FRAMEBUFFER, None) };
+            return;
+        }
+
+        let vertices: Vec<f32> =
+            segments.iter().flat_map(|segment| stroke_quad(segment, size, scale)).collect();
+        unsafe {
+            gl.bind_framebuffer(FRAMEBUFFER, Some(target.framebuffer));
+            gl.viewport(0, 0, size.0 as i32, size.1 as i32);
+            gl.use_program(Some(self.stroke_program));
+            let [r, g, b] = STROKE_COLOR;
+            gl.uniform_3_f32(self.color.as_ref(), r, g, b);
+            self.bind_vertices(gl, self.stroke_program, self.stroke_buffer);
+            let bytes: Vec<u8> = vertices.iter().flat_map(|v| v.to_ne_bytes()).collect();
+            gl.buffer_data_u8_slice(ARRAY_BUFFER, &bytes, STREAM_DRAW);
+            gl.draw_arrays(TRIANGLES, 0, vertices.len() as i32 / 2);
+            self.unbind_vertices(gl);
+            gl.use_program(None);
+            gl.bind_framebuffer(FRAMEBUFFER, None);
+        }
+    }
+
+    /// Makes an empty sheet of the size and copies the strokes of the old one into it, the
+    /// framebuffer of the new one stays bound.
+    fn resize(
+        &self,
+        gl: &Context,
+        old: Option<Target>,
+        size: (u32, u32),
+        scale: f32,
+    ) -> Result<Target, String> {
+        unsafe {
+            let max_size = gl.get_parameter_i32(MAX_TEXTURE_SIZE) as u32;
+            if size.0 > max_size || size.1 > max_size {
+                if let Some(old) = old {
+                    old.destroy(gl);
+                }
+                return Err(format!(
+                    "it would be {}x{}, textures can be at most {max_size}x{max_size}",
+                    size.0, size.1
+                ));
+            }
+
+            let texture = gl.create_texture()?;
+            gl.bind_texture(TEXTURE_2D, Some(texture));
+            gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_MIN_FILTER, LINEAR as i32);
+            gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_MAG_FILTER, LINEAR as i32);
+            gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_WRAP_S, CLAMP_TO_EDGE as i32);
+            gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_WRAP_T, CLAMP_TO_EDGE as i32);
+            gl.tex_image_2d(
+                TEXTURE_2D,
+                0,
+                RGBA as i32,
+                size.0 as i32,
+                size.1 as i32,
+                0,
+                RGBA,
+                UNSIGNED_BYTE,
+                PixelUnpackData::Slice(None),
+            );
+            gl.bind_texture(TEXTURE_2D, None);
+
+            let framebuffer = gl.create_framebuffer()?;
+            gl.bind_framebuffer(FRAMEBUFFER, Some(framebuffer));
+            gl.framebuffer_texture_2d(
+                FRAMEBUFFER,
+                COLOR_ATTACHMENT0,
+                TEXTURE_2D,
+                Some(texture),
+                0,
+            );
+            let target = Target { texture, framebuffer, size, scale };
+            let status = gl.check_framebuffer_status(FRAMEBUFFER);
+            if status != FRAMEBUFFER_COMPLETE {
+                gl.bind_framebuffer(FRAMEBUFFER, None);
+                target.destroy(gl);
+                if let Some(old) = old {
+                    old.destroy(gl);
+                }
+                return Err(format!("the framebuffer is incomplete, status {status:#x}"));
+            }
+
+            gl.viewport(0, 0, size.0 as i32, size.1 as i32);
+            gl.clear_color(0.0, 0.0, 0.0, 0.0);
+            gl.clear(COLOR_BUFFER_BIT);
+            if let Some(old) = old {
+                // Each stroke keeps its logical position from the top left, GL counts from
+                // the bottom left
+                let factor = scale / old.scale;
+                let copy_width = (old.size.0 as f32 * factor).round() as i32;
+                let copy_height = (old.size.1 as f32 * factor).round() as i32;
+                gl.viewport(0, size.1 as i32 - copy_height, copy_width, copy_height);
+                self.draw_sheet(gl, &old, &IDENTITY, false);
+                old.destroy(gl);
+            }
+            Ok(target)
+        }
+    }
+
+    /// Blends the strokes over the current viewport, which should be the content area. Goes
+    /// after the scene and the quads, it covers them.
+    pub fn draw(&self, gl: &Context, sheet: &Sheet, view: &View) {
+        let Some(target) = &sheet.target else {
+            return;
+        };
+        if view.plain {
+            return;
+        }
+        unsafe {
+            // The sheet holds premultiplied alpha like the framebuffer
+            gl.enable(BLEND);
+            gl.blend_func(ONE, ONE_MINUS_SRC_ALPHA);
+            self.draw_sheet(gl, target, &view.ndc_transform, view.srgb);
+            gl.disable(BLEND);
+        }
+    }
+
+    /// Draws the texture of the sheet over the whole viewport.
+    unsafe fn draw_sheet(&self, gl: &Context, target: &Target, transform: &[f32; 4], srgb: bool) {
+        unsafe {
+            gl.use_program(Some(self.sheet_program));
+            gl.uniform_matrix_2_f32_slice(self.transform.as_ref(), false, transform);
+            gl.uniform_1_i32(self.srgb_framebuffer.as_ref(), srgb as i32);
+            // The sampler uniform defaults to unit 0
+            gl.active_texture(TEXTURE0);
+            gl.bind_texture(TEXTURE_2D, Some(target.texture));
+            self.bind_vertices(gl, self.sheet_program, self.quad_buffer);
+            gl.draw_arrays(TRIANGLE_STRIP, 0, 4);
+            self.unbind_vertices(gl);
+            gl.bind_texture(TEXTURE_2D, None);
+            gl.use_program(None);
+        }
+    }
+
+    /// Deletes the GL objects, the context must still be current.
+    pub fn destroy(self, gl: &Context) {
+        unsafe {
+            gl.delete_program(self.stroke_program);
+            gl.delete_program(self.sheet_program);
+            gl.delete_buffer(self.quad_buffer);
+            gl.delete_buffer(self.stroke_buffer);
+            if let Some(vertex_array) = self.vertex_array {
+                gl.delete_vertex_array(vertex_array);
+            }
+        }
+    }
+}
+
+impl Sheet {
+    /// Deletes the texture and the framebuffer object, the context must still be current.
+    pub fn destroy(self, gl: &Context) {
+        if let Some(target) = self.target {
+            target.destroy(gl);
+        }
+    }
+}
+
+impl Target {
+    fn destroy(self, gl: &Context) {
+        unsafe {
+            gl.delete_framebuffer(self.framebuffer);
+            gl.delete_texture(self.texture);
+        }
+    }
+}
+
+/// The two triangles of a segment in normalized device coordinates of the sheet. The ends are
+/// square and reach half the width past the points, so the segments of a stroke overlap at the
+/// joints and a stroke of a single point is a square.
+fn stroke_quad(segment: &StrokeSegment, size: (u32, u32), scale: f32) -> [f32; 12] {
+    let (width, height) = (size.0 as f32, size.1 as f32);
+    let pixel = |(x, y): (f32, f32)| (x * scale, height - y * scale);
+    let (from, to) = (pixel(segment.from), pixel(segment.to));
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = dx.hypot(dy);
+    let (ux, uy) = if length > 0.01 { (dx / length, dy / length) } else { (1.0, 0.0) };
+    let corners = |(x, y): (f32, f32), half_width: f32, along: f32| {
+        let (x, y) = (x + along * ux * half_width, y + along * uy * half_width);
+        let ndc = |x: f32, y: f32| [2.0 * x / width - 1.0, 2.0 * y / height - 1.0];
+        let (nx, ny) = (-uy * half_width, ux * half_width);
+        (ndc(x + nx, y + ny), ndc(x - nx, y - ny))
+    };
+    let (a, b) = corners(from, segment.from_width * scale / 2.0, -1.0);
+    let (c, d) = corners(to, segment.to_width * scale / 2.0, 1.0);
+    [a[0], a[1], b[0], b[1], c[0], c[1], b[0], b[1], d[0], d[1], c[0], c[1]]
+}
@@ -0,0 +1,91 @@
+//! Picking the config to draw with out of the ones matching what we ask for. Drivers sort them
+//! by their own rules, the first one may be slow, have more than 8 bits per color or come with
+//! depth and stencil buffers the scene never uses.
+
+/// Bits of depth `--depth` asks for.
+const DEPTH_SIZE: u8 = 24;
+
+/// Bits of stencil `--stencil` asks for.
+const STENCIL_SIZE: u8 = 8;
+
+/// What the scoring looks at, read out of an EGL or glutin config.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigInfo {
+    /// Bits of red, green, blue and alpha.
+    pub color: [u8; 4],
+    pub depth: u8,
+    pub stencil: u8,
+    pub samples: u8,
+    /// Whether the driver marks the config as slow or non-conformant, `EGL_CONFIG_CAVEAT`.
+    pub caveat: bool,
+    /// Whether native rendering can draw into the surfaces too, glutin doesn't tell.
+    pub native_renderable: Option<bool>,
+}
+
+impl ConfigInfo {
+    /// Lower is better. In order: no caveat, exactly RGBA8888, natively renderable, the samples
+    /// asked for, and the depth and stencil bits closest to the ones asked for, which may be
+    /// none. Memory spent on buffers the scene never uses is wasted.
+    fn score(&self, samples: u8, depth: u8, stencil: u8) -> (bool, bool, bool, u8, u16) {
+        (
+            self.caveat,
+            self.color != [8, 8, 8, 8],
+            self.native_renderable == Some(false),
+            self.samples.abs_diff(samples),
+            self.depth.abs_diff(depth) as u16 + self.stencil.abs_diff(stencil) as u16,
+        )
+    }
+}
+
+/// The index of the best candidate for the sample count and the bits of depth and stencil. The
+/// candidates are logged as a table under debug logging.
+pub fn best_config(
+    candidates: &[ConfigInfo],
+    samples: u8,
+    depth: u8,
+    stencil: u8,
+) -> Option<usize> {
+    let best = (0..candidates.len())
+        .min_by_key(|&index| candidates[index].score(samples, depth, stencil))?;
+    if log::log_enabled!(log::Level::Debug) {
+        log::debug!(
+            "{} configs for {samples} samples, {depth} depth and {stencil} stencil bits, \
+             picked {best}:",
+            candidates.len()
+        );
+        for line in config_table(candidates) {
+            log::debug!("{line}");
+        }
+    }
+    Some(best)
+}
+
+/// The bits of depth and stencil `--depth` and `--stencil` ask for, 0 without them.
+pub fn depth_stencil_sizes(depth: bool, stencil: bool) -> (u8, u8) {
+    (
+        if depth { DEPTH_SIZE } else { 0 },
+        if stencil { STENCIL_SIZE } else { 0 },
+    )
+}
+
+/// The configs as the lines of a table with a header, for `--dump-configs` and the debug log.
+pub fn config_table(configs: &[ConfigInfo]) -> Vec<String> {
+    let header = "   #  RGBA         depth  stencil  samples  caveat  native".to_string();
+    let rows = configs.iter().enumerate().map(|(index, info)| {
+        let [red, green, blue, alpha] = info.color;
+        let native = match info.native_renderable {
+            Some(true) => "yes",
+            Some(false) => "no",
+            None => "?",
+        };
+        format!(
+            "{index:>4}  {:<11}  {:>5}  {:>7}  {:>7}  {:<6}  {native}",
+            format!("{red}-{green}-{blue}-{alpha}"),
+            info.depth,
+            info.stencil,
+            info.samples,
+            if info.caveat { "yes" } else { "no" },
+        )
+    });
+    std::iter::once(header).chain(rows).collect()
+}
@@ -0,0 +1,167 @@
+//! Damage tracking, so a frame only redraws what changed since the buffer it draws into was
+//! last drawn. All rectangles are `[x, y, width, height]` in GL buffer coordinates (origin
+//! bottom left), like the ones [`BaseApp`] hands out.
+//!
+//! Mostly only the scene moves, the triangle unless `--scene` picks another one, and the quads
+//! of `--depth`, but the clear color cycles while
+//! the pointer is away from the window and nothing was pasted, then every frame damages the
+//! whole buffer.
+
+use std::collections::VecDeque;
+
+use wayland_app_common::BaseApp;
+
+use crate::Quads;
+
+/// Frames of damage that are kept. Buffers older than this are redrawn completely, swap chains
+/// rarely have more than three or four buffers.
+const HISTORY_LEN: usize = 4;
+
+/// Width of the `--show-damage` outline in buffer pixels.
+const OUTLINE_WIDTH: i32 = 2;
+
+/// Color of the `--show-damage` outline.
+pub const OUTLINE_COLOR: [f32; 3] = [1.0, 0.0, 1.0];
+
+/// Everything a frame shows apart from the scene and the quads. When any of it changes, the
+/// whole buffer is damaged.
+#[derive(PartialEq)]
+struct Backdrop {
+    buffer_size: (u32, u32),
+    background: [f32; 4],
+    test_pattern: [[i32; 4]; 2],
+    decorations: Vec<([i32; 4], [f32; 3])>,
+    confinement: Vec<[i32; 4]>,
+    touches: Vec<([i32; 4], [f32; 3])>,
+    text: Vec<([i32; 4], [f32; 3])>,
+    badge: Vec<([i32; 4], [f32; 3])>,
+    content: [i32; 4],
+    /// See [`BaseApp::sketch_version`], a stroke may go anywhere in the content area.
+    sketch: u64,
+}
+
+impl Backdrop {
+    fn of(base: &BaseApp) -> Backdrop {
+        Backdrop {
+            buffer_size: base.buffer_size(),
+            background: base.background(),
+            test_pattern: base.test_pattern(),
+            decorations: base.decoration_rects(),
+            confinement: base.confinement_outline(),
+            touches: base.touch_rects(),
+            text: base.text_rects(),
+            badge: base.shortcuts_badge_rects(),
+            content: base.content_rect(),
+            sketch: base.sketch_version(),
+        }
+    }
+}
+
+/// The damage of the latest frames of one surface.
+#[derive(Default)]
+pub struct DamageTracker {
+    /// The backdrop of the last frame.
+    backdrop: Option<Backdrop>,
+    /// Where the scene drew in the last frame, see [`crate::Renderer::bounds`].
+    scene_bounds: Option<[i32; 4]>,
+    /// Where the quads were in the last frame, if it had them.
+    quads: Option<[i32; 4]>,
+    /// What the last frame changed itself, the `--show-damage` outline goes around it.
+    change: [i32; 4],
+    /// What the latest frames changed, newest first.
+    history: VecDeque<[i32; 4]>,
+}
+
+impl DamageTracker {
+    /// Records what the frame about to be drawn changes and returns what to redraw in a buffer
+    /// of the given age. That is the damage of this frame and of the frames since the buffer
+    /// was last drawn, an age of 0 means its contents are unknown and everything is redrawn.
+    ///
+    /// Only one rectangle per frame is kept, the whole buffer or the area the scene left and
+    /// entered, `scene_bounds` being where it draws this frame. A resize changes the backdrop,
+    /// the whole buffer is damaged then. Empty when nothing changed at all, e.g. while the
+    /// triangle is out of sight.
+    pub fn frame(
+        &mut self,
+        base: &BaseApp,
+        scene_bounds: Option<[i32; 4]>,
+        age: u32,
+    ) -> Vec<[i32; 4]> {
+        let (width, height) = base.buffer_size();
+        let full = [0, 0, width as i32, height as i32];
+
+        let backdrop = Backdrop::of(base);
+        let quads = if base.depth { Quads::bounds(base) } else { None };
+        let change = if self.backdrop.as_ref() == Some(&backdrop) {
+            [self.scene_bounds, scene_bounds, self.quads, quads]
+                .into_iter()
+                .flatten()
+                .fold([0, 0, 0, 0], union)
+        } else {
+            full
+        };
+        // The outline of the last frame has to go as well
+        let damage = if base.show_damage { union(self.change, change) } else { change };
+        self.backdrop = Some(backdrop);
+        self.scene_bounds = scene_bounds;
+        self.quads = quads;
+        self.change = change;
+        self.history.push_front(damage);
+        self.history.truncate(HISTORY_LEN);
+
+        let age = age as usize;
+        if age == 0 || age > self.history.len() {
+            return vec![full];
+        }
+        let damage: Vec<[i32; 4]> = self
+            .history
+            .iter()
+            .take(age)
+            .copied()
+            .filter(|&[_, _, width, height]| width > 0 && height > 0)
+            .collect();
+        if damage.contains(&full) { vec![full] } else { damage }
+    }
+
+    /// The `--show-damage` outline of the latest frame, inside of what it changed. Drawn last,
+    /// the damage of the next frame covers it again.
+    pub fn outline(&self) -> Vec<[i32; 4]> {
+        let [x, y, width, height] = self.change;
+        if width <= 0 || height <= 0 {
+            return Vec::new();
+        }
+        let line = OUTLINE_WIDTH.min(width).min(height);
+        vec![
+            [x, y, width, line],
+            [x, y + height - line, width, line],
+            [x, y, line, height],
+            [x + width - line, y, line, height],
+        ]
+    }
+
+    /// Forgets the earlier frames, the next one redraws everything. For changes the tracker
+    /// can't see, like a new image.
+    pub fn reset(&mut self) {
+        *self = DamageTracker::default();
+    }
+}
+
+/// The smallest rectangle containing both. An empty rectangle adds nothing.
+pub fn union(a: [i32; 4], b: [i32; 4]) -> [i32; 4] {
+    let empty = |[_, _, width, height]: [i32; 4]| width <= 0 || height <= 0;
+    if empty(a) {
+        return b;
+    } else if empty(b) {
+        return a;
+    }
+    let (left, bottom) = (a[0].min(b[0]), a[1].min(b[1]));
+    let (right, top) = ((a[0] + a[2]).max(b[0] + b[2]), (a[1] + a[3]).max(b[1] + b[3]));
+    [left, bottom, right - left, top - bottom]
+}
+
+/// The part the rectangles have in common, `None` when they don't overlap.
+pub fn intersection(a: [i32; 4], b: [i32; 4]) -> Option<[i32; 4]> {
+    let (left, bottom) = (a[0].max(b[0]), a[1].max(b[1]));
+    let (right, top) = ((a[0] + a[2]).min(b[0] + b[2]), (a[1] + a[3]).min(b[1] + b[3]));
+    (right > left && top > bottom).then_some([left, bottom, right - left, top - bottom])
+}
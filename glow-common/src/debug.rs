@@ -0,0 +1,69 @@
+//! GL debugging, on with `--gl-debug` or when debug logging is. With GL_KHR_debug the driver
+//! reports what goes wrong itself, otherwise the renderers ask `glGetError` after each draw step.
+
+use glow::{
+    Context, HasContext, DEBUG_OUTPUT, DEBUG_OUTPUT_SYNCHRONOUS, DEBUG_SEVERITY_NOTIFICATION,
+    NO_ERROR,
+};
+use wayland_app_common::BaseApp;
+
+/// Most errors logged per check. A lost context may keep raising one.
+const MAX_ERRORS: usize = 8;
+
+/// The GL debugging of one context.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GlDebug {
+    /// Without GL_KHR_debug errors are only found by asking for them.
+    check_errors: bool,
+}
+
+impl GlDebug {
+    /// Whether the window wants GL debugging, the renderers then ask for a debug context.
+    pub fn wanted(base: &BaseApp) -> bool {
+        base.gl_debug || log::log_enabled!(log::Level::Debug)
+    }
+
+    /// Sets up debugging for the current context, if `enabled`. The driver messages arrive
+    /// synchronously, so they are logged from within the call that caused them.
+    pub fn new(gl: &mut Context, enabled: bool) -> GlDebug {
+        if !enabled {
+            return GlDebug::default();
+        }
+        if !gl.supports_debug() {
+            log::info!("GL_KHR_debug not available, checking glGetError after each draw step");
+            return GlDebug { check_errors: true };
+        }
+
+        unsafe {
+            gl.enable(DEBUG_OUTPUT);
+            gl.enable(DEBUG_OUTPUT_SYNCHRONOUS);
+            gl.debug_message_callback(|source, kind, id, severity, message| {
+                if severity == DEBUG_SEVERITY_NOTIFICATION {
+                    log::debug!("GL: {message}");
+                } else {
+                    log::warn!(
+                        "GL: {message} (source {source:#x}, type {kind:#x}, id {id}, severity \
+                         {severity:#x})"
+                    );
+                }
+            });
+        }
+        log::info!("GL debug messages enabled");
+        GlDebug::default()
+    }
+
+    /// Logs the GL errors raised since the last check, `step` tells what happened in between.
+    /// Does nothing with GL_KHR_debug, which reported them already.
+    pub fn check(&self, gl: &Context, step: &str) {
+        if !self.check_errors {
+            return;
+        }
+        for _ in 0..MAX_ERRORS {
+            let error = unsafe { gl.get_error() };
+            if error == NO_ERROR {
+                break;
+            }
+            log::warn!("GL error {error:#x} after {step}");
+        }
+    }
+}
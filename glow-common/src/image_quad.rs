@@ -0,0 +1,217 @@
+use glow::{
+    Context, HasContext, NativeBuffer, NativeProgram, NativeTexture, NativeUniformLocation,
+    NativeVertexArray, PixelUnpackData, ARRAY_BUFFER, BLEND, CLAMP_TO_EDGE, FLOAT, LINEAR,
+    MAX_TEXTURE_SIZE, ONE, ONE_MINUS_SRC_ALPHA, RGBA, SRC_ALPHA, STATIC_DRAW, TEXTURE0,
+    TEXTURE_2D, TEXTURE_MAG_FILTER, TEXTURE_MIN_FILTER, TEXTURE_WRAP_S, TEXTURE_WRAP_T,
+    TRIANGLE_STRIP, UNSIGNED_BYTE,
+};
+use wayland_app_common::{DroppedImage, GlInitError};
+
+use crate::{
+    shader::{link_program, Dialect},
+    srgb,
+    View,
+};
+
+/// Corners of the unit quad as a triangle strip, the shader scales it to the image.
+#[rustfmt::skip]
+const VERTICES: [f32; 8] = [
+    -1.0, -1.0,
+     1.0, -1.0,
+    -1.0,  1.0,
+     1.0,  1.0,
+];
+
+/// How much of the content area the image may cover at most.
+const MAX_COVERAGE: f32 = 0.8;
+
+const VERTEX_SHADER_SOURCE: &str = r#"
+ATTRIBUTE vec2 position;
+VARYING vec2 v_tex_coord;
+
+// Half the size of the image in normalized device coordinates
+uniform vec2 size;
+// Maps upright surface coordinates to the rotated buffer, see BaseApp::ndc_transform
+uniform mat2 transform;
+
+void main() {
+    gl_Position = vec4(transform * (position * size), 0.0, 1.0);
+    // The rows of the image go top to bottom
+    v_tex_coord = vec2(position.x * 0.5 + 0.5, 0.5 - position.y * 0.5);
+}
+"#;
+
+const FRAGMENT_SHADER_SOURCE: &str = r#"
+VARYING vec2 v_tex_coord;
+
+uniform sampler2D image;
+
+void main() {
+    vec4 color = TEXTURE(image, v_tex_coord);
+    FRAG_COLOR = vec4(framebuffer_color(color.rgb), color.a);
+}
+"#;
+
+/// A dropped image, drawn centered in the content area as a textured quad.
+pub struct ImageQuad {
+    program: NativeProgram,
+    texture: NativeTexture,
+    vertex_buffer: NativeBuffer,
+    vertex_array: Option<NativeVertexArray>,
+    size: Option<NativeUniformLocation>,
+    transform: Option<NativeUniformLocation>,
+    srgb_framebuffer: Option<NativeUniformLocation>,
+    /// Size of the image in pixels.
+    width: u32,
+    height: u32,
+}
+
+impl ImageQuad {
+    /// Uploads the image into a texture, the context must be current. Fails for images larger
+    /// than the biggest texture the driver takes.
+    pub fn new(gl: &Context, image: &DroppedImage) -> Result<ImageQuad, GlInitError> {
+        let dialect = Dialect::of(gl);
+
+        unsafe {
+            let max_size = gl.get_parameter_i32(MAX_TEXTURE_SIZE) as u32;
+            if image.width > max_size || image.height > max_size {
+                return Err(GlInitError::Shader(format!(
+                    "the image is {}x{}, textures can be at most {max_size}x{max_size}",
+                    image.width, image.height
+                )));
+            }
+
+            let program = link_program(
+                gl,
+                &format!("{}{VERTEX_SHADER_SOURCE}", dialect.vertex_header),
+                &format!(
+                    "{}{}{FRAGMENT_SHADER_SOURCE}",
+                    dialect.fragment_header,
+                    srgb::SHADER_SOURCE
+                ),
+            )?;
+
+            // Without mipmaps and with clamping any size works, even on GLES2
+            let texture = gl.create_texture().map_err(GlInitError::Shader)?;
+            gl.bind_texture(TEXTURE_2D, Some(texture));
+            gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_MIN_FILTER, LINEAR as i32);
+            gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_MAG_FILTER, LINEAR as i32);
+            gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_WRAP_S, CLAMP_TO_EDGE as i32);
+            gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_WRAP_T, CLAMP_TO_EDGE as i32);
+            gl.tex_image_2d(
+                TEXTURE_2D,
+                0,
+                RGBA as i32,
+                image.width as i32,
+                image.height as i32,
+                0,
+                RGBA,
+                UNSIGNED_BYTE,
+                PixelUnpackData::Slice(Some(&image.pixels)),
+            );
+            gl.bind_texture(TEXTURE_2D, None);
+
+            let vertex_array = if dialect.needs_vertex_array {
+                let vertex_array = gl.create_vertex_array().map_err(GlInitError::Shader)?;
+                gl.bind_vertex_array(Some(vertex_array));
+                Some(vertex_array)
+            } else {
+                None
+            };
+
+            let vertex_buffer = gl.create_buffer().map_err(GlInitError::Shader)?;
+            gl.bind_buffer(ARRAY_BUFFER, Some(vertex_buffer));
+            let bytes: Vec<u8> = VERTICES.iter().flat_map(|v| v.to_ne_bytes()).collect();
+            gl.buffer_data_u8_slice(ARRAY_BUFFER, &bytes, STATIC_DRAW);
+
+            let quad = ImageQuad {
+                size: gl.get_uniform_location(program, "size"),
+                transform: gl.get_uniform_location(program, "transform"),
+                srgb_framebuffer: gl.get_uniform_location(program, "srgb_framebuffer"),
+                program,
+                texture,
+                vertex_buffer,
+                vertex_array,
+                width: image.width,
+                height: image.height,
+            };
+            quad.bind_attributes(gl);
+
+            if vertex_array.is_some() {
+                gl.bind_vertex_array(None);
+            }
+            gl.bind_buffer(ARRAY_BUFFER, None);
+            Ok(quad)
+        }
+    }
+
+    /// Points the position attribute at the vertex buffer, which must be bound.
+    unsafe fn bind_attributes(&self, gl: &Context) {
+        unsafe {
+            if let Some(location) = gl.get_attrib_location(self.program, "position") {
+                gl.enable_vertex_attrib_array(location);
+                gl.vertex_attrib_pointer_f32(location, 2, FLOAT, false, 0, 0);
+            }
+        }
+    }
+
+    /// Draws the image into the current viewport, which should be the content area. It keeps
+    /// its aspect ratio and is only ever scaled down, one image pixel per logical pixel at most.
+    pub fn draw(&self, gl: &Context, view: &View) {
+        let (width, height) = view.content_size;
+        let (width, height) = (width.max(1) as f32, height.max(1) as f32);
+        let (image_width, image_height) = (self.width as f32, self.height as f32);
+        let scale = (MAX_COVERAGE * width / image_width)
+            .min(MAX_COVERAGE * height / image_height)
+            .min(1.0);
+
+        unsafe {
+            gl.use_program(Some(self.program));
+            gl.uniform_2_f32(
+                self.size.as_ref(),
+                image_width * scale / width,
+                image_height * scale / height,
+            );
+            gl.uniform_matrix_2_f32_slice(self.transform.as_ref(), false, &view.ndc_transform);
+            gl.uniform_1_i32(self.srgb_framebuffer.as_ref(), view.srgb as i32);
+            // The sampler uniform defaults to unit 0
+            gl.active_texture(TEXTURE0);
+            gl.bind_texture(TEXTURE_2D, Some(self.texture));
+
+            match self.vertex_array {
+                Some(vertex_array) => gl.bind_vertex_array(Some(vertex_array)),
+                None => {
+                    gl.bind_buffer(ARRAY_BUFFER, Some(self.vertex_buffer));
+                    self.bind_attributes(gl);
+                }
+            }
+
+            // PNGs may be translucent, let the clear color show through. The framebuffer holds
+            // premultiplied alpha for the compositor, the image colors are multiplied on the
+            // way in and the alphas are added up as `over` does.
+            gl.enable(BLEND);
+            gl.blend_func_separate(SRC_ALPHA, ONE_MINUS_SRC_ALPHA, ONE, ONE_MINUS_SRC_ALPHA);
+            gl.draw_arrays(TRIANGLE_STRIP, 0, 4);
+            gl.disable(BLEND);
+
+            if self.vertex_array.is_some() {
+                gl.bind_vertex_array(None);
+            }
+            gl.bind_buffer(ARRAY_BUFFER, None);
+            gl.bind_texture(TEXTURE_2D, None);
+            gl.use_program(None);
+        }
+    }
+
+    /// Deletes the GL objects, the context must still be current.
+    pub fn destroy(self, gl: &Context) {
+        unsafe {
+            gl.delete_program(self.program);
+            gl.delete_texture(self.texture);
+            gl.delete_buffer(self.vertex_buffer);
+            if let Some(vertex_array) = self.vertex_array {
+                gl.delete_vertex_array(vertex_array);
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! `--gl-info`, everything EGL and GL tell about themselves, for reports of windows that stay
+//! black. The examples ask EGL through their own bindings and fill in an [`EglInfo`], the GL
+//! half is asked of the glow context the same way in both.
+
+use glow::HasContext;
+
+/// How the value of a config attribute reads best.
+#[derive(Clone, Copy)]
+enum Value {
+    Number,
+    /// Bits like `EGL_SURFACE_TYPE`, in hex.
+    Mask,
+    /// An EGL enum like `EGL_CONFIG_CAVEAT`, in hex.
+    Enum,
+}
+
+/// A config attribute of the report, see [`CONFIG_ATTRIBUTES`].
+pub struct ConfigAttribute {
+    pub name: &'static str,
+    /// The `EGLint` to query it with.
+    pub id: i32,
+    value: Value,
+}
+
+const fn attribute(name: &'static str, id: i32, value: Value) -> ConfigAttribute {
+    ConfigAttribute { name, id, value }
+}
+
+/// Every attribute of EGL 1.5 a config has, in the order of the spec's table.
+pub const CONFIG_ATTRIBUTES: &[ConfigAttribute] = &[
+    attribute("EGL_CONFIG_ID", 0x3028, Value::Number),
+    attribute("EGL_BUFFER_SIZE", 0x3020, Value::Number),
+    attribute("EGL_RED_SIZE", 0x3024, Value::Number),
+    attribute("EGL_GREEN_SIZE", 0x3023, Value::Number),
+    attribute("EGL_BLUE_SIZE", 0x3022, Value::Number),
+    attribute("EGL_LUMINANCE_SIZE", 0x303D, Value::Number),
+    attribute("EGL_ALPHA_SIZE", 0x3021, Value::Number),
+    attribute("EGL_ALPHA_MASK_SIZE", 0x303E, Value::Number),
+    attribute("EGL_BIND_TO_TEXTURE_RGB", 0x3039, Value::Number),
+    attribute("EGL_BIND_TO_TEXTURE_RGBA", 0x303A, Value::Number),
+    attribute("EGL_COLOR_BUFFER_TYPE", 0x303F, Value::Enum),
+    attribute("EGL_CONFIG_CAVEAT", 0x3027, Value::Enum),
+    attribute("EGL_CONFORMANT", 0x3042, Value::Mask),
+    attribute("EGL_DEPTH_SIZE", 0x3025, Value::Number),
+    attribute("EGL_LEVEL", 0x3029, Value::Number),
+    attribute("EGL_MAX_PBUFFER_WIDTH", 0x302C, Value::Number),
+    attribute("EGL_MAX_PBUFFER_HEIGHT", 0x302A, Value::Number),
+    attribute("EGL_MAX_PBUFFER_PIXELS", 0x302B, Value::Number),
+    attribute("EGL_MAX_SWAP_INTERVAL", 0x303C, Value::Number),
+    attribute("EGL_MIN_SWAP_INTERVAL", 0x303B, Value::Number),
+    attribute("EGL_NATIVE_RENDERABLE", 0x302D, Value::Number),
+    attribute("EGL_NATIVE_VISUAL_ID", 0x302E, Value::Number),
+    attribute("EGL_NATIVE_VISUAL_TYPE", 0x302F, Value::Enum),
+    attribute("EGL_RENDERABLE_TYPE", 0x3040, Value::Mask),
+    attribute("EGL_SAMPLE_BUFFERS", 0x3032, Value::Number),
+    attribute("EGL_SAMPLES", 0x3031, Value::Number),
+    attribute("EGL_STENCIL_SIZE", 0x3026, Value::Number),
+    attribute("EGL_SURFACE_TYPE", 0x3033, Value::Mask),
+    attribute("EGL_TRANSPARENT_TYPE", 0x3034, Value::Enum),
+    attribute("EGL_TRANSPARENT_RED_VALUE", 0x3037, Value::Number),
+    attribute("EGL_TRANSPARENT_GREEN_VALUE", 0x3036, Value::Number),
+    attribute("EGL_TRANSPARENT_BLUE_VALUE", 0x3035, Value::Number),
+];
+
+/// What EGL tells about the display and the config drawn with.
+pub struct EglInfo {
+    pub vendor: String,
+    pub version: String,
+    /// Space separated, as EGL has them. Empty without EGL 1.5 or EGL_EXT_client_extensions.
+    pub client_extensions: String,
+    pub display_extensions: String,
+    /// The value of each of [`CONFIG_ATTRIBUTES`], `None` when the driver failed to tell.
+    pub config: Vec<Option<i32>>,
+}
+
+/// Prints the report of `--gl-info` for the context current with `gl`.
+pub fn print_report(egl: &EglInfo, gl: &glow::Context) {
+    for line in report(egl, gl) {
+        println!("{line}");
+    }
+}
+
+/// The lines of the report, the extensions one per line.
+fn report(egl: &EglInfo, gl: &glow::Context) -> Vec<String> {
+    let mut lines = vec![
+        format!("EGL vendor: {}", egl.vendor),
+        format!("EGL version: {}", egl.version),
+    ];
+    extension_lines(&mut lines, "EGL client", egl.client_extensions.split_ascii_whitespace());
+    extension_lines(&mut lines, "EGL display", egl.display_extensions.split_ascii_whitespace());
+
+    lines.push("EGL config:".to_string());
+    for (attribute, value) in CONFIG_ATTRIBUTES.iter().zip(&egl.config) {
+        let value = match (value, attribute.value) {
+            (None, _) => "?".to_string(),
+            (Some(value), Value::Number) => value.to_string(),
+            (Some(value), Value::Mask | Value::Enum) => format!("{value:#x}"),
+        };
+        lines.push(format!("    {:<28} {value}", attribute.name));
+    }
+
+    let string = |parameter| unsafe { gl.get_parameter_string(parameter) };
+    lines.push(format!("GL vendor: {}", string(glow::VENDOR)));
+    lines.push(format!("GL renderer: {}", string(glow::RENDERER)));
+    lines.push(format!("GL version: {}", string(glow::VERSION)));
+    lines.push(format!("GLSL version: {}", string(glow::SHADING_LANGUAGE_VERSION)));
+    let extensions = gl_extensions(gl);
+    extension_lines(&mut lines, "GL", extensions.iter().map(String::as_str));
+    lines
+}
+
+fn extension_lines<'a>(
+    lines: &mut Vec<String>,
+    what: &str,
+    extensions: impl Iterator<Item = &'a str>,
+) {
+    let extensions: Vec<&str> = extensions.collect();
+    lines.push(format!("{what} extensions ({}):", extensions.len()));
+    lines.extend(extensions.iter().map(|extension| format!("    {extension}")));
+}
+
+/// The extensions of the context. GL 3 and GLES 3 list them by index, core profiles don't
+/// have the single string of the older versions at all.
+fn gl_extensions(gl: &glow::Context) -> Vec<String> {
+    unsafe {
+        if gl.version().major >= 3 {
+            let count = gl.get_parameter_i32(glow::NUM_EXTENSIONS).max(0) as u32;
+            (0..count)
+                .map(|index| gl.get_parameter_indexed_string(glow::EXTENSIONS, index))
+                .collect()
+        } else {
+            let extensions = gl.get_parameter_string(glow::EXTENSIONS);
+            extensions.split_ascii_whitespace().map(str::to_string).collect()
+        }
+    }
+}
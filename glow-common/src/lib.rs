@@ -0,0 +1,31 @@
+//! The GL scene shared by the glow examples. They only differ in how they get a context, once
+//! one is current everything in here works the same on top of glutin and raw EGL.
+
+mod canvas;
+mod config;
+pub mod damage;
+mod debug;
+mod image_quad;
+pub mod info;
+pub mod msaa;
+mod quads;
+mod renderer;
+mod robustness;
+pub mod screenshot;
+mod shader;
+pub mod software;
+pub mod srgb;
+mod triangle;
+mod view;
+
+pub use canvas::{Canvas, Sheet};
+pub use config::{best_config, config_table, depth_stencil_sizes, ConfigInfo};
+pub use damage::DamageTracker;
+pub use debug::GlDebug;
+pub use image_quad::ImageQuad;
+pub use quads::Quads;
+pub use renderer::{builtin_renderer, Renderer, RendererFactory, Scene};
+pub use robustness::ResetStatus;
+pub use software::{Pixels, Software};
+pub use triangle::Triangle;
+pub use view::View;
@@ -0,0 +1,18 @@
+//! Multisampling, `--msaa`. The samples are part of the config, so they're picked once with
+//! the display and every context renders with them.
+
+use glow::{Context, HasContext, MULTISAMPLE};
+
+/// The sample counts to try for the requested one, that one first and then each lower count
+/// down to none. Drivers don't have a config for every count.
+pub fn sample_counts(requested: u8) -> Vec<u8> {
+    [8, 4, 2, 0].into_iter().filter(|&samples| samples <= requested).collect()
+}
+
+/// Turns multisampling on for the current context, the edges of the triangle are smooth then.
+/// GLES has no switch for it, multisampled configs always use it.
+pub fn enable(gl: &Context, samples: u8) {
+    if samples > 0 && !gl.version().is_embedded {
+        unsafe { gl.enable(MULTISAMPLE) };
+    }
+}
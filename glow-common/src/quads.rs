@@ -0,0 +1,210 @@
+//! The 3D scene of `--depth`, two quads turning through each other. Where they cross only the
+//! depth test decides which one is in front, without a depth buffer they'd simply overlap in
+//! drawing order.
+
+use glow::{
+    Context, HasContext, NativeBuffer, NativeUniformLocation, NativeVertexArray, ARRAY_BUFFER,
+    DEPTH_BUFFER_BIT, DEPTH_TEST, FLOAT, STATIC_DRAW, STENCIL_BUFFER_BIT, TRIANGLE_STRIP,
+};
+use wayland_app_common::{BaseApp, GlInitError};
+
+use crate::{
+    shader::{link_program, Dialect},
+    srgb,
+    triangle::circle_bounds,
+    View,
+};
+
+/// The corners of a quad around its center, drawn as a triangle strip.
+#[rustfmt::skip]
+const VERTICES: [f32; 8] = [
+    -0.3, -0.3,
+     0.3, -0.3,
+    -0.3,  0.3,
+     0.3,  0.3,
+];
+
+/// Distance of the farthest corner from the middle of the content area. The quads are 0.15
+/// off to the side and a corner is 0.3 × √2 from their center.
+const RADIUS: f32 = 0.58;
+
+/// Radians per millisecond, a full turn takes about four seconds.
+const ROTATION_SPEED: f32 = std::f32::consts::TAU / 4000.0;
+
+const VERTEX_SHADER_SOURCE: &str = r#"
+ATTRIBUTE vec2 position;
+
+uniform mat3 rotation;
+// Where the quad is, x and y in normalized device coordinates and z in depth
+uniform vec3 center;
+uniform vec2 aspect;
+// Maps upright surface coordinates to the rotated buffer, see BaseApp::ndc_transform
+uniform mat2 transform;
+
+void main() {
+    vec3 corner = rotation * vec3(position, 0.0) + center;
+    gl_Position = vec4(transform * (corner.xy * aspect), corner.z, 1.0);
+}
+"#;
+
+const FRAGMENT_SHADER_SOURCE: &str = r#"
+uniform vec3 color;
+
+void main() {
+    FRAG_COLOR = vec4(framebuffer_color(color), 1.0);
+}
+"#;
+
+/// Two quads at different depths, one turning about the vertical axis and one about the
+/// horizontal axis, so they keep crossing each other.
+pub struct Quads {
+    program: glow::NativeProgram,
+    vertex_buffer: NativeBuffer,
+    vertex_array: Option<NativeVertexArray>,
+    rotation: Option<NativeUniformLocation>,
+    center: Option<NativeUniformLocation>,
+    aspect: Option<NativeUniformLocation>,
+    transform: Option<NativeUniformLocation>,
+    color: Option<NativeUniformLocation>,
+    srgb_framebuffer: Option<NativeUniformLocation>,
+}
+
+impl Quads {
+    /// Compiles the shaders and uploads the vertices, the context must be current.
+    pub fn new(gl: &Context) -> Result<Quads, GlInitError> {
+        let dialect = Dialect::of(gl);
+
+        unsafe {
+            let program = link_program(
+                gl,
+                &format!("{}{VERTEX_SHADER_SOURCE}", dialect.vertex_header),
+                &format!(
+                    "{}{}{FRAGMENT_SHADER_SOURCE}",
+                    dialect.fragment_header,
+                    srgb::SHADER_SOURCE
+                ),
+            )?;
+
+            let vertex_array = if dialect.needs_vertex_array {
+                let vertex_array = gl.create_vertex_array().map_err(GlInitError::Shader)?;
+                gl.bind_vertex_array(Some(vertex_array));
+                Some(vertex_array)
+            } else {
+                None
+            };
+
+            let vertex_buffer = gl.create_buffer().map_err(GlInitError::Shader)?;
+            gl.bind_buffer(ARRAY_BUFFER, Some(vertex_buffer));
+            let bytes: Vec<u8> = VERTICES.iter().flat_map(|v| v.to_ne_bytes()).collect();
+            gl.buffer_data_u8_slice(ARRAY_BUFFER, &bytes, STATIC_DRAW);
+
+            let quads = Quads {
+                rotation: gl.get_uniform_location(program, "rotation"),
+                center: gl.get_uniform_location(program, "center"),
+                aspect: gl.get_uniform_location(program, "aspect"),
+                transform: gl.get_uniform_location(program, "transform"),
+                color: gl.get_uniform_location(program, "color"),
+                srgb_framebuffer: gl.get_uniform_location(program, "srgb_framebuffer"),
+                program,
+                vertex_buffer,
+                vertex_array,
+            };
+            quads.bind_attributes(gl);
+
+            if vertex_array.is_some() {
+                gl.bind_vertex_array(None);
+            }
+            gl.bind_buffer(ARRAY_BUFFER, None);
+            Ok(quads)
+        }
+    }
+
+    /// Points the position attribute at the vertex buffer, which must be bound.
+    unsafe fn bind_attributes(&self, gl: &Context) {
+        unsafe {
+            if let Some(location) = gl.get_attrib_location(self.program, "position") {
+                gl.enable_vertex_attrib_array(location);
+                gl.vertex_attrib_pointer_f32(location, 2, FLOAT, false, 0, 0);
+            }
+        }
+    }
+
+    /// Draws the quads into the current viewport, which should be the content area. The depth
+    /// buffer is cleared first, within the scissor box when the scissor test is on, so a resized
+    /// buffer never tests against the depths of an older frame.
+    pub fn draw(&self, gl: &Context, view: &View) {
+        let (width, height) = view.content_size;
+        let (width, height) = (width.max(1) as f32, height.max(1) as f32);
+        let side = width.min(height);
+
+        let angle = view.time as f32 * ROTATION_SPEED % std::f32::consts::TAU;
+        let (sin, cos) = angle.sin_cos();
+        // Column major. The orange quad is in front and turns about the vertical axis, the
+        // blue one behind it about the horizontal axis. Their corners reach 0.3 in depth,
+        // more than the 0.15 their centers are apart, so they cut through each other.
+        #[rustfmt::skip]
+        let quads = [
+            ([-0.15, 0.0, -0.15], [1.0, 0.6, 0.0], [
+                cos, 0.0, -sin,
+                0.0, 1.0, 0.0,
+                sin, 0.0, cos,
+            ]),
+            ([0.15, 0.0, 0.15], [0.0, 0.6, 0.8], [
+                1.0, 0.0, 0.0,
+                0.0, cos, sin,
+                0.0, -sin, cos,
+            ]),
+        ];
+
+        unsafe {
+            gl.enable(DEPTH_TEST);
+            // Packed depth and stencil buffers clear faster together
+            let stencil = if view.stencil { STENCIL_BUFFER_BIT } else { 0 };
+            gl.clear(DEPTH_BUFFER_BIT | stencil);
+
+            gl.use_program(Some(self.program));
+            gl.uniform_2_f32(self.aspect.as_ref(), side / width, side / height);
+            gl.uniform_matrix_2_f32_slice(self.transform.as_ref(), false, &view.ndc_transform);
+            gl.uniform_1_i32(self.srgb_framebuffer.as_ref(), view.srgb as i32);
+
+            match self.vertex_array {
+                Some(vertex_array) => gl.bind_vertex_array(Some(vertex_array)),
+                None => {
+                    gl.bind_buffer(ARRAY_BUFFER, Some(self.vertex_buffer));
+                    self.bind_attributes(gl);
+                }
+            }
+
+            for ([x, y, z], [red, green, blue], rotation) in quads {
+                gl.uniform_3_f32(self.center.as_ref(), x, y, z);
+                gl.uniform_3_f32(self.color.as_ref(), red, green, blue);
+                gl.uniform_matrix_3_f32_slice(self.rotation.as_ref(), false, &rotation);
+                gl.draw_arrays(TRIANGLE_STRIP, 0, 4);
+            }
+
+            if self.vertex_array.is_some() {
+                gl.bind_vertex_array(None);
+            }
+            gl.bind_buffer(ARRAY_BUFFER, None);
+            gl.use_program(None);
+            gl.disable(DEPTH_TEST);
+        }
+    }
+
+    /// The square the quads stay within at any angle, in GL buffer coordinates. `None` when
+    /// the content area is empty.
+    pub fn bounds(base: &BaseApp) -> Option<[i32; 4]> {
+        circle_bounds(base, RADIUS, (0.0, 0.0))
+    }
+
+    /// Deletes the GL objects, the context must still be current.
+    pub fn destroy(self, gl: &Context) {
+        unsafe {
+            gl.delete_program(self.program);
+            gl.delete_buffer(self.vertex_buffer);
+            if let Some(vertex_array) = self.vertex_array {
+                gl.delete_vertex_array(vertex_array);
+            }
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! What the examples draw in the content area, behind a trait so the Wayland and EGL setup of
+//! either example can draw any scene. The background, the decorations, the image of a drop and
+//! the quads of `--depth` stay with the examples.
+//!
+//! Every method runs on the thread the GL context is current on, with the context of the window
+//! current. That is the thread of the event loop, except with `--render-thread` of the raw EGL
+//! example which draws on a thread of its own. glutin gives each window a context and a
+//! renderer of its own, raw EGL shares one context and one renderer between all of them.
+//! Either way the context may be lost and set up again, with a new renderer from the same
+//! factory. Without any GL the [`crate::software`] fallback draws the same renderers with the
+//! CPU.
+
+use std::sync::Arc;
+
+use glow::{Context, HasContext, COLOR_BUFFER_BIT, SCISSOR_BOX, SCISSOR_TEST, VIEWPORT};
+use wayland_app_common::{BaseApp, BuiltinScene, GlInitError};
+
+use crate::{damage::intersection, srgb::framebuffer_color, Pixels, Triangle, View};
+
+/// A scene drawn into the content area of a window. Object-safe, the examples hold a
+/// `Box<dyn Renderer>`.
+pub trait Renderer {
+    /// Creates the GL objects. Called once, before anything else, with the context current.
+    fn init(&mut self, gl: &Context) -> Result<(), GlInitError>;
+
+    /// The logical size of the content area changed, called before the draw at the new size.
+    /// With one renderer for several windows this happens whenever the next one is another
+    /// size.
+    fn resize(&mut self, width: u32, height: u32);
+
+    /// Draws a frame at `t` seconds of animation time. The viewport is the content area and
+    /// the framebuffer is bound, parts outside of the damage of the frame may be left out by
+    /// the scissor test of the example.
+    fn draw(&mut self, gl: &Context, t: f64);
+
+    /// Draws with everything the window state tells, the zoom, the transform of the buffer
+    /// and so on. Only [`Renderer::draw`] by default.
+    fn draw_view(&mut self, gl: &Context, view: &View) {
+        self.draw(gl, view.time as f64 / 1000.0);
+    }
+
+    /// What the scene may draw over, in GL buffer coordinates, see [`BaseApp::buffer_rect`].
+    /// The damage of a frame is where it was and is now. The whole content area by default,
+    /// `None` when it draws nothing.
+    fn bounds(&self, base: &BaseApp) -> Option<[i32; 4]> {
+        Some(base.content_rect())
+    }
+
+    /// Draws a frame with the CPU into the viewport of the pixels, for the software fallback.
+    /// [`Renderer::init`] is never called then, there is no context. Draws nothing by default,
+    /// the background shows through.
+    fn draw_pixels(&mut self, _pixels: &mut Pixels, _view: &View) {}
+
+    /// Deletes the GL objects, the context is still current. Not called when the context was
+    /// lost, the objects are gone with it.
+    fn destroy(&mut self, _gl: &Context) {}
+}
+
+/// Makes the renderer of a window, or of the shared context. It is called on the thread that
+/// draws, so the renderers need not be `Send` but the factory has to be.
+pub type RendererFactory = Arc<dyn Fn() -> Box<dyn Renderer> + Send + Sync>;
+
+/// The renderer of a scene of `--scene`.
+pub fn builtin_renderer(scene: BuiltinScene) -> Box<dyn Renderer> {
+    match scene {
+        BuiltinScene::Triangle => Box::new(TriangleRenderer::default()),
+        BuiltinScene::BlueClear => Box::new(BlueClear::default()),
+    }
+}
+
+/// A renderer once it is set up, calling [`Renderer::resize`] when the content size changes.
+pub struct Scene {
+    renderer: Box<dyn Renderer>,
+    /// The content size of the last draw.
+    size: Option<(u32, u32)>,
+}
+
+impl Scene {
+    /// Sets up the renderer, the context must be current.
+    pub fn new(mut renderer: Box<dyn Renderer>, gl: &Context) -> Result<Scene, GlInitError> {
+        renderer.init(gl)?;
+        Ok(Scene { renderer, size: None })
+    }
+
+    /// A renderer of the software fallback, which has no context to set it up with.
+    pub fn software(renderer: Box<dyn Renderer>) -> Scene {
+        Scene { renderer, size: None }
+    }
+
+    /// Draws into the current viewport, which should be the content area. Dialogs and
+    /// `--self-check` show only the background.
+    pub fn draw(&mut self, gl: &Context, view: &View) {
+        if view.plain {
+            return;
+        }
+        self.resize(view);
+        self.renderer.draw_view(gl, view);
+    }
+
+    /// [`Self::draw`] with the CPU, into the viewport of the pixels.
+    pub fn draw_pixels(&mut self, pixels: &mut Pixels, view: &View) {
+        if view.plain {
+            return;
+        }
+        self.resize(view);
+        self.renderer.draw_pixels(pixels, view);
+    }
+
+    fn resize(&mut self, view: &View) {
+        if self.size != Some(view.content_size) {
+            self.size = Some(view.content_size);
+            let (width, height) = view.content_size;
+            self.renderer.resize(width, height);
+        }
+    }
+
+    /// See [`Renderer::bounds`].
+    pub fn bounds(&self, base: &BaseApp) -> Option<[i32; 4]> {
+        if base.plain() {
+            return None;
+        }
+        self.renderer.bounds(base)
+    }
+
+    /// Deletes the GL objects, the context must still be current.
+    pub fn destroy(mut self, gl: &Context) {
+        self.renderer.destroy(gl);
+    }
+}
+
+/// `--scene triangle`, the rotating triangle.
+#[derive(Default)]
+struct TriangleRenderer {
+    triangle: Option<Triangle>,
+    size: (u32, u32),
+}
+
+impl Renderer for TriangleRenderer {
+    fn init(&mut self, gl: &Context) -> Result<(), GlInitError> {
+        self.triangle = Some(Triangle::new(gl)?);
+        Ok(())
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.size = (width, height);
+    }
+
+    fn draw(&mut self, gl: &Context, t: f64) {
+        let view = View::offscreen(self.size, (t * 1000.0) as u32, false, false);
+        self.draw_view(gl, &view);
+    }
+
+    fn draw_view(&mut self, gl: &Context, view: &View) {
+        if let Some(triangle) = &self.triangle {
+            triangle.draw(gl, view);
+        }
+    }
+
+    fn draw_pixels(&mut self, pixels: &mut Pixels, view: &View) {
+        Triangle::draw_pixels(pixels, view);
+    }
+
+    fn bounds(&self, base: &BaseApp) -> Option<[i32; 4]> {
+        Triangle::bounds(base)
+    }
+
+    fn destroy(&mut self, gl: &Context) {
+        if let Some(triangle) = self.triangle.take() {
+            triangle.destroy(gl);
+        }
+    }
+}
+
+/// `--scene blue-clear`, a blue content area and nothing else.
+#[derive(Default)]
+struct BlueClear {
+    /// Whether the framebuffer of the latest view encodes sRGB.
+    srgb: bool,
+}
+
+impl Renderer for BlueClear {
+    fn init(&mut self, _gl: &Context) -> Result<(), GlInitError> {
+        Ok(())
+    }
+
+    fn resize(&mut self, _width: u32, _height: u32) {}
+
+    fn draw(&mut self, gl: &Context, _t: f64) {
+        let [red, green, blue] = framebuffer_color([0.1, 0.2, 0.8], self.srgb);
+        unsafe {
+            // Clearing ignores the viewport, only the scissor keeps it off the decorations. The
+            // scissor of the example, e.g. the damage, is kept within it.
+            let mut viewport = [0; 4];
+            gl.get_parameter_i32_slice(VIEWPORT, &mut viewport);
+            let scissor = gl.is_enabled(SCISSOR_TEST).then(|| {
+                let mut scissor = [0; 4];
+                gl.get_parameter_i32_slice(SCISSOR_BOX, &mut scissor);
+                scissor
+            });
+            let area = match scissor {
+                Some(scissor) => intersection(viewport, scissor),
+                None => Some(viewport),
+            };
+            if let Some([x, y, width, height]) = area {
+                gl.enable(SCISSOR_TEST);
+                gl.scissor(x, y, width, height);
+                gl.clear_color(red, green, blue, 1.0);
+                gl.clear(COLOR_BUFFER_BIT);
+            }
+            match scissor {
+                Some([x, y, width, height]) => gl.scissor(x, y, width, height),
+                None => gl.disable(SCISSOR_TEST),
+            }
+        }
+    }
+
+    fn draw_view(&mut self, gl: &Context, view: &View) {
+        self.srgb = view.srgb;
+        self.draw(gl, view.time as f64 / 1000.0);
+    }
+
+    fn draw_pixels(&mut self, pixels: &mut Pixels, view: &View) {
+        let color = framebuffer_color([0.1, 0.2, 0.8], view.srgb);
+        pixels.fill(pixels.viewport, color);
+    }
+
+    // The same every frame, only a change of the content area damages it
+    fn bounds(&self, _base: &BaseApp) -> Option<[i32; 4]> {
+        None
+    }
+}
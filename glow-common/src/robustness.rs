@@ -0,0 +1,57 @@
+//! Noticing a lost context. After a GPU reset the context of a robust context keeps failing,
+//! the renderers then throw the GL state away and set it up again, the windows stay.
+
+use std::ffi::c_void;
+
+use glow::{Context, HasContext, NO_ERROR};
+
+/// `glGetGraphicsResetStatus`, which is reachable under the name of the robustness extension
+/// the driver has.
+type GetGraphicsResetStatus = unsafe extern "system" fn() -> u32;
+
+/// Asks the driver whether the context was lost, once per frame.
+pub struct ResetStatus(Option<GetGraphicsResetStatus>);
+
+impl ResetStatus {
+    /// Loads the function for the current context. Without a robustness extension a reset goes
+    /// unnoticed until swapping fails.
+    pub fn load(gl: &Context, get_proc_address: impl Fn(&str) -> *const c_void) -> ResetStatus {
+        let extensions = gl.supported_extensions();
+        let name = if extensions.contains("GL_KHR_robustness") {
+            // Only GLES suffixes the KHR functions
+            if gl.version().is_embedded {
+                "glGetGraphicsResetStatusKHR"
+            } else {
+                "glGetGraphicsResetStatus"
+            }
+        } else if extensions.contains("GL_EXT_robustness") {
+            "glGetGraphicsResetStatusEXT"
+        } else if extensions.contains("GL_ARB_robustness") {
+            "glGetGraphicsResetStatusARB"
+        } else {
+            log::info!("no GL robustness extension, GPU resets are only noticed when swapping");
+            return ResetStatus(None);
+        };
+
+        let function = get_proc_address(name);
+        if function.is_null() {
+            return ResetStatus(None);
+        }
+        ResetStatus(Some(unsafe {
+            std::mem::transmute::<*const c_void, GetGraphicsResetStatus>(function)
+        }))
+    }
+
+    /// Whether a GPU reset took the current context with it. It has to be recreated then,
+    /// along with everything in it.
+    pub fn context_lost(&self) -> bool {
+        let Some(get_graphics_reset_status) = self.0 else {
+            return false;
+        };
+        let status = unsafe { get_graphics_reset_status() };
+        if status != NO_ERROR {
+            log::warn!("GL context lost to a GPU reset, status {status:#x}");
+        }
+        status != NO_ERROR
+    }
+}
@@ -0,0 +1,64 @@
+//! Saving a frame as a PNG, S asks for one of the focused window and SIGUSR1 for one of every
+//! window.
+
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use glow::{Context, HasContext, PixelPackData, RGBA, UNSIGNED_BYTE};
+use image::{ExtendedColorType, ImageError};
+use wayland_app_common::BaseApp;
+
+/// Saves the frame when [`BaseApp::screenshot`] asks for it. Call it once everything is drawn
+/// and before swapping, the back buffer holds the frame exactly as it is presented then and
+/// is undefined after the swap.
+pub fn save_if_requested(gl: &Context, base: &mut BaseApp) {
+    if !base.screenshot {
+        return;
+    }
+    base.screenshot = false;
+    match save(gl, base) {
+        Ok(path) => println!("saved a screenshot to {}", path.display()),
+        Err(err) => println!("failed to save the screenshot: {err}"),
+    }
+}
+
+/// Reads the whole buffer in physical pixels and writes it into the working directory,
+/// named after the time. The buffer is saved as drawn, so a transformed window comes out
+/// rotated like its output.
+fn save(gl: &Context, base: &BaseApp) -> Result<PathBuf, ImageError> {
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let path = PathBuf::from(format!(
+        "screenshot-{}.{:03}-{}.png",
+        time.as_secs(),
+        time.subsec_millis(),
+        base.id().protocol_id()
+    ));
+    save_framebuffer(gl, base.buffer_size(), &path)?;
+    Ok(path)
+}
+
+/// Reads the bound framebuffer of the size and writes it to `path` as a PNG.
+pub fn save_framebuffer(gl: &Context, size: (u32, u32), path: &Path) -> Result<(), ImageError> {
+    let (width, height) = size;
+    let row = width as usize * 4;
+    let mut pixels = vec![0; row * height as usize];
+    unsafe {
+        // RGBA with unsigned bytes is the only combination GLES2 guarantees. The rows of it are
+        // a multiple of 4 bytes, so the default pack alignment fits.
+        gl.read_pixels(
+            0,
+            0,
+            width as i32,
+            height as i32,
+            RGBA,
+            UNSIGNED_BYTE,
+            PixelPackData::Slice(Some(&mut pixels)),
+        );
+    }
+
+    // GL reads the rows bottom to top, PNG stores them top to bottom
+    let flipped: Vec<u8> = pixels.chunks_exact(row).rev().flatten().copied().collect();
+    image::save_buffer(path, &flipped, width, height, ExtendedColorType::Rgba8)
+}
@@ -0,0 +1,106 @@
+//! What the GL objects of the scene have in common: shaders written once for every GLSL
+//! dialect the examples may get a context for.
+
+use glow::{Context, HasContext, NativeProgram, FRAGMENT_SHADER, VERTEX_SHADER};
+use wayland_app_common::GlInitError;
+
+/// The GLSL dialect of the current context. The shader sources are written against a few
+/// macros so the same code compiles as GLSL ES 1.00, legacy desktop GLSL and core profile GLSL.
+pub(crate) struct Dialect {
+    pub vertex_header: &'static str,
+    pub fragment_header: &'static str,
+    /// Core profiles refuse to draw without a vertex array object bound.
+    pub needs_vertex_array: bool,
+}
+
+impl Dialect {
+    pub fn of(gl: &Context) -> Dialect {
+        let version = gl.version();
+        if version.is_embedded {
+            // Every GLES context understands GLSL ES 1.00, GLES2 knows nothing else
+            Dialect {
+                vertex_header: "#version 100\n\
+                    #define ATTRIBUTE attribute\n\
+                    #define VARYING varying\n",
+                fragment_header: "#version 100\n\
+                    precision mediump float;\n\
+                    #define VARYING varying\n\
+                    #define FRAG_COLOR gl_FragColor\n\
+                    #define TEXTURE texture2D\n",
+                needs_vertex_array: false,
+            }
+        } else if (version.major, version.minor) >= (3, 3) {
+            Dialect {
+                vertex_header: "#version 330 core\n\
+                    #define ATTRIBUTE in\n\
+                    #define VARYING out\n",
+                fragment_header: "#version 330 core\n\
+                    #define VARYING in\n\
+                    out vec4 frag_color;\n\
+                    #define FRAG_COLOR frag_color\n\
+                    #define TEXTURE texture\n",
+                needs_vertex_array: true,
+            }
+        } else {
+            Dialect {
+                vertex_header: "#version 110\n\
+                    #define ATTRIBUTE attribute\n\
+                    #define VARYING varying\n",
+                fragment_header: "#version 110\n\
+                    #define VARYING varying\n\
+                    #define FRAG_COLOR gl_FragColor\n\
+                    #define TEXTURE texture2D\n",
+                needs_vertex_array: false,
+            }
+        }
+    }
+}
+
+/// Compiles and links the shader pair. Failures carry the info log of the stage that failed.
+pub(crate) unsafe fn link_program(
+    gl: &Context,
+    vertex_source: &str,
+    fragment_source: &str,
+) -> Result<NativeProgram, GlInitError> {
+    unsafe {
+        let program = gl.create_program().map_err(GlInitError::Shader)?;
+
+        let mut shaders = Vec::new();
+        for (kind, name, source) in [
+            (VERTEX_SHADER, "vertex", vertex_source),
+            (FRAGMENT_SHADER, "fragment", fragment_source),
+        ] {
+            let shader = gl.create_shader(kind).map_err(GlInitError::Shader)?;
+            gl.shader_source(shader, source);
+            gl.compile_shader(shader);
+            if !gl.get_shader_compile_status(shader) {
+                let log = gl.get_shader_info_log(shader);
+                gl.delete_shader(shader);
+                gl.delete_program(program);
+                return Err(GlInitError::Shader(format!(
+                    "{name} shader failed to compile:\n{log}"
+                )));
+            }
+            gl.attach_shader(program, shader);
+            shaders.push(shader);
+        }
+
+        gl.link_program(program);
+        let linked = gl.get_program_link_status(program);
+
+        // The program keeps what it needs, the shader objects can go either way
+        for shader in shaders {
+            gl.detach_shader(program, shader);
+            gl.delete_shader(shader);
+        }
+
+        if !linked {
+            let log = gl.get_program_info_log(program);
+            gl.delete_program(program);
+            return Err(GlInitError::Shader(format!("shader program failed to link:\n{log}")));
+        }
+
+        log::debug!("shader program linked");
+        Ok(program)
+    }
+}
@@ -0,0 +1,223 @@
+//! The fallback when GL can't be set up, e.g. without a GPU or in a container without the
+//! drivers: the CPU draws the frames into wl_shm buffers. It only knows filled rectangles and
+//! triangles with their corner colors blended across, which is what the background, the
+//! decorations and the built-in scenes are made of. Images, the quads, the canvas and the
+//! overlay are left out. The windows still show up, and the configures, the frame callbacks and
+//! the input work the same as with GL, which is what the fallback is for.
+//!
+//! `--force-software` draws this way also where GL works.
+
+use std::collections::HashMap;
+
+use smithay_client_toolkit::shell::WaylandSurface;
+use wayland_app_common::{BaseApp, GlInitError, ShmBuffers};
+use wayland_client::backend::ObjectId;
+
+use crate::{damage::intersection, Renderer, Scene, View};
+
+/// A frame in memory, what a [`Renderer`] draws into instead of a framebuffer. Rectangles and
+/// points are in GL buffer coordinates with the origin at the bottom left, see
+/// [`BaseApp::buffer_rect`], so the rectangles of the window state fit as they are.
+pub struct Pixels<'a> {
+    /// Premultiplied Argb8888, the top row first.
+    data: &'a mut [u8],
+    width: u32,
+    height: u32,
+    /// Where the scene goes, like the GL viewport. The content area of the window.
+    pub viewport: [i32; 4],
+}
+
+impl Pixels<'_> {
+    /// The whole buffer.
+    pub fn bounds(&self) -> [i32; 4] {
+        [0, 0, self.width as i32, self.height as i32]
+    }
+
+    /// Fills the part of the rectangle within the buffer.
+    pub fn fill(&mut self, rect: [i32; 4], color: [f32; 3]) {
+        self.fill_translucent(rect, color, 1.0);
+    }
+
+    /// [`Self::fill`] with an alpha, the pixels are replaced like by `glClear`.
+    pub fn fill_translucent(&mut self, rect: [i32; 4], color: [f32; 3], alpha: f32) {
+        let Some([x, y, width, height]) = intersection(rect, self.bounds()) else {
+            return;
+        };
+        let pixel = argb(color, alpha);
+        for row in y..y + height {
+            for column in x..x + width {
+                self.put(column, row, pixel);
+            }
+        }
+    }
+
+    /// Fills the triangle between the corners with their colors blended across, like GL
+    /// interpolates a varying. Only the pixels with their centers inside of it are drawn, and
+    /// only those within the viewport.
+    pub fn triangle(&mut self, corners: [[f32; 2]; 3], colors: [[f32; 3]; 3]) {
+        let [a, b, c] = corners;
+        let area = edge(a, b, c);
+        if area == 0.0 {
+            return;
+        }
+        let (xs, ys) = (corners.map(|[x, _]| x), corners.map(|[_, y]| y));
+        let left = xs.iter().copied().fold(f32::INFINITY, f32::min).floor() as i32;
+        let right = xs.iter().copied().fold(f32::NEG_INFINITY, f32::max).ceil() as i32;
+        let bottom = ys.iter().copied().fold(f32::INFINITY, f32::min).floor() as i32;
+        let top = ys.iter().copied().fold(f32::NEG_INFINITY, f32::max).ceil() as i32;
+        let Some(area_box) = intersection(self.viewport, self.bounds())
+            .and_then(|clip| intersection([left, bottom, right - left, top - bottom], clip))
+        else {
+            return;
+        };
+
+        let [x, y, width, height] = area_box;
+        for row in y..y + height {
+            for column in x..x + width {
+                let center = [column as f32 + 0.5, row as f32 + 0.5];
+                // Either winding, the weights are all positive inside
+                let weights = [edge(b, c, center), edge(c, a, center), edge(a, b, center)]
+                    .map(|weight| weight / area);
+                if weights.iter().any(|&weight| weight < 0.0) {
+                    continue;
+                }
+                let color = std::array::from_fn(|channel| {
+                    (0..3).map(|corner| weights[corner] * colors[corner][channel]).sum()
+                });
+                self.put(column, row, argb(color, 1.0));
+            }
+        }
+    }
+
+    /// Writes the pixel at the point in GL buffer coordinates, the rows count from the bottom.
+    fn put(&mut self, x: i32, y: i32, pixel: [u8; 4]) {
+        let row = (self.height as i32 - 1 - y) as usize;
+        let offset = (row * self.width as usize + x as usize) * 4;
+        self.data[offset..offset + 4].copy_from_slice(&pixel);
+    }
+}
+
+/// Twice the signed area of the triangle `a`, `b`, `p`, positive when `p` is left of `a` to
+/// `b`.
+fn edge(a: [f32; 2], b: [f32; 2], p: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+/// The bytes of a premultiplied Argb8888 pixel. It is stored little endian, so blue comes
+/// first.
+fn argb(color: [f32; 3], alpha: f32) -> [u8; 4] {
+    let alpha = alpha.clamp(0.0, 1.0);
+    let [red, green, blue] = color.map(|c| (c.clamp(0.0, 1.0) * alpha * 255.0).round() as u8);
+    [blue, green, red, (alpha * 255.0).round() as u8]
+}
+
+/// The software fallback of an example, one scene drawn into the buffers of every window.
+pub struct Software {
+    scene: Scene,
+    /// The buffers of each window, keyed by [`BaseApp::id`].
+    windows: HashMap<ObjectId, ShmBuffers>,
+}
+
+impl Software {
+    /// Draws the scene of the renderer from now on. There is no context, so the renderer is
+    /// not initialized, it only draws with [`Renderer::draw_pixels`].
+    pub fn new(renderer: Box<dyn Renderer>) -> Software {
+        Software {
+            scene: Scene::software(renderer),
+            windows: HashMap::new(),
+        }
+    }
+
+    /// Whether the window has its buffer pool.
+    pub fn is_open(&self, id: &ObjectId) -> bool {
+        self.windows.contains_key(id)
+    }
+
+    /// Creates the buffer pool of the window, on its first configure.
+    pub fn open(&mut self, base: &BaseApp) -> Result<(), GlInitError> {
+        let buffers = ShmBuffers::new(base)
+            .map_err(|err| GlInitError::Surface(format!("no wl_shm pool: {err}")))?;
+        self.windows.insert(base.id(), buffers);
+        Ok(())
+    }
+
+    /// Draws the frame of the window into a buffer the compositor is done with, damages all of
+    /// it and commits.
+    pub fn draw(&mut self, base: &mut BaseApp) {
+        // There is no canvas, the strokes would only pile up
+        base.take_strokes();
+        if base.screenshot {
+            base.screenshot = false;
+            println!("no screenshots in software, only GL reads its frames back");
+        }
+        let Some((width, height)) = base.non_zero_buffer_size() else {
+            return;
+        };
+        let (width, height) = (width.get(), height.get());
+        let Some(buffers) = self.windows.get_mut(&base.id()) else {
+            return;
+        };
+        let frame = match buffers.acquire(width, height) {
+            Ok(frame) => frame,
+            Err(err) => {
+                // Commit anyway, so the frame callback request goes out and the window keeps
+                // trying
+                log::warn!("no buffer for window {}: {err}", base.id());
+                base.window().wl_surface().commit();
+                return;
+            }
+        };
+        let mut pixels = Pixels {
+            data: frame.canvas,
+            width,
+            height,
+            viewport: base.content_rect(),
+        };
+        draw_frame(&mut self.scene, &mut pixels, base);
+
+        let surface = base.window().wl_surface();
+        surface.damage_buffer(0, 0, width as i32, height as i32);
+        if let Err(err) = frame.buffer.attach_to(surface) {
+            log::warn!("failed to attach the buffer of window {}: {err}", base.id());
+        }
+        surface.commit();
+    }
+
+    /// Drops the buffers of the window, once the compositor releases them.
+    pub fn close(&mut self, id: &ObjectId) {
+        self.windows.remove(id);
+    }
+}
+
+/// Draws what the GL examples draw without the GL-only parts: the background, the test
+/// pattern, the decorations and the rest of the rectangles of the window state, then the
+/// scene in the content area.
+fn draw_frame(scene: &mut Scene, pixels: &mut Pixels, base: &BaseApp) {
+    let [red, green, blue, alpha] = base.background();
+    pixels.fill_translucent(pixels.bounds(), [red, green, blue], alpha);
+    for rect in base.test_pattern() {
+        pixels.fill(rect, [1.0, 1.0, 1.0]);
+    }
+    for (rect, color) in base.decoration_rects() {
+        pixels.fill(rect, color);
+    }
+    for rect in base.confinement_outline() {
+        pixels.fill(rect, [1.0, 0.5, 0.0]);
+    }
+    for (rect, color) in base.touch_rects() {
+        pixels.fill(rect, color);
+    }
+    for (rect, color) in base.text_rects() {
+        pixels.fill(rect, color);
+    }
+    for (rect, color) in base.shortcuts_badge_rects() {
+        pixels.fill(rect, color);
+    }
+
+    // The buffers hold the colors as they are, like a framebuffer without sRGB
+    let view = View {
+        srgb: false,
+        ..View::of(base)
+    };
+    scene.draw_pixels(pixels, &view);
+}
@@ -0,0 +1,44 @@
+//! sRGB framebuffers. The colors of the scene are sRGB values, they look right on a plain
+//! framebuffer as they are. An sRGB framebuffer encodes what is written to it, so it gets
+//! linear values instead: clear colors go through [`framebuffer_color`], the shaders through
+//! the GLSL function of the same name. Blending and the gradient of the triangle then happen
+//! in linear light, which is the visible difference.
+
+use glow::{Context, HasContext, FRAMEBUFFER_SRGB};
+
+/// The GLSL version of [`framebuffer_color`], for the fragment shaders after the dialect
+/// header. The `srgb_framebuffer` uniform switches it.
+pub(crate) const SHADER_SOURCE: &str = r#"
+uniform bool srgb_framebuffer;
+
+vec3 framebuffer_color(vec3 color) {
+    if (!srgb_framebuffer) {
+        return color;
+    }
+    return mix(color / 12.92, pow((color + 0.055) / 1.055, vec3(2.4)), step(0.04045, color));
+}
+"#;
+
+/// The value to write for an sRGB color, decoded to linear light when the framebuffer encodes
+/// it again.
+pub fn framebuffer_color(color: [f32; 3], srgb: bool) -> [f32; 3] {
+    if !srgb {
+        return color;
+    }
+    color.map(|c| if c < 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) })
+}
+
+/// Lets the current framebuffer encode or stops it. GLES encodes whenever the surface is sRGB,
+/// desktop GL only while `GL_FRAMEBUFFER_SRGB` is on.
+pub fn set_encoding(gl: &Context, srgb: bool) {
+    if gl.version().is_embedded {
+        return;
+    }
+    unsafe {
+        if srgb {
+            gl.enable(FRAMEBUFFER_SRGB);
+        } else {
+            gl.disable(FRAMEBUFFER_SRGB);
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use glow::{
+    Context, HasContext, NativeBuffer, NativeUniformLocation, NativeVertexArray, ARRAY_BUFFER,
+    FLOAT, STATIC_DRAW, TRIANGLES,
+};
+use wayland_app_common::{BaseApp, GlInitError};
+
+use crate::{
+    shader::{link_program, Dialect},
+    srgb, Pixels, View,
+};
+
+/// Interleaved position and color of the triangle corners.
+#[rustfmt::skip]
+const VERTICES: [f32; 15] = [
+    // x, y, r, g, b
+     0.0,  0.8, 1.0, 0.0, 0.0,
+    -0.7, -0.4, 0.0, 1.0, 0.0,
+     0.7, -0.4, 0.0, 0.0, 1.0,
+];
+
+/// Distance of the farthest corner from the center, whatever the angle the triangle stays
+/// within a circle this large.
+const RADIUS: f32 = 0.81;
+
+/// Radians per millisecond, a full turn takes about six seconds.
+const ROTATION_SPEED: f32 = std::f32::consts::TAU / 6000.0;
+
+const VERTEX_SHADER_SOURCE: &str = r#"
+ATTRIBUTE vec2 position;
+ATTRIBUTE vec3 color;
+VARYING vec3 v_color;
+
+uniform float angle;
+uniform vec2 aspect;
+// Where the arrow keys moved the triangle, in normalized device coordinates
+uniform vec2 offset;
+// Maps upright surface coordinates to the rotated buffer, see BaseApp::ndc_transform
+uniform mat2 transform;
+
+void main() {
+    mat2 rotation = mat2(cos(angle), sin(angle), -sin(angle), cos(angle));
+    gl_Position = vec4(transform * (rotation * position * aspect + offset), 0.0, 1.0);
+    v_color = color;
+}
+"#;
+
+const FRAGMENT_SHADER_SOURCE: &str = r#"
+VARYING vec3 v_color;
+
+void main() {
+    FRAG_COLOR = vec4(framebuffer_color(v_color), 1.0);
+}
+"#;
+
+/// A colored triangle rotating with the frame callback time.
+pub struct Triangle {
+    program: glow::NativeProgram,
+    vertex_buffer: NativeBuffer,
+    vertex_array: Option<NativeVertexArray>,
+    angle: Option<NativeUniformLocation>,
+    aspect: Option<NativeUniformLocation>,
+    offset: Option<NativeUniformLocation>,
+    transform: Option<NativeUniformLocation>,
+    srgb_framebuffer: Option<NativeUniformLocation>,
+}
+
+impl Triangle {
+    /// Compiles the shaders and uploads the vertices, the context must be current.
+    pub fn new(gl: &Context) -> Result<Triangle, GlInitError> {
+        let dialect = Dialect::of(gl);
+
+        unsafe {
+            let program = link_program(
+                gl,
+                &format!("{}{VERTEX_SHADER_SOURCE}", dialect.vertex_header),
+                &format!(
+                    "{}{}{FRAGMENT_SHADER_SOURCE}",
+                    dialect.fragment_header,
+                    srgb::SHADER_SOURCE
+                ),
+            )?;
+
+            let vertex_array = if dialect.needs_vertex_array {
+                let vertex_array = gl.create_vertex_array().map_err(GlInitError::Shader)?;
+                gl.bind_vertex_array(Some(vertex_array));
+                Some(vertex_array)
+            } else {
+                None
+            };
+
+            let vertex_buffer = gl.create_buffer().map_err(GlInitError::Shader)?;
+            gl.bind_buffer(ARRAY_BUFFER, Some(vertex_buffer));
+            let bytes: Vec<u8> = VERTICES.iter().flat_map(|v| v.to_ne_bytes()).collect();
+            gl.buffer_data_u8_slice(ARRAY_BUFFER, &bytes, STATIC_DRAW);
+
+            let triangle = Triangle {
+                angle: gl.get_uniform_location(program, "angle"),
+                aspect: gl.get_uniform_location(program, "aspect"),
+                offset: gl.get_uniform_location(program, "offset"),
+                transform: gl.get_uniform_location(program, "transform"),
+                srgb_framebuffer: gl.get_uniform_location(program, "srgb_framebuffer"),
+                program,
+                vertex_buffer,
+                vertex_array,
+            };
+            triangle.bind_attributes(gl);
+
+            if vertex_array.is_some() {
+                gl.bind_vertex_array(None);
+            }
+            gl.bind_buffer(ARRAY_BUFFER, None);
+            Ok(triangle)
+        }
+    }
+
+    /// Points the attributes at the vertex buffer, which must be bound.
+    unsafe fn bind_attributes(&self, gl: &Context) {
+        const STRIDE: i32 = 5 * size_of::<f32>() as i32;
+
+        unsafe {
+            for (name, size, offset) in [("position", 2, 0), ("color", 3, 2)] {
+                let Some(location) = gl.get_attrib_location(self.program, name) else {
+                    continue;
+                };
+                gl.enable_vertex_attrib_array(location);
+                gl.vertex_attrib_pointer_f32(
+                    location,
+                    size,
+                    FLOAT,
+                    false,
+                    STRIDE,
+                    offset * size_of::<f32>() as i32,
+                );
+            }
+        }
+    }
+
+    /// Draws the triangle into the current viewport, which should be the content area.
+    pub fn draw(&self, gl: &Context, view: &View) {
+        // Keep the triangle from stretching with the window, the content size is logical so
+        // this is independent of the scale and transform
+        let (width, height) = view.content_size;
+        let (width, height) = (width.max(1) as f32, height.max(1) as f32);
+        let side = width.min(height);
+        let zoom = view.zoom;
+
+        unsafe {
+            gl.use_program(Some(self.program));
+            let angle =
+                (view.time as f32 * ROTATION_SPEED + view.look_angle) % std::f32::consts::TAU;
+            gl.uniform_1_f32(self.angle.as_ref(), angle);
+            gl.uniform_2_f32(self.aspect.as_ref(), zoom * side / width, zoom * side / height);
+            let (x, y) = view.triangle_offset;
+            gl.uniform_2_f32(self.offset.as_ref(), 2.0 * x / width, -2.0 * y / height);
+            gl.uniform_matrix_2_f32_slice(self.transform.as_ref(), false, &view.ndc_transform);
+            gl.uniform_1_i32(self.srgb_framebuffer.as_ref(), view.srgb as i32);
+
+            match self.vertex_array {
+                Some(vertex_array) => gl.bind_vertex_array(Some(vertex_array)),
+                None => {
+                    gl.bind_buffer(ARRAY_BUFFER, Some(self.vertex_buffer));
+                    self.bind_attributes(gl);
+                }
+            }
+
+            gl.draw_arrays(TRIANGLES, 0, 3);
+
+            if self.vertex_array.is_some() {
+                gl.bind_vertex_array(None);
+            }
+            gl.bind_buffer(ARRAY_BUFFER, None);
+            gl.use_program(None);
+        }
+    }
+
+    /// Draws the triangle into the viewport of the pixels of the software fallback, with the
+    /// math of the vertex shader.
+    pub fn draw_pixels(pixels: &mut Pixels, view: &View) {
+        let (width, height) = view.content_size;
+        let (width, height) = (width.max(1) as f32, height.max(1) as f32);
+        let side = width.min(height);
+        let zoom = view.zoom;
+        let angle = (view.time as f32 * ROTATION_SPEED + view.look_angle) % std::f32::consts::TAU;
+        let (sin, cos) = angle.sin_cos();
+        let aspect = [zoom * side / width, zoom * side / height];
+        let (x, y) = view.triangle_offset;
+        let offset = [2.0 * x / width, -2.0 * y / height];
+        let [m0, m1, m2, m3] = view.ndc_transform;
+        let [viewport_x, viewport_y, viewport_width, viewport_height] = pixels.viewport;
+
+        let corner = |vertex: &[f32]| {
+            let [x, y] = [vertex[0], vertex[1]];
+            let [x, y] = [
+                (cos * x - sin * y) * aspect[0] + offset[0],
+                (sin * x + cos * y) * aspect[1] + offset[1],
+            ];
+            // Column major like the uniform
+            let [x, y] = [m0 * x + m2 * y, m1 * x + m3 * y];
+            [
+                viewport_x as f32 + (x + 1.0) / 2.0 * viewport_width as f32,
+                viewport_y as f32 + (y + 1.0) / 2.0 * viewport_height as f32,
+            ]
+        };
+        let color = |vertex: &[f32]| [vertex[2], vertex[3], vertex[4]];
+        let vertices = [0, 1, 2].map(|index| &VERTICES[index * 5..index * 5 + 5]);
+        pixels.triangle(vertices.map(corner), vertices.map(color));
+    }
+
+    /// The square the triangle stays within at any angle, in GL buffer coordinates, see
+    /// [`BaseApp::buffer_rect`]. It is cut to the content area, the triangle is clipped to the
+    /// viewport anyway. `None` when the triangle was moved out of sight.
+    pub fn bounds(base: &BaseApp) -> Option<[i32; 4]> {
+        circle_bounds(base, RADIUS * base.zoom, base.triangle_offset)
+    }
+
+    /// Deletes the GL objects, the context must still be current.
+    pub fn destroy(self, gl: &Context) {
+        unsafe {
+            gl.delete_program(self.program);
+            gl.delete_buffer(self.vertex_buffer);
+            if let Some(vertex_array) = self.vertex_array {
+                gl.delete_vertex_array(vertex_array);
+            }
+        }
+    }
+}
+
+/// The square around a circle of the radius, relative to the smaller side of the content area
+/// like the shapes, cut to the content area. The offset moves the center away from the middle
+/// of the content area, in logical pixels. `None` when nothing of it is left.
+pub(crate) fn circle_bounds(base: &BaseApp, radius: f32, offset: (f32, f32)) -> Option<[i32; 4]> {
+    let [x, y, width, height] = base.content_area();
+    let side = width.min(height) as f32;
+    // A pixel more to cover the partly covered pixels at the edges
+    let radius = radius * side / 2.0 + 1.0;
+    let (offset_x, offset_y) = offset;
+    let center_x = x as f32 + width as f32 / 2.0 + offset_x;
+    let center_y = y as f32 + height as f32 / 2.0 + offset_y;
+
+    let clamp_x = |v: f32| v.clamp(x as f32, (x + width) as f32) as u32;
+    let clamp_y = |v: f32| v.clamp(y as f32, (y + height) as f32) as u32;
+    let (left, right) = (clamp_x(center_x - radius), clamp_x((center_x + radius).ceil()));
+    let (top, bottom) = (clamp_y(center_y - radius), clamp_y((center_y + radius).ceil()));
+    if left == right || top == bottom {
+        return None;
+    }
+    Some(base.buffer_rect(left, top, right - left, bottom - top))
+}
@@ -0,0 +1,60 @@
+//! What the shapes are drawn from. Windows take it from their [`BaseApp`], `--offscreen` has no
+//! window and makes it up for a buffer of its own.
+
+use wayland_app_common::BaseApp;
+
+/// The part of the window state the triangle, the quads and the image look at.
+#[derive(Debug, Clone, Copy)]
+pub struct View {
+    /// Logical size of the content area, the shapes keep their proportions within it.
+    pub content_size: (u32, u32),
+    /// See [`BaseApp::ndc_transform`].
+    pub ndc_transform: [f32; 4],
+    /// Milliseconds, drives the animation.
+    pub time: u32,
+    pub look_angle: f32,
+    pub zoom: f32,
+    /// See [`BaseApp::triangle_offset`].
+    pub triangle_offset: (f32, f32),
+    /// Whether the framebuffer encodes sRGB, see [`crate::srgb`].
+    pub srgb: bool,
+    /// Whether the quads clear the stencil buffer along with the depth buffer.
+    pub stencil: bool,
+    /// Whether the window shows no scene, e.g. a dialog, see [`BaseApp::plain`].
+    pub plain: bool,
+}
+
+impl View {
+    /// The view of the content area of a window.
+    pub fn of(base: &BaseApp) -> View {
+        let [_, _, width, height] = base.content_area();
+        View {
+            content_size: (width, height),
+            ndc_transform: base.ndc_transform(),
+            time: base.time,
+            look_angle: base.look_angle,
+            zoom: base.zoom,
+            triangle_offset: base.triangle_offset,
+            srgb: base.srgb,
+            stencil: base.stencil,
+            plain: base.plain(),
+        }
+    }
+
+    /// The view of an untransformed buffer of the size, the same as the content of a focused
+    /// window that size at scale 1 with server-side decorations shows at `time`, as long as
+    /// the pointer is elsewhere and nothing moved or zoomed the triangle.
+    pub fn offscreen(size: (u32, u32), time: u32, srgb: bool, stencil: bool) -> View {
+        View {
+            content_size: size,
+            ndc_transform: [1.0, 0.0, 0.0, 1.0],
+            time,
+            look_angle: 0.0,
+            zoom: 1.0,
+            triangle_offset: (0.0, 0.0),
+            srgb,
+            stencil,
+            plain: false,
+        }
+    }
+}
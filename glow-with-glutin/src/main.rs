@@ -1,105 +1,130 @@
-use std::num::NonZeroU32;
-
-use smithay_client_toolkit::{
-    compositor::{CompositorHandler, CompositorState},
-    delegate_compositor, delegate_output, delegate_registry, delegate_seat, delegate_xdg_shell,
-    delegate_xdg_window,
-    output::{OutputHandler, OutputState},
-    registry::{ProvidesRegistryState, RegistryState},
-    registry_handlers,
-    seat::{Capability, SeatHandler, SeatState},
-    shell::{
-        xdg::{
-            window::{Window, WindowConfigure, WindowDecorations, WindowHandler},
-            XdgShell,
-        },
-        WaylandSurface,
-    },
+use std::{collections::HashMap, ffi::CStr, num::NonZeroU32, sync::Arc, time::Instant};
+
+use smithay_client_toolkit::shell::{xdg::window::WindowConfigure, WaylandSurface};
+use wayland_app_common::{
+    App, AppHandler, BaseApp, DroppedImage, GlApi, GlInitError, Options, DEFAULT_SIZE,
 };
-use wayland_client::{
-    globals::registry_queue_init,
-    protocol::{wl_output, wl_seat, wl_surface},
-    Connection, Proxy, QueueHandle,
+use wayland_client::{backend::ObjectId, Proxy, QueueHandle};
+use glow_common::{
+    damage::OUTLINE_COLOR,
+    info::{self, EglInfo, CONFIG_ATTRIBUTES},
+    msaa, screenshot,
+    srgb::{self, framebuffer_color},
+    best_config, builtin_renderer, config_table, depth_stencil_sizes, Canvas, ConfigInfo,
+    DamageTracker, GlDebug, ImageQuad, Quads, RendererFactory, ResetStatus, Scene, Sheet,
+    Software, View,
 };
 use raw_window_handle::{
     HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle, WaylandDisplayHandle,
     WaylandWindowHandle,
 };
 use glutin::{
-    config::ConfigTemplateBuilder,
-    context::{ContextAttributesBuilder, PossiblyCurrentContext},
-    surface::{Surface, SurfaceAttributesBuilder, WindowSurface},
+    config::{Api, AsRawConfig, ColorBufferType, Config, ConfigTemplateBuilder, RawConfig},
+    context::{
+        ContextApi, ContextAttributesBuilder, GlProfile, NotCurrentContext,
+        PossiblyCurrentContext, Robustness, Version,
+    },
+    display::{AsRawDisplay, Display, DisplayFeatures, GetDisplayExtensions, RawDisplay},
+    error::ErrorKind,
+    surface::{
+        PbufferSurface, Rect, Surface, SurfaceAttributesBuilder, SwapInterval, WindowSurface,
+    },
 };
-use glow::{Context, HasContext, COLOR_BUFFER_BIT, RENDERER, VERSION};
+use glow::{Context, HasContext, COLOR_BUFFER_BIT, RENDERER, SCISSOR_TEST, VERSION};
 
 fn main() {
-    env_logger::init();
-
-    let conn = Connection::connect_to_env().unwrap();
-    let (globals, mut event_queue) = registry_queue_init(&conn).unwrap();
-    let qh = event_queue.handle();
-
-    // Initialize xdg_shell handlers
-    let compositor_state =
-        CompositorState::bind(&globals, &qh).expect("wl_compositor not available");
-    let xdg_shell_state = XdgShell::bind(&globals, &qh).expect("xdg shell not available");
-
-    let surface = compositor_state.create_surface(&qh);
-    // Create the window
-    let window = xdg_shell_state.create_window(surface, WindowDecorations::ServerDefault, &qh);
-    window.set_title("glow wayland window");
-    // GitHub does not let projects use the `org.github` domain but the `io.github` domain is fine.
-    window.set_app_id("io.github.smithay.client-toolkit.GlowExample");
-    window.set_min_size(Some((256, 256)));
-    window.commit();
-
-    let mut glow_app = GlowApp {
-        registry_state: RegistryState::new(&globals),
-        seat_state: SeatState::new(&globals, &qh),
-        output_state: OutputState::new(&globals, &qh),
-
-        exit: false,
-        width: 256,
-        height: 256,
-        window,
-        conn,
-
-        gl_display_context: None,
-        gl_surface: None,
-        gl_context: None,
-    };
-
-    // We don't draw immediately, the configure will notify us when to first draw.
-    loop {
-        event_queue.blocking_dispatch(&mut glow_app).unwrap();
-
-        if glow_app.exit {
-            println!("exiting example");
-            break;
-        }
+    let options = Options::from_args();
+    let mut logger = env_logger::Builder::from_default_env();
+    if let Some(level) = options.log_level {
+        logger.filter_level(level);
     }
-
-    // Clean up in the proper order
-    drop(glow_app.gl_context);
-    drop(glow_app.gl_surface);
-    drop(glow_app.gl_display_context);
-    drop(glow_app.window);
+    logger.init();
+
+    wayland_app_common::run_app(
+        options,
+        |options| {
+            // GitHub does not let projects use the `org.github` domain but the `io.github`
+            // domain is fine.
+            let scene = options.scene;
+            let new_renderer: RendererFactory = Arc::new(move || builtin_renderer(scene));
+            wayland_app_common::init_with_options(
+                "glow wayland window",
+                "io.github.smithay.client-toolkit.GlowExample",
+                GlowApp {
+                    gl_display: None,
+                    windows: HashMap::new(),
+                    software: options.force_software.then(|| Software::new(new_renderer())),
+                    new_renderer,
+                },
+                options,
+            )
+        },
+        |mut app| {
+            // Clean up in the proper order, the GL state of every window goes before the
+            // windows
+            for (_, window) in app.handler.windows.drain() {
+                window.destroy();
+            }
+            // The display belongs to the connection, after a reconnect EGL might hand it out
+            // again for the new one
+            if let Some((Display::Egl(display), _)) = app.handler.gl_display.take() {
+                unsafe { display.terminate() };
+            }
+            drop(app.handler);
+            drop(app.windows);
+        },
+    );
 }
 
 struct GlowApp {
-    registry_state: RegistryState,
-    seat_state: SeatState,
-    output_state: OutputState,
-
-    exit: bool,
-    width: u32,
-    height: u32,
-    window: Window,
-    conn: Connection,
-
-    gl_display_context: Option<PossiblyCurrentContext>,
-    gl_surface: Option<Surface<WindowSurface>>,
-    gl_context: Option<Context>,
+    /// Set up with the first window and shared by all of them.
+    gl_display: Option<(Display, Config)>,
+    /// The GL state of each window, keyed by [`BaseApp::id`].
+    windows: HashMap<ObjectId, GlWindow>,
+    /// Draws all windows instead of GL with `--force-software`, or once GL couldn't be set up
+    /// for the first window.
+    software: Option<Software>,
+    /// Makes the scene of each window, any [`glow_common::Renderer`] will do.
+    new_renderer: RendererFactory,
+}
+
+/// A context and surface per window, so the windows don't depend on each other.
+struct GlWindow {
+    context: PossiblyCurrentContext,
+    surface: Surface<WindowSurface>,
+    gl: Context,
+    debug: GlDebug,
+    reset_status: ResetStatus,
+    /// What is drawn in the content area.
+    scene: Scene,
+    /// The 3D scene of `--depth`.
+    quads: Quads,
+    /// The last image dropped onto the window.
+    image: Option<ImageQuad>,
+    /// What a tablet drew on the window, and what it is drawn with.
+    canvas: Canvas,
+    sheet: Sheet,
+    /// What the latest frames changed, for telling the compositor.
+    damage: DamageTracker,
+}
+
+impl GlWindow {
+    /// Frees the GL objects while the context is still current, then the context and surface.
+    fn destroy(self) {
+        use glutin::prelude::*;
+
+        if let Err(err) = self.context.make_current(&self.surface) {
+            log::warn!("failed to make the context current for cleanup: {err}");
+            return;
+        }
+        self.scene.destroy(&self.gl);
+        self.quads.destroy(&self.gl);
+        if let Some(image) = self.image {
+            image.destroy(&self.gl);
+        }
+        self.canvas.destroy(&self.gl);
+        self.sheet.destroy(&self.gl);
+    }
 }
 
 // Implement raw-window-handle traits for our window
@@ -123,277 +148,686 @@ impl HasWindowHandle for WaylandWindow {
 }
 
 impl GlowApp {
-    fn init_gl(&mut self) {
+    /// Sets up the context and surface of a window, the display is only created once.
+    fn init_gl(&mut self, base: &mut BaseApp) -> Result<GlWindow, GlInitError> {
         use glutin::prelude::*;
 
         // Create a window handle for glutin
         let wayland_window = WaylandWindow {
-            display: self.conn.backend().display_ptr() as *mut _,
-            surface: self.window.wl_surface().id().as_ptr() as *mut _,
-        };
-
-        // Create glutin display
-        let gl_display = unsafe {
-            glutin::display::Display::new(
-                wayland_window.display_handle().unwrap().as_raw(),
-                glutin::display::DisplayApiPreference::Egl,
-            )
-            .expect("Failed to create GL display")
-        };
-
-        // Configure the GL context
-        let config_template = ConfigTemplateBuilder::new()
-            .with_alpha_size(8)
-            .with_transparency(true)
-            .build();
-
-        let config = unsafe {
-            gl_display
-                .find_configs(config_template)
-                .expect("Failed to find GL configs")
-                .next()
-                .expect("No GL config found")
+            display: base.connection().backend().display_ptr() as *mut _,
+            surface: base.window().wl_surface().id().as_ptr() as *mut _,
         };
 
-        println!("Using GL config: {:?}", config);
+        if self.gl_display.is_none() {
+            self.gl_display = Some(init_display(&wayland_window, base)?);
+        }
+        let (gl_display, config) = self.gl_display.as_ref().unwrap();
 
         // Create GL context
-        let context_attributes = ContextAttributesBuilder::new()
-            .build(Some(wayland_window.window_handle().unwrap().as_raw()));
+        let debug = GlDebug::wanted(base);
+        // A robust context reports a GPU reset instead of leaving the app with a broken one
+        let robust = gl_display.supported_features().contains(DisplayFeatures::CONTEXT_ROBUSTNESS);
+        if !robust {
+            log::info!("no robust contexts, GPU resets may go unnoticed");
+        }
+        let context_attributes = |api: Option<GlApi>| {
+            let mut builder = ContextAttributesBuilder::new().with_debug(debug);
+            if robust {
+                builder = builder.with_robustness(Robustness::RobustLoseContextOnReset);
+            }
+            // The shaders need no more than these versions, see `Dialect`
+            builder = match api {
+                Some(GlApi::Gles2) => {
+                    builder.with_context_api(ContextApi::Gles(Some(Version::new(2, 0))))
+                }
+                Some(GlApi::Gles3) => {
+                    builder.with_context_api(ContextApi::Gles(Some(Version::new(3, 0))))
+                }
+                Some(GlApi::Gl) => builder
+                    .with_context_api(ContextApi::OpenGl(Some(Version::new(3, 3))))
+                    .with_profile(GlProfile::Core),
+                None => builder,
+            };
+            builder.build(Some(wayland_window.window_handle().unwrap().as_raw()))
+        };
 
-        let context = unsafe {
-            gl_display
-                .create_context(&config, &context_attributes)
-                .expect("Failed to create GL context")
+        // Try the API `--api` asks for first, then the ones below it. Without it glutin picks.
+        let apis: Vec<Option<GlApi>> = match base.gl_api {
+            Some(api) => api.fallbacks().iter().copied().map(Some).collect(),
+            None => vec![None],
         };
+        let mut created = Err(GlInitError::Context("no API to try".to_string()));
+        for api in apis {
+            created = unsafe { gl_display.create_context(config, &context_attributes(api)) }
+                .map(|context| (api, context))
+                .map_err(|err| GlInitError::Context(err.to_string()));
+            match (&created, api) {
+                (Ok(_), _) => break,
+                (Err(err), Some(api)) => log::warn!("no {api} context: {err}"),
+                (Err(_), None) => {}
+            }
+        }
+        let (api, context) = created?;
+        match api {
+            Some(api) => println!("API: {api}"),
+            None => println!("API: the default of glutin"),
+        }
+        if base.gl_info_only {
+            report_without_window(gl_display, config, context);
+        }
 
-        // Create GL surface
-        let surface_attributes = SurfaceAttributesBuilder::<WindowSurface>::new()
-            .build(
+        // Create GL surface, sized in physical pixels. Until the window has an area use the
+        // default size, the next resize fixes it up.
+        let default_size = NonZeroU32::new(DEFAULT_SIZE).unwrap();
+        let (buffer_width, buffer_height) =
+            base.non_zero_buffer_size().unwrap_or((default_size, default_size));
+        let surface_attributes = |srgb| {
+            SurfaceAttributesBuilder::<WindowSurface>::new().with_srgb(Some(srgb)).build(
                 wayland_window.window_handle().unwrap().as_raw(),
-                NonZeroU32::new(self.width).unwrap(),
-                NonZeroU32::new(self.height).unwrap(),
-            );
-
-        let surface = unsafe {
-            gl_display
-                .create_window_surface(&config, &surface_attributes)
-                .expect("Failed to create GL surface")
+                buffer_width,
+                buffer_height,
+            )
         };
 
+        // glutin leaves the colorspace alone when the display has no sRGB framebuffers, and
+        // not every config can be sRGB even when it has
+        let srgb = base.srgb && config.srgb_capable();
+        let surface = match unsafe {
+            gl_display.create_window_surface(config, &surface_attributes(srgb))
+        } {
+            Err(err) if srgb => {
+                log::info!("no sRGB surface ({err}), falling back to a linear one");
+                base.srgb = false;
+                unsafe { gl_display.create_window_surface(config, &surface_attributes(false)) }
+            }
+            result => {
+                base.srgb = srgb;
+                result
+            }
+        }
+        .map_err(|err| GlInitError::Surface(err.to_string()))?;
+        println!("colorspace: {}", if base.srgb { "sRGB" } else { "linear" });
+
         // Make context current
         let context = context
             .make_current(&surface)
-            .expect("Failed to make GL context current");
+            .map_err(|err| GlInitError::MakeCurrent(err.to_string()))?;
+        set_swap_interval(&context, &surface, base.swap_interval());
 
         // Create glow context
-        let gl = unsafe {
-            Context::from_loader_function(|s| {
-                gl_display.get_proc_address(&std::ffi::CString::new(s).unwrap())
-            })
-        };
+        let get_proc_address =
+            |s: &str| gl_display.get_proc_address(&std::ffi::CString::new(s).unwrap());
+        let mut gl = unsafe { Context::from_loader_function(get_proc_address) };
+        let debug = GlDebug::new(&mut gl, debug);
+        let reset_status = ResetStatus::load(&gl, get_proc_address);
+        msaa::enable(&gl, config.num_samples());
+        if base.gl_info
+            && let Some(egl_info) = egl_info(gl_display, config)
+        {
+            info::print_report(&egl_info, &gl);
+        }
+        // The quads of `--depth` need a depth buffer, which the driver may not have
+        base.depth &= config.depth_size() > 0;
+        base.stencil &= config.stencil_size() > 0;
+        // Without a transparent config the alpha is dropped, the background would turn black
+        base.transparent &= config.supports_transparency().unwrap_or(false);
 
         println!("OpenGL version: {}", unsafe { gl.get_parameter_string(VERSION) });
         println!("OpenGL renderer: {}", unsafe { gl.get_parameter_string(RENDERER) });
+        println!(
+            "swap interval {}, {}",
+            base.swap_interval(),
+            if base.vsync { "vsync" } else { "async" }
+        );
+
+        let scene = Scene::new((self.new_renderer)(), &gl)?;
+        debug.check(&gl, "setting up the scene");
+        let quads = Quads::new(&gl)?;
+        debug.check(&gl, "setting up the quads");
+        let canvas = Canvas::new(&gl)?;
+        debug.check(&gl, "setting up the canvas");
+
+        Ok(GlWindow {
+            context,
+            surface,
+            gl,
+            debug,
+            reset_status,
+            scene,
+            quads,
+            image: None,
+            canvas,
+            sheet: Sheet::default(),
+            damage: DamageTracker::default(),
+        })
+    }
+
+    /// Sets up GL for the window, or closes it when that fails.
+    fn set_up_window(&mut self, base: &mut BaseApp) {
+        if let Some(software) = &mut self.software {
+            if let Err(err) = software.open(base) {
+                eprintln!("{err}");
+                base.close = true;
+            }
+            return;
+        }
+        match self.init_gl(base) {
+            Ok(window) => {
+                self.windows.insert(base.id(), window);
+            }
+            // Without any window drawn GL itself doesn't work, not just this window
+            Err(err) if self.windows.is_empty() => {
+                eprintln!("{err}");
+                println!("falling back to software rendering into wl_shm buffers");
+                self.software = Some(Software::new((self.new_renderer)()));
+                self.set_up_window(base);
+            }
+            Err(err) => {
+                eprintln!("{err}");
+                base.close = true;
+            }
+        }
+    }
 
-        self.gl_display_context = Some(context);
-        self.gl_surface = Some(surface);
-        self.gl_context = Some(gl);
+    /// Throws away the context of the window with everything in it and sets it up again. The
+    /// other windows have contexts of their own. A dropped image is gone.
+    fn recreate_window(&mut self, base: &mut BaseApp) {
+        if self.software.is_some() {
+            return;
+        }
+        if let Some(window) = self.windows.remove(&base.id()) {
+            window.destroy();
+        }
+        self.set_up_window(base);
     }
 
-    fn draw(&self) {
+    /// Draws the window, an error means nothing was presented.
+    fn draw_window(&mut self, base: &mut BaseApp) -> Result<(), glutin::error::Error> {
         use glutin::prelude::*;
 
-        let gl = self.gl_context.as_ref().unwrap();
-        let surface = self.gl_surface.as_ref().unwrap();
-        let context = self.gl_display_context.as_ref().unwrap();
+        let Some(window) = self.windows.get_mut(&base.id()) else {
+            return Ok(());
+        };
+        let gl = &window.gl;
+        let srgb = base.srgb;
 
-        // Clear the screen with a blue color
+        let draw_start = Instant::now();
+
+        // Every window has its own context, switch to the one of this window
+        window.context.make_current(&window.surface)?;
+        if window.reset_status.context_lost() {
+            return Err(ErrorKind::ContextLost.into());
+        }
+        window.canvas.update(gl, &mut window.sheet, base);
+        window.debug.check(gl, "drawing the strokes");
+
+        // Clear the screen with a color following the frame callbacks and the pointer, or half
+        // transparent black with `--transparent`
+        srgb::set_encoding(gl, srgb);
+        let [red, green, blue, alpha] = base.background();
+        let [red, green, blue] = framebuffer_color([red, green, blue], srgb);
         unsafe {
-            gl.clear_color(0.0, 0.0, 1.0, 1.0);
+            gl.clear_color(red, green, blue, alpha);
             gl.clear(COLOR_BUFFER_BIT);
-        }
 
-        // Swap buffers
-        surface.swap_buffers(context)
-            .expect("Failed to swap buffers");
-        
-        // Commit the surface to display the changes
-        self.window.wl_surface().commit();
-    }
+            // Draw the test pattern, it shows whether the scale and transform are right
+            gl.enable(SCISSOR_TEST);
+            gl.clear_color(1.0, 1.0, 1.0, 1.0);
+            for [x, y, width, height] in base.test_pattern() {
+                gl.scissor(x, y, width, height);
+                gl.clear(COLOR_BUFFER_BIT);
+            }
 
-    fn resize(&mut self, width: u32, height: u32) {
-        use glutin::prelude::*;
+            // Without server-side decorations we draw a title bar, the content goes below it
+            for ([x, y, width, height], color) in base.decoration_rects() {
+                let [red, green, blue] = framebuffer_color(color, srgb);
+                gl.scissor(x, y, width, height);
+                gl.clear_color(red, green, blue, 1.0);
+                gl.clear(COLOR_BUFFER_BIT);
+            }
+
+            // The region C confines the pointer to
+            let [red, green, blue] = framebuffer_color([1.0, 0.5, 0.0], srgb);
+            gl.clear_color(red, green, blue, 1.0);
+            for [x, y, width, height] in base.confinement_outline() {
+                gl.scissor(x, y, width, height);
+                gl.clear(COLOR_BUFFER_BIT);
+            }
+
+            // A square under each finger touching the window
+            for ([x, y, width, height], color) in base.touch_rects() {
+                let [red, green, blue] = framebuffer_color(color, srgb);
+                gl.scissor(x, y, width, height);
+                gl.clear_color(red, green, blue, 1.0);
+                gl.clear(COLOR_BUFFER_BIT);
+            }
+
+            // The text typed through the input method, and its preedit underlined
+            for ([x, y, width, height], color) in base.text_rects() {
+                let [red, green, blue] = framebuffer_color(color, srgb);
+                gl.scissor(x, y, width, height);
+                gl.clear_color(red, green, blue, 1.0);
+                gl.clear(COLOR_BUFFER_BIT);
+            }
+
+            // The badge while F10 has the compositor's shortcuts
+            for ([x, y, width, height], color) in base.shortcuts_badge_rects() {
+                let [red, green, blue] = framebuffer_color(color, srgb);
+                gl.scissor(x, y, width, height);
+                gl.clear_color(red, green, blue, 1.0);
+                gl.clear(COLOR_BUFFER_BIT);
+            }
+            gl.disable(SCISSOR_TEST);
+
+            let [x, y, width, height] = base.content_rect();
+            gl.viewport(x, y, width, height);
+        }
+        window.debug.check(gl, "clearing");
 
-        self.width = width;
-        self.height = height;
-
-        if let (Some(surface), Some(context)) = (&self.gl_surface, &self.gl_display_context) {
-            surface.resize(
-                context,
-                NonZeroU32::new(width).unwrap(),
-                NonZeroU32::new(height).unwrap(),
-            );
-            
-            if let Some(gl) = &self.gl_context {
-                unsafe {
-                    gl.viewport(0, 0, width as i32, height as i32);
+        let view = View::of(base);
+        if let Some(image) = &window.image {
+            image.draw(gl, &view);
+            window.debug.check(gl, "drawing the image");
+        }
+        window.scene.draw(gl, &view);
+        window.debug.check(gl, "drawing the scene");
+        if base.depth {
+            window.quads.draw(gl, &view);
+            window.debug.check(gl, "drawing the quads");
+        }
+        window.canvas.draw(gl, &window.sheet, &view);
+        window.debug.check(gl, "drawing the canvas");
+
+        // Every frame is drawn completely, so the compositor only has to know what changed since
+        // the previous one, that is the damage for a buffer of age 1
+        let (buffer_width, buffer_height) = base.buffer_size();
+        let mut damage = window.damage.frame(base, window.scene.bounds(base), 1);
+        if base.full_damage {
+            damage = vec![[0, 0, buffer_width as i32, buffer_height as i32]];
+        }
+        if base.show_damage {
+            unsafe {
+                gl.enable(SCISSOR_TEST);
+                let [red, green, blue] = OUTLINE_COLOR;
+                gl.clear_color(red, green, blue, 1.0);
+                for [x, y, width, height] in window.damage.outline() {
+                    gl.scissor(x, y, width, height);
+                    gl.clear(COLOR_BUFFER_BIT);
                 }
+                gl.disable(SCISSOR_TEST);
+            }
+        }
+        screenshot::save_if_requested(gl, base);
+
+        // The rectangles are in buffer pixels, so they already follow the scale. wl_surface
+        // damage has its origin at the top left, unlike GL.
+        let wl_surface = base.window().wl_surface();
+        if wl_surface.version() >= 4 {
+            for &[x, y, width, height] in &damage {
+                wl_surface.damage_buffer(x, buffer_height as i32 - y - height, width, height);
             }
         }
+
+        let swap_start = Instant::now();
+        // Swap buffers, this also commits the surface. glutin falls back to a plain swap, which
+        // damages everything, when EGL lacks swapping with damage.
+        let rects: Vec<Rect> =
+            damage.iter().map(|&[x, y, width, height]| Rect::new(x, y, width, height)).collect();
+        let swapped = match (&window.surface, &window.context) {
+            (Surface::Egl(surface), PossiblyCurrentContext::Egl(context)) => {
+                surface.swap_buffers_with_damage(context, &rects)
+            }
+            (surface, context) => surface.swap_buffers(context),
+        };
+        swapped?;
+        base.frame_stats.record(swap_start - draw_start, swap_start.elapsed());
+        Ok(())
     }
 }
 
-impl CompositorHandler for GlowApp {
-    fn scale_factor_changed(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _new_factor: i32,
-    ) {
-        // Not needed for this example.
+/// Sets the swap interval of the surface, which must be current. Failing to is not fatal, the
+/// driver default stays.
+fn set_swap_interval(
+    context: &PossiblyCurrentContext,
+    surface: &Surface<WindowSurface>,
+    swap_interval: u32,
+) {
+    use glutin::prelude::*;
+
+    let interval = match NonZeroU32::new(swap_interval) {
+        Some(interval) => SwapInterval::Wait(interval),
+        None => SwapInterval::DontWait,
+    };
+    if let Err(err) = surface.set_swap_interval(context, interval) {
+        log::warn!("failed to set the swap interval to {swap_interval}: {err}");
     }
+}
 
-    fn transform_changed(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _new_transform: wl_output::Transform,
-    ) {
-        // Not needed for this example.
-    }
+/// Creates the glutin display and picks the config all windows use, with as many samples per
+/// pixel as the driver has up to `--msaa` and the depth and stencil buffers of `--depth` and
+/// `--stencil`. With `--api` the config has to work with that API and the ones it falls back
+/// to.
+fn init_display(
+    wayland_window: &WaylandWindow,
+    base: &BaseApp,
+) -> Result<(Display, Config), GlInitError> {
+    use glutin::prelude::*;
+
+    // Create glutin display
+    let gl_display = unsafe {
+        glutin::display::Display::new(
+            wayland_window.display_handle().unwrap().as_raw(),
+            glutin::display::DisplayApiPreference::Egl,
+        )
+        .map_err(|err| GlInitError::Display(err.to_string()))?
+    };
 
-    fn frame(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _time: u32,
-    ) {
+    if base.dump_configs {
+        let configs = unsafe { gl_display.find_configs(ConfigTemplateBuilder::new().build()) }
+            .map(|configs| configs.map(|config| config_info(&config)).collect::<Vec<_>>())
+            .unwrap_or_default();
+        for line in config_table(&configs) {
+            println!("{line}");
+        }
+        std::process::exit(0);
     }
 
-    fn surface_enter(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _output: &wl_output::WlOutput,
-    ) {
-        // Not needed for this example.
-    }
+    // Configure the GL context, software and older drivers may lack transparent configs so
+    // retry without before giving up. Fewer samples are better than opaque windows.
+    let (msaa, gl_api) = (base.msaa, base.gl_api);
+    let (depth, stencil) = depth_stencil_sizes(base.depth, base.stencil);
+    let mut templates = [true, false].into_iter().flat_map(|transparency| {
+        msaa::sample_counts(msaa).into_iter().map(move |samples| {
+            let mut template = ConfigTemplateBuilder::new()
+                .with_alpha_size(8)
+                .with_depth_size(depth)
+                .with_stencil_size(stencil)
+                .with_transparency(transparency);
+            if let Some(gl_api) = gl_api {
+                template = template.with_api(config_api(gl_api));
+            }
+            // Without multisampling glutin leaves the samples to the driver
+            let template = match samples {
+                0 => template.build(),
+                samples => template.with_multisampling(samples).build(),
+            };
+            (samples, template)
+        })
+    });
+
+    // Every matching config is a candidate, the first one isn't always the best one for us
+    let config = templates
+        .find_map(|(samples, template)| {
+            let configs: Vec<Config> = unsafe { gl_display.find_configs(template) }.ok()?.collect();
+            let candidates: Vec<ConfigInfo> = configs.iter().map(config_info).collect();
+            let best = best_config(&candidates, samples, depth, stencil)?;
+            configs.into_iter().nth(best)
+        })
+        .ok_or_else(|| GlInitError::Config {
+            requested: "8 bit alpha, with or without transparency".to_string(),
+            available: unsafe { gl_display.find_configs(ConfigTemplateBuilder::new().build()) }
+                .map(|configs| configs.map(|config| describe_config(&config)).collect())
+                .unwrap_or_default(),
+        })?;
+
+    println!("Using GL config: {:?}", config);
+    // The config may have more samples than asked for, or fewer after falling back
+    println!("MSAA: {} samples, {msaa} asked for", config.num_samples());
+    println!(
+        "depth: {} bits, stencil: {} bits",
+        config.depth_size(),
+        config.stencil_size()
+    );
+    Ok((gl_display, config))
+}
 
-    fn surface_leave(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _output: &wl_output::WlOutput,
-    ) {
-        // Not needed for this example.
+/// What the config scoring looks at, see [`best_config`]. glutin only tells whether the config
+/// is slow, not whether it is non-conformant.
+fn config_info(config: &Config) -> ConfigInfo {
+    use glutin::prelude::*;
+
+    let (red, green, blue) = match config.color_buffer_type() {
+        Some(ColorBufferType::Rgb { r_size, g_size, b_size }) => (r_size, g_size, b_size),
+        _ => (0, 0, 0),
+    };
+    ConfigInfo {
+        color: [red, green, blue, config.alpha_size()],
+        depth: config.depth_size(),
+        stencil: config.stencil_size(),
+        samples: config.num_samples(),
+        caveat: !config.hardware_accelerated(),
+        native_renderable: None,
     }
 }
 
-impl OutputHandler for GlowApp {
-    fn output_state(&mut self) -> &mut OutputState {
-        &mut self.output_state
-    }
+/// The config bits of the API and the ones it falls back to.
+fn config_api(gl_api: GlApi) -> Api {
+    gl_api
+        .fallbacks()
+        .iter()
+        .map(|api| match api {
+            GlApi::Gles2 => Api::GLES2,
+            GlApi::Gles3 => Api::GLES3,
+            GlApi::Gl => Api::OPENGL,
+        })
+        .fold(Api::empty(), |bits, api| bits | api)
+}
 
-    fn new_output(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _output: wl_output::WlOutput,
-    ) {
-    }
+/// What EGL tells about the display and the config, for `--gl-info`. glutin has getters for
+/// little of it, the EGL functions it loaded answer the rest. `None` for displays of other
+/// APIs.
+fn egl_info(gl_display: &Display, config: &Config) -> Option<EglInfo> {
+    const EGL_VENDOR: i32 = 0x3053;
+    const EGL_VERSION: i32 = 0x3054;
+    const EGL_EXTENSIONS: i32 = 0x3055;
+
+    let (Display::Egl(egl_display), RawDisplay::Egl(display), RawConfig::Egl(config)) =
+        (gl_display, gl_display.raw_display(), config.raw_config())
+    else {
+        return None;
+    };
+    let egl = egl_display.egl();
+    let string = |display, name| {
+        let string = unsafe { egl.QueryString(display, name) };
+        if string.is_null() {
+            return String::new();
+        }
+        unsafe { CStr::from_ptr(string) }.to_string_lossy().into_owned()
+    };
+    Some(EglInfo {
+        vendor: string(display, EGL_VENDOR),
+        version: string(display, EGL_VERSION),
+        // The client extensions belong to no display
+        client_extensions: string(std::ptr::null(), EGL_EXTENSIONS),
+        display_extensions: string(display, EGL_EXTENSIONS),
+        config: CONFIG_ATTRIBUTES
+            .iter()
+            .map(|attribute| {
+                let mut value = 0;
+                let ok = unsafe { egl.GetConfigAttrib(display, config, attribute.id, &mut value) };
+                (ok != 0).then_some(value)
+            })
+            .collect(),
+    })
+}
 
-    fn update_output(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _output: wl_output::WlOutput,
-    ) {
-    }
+/// `--gl-info-only`, prints the report with the context current without a window surface and
+/// exits. glutin makes it current without any surface when EGL has
+/// EGL_KHR_surfaceless_context, otherwise a 1x1 pbuffer has to do.
+fn report_without_window(gl_display: &Display, config: &Config, context: NotCurrentContext) -> ! {
+    use glutin::prelude::*;
+
+    let size = NonZeroU32::MIN;
+    let attributes = SurfaceAttributesBuilder::<PbufferSurface>::new().build(size, size);
+    let surfaceless = match gl_display {
+        Display::Egl(display) => display.extensions().contains("EGL_KHR_surfaceless_context"),
+        _ => false,
+    };
+    // The pbuffer has to outlive the context being current with it
+    let mut pbuffer = None;
+    let current = match context {
+        NotCurrentContext::Egl(context) if surfaceless => {
+            context.make_current_surfaceless().map(PossiblyCurrentContext::Egl)
+        }
+        context => unsafe { gl_display.create_pbuffer_surface(config, &attributes) }
+            .and_then(|surface| context.make_current(pbuffer.insert(surface))),
+    };
+    let _context = match current {
+        Ok(context) => context,
+        Err(err) => {
+            eprintln!("failed to make the context current without a window: {err}");
+            std::process::exit(1);
+        }
+    };
 
-    fn output_destroyed(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _output: wl_output::WlOutput,
-    ) {
+    let get_proc_address =
+        |s: &str| gl_display.get_proc_address(&std::ffi::CString::new(s).unwrap());
+    let gl = unsafe { Context::from_loader_function(get_proc_address) };
+    match egl_info(gl_display, config) {
+        Some(egl_info) => info::print_report(&egl_info, &gl),
+        None => eprintln!("not an EGL display, nothing to report"),
     }
+    std::process::exit(0);
 }
 
-impl WindowHandler for GlowApp {
-    fn request_close(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &Window) {
-        self.exit = true;
-    }
+fn describe_config(config: &glutin::config::Config) -> String {
+    use glutin::prelude::*;
+
+    format!(
+        "{:?}, alpha {}, depth {}, stencil {}, samples {}, transparency {}",
+        config.color_buffer_type(),
+        config.alpha_size(),
+        config.depth_size(),
+        config.stencil_size(),
+        config.num_samples(),
+        config.supports_transparency().unwrap_or(false),
+    )
+}
 
+impl AppHandler for GlowApp {
     fn configure(
         &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _window: &Window,
+        base: &mut BaseApp,
+        _qh: &QueueHandle<App<Self>>,
         configure: WindowConfigure,
         _serial: u32,
     ) {
-        let (new_width, new_height) = configure.new_size;
-        let width = new_width.map_or(256, |v| v.get());
-        let height = new_height.map_or(256, |v| v.get());
-
-        // Initialize GL on first configure
-        if self.gl_context.is_none() {
-            self.width = width;
-            self.height = height;
-            self.init_gl();
-            self.draw();
-        } else if self.width != width || self.height != height {
-            // Only resize and redraw if dimensions actually changed
-            self.resize(width, height);
-            self.draw();
+        let (width, height) = base.configured_size(&configure);
+
+        // Initialize GL on the first configure of the window
+        let id = base.id();
+        let set_up = self.windows.contains_key(&id)
+            || self.software.as_ref().is_some_and(|software| software.is_open(&id));
+        if !set_up {
+            base.width = width;
+            base.height = height;
+            self.set_up_window(base);
+        } else if base.set_size(width, height) {
+            // Only resize if the buffer size actually changed
+            self.resize(base);
         }
     }
-}
 
-impl SeatHandler for GlowApp {
-    fn seat_state(&mut self) -> &mut SeatState {
-        &mut self.seat_state
+    fn resize(&mut self, base: &mut BaseApp) {
+        use glutin::prelude::*;
+
+        let Some((width, height)) = base.non_zero_buffer_size() else {
+            // Drawing is skipped until we get a usable size, keep the old surface until then.
+            log::warn!("ignoring resize to a zero size");
+            return;
+        };
+
+        // The viewport is set with every draw, only the surface needs resizing
+        if let Some(window) = self.windows.get(&base.id()) {
+            window.surface.resize(&window.context, width, height);
+        }
     }
 
-    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+    fn draw(&mut self, base: &mut BaseApp) {
+        if let Some(software) = &mut self.software {
+            software.draw(base);
+            return;
+        }
+        let mut result = self.draw_window(base);
+        if let Err(err) = &result
+            && err.error_kind() == ErrorKind::ContextLost
+        {
+            log::warn!("the GL context of window {} is lost, setting it up again", base.id());
+            self.recreate_window(base);
+            result = self.draw_window(base);
+        }
 
-    fn new_capability(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _seat: wl_seat::WlSeat,
-        _capability: Capability,
-    ) {
+        if let Err(err) = result {
+            // Nothing was presented, commit anyway so the frame callback request goes out and
+            // the window keeps trying
+            log::warn!("failed to draw window {}: {err}", base.id());
+            base.window().wl_surface().commit();
+        }
     }
 
-    fn remove_capability(
-        &mut self,
-        _conn: &Connection,
-        _: &QueueHandle<Self>,
-        _: wl_seat::WlSeat,
-        _capability: Capability,
-    ) {
+    fn window_closed(&mut self, base: &mut BaseApp) {
+        if let Some(software) = &mut self.software {
+            software.close(&base.id());
+            return;
+        }
+        if let Some(window) = self.windows.remove(&base.id()) {
+            window.destroy();
+        }
     }
 
-    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
-}
+    const SCREENSHOTS: bool = true;
+
+    fn srgb_changed(&mut self, base: &mut BaseApp) {
+        // The colorspace is fixed when the surface is created. The surface can't go while the
+        // context is current with it, so both go along with the image.
+        self.recreate_window(base);
+    }
+
+    fn simulate_context_loss(&mut self, base: &mut BaseApp) {
+        if self.software.is_some() {
+            println!("no context to lose in software");
+            return;
+        }
+        // The same as after a real reset, only the old context still works for cleaning up
+        self.recreate_window(base);
+    }
+
+    fn vsync_changed(&mut self, base: &mut BaseApp) {
+        use glutin::prelude::*;
+
+        let Some(window) = self.windows.get(&base.id()) else {
+            return;
+        };
+        match window.context.make_current(&window.surface) {
+            Ok(()) => set_swap_interval(&window.context, &window.surface, base.swap_interval()),
+            Err(err) => {
+                log::warn!("failed to make the context of window {} current: {err}", base.id());
+            }
+        }
+    }
+
+    fn image_dropped(&mut self, base: &mut BaseApp, image: DroppedImage) {
+        use glutin::prelude::*;
 
-delegate_compositor!(GlowApp);
-delegate_output!(GlowApp);
-delegate_seat!(GlowApp);
-delegate_xdg_shell!(GlowApp);
-delegate_xdg_window!(GlowApp);
-delegate_registry!(GlowApp);
+        let Some(window) = self.windows.get_mut(&base.id()) else {
+            return;
+        };
+        if let Err(err) = window.context.make_current(&window.surface) {
+            log::warn!("failed to make the context of window {} current: {err}", base.id());
+            return;
+        }
 
-impl ProvidesRegistryState for GlowApp {
-    fn registry(&mut self) -> &mut RegistryState {
-        &mut self.registry_state
+        let quad = ImageQuad::new(&window.gl, &image);
+        window.debug.check(&window.gl, "uploading the image");
+        match quad {
+            Ok(quad) => {
+                if let Some(old) = window.image.replace(quad) {
+                    old.destroy(&window.gl);
+                }
+                // The damage tracking doesn't know about images
+                window.damage.reset();
+            }
+            Err(err) => println!("{err}"),
+        }
     }
-    registry_handlers![OutputState];
 }
@@ -0,0 +1,267 @@
+//! `--device` and `--gpu`, which GPU the raw EGL example draws with. EGL_EXT_device_enumeration
+//! lists the devices, EGL_EXT_device_drm tells their DRM nodes and EGL_EXT_explicit_device
+//! creates the display of the Wayland connection on the one picked. Without either option EGL
+//! picks, usually the GPU the compositor renders with.
+//!
+//! The buffers of any other GPU only get to the compositor as dmabufs it imports. When the
+//! driver can't do that EGL fails with little more than BAD_MATCH or BAD_ALLOC, [`explain`]
+//! says what is going on.
+
+use std::{
+    ffi::{c_char, c_void, CStr},
+    os::unix::fs::MetadataExt,
+    path::Path,
+    ptr::null_mut,
+};
+
+use wayland_app_common::{device_name, BaseApp, GlInitError, GpuChoice};
+
+use crate::platform::Platform;
+/// `EGL_DEVICE_EXT`, the attribute of EGL_EXT_explicit_device and what EGL_EXT_device_query
+/// asks a display for.
+const DEVICE_EXT: khronos_egl::Int = 0x322C;
+/// `EGL_DRM_DEVICE_FILE_EXT` of EGL_EXT_device_drm and `EGL_DRM_RENDER_NODE_FILE_EXT` of
+/// EGL_EXT_device_drm_render_node.
+const DRM_DEVICE_FILE_EXT: khronos_egl::Int = 0x3233;
+const DRM_RENDER_NODE_FILE_EXT: khronos_egl::Int = 0x3377;
+
+/// `eglQueryDevicesEXT` of EGL_EXT_device_enumeration.
+type QueryDevices = unsafe extern "system" fn(
+    khronos_egl::Int,
+    *mut *mut c_void,
+    *mut khronos_egl::Int,
+) -> khronos_egl::Boolean;
+
+/// `eglQueryDeviceStringEXT` of EGL_EXT_device_query.
+type QueryDeviceString = unsafe extern "system" fn(*mut c_void, khronos_egl::Int) -> *const c_char;
+
+/// `eglQueryDisplayAttribEXT` of the same extension.
+type QueryDisplayAttrib = unsafe extern "system" fn(
+    khronos_egl::EGLDisplay,
+    khronos_egl::Int,
+    *mut isize,
+) -> khronos_egl::Boolean;
+
+/// An EGL device and its DRM nodes. The software renderer has none.
+pub(crate) struct Device {
+    ptr: *mut c_void,
+    /// The primary node, e.g. `/dev/dri/card0`.
+    primary: Option<String>,
+    /// The render node, e.g. `/dev/dri/renderD128`.
+    pub(crate) render: Option<String>,
+}
+
+impl Device {
+    fn new(query_string: QueryDeviceString, ptr: *mut c_void) -> Device {
+        let string = |name| {
+            let string = unsafe { query_string(ptr, name) };
+            (!string.is_null())
+                .then(|| unsafe { CStr::from_ptr(string) }.to_string_lossy().into_owned())
+        };
+        Device {
+            ptr,
+            primary: string(DRM_DEVICE_FILE_EXT),
+            render: string(DRM_RENDER_NODE_FILE_EXT),
+        }
+    }
+
+    fn nodes(&self) -> impl Iterator<Item = &String> {
+        self.render.iter().chain(&self.primary)
+    }
+
+    /// The nodes as text, e.g. `/dev/dri/renderD128 (/dev/dri/card0)`.
+    pub(crate) fn name(&self) -> String {
+        match (&self.render, &self.primary) {
+            (Some(render), Some(primary)) => format!("{render} ({primary})"),
+            (Some(node), None) | (None, Some(node)) => node.clone(),
+            (None, None) => "a device without DRM nodes, likely software".to_string(),
+        }
+    }
+
+    /// Whether the path leads to one of the nodes, also through the links under
+    /// /dev/dri/by-path.
+    fn matches(&self, path: &str) -> bool {
+        let path = Path::new(path).canonicalize().ok();
+        path.is_some_and(|path| self.nodes().any(|node| Path::new(node) == path))
+    }
+
+    /// Whether `dev_t` is one of the nodes, the compositor may name either.
+    fn has_node(&self, device: u64) -> bool {
+        self.nodes()
+            .any(|node| std::fs::metadata(node).is_ok_and(|metadata| metadata.rdev() == device))
+    }
+}
+
+/// The display of the Wayland connection, on the GPU of `--device` or `--gpu` when one is
+/// picked, which comes along.
+pub(crate) fn get_display(
+    egl: &khronos_egl::Instance<khronos_egl::Static>,
+    platform: Platform,
+    display_ptr: *mut c_void,
+    gpu: Option<&GpuChoice>,
+) -> Result<(khronos_egl::Display, Option<Device>), GlInitError> {
+    let Some(gpu) = gpu else {
+        let display = platform
+            .get_display(egl, display_ptr, &[khronos_egl::ATTRIB_NONE])
+            .map_err(GlInitError::Display)?;
+        return Ok((display, None));
+    };
+    // The device goes in as a pointer, which only the attributes of EGL 1.5 can carry
+    if !matches!(platform, Platform::Core) {
+        return Err(GlInitError::Display(format!(
+            "picking {gpu} needs EGL 1.5 with EGL_KHR_platform_wayland"
+        )));
+    }
+
+    let extensions = egl
+        .query_string(None, khronos_egl::EXTENSIONS)
+        .map(|extensions| extensions.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let has = |name: &str| extensions.split_ascii_whitespace().any(|e| e == name);
+    let needed = ["EGL_EXT_device_enumeration", "EGL_EXT_device_query", "EGL_EXT_explicit_device"];
+    if let Some(missing) = needed.into_iter().find(|&name| !has(name)) {
+        return Err(GlInitError::Display(format!("EGL has no {missing} to pick {gpu} with")));
+    }
+
+    let device = pick(devices(egl).map_err(GlInitError::Display)?, gpu)?;
+    let attributes = [
+        DEVICE_EXT as khronos_egl::Attrib, device.ptr as khronos_egl::Attrib,
+        khronos_egl::ATTRIB_NONE,
+    ];
+    let display = platform
+        .get_display(egl, display_ptr, &attributes)
+        .map_err(|err| {
+            GlInitError::Display(format!("no EGL display on {}: {err}", device.name()))
+        })?;
+    Ok((display, Some(device)))
+}
+
+/// The display of a device without a compositor, for `--offscreen` without a Wayland
+/// connection. The device of `--device` or `--gpu`, otherwise the first one with a render
+/// node, so the software renderer only draws when there is no GPU.
+pub(crate) fn headless_display(
+    egl: &khronos_egl::Instance<khronos_egl::Static>,
+    gpu: Option<&GpuChoice>,
+) -> Result<(khronos_egl::Display, Device), GlInitError> {
+    let devices = devices(egl).map_err(GlInitError::Display)?;
+    let device = match gpu {
+        Some(gpu) => pick(devices, gpu)?,
+        None => {
+            let index = devices.iter().position(|device| device.render.is_some()).unwrap_or(0);
+            devices.into_iter().nth(index).ok_or_else(|| {
+                GlInitError::Display("EGL has no devices to draw with".to_string())
+            })?
+        }
+    };
+    let display = Platform::get_device_display(egl, device.ptr).map_err(|err| {
+        GlInitError::Display(format!("no EGL display on {}: {err}", device.name()))
+    })?;
+    Ok((display, device))
+}
+
+/// The device of `--device` or `--gpu`. The devices are logged first, that is how to tell
+/// their numbers.
+fn pick(devices: Vec<Device>, gpu: &GpuChoice) -> Result<Device, GlInitError> {
+    for (index, device) in devices.iter().enumerate() {
+        log::info!("EGL device {index}: {}", device.name());
+    }
+    let mut devices = devices.into_iter();
+    match gpu {
+        GpuChoice::Node(node) => devices.find(|device| device.matches(node)),
+        GpuChoice::Index(index) => devices.nth(*index),
+    }
+    .ok_or_else(|| {
+        GlInitError::Display(format!("no EGL device is {gpu}, RUST_LOG=info lists them"))
+    })
+}
+
+/// Every EGL device, in the order `--gpu` counts them.
+fn devices(egl: &khronos_egl::Instance<khronos_egl::Static>) -> Result<Vec<Device>, String> {
+    let (Some(query_devices), Some(query_string)) = (
+        egl.get_proc_address("eglQueryDevicesEXT"),
+        egl.get_proc_address("eglQueryDeviceStringEXT"),
+    ) else {
+        return Err("EGL lacks the device functions".to_string());
+    };
+    let (query_devices, query_string) = unsafe {
+        (
+            std::mem::transmute::<extern "system" fn(), QueryDevices>(query_devices),
+            std::mem::transmute::<extern "system" fn(), QueryDeviceString>(query_string),
+        )
+    };
+
+    // Asked for twice, first the count and then the devices
+    let mut count = 0;
+    if unsafe { query_devices(0, null_mut(), &mut count) } != khronos_egl::TRUE {
+        return Err(format!("eglQueryDevicesEXT: {:?}", egl.get_error()));
+    }
+    let mut devices = vec![null_mut(); count.max(0) as usize];
+    if unsafe { query_devices(count, devices.as_mut_ptr(), &mut count) } != khronos_egl::TRUE {
+        return Err(format!("eglQueryDevicesEXT: {:?}", egl.get_error()));
+    }
+    devices.truncate(count.max(0) as usize);
+    Ok(devices.into_iter().map(|device| Device::new(query_string, device)).collect())
+}
+
+/// The device the display draws with, picked or not. `None` when EGL can't tell.
+pub(crate) fn display_device(
+    egl: &khronos_egl::Instance<khronos_egl::Static>,
+    display: khronos_egl::Display,
+) -> Option<Device> {
+    // EGL_EXT_device_base is the older name of enumeration and query together
+    let extensions = egl.query_string(None, khronos_egl::EXTENSIONS).ok()?.to_string_lossy();
+    if !extensions
+        .split_ascii_whitespace()
+        .any(|e| e == "EGL_EXT_device_query" || e == "EGL_EXT_device_base")
+    {
+        return None;
+    }
+    let (Some(query_display), Some(query_string)) = (
+        egl.get_proc_address("eglQueryDisplayAttribEXT"),
+        egl.get_proc_address("eglQueryDeviceStringEXT"),
+    ) else {
+        return None;
+    };
+    let (query_display, query_string) = unsafe {
+        (
+            std::mem::transmute::<extern "system" fn(), QueryDisplayAttrib>(query_display),
+            std::mem::transmute::<extern "system" fn(), QueryDeviceString>(query_string),
+        )
+    };
+
+    let mut device = 0;
+    if unsafe { query_display(display.as_ptr(), DEVICE_EXT, &mut device) } != khronos_egl::TRUE
+    {
+        return None;
+    }
+    Some(Device::new(query_string, device as *mut c_void))
+}
+
+/// Adds what is likely wrong to a failure on a GPU picked with `--device` or `--gpu`, when it
+/// isn't the one the compositor renders with.
+pub(crate) fn explain(err: GlInitError, picked: Option<&Device>, base: &BaseApp) -> GlInitError {
+    let Some(picked) = picked else {
+        return err;
+    };
+    let reason = match base.compositor_device() {
+        Some(device) if picked.has_node(device) => return err,
+        Some(device) => format!(
+            "{} is not the GPU the compositor renders with, that is {}. The driver has to hand \
+             its buffers over as dmabufs the compositor can import, --dmabuf-info shows which \
+             it takes",
+            picked.name(),
+            device_name(device)
+        ),
+        None => format!(
+            "the compositor gave no dmabuf feedback, nothing tells whether it takes buffers \
+             from {}",
+            picked.name()
+        ),
+    };
+    match err {
+        GlInitError::Display(err) => GlInitError::Display(format!("{err}\n  {reason}")),
+        GlInitError::Surface(err) => GlInitError::Surface(format!("{err}\n  {reason}")),
+        GlInitError::MakeCurrent(err) => GlInitError::MakeCurrent(format!("{err}\n  {reason}")),
+        err => err,
+    }
+}
@@ -0,0 +1,480 @@
+//! `--explicit-sync`, wp_linux_drm_syncobj_manager_v1 for the surfaces of the windows. After
+//! drawing a frame the driver hands out a fence of EGL_ANDROID_native_fence_sync, which goes
+//! onto a DRM timeline as the acquire point the compositor waits on before reading the buffer.
+//! The compositor signals the release point once it is done, which is waited on before drawing
+//! into the buffer again.
+//!
+//! Without the global, the EGL extension or a DRM device the windows stay with implicit sync,
+//! the kernel and the driver keep the buffers in order as usual.
+
+use std::{
+    cell::OnceCell,
+    ffi::c_void,
+    fs::File,
+    io,
+    os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd},
+    rc::Rc,
+};
+
+use glow::{Context, HasContext};
+use smithay_client_toolkit::reexports::protocols::wp::linux_drm_syncobj::v1::client::{
+    wp_linux_drm_syncobj_manager_v1::WpLinuxDrmSyncobjManagerV1,
+    wp_linux_drm_syncobj_surface_v1::WpLinuxDrmSyncobjSurfaceV1,
+    wp_linux_drm_syncobj_timeline_v1::WpLinuxDrmSyncobjTimelineV1,
+};
+use wayland_app_common::App;
+use wayland_client::{protocol::wl_surface::WlSurface, Connection, Dispatch, Proxy, QueueHandle};
+
+use crate::GlowApp;
+
+/// `EGL_SYNC_NATIVE_FENCE_ANDROID` and its attribute, the fence gets a new sync file.
+const SYNC_NATIVE_FENCE_ANDROID: khronos_egl::Enum = 0x3144;
+const SYNC_NATIVE_FENCE_FD_ANDROID: khronos_egl::Int = 0x3145;
+const NO_NATIVE_FENCE_FD_ANDROID: khronos_egl::Int = -1;
+
+/// `eglCreateSyncKHR` of EGL_KHR_fence_sync.
+type CreateSync = unsafe extern "system" fn(
+    khronos_egl::EGLDisplay,
+    khronos_egl::Enum,
+    *const khronos_egl::Int,
+) -> *mut c_void;
+
+/// `eglDestroySyncKHR` of the same extension.
+type DestroySync =
+    unsafe extern "system" fn(khronos_egl::EGLDisplay, *mut c_void) -> khronos_egl::Boolean;
+
+/// `eglDupNativeFenceFDANDROID` of EGL_ANDROID_native_fence_sync.
+type DupNativeFenceFd =
+    unsafe extern "system" fn(khronos_egl::EGLDisplay, *mut c_void) -> khronos_egl::Int;
+
+/// The DRM syncobj ioctls, `DRM_IOWR` with the size of their argument.
+const fn drm_iowr(nr: u64, size: u64) -> u64 {
+    (3 << 30) | (size << 16) | ((b'd' as u64) << 8) | nr
+}
+const SYNCOBJ_CREATE: u64 = drm_iowr(0xBF, size_of::<SyncobjCreate>() as u64);
+const SYNCOBJ_DESTROY: u64 = drm_iowr(0xC0, size_of::<SyncobjDestroy>() as u64);
+const SYNCOBJ_HANDLE_TO_FD: u64 = drm_iowr(0xC1, size_of::<SyncobjHandle>() as u64);
+const SYNCOBJ_FD_TO_HANDLE: u64 = drm_iowr(0xC2, size_of::<SyncobjHandle>() as u64);
+const SYNCOBJ_TIMELINE_WAIT: u64 = drm_iowr(0xCA, size_of::<SyncobjTimelineWait>() as u64);
+const SYNCOBJ_TRANSFER: u64 = drm_iowr(0xCC, size_of::<SyncobjTransfer>() as u64);
+
+/// `DRM_SYNCOBJ_FD_TO_HANDLE_FLAGS_IMPORT_SYNC_FILE`, the fd is a sync file to put into an
+/// existing syncobj.
+const FD_TO_HANDLE_IMPORT_SYNC_FILE: u32 = 1 << 0;
+/// `DRM_SYNCOBJ_WAIT_FLAGS_WAIT_FOR_SUBMIT`, also wait for the point to get a fence at all.
+const WAIT_FOR_SUBMIT: u32 = 1 << 1;
+
+/// How long to wait for the compositor to release a buffer before drawing into it anyway.
+const RELEASE_TIMEOUT_NS: i64 = 100_000_000;
+
+#[repr(C)]
+#[derive(Default)]
+struct SyncobjCreate {
+    handle: u32,
+    flags: u32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct SyncobjDestroy {
+    handle: u32,
+    pad: u32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct SyncobjHandle {
+    handle: u32,
+    flags: u32,
+    fd: i32,
+    pad: u32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct SyncobjTransfer {
+    src_handle: u32,
+    dst_handle: u32,
+    src_point: u64,
+    dst_point: u64,
+    flags: u32,
+    pad: u32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct SyncobjTimelineWait {
+    handles: u64,
+    points: u64,
+    timeout_nsec: i64,
+    count_handles: u32,
+    flags: u32,
+    first_signaled: u32,
+    pad: u32,
+}
+
+/// User data of the syncobj objects, none of them has events.
+pub(crate) struct Syncobj;
+
+/// The bound global, the DRM device and the EGL functions are set up with the first window.
+pub(crate) struct SyncobjManager {
+    manager: WpLinuxDrmSyncobjManagerV1,
+    qh: QueueHandle<App<GlowApp>>,
+    /// `None` when something is missing, the windows stay with implicit sync then.
+    device: OnceCell<Option<Rc<Device>>>,
+}
+
+impl SyncobjManager {
+    /// Binds wp_linux_drm_syncobj_manager_v1, or says why the windows stay with implicit sync.
+    pub(crate) fn bind(app: &App<GlowApp>, qh: &QueueHandle<App<GlowApp>>) -> Option<Self> {
+        match app.shared.registry_state.bind_one(qh, 1..=1, Syncobj) {
+            Ok(manager) => Some(SyncobjManager {
+                manager,
+                qh: qh.clone(),
+                device: OnceCell::new(),
+            }),
+            Err(err) => {
+                println!("no wp_linux_drm_syncobj_manager_v1 ({err}), staying with implicit sync");
+                None
+            }
+        }
+    }
+
+    /// Creates the syncobj surface of a surface EGL draws into. `None` keeps it with implicit
+    /// sync.
+    pub(crate) fn surface(
+        &self,
+        egl: &khronos_egl::Instance<khronos_egl::Static>,
+        display: khronos_egl::Display,
+        surface: &WlSurface,
+    ) -> Option<SurfaceSync> {
+        let device = self.device.get_or_init(|| match Device::open(egl, display) {
+            Ok(device) => Some(Rc::new(device)),
+            Err(err) => {
+                println!("no explicit sync: {err}, staying with implicit sync");
+                None
+            }
+        });
+        let device = device.as_ref()?;
+        match SurfaceSync::new(&self.manager, &self.qh, device.clone(), surface) {
+            Ok(sync) => {
+                println!("explicit sync for surface {}", surface.id());
+                Some(sync)
+            }
+            Err(err) => {
+                log::warn!("no explicit sync for surface {}: {err}", surface.id());
+                None
+            }
+        }
+    }
+}
+
+/// The DRM device the timelines live on and the EGL functions for the fences.
+struct Device {
+    file: File,
+    display: khronos_egl::Display,
+    create_sync: CreateSync,
+    destroy_sync: DestroySync,
+    dup_native_fence_fd: DupNativeFenceFd,
+}
+
+impl Device {
+    fn open(
+        egl: &khronos_egl::Instance<khronos_egl::Static>,
+        display: khronos_egl::Display,
+    ) -> Result<Device, String> {
+        let extensions = egl
+            .query_string(Some(display), khronos_egl::EXTENSIONS)
+            .map(|extensions| extensions.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let has = |name: &str| extensions.split_ascii_whitespace().any(|e| e == name);
+        if !has("EGL_ANDROID_native_fence_sync") || !has("EGL_KHR_fence_sync") {
+            return Err("EGL has no EGL_ANDROID_native_fence_sync".to_string());
+        }
+        let (Some(create_sync), Some(destroy_sync), Some(dup_native_fence_fd)) = (
+            egl.get_proc_address("eglCreateSyncKHR"),
+            egl.get_proc_address("eglDestroySyncKHR"),
+            egl.get_proc_address("eglDupNativeFenceFDANDROID"),
+        ) else {
+            return Err("EGL lacks the native fence functions".to_string());
+        };
+
+        let path = crate::device::display_device(egl, display)
+            .and_then(|device| device.render)
+            .or_else(first_render_node)
+            .ok_or("no DRM render node")?;
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .map_err(|err| format!("{path}: {err}"))?;
+        println!("explicit sync timelines on {path}");
+
+        Ok(unsafe {
+            Device {
+                file,
+                display,
+                create_sync: std::mem::transmute::<extern "system" fn(), CreateSync>(create_sync),
+                destroy_sync: std::mem::transmute::<extern "system" fn(), DestroySync>(
+                    destroy_sync,
+                ),
+                dup_native_fence_fd: std::mem::transmute::<extern "system" fn(), DupNativeFenceFd>(
+                    dup_native_fence_fd,
+                ),
+            }
+        })
+    }
+
+    /// Runs a DRM ioctl, again when a signal interrupted it like libdrm does.
+    fn ioctl<T>(&self, request: u64, arg: &mut T) -> io::Result<()> {
+        loop {
+            let result =
+                unsafe { libc::ioctl(self.file.as_raw_fd(), request as _, arg as *mut T) };
+            if result != -1 {
+                return Ok(());
+            }
+            let err = io::Error::last_os_error();
+            if !matches!(err.raw_os_error(), Some(libc::EINTR | libc::EAGAIN)) {
+                return Err(err);
+            }
+        }
+    }
+
+    fn create_syncobj(&self) -> io::Result<u32> {
+        let mut create = SyncobjCreate::default();
+        self.ioctl(SYNCOBJ_CREATE, &mut create)?;
+        Ok(create.handle)
+    }
+
+    fn destroy_syncobj(&self, handle: u32) {
+        let mut destroy = SyncobjDestroy { handle, pad: 0 };
+        if let Err(err) = self.ioctl(SYNCOBJ_DESTROY, &mut destroy) {
+            log::warn!("failed to destroy syncobj {handle}: {err}");
+        }
+    }
+
+    /// The syncobj as a file descriptor, which is what the compositor imports.
+    fn export_syncobj(&self, handle: u32) -> io::Result<OwnedFd> {
+        let mut export = SyncobjHandle {
+            handle,
+            ..SyncobjHandle::default()
+        };
+        self.ioctl(SYNCOBJ_HANDLE_TO_FD, &mut export)?;
+        Ok(unsafe { OwnedFd::from_raw_fd(export.fd) })
+    }
+
+    /// Creates a timeline and hands it to the compositor.
+    fn import_timeline(
+        &self,
+        manager: &WpLinuxDrmSyncobjManagerV1,
+        qh: &QueueHandle<App<GlowApp>>,
+    ) -> io::Result<(u32, WpLinuxDrmSyncobjTimelineV1)> {
+        let handle = self.create_syncobj()?;
+        match self.export_syncobj(handle) {
+            // The fd is duplicated when the request is queued, ours is closed right away
+            Ok(fd) => Ok((handle, manager.import_timeline(fd.as_fd(), qh, Syncobj))),
+            Err(err) => {
+                self.destroy_syncobj(handle);
+                Err(err)
+            }
+        }
+    }
+
+    /// A sync file signaled once the GL commands so far are done, flushing them.
+    fn native_fence(&self, gl: &Context) -> Result<OwnedFd, String> {
+        let display = self.display.as_ptr();
+        let attributes = [
+            SYNC_NATIVE_FENCE_FD_ANDROID, NO_NATIVE_FENCE_FD_ANDROID,
+            khronos_egl::NONE,
+        ];
+        let sync =
+            unsafe { (self.create_sync)(display, SYNC_NATIVE_FENCE_ANDROID, attributes.as_ptr()) };
+        if sync.is_null() {
+            return Err("eglCreateSyncKHR failed".to_string());
+        }
+        // The fence only gets its fd once it is submitted
+        unsafe {
+            gl.flush();
+        }
+        let fd = unsafe { (self.dup_native_fence_fd)(display, sync) };
+        unsafe {
+            (self.destroy_sync)(display, sync);
+        }
+        if fd == NO_NATIVE_FENCE_FD_ANDROID {
+            return Err("eglDupNativeFenceFDANDROID failed".to_string());
+        }
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+}
+
+/// The syncobj surface of one surface EGL draws into, with the timelines of its frames. The
+/// points count the frames, the acquire and release points of a frame are the same number on
+/// the two timelines.
+pub(crate) struct SurfaceSync {
+    device: Rc<Device>,
+    surface: WpLinuxDrmSyncobjSurfaceV1,
+    acquire: (u32, WpLinuxDrmSyncobjTimelineV1),
+    release: (u32, WpLinuxDrmSyncobjTimelineV1),
+    /// A binary syncobj the sync file of each frame is imported into, to transfer it onto the
+    /// acquire timeline.
+    fence: u32,
+    /// The point of the last frame, 0 before the first one.
+    point: u64,
+}
+
+impl SurfaceSync {
+    fn new(
+        manager: &WpLinuxDrmSyncobjManagerV1,
+        qh: &QueueHandle<App<GlowApp>>,
+        device: Rc<Device>,
+        surface: &WlSurface,
+    ) -> Result<SurfaceSync, String> {
+        let fence = device.create_syncobj().map_err(|err| format!("syncobj: {err}"))?;
+        let acquire = device.import_timeline(manager, qh);
+        let release = device.import_timeline(manager, qh);
+        let (acquire, release) = match (acquire, release) {
+            (Ok(acquire), Ok(release)) => (acquire, release),
+            (acquire, release) => {
+                let mut err = String::new();
+                for timeline in [acquire, release] {
+                    match timeline {
+                        Ok((handle, timeline)) => {
+                            timeline.destroy();
+                            device.destroy_syncobj(handle);
+                        }
+                        Err(timeline_err) => err = format!("timeline: {timeline_err}"),
+                    }
+                }
+                device.destroy_syncobj(fence);
+                return Err(err);
+            }
+        };
+
+        let surface = manager.get_surface(surface, qh, Syncobj);
+        Ok(SurfaceSync {
+            device,
+            surface,
+            acquire,
+            release,
+            fence,
+            point: 0,
+        })
+    }
+
+    /// Waits until the compositor is done with the buffer the next frame may draw into. Each
+    /// frame replaces the one before, which the compositor releases once it shows the new one,
+    /// so that is the frame before the last one. With more buffers it was released even
+    /// earlier.
+    pub(crate) fn wait_for_release(&self) {
+        let Some(point) = self.point.checked_sub(1).filter(|&point| point > 0) else {
+            return;
+        };
+        let mut now = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        unsafe {
+            libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now);
+        }
+        let handles = [self.release.0];
+        let points = [point];
+        // time_t and c_long are 32-bit on some targets, the casts only widen there
+        #[allow(clippy::unnecessary_cast)]
+        let now_nsec = now.tv_sec as i64 * 1_000_000_000 + now.tv_nsec as i64;
+        // The timeout is a point in time, not a duration
+        let mut wait = SyncobjTimelineWait {
+            handles: handles.as_ptr() as u64,
+            points: points.as_ptr() as u64,
+            timeout_nsec: now_nsec + RELEASE_TIMEOUT_NS,
+            count_handles: 1,
+            flags: WAIT_FOR_SUBMIT,
+            ..SyncobjTimelineWait::default()
+        };
+        match self.device.ioctl(SYNCOBJ_TIMELINE_WAIT, &mut wait) {
+            Ok(()) => {}
+            Err(err) if err.raw_os_error() == Some(libc::ETIME) => {
+                log::warn!("release point {point} not signaled after 100 ms, drawing anyway");
+            }
+            Err(err) => log::warn!("failed to wait for release point {point}: {err}"),
+        }
+    }
+
+    /// Puts the fence of the frame onto the acquire timeline and sets the points for the
+    /// commit of the swap. Must come right before swapping, once everything is drawn.
+    pub(crate) fn set_points(&mut self, gl: &Context) -> Result<(), String> {
+        let sync_file = self.device.native_fence(gl)?;
+        let mut import = SyncobjHandle {
+            handle: self.fence,
+            flags: FD_TO_HANDLE_IMPORT_SYNC_FILE,
+            fd: sync_file.as_raw_fd(),
+            pad: 0,
+        };
+        self.device
+            .ioctl(SYNCOBJ_FD_TO_HANDLE, &mut import)
+            .map_err(|err| format!("importing the fence: {err}"))?;
+
+        let point = self.point + 1;
+        let mut transfer = SyncobjTransfer {
+            src_handle: self.fence,
+            dst_handle: self.acquire.0,
+            dst_point: point,
+            ..SyncobjTransfer::default()
+        };
+        self.device
+            .ioctl(SYNCOBJ_TRANSFER, &mut transfer)
+            .map_err(|err| format!("setting acquire point {point}: {err}"))?;
+
+        let (high, low) = ((point >> 32) as u32, point as u32);
+        self.surface.set_acquire_point(&self.acquire.1, high, low);
+        self.surface.set_release_point(&self.release.1, high, low);
+        self.point = point;
+        Ok(())
+    }
+}
+
+impl Drop for SurfaceSync {
+    fn drop(&mut self) {
+        // Back to implicit sync, the compositor keeps its own references to the timelines
+        self.surface.destroy();
+        for (handle, timeline) in [&self.acquire, &self.release] {
+            timeline.destroy();
+            self.device.destroy_syncobj(*handle);
+        }
+        self.device.destroy_syncobj(self.fence);
+    }
+}
+
+/// The first render node under /dev/dri, for drivers that don't say which one is theirs.
+fn first_render_node() -> Option<String> {
+    let mut nodes: Vec<String> = std::fs::read_dir("/dev/dri")
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path().display().to_string())
+        .filter(|path| path.starts_with("/dev/dri/renderD"))
+        .collect();
+    nodes.sort();
+    nodes.into_iter().next()
+}
+
+/// Implements `Dispatch` for the syncobj objects, none of them has events.
+macro_rules! ignore_events {
+    ($($interface:ty),*) => {$(
+        impl Dispatch<$interface, Syncobj> for App<GlowApp> {
+            fn event(
+                _app: &mut Self,
+                _proxy: &$interface,
+                _event: <$interface as Proxy>::Event,
+                _data: &Syncobj,
+                _conn: &Connection,
+                _qh: &QueueHandle<Self>,
+            ) {
+            }
+        }
+    )*};
+}
+
+ignore_events!(
+    WpLinuxDrmSyncobjManagerV1,
+    WpLinuxDrmSyncobjSurfaceV1,
+    WpLinuxDrmSyncobjTimelineV1
+);
@@ -1,381 +1,1839 @@
-use smithay_client_toolkit::{
-    compositor::{CompositorHandler, CompositorState},
-    delegate_compositor, delegate_output, delegate_registry, delegate_seat, delegate_xdg_shell,
-    delegate_xdg_window,
-    output::{OutputHandler, OutputState},
-    registry::{ProvidesRegistryState, RegistryState},
-    registry_handlers,
-    seat::{Capability, SeatHandler, SeatState},
-    shell::{
-        xdg::{
-            window::{Window, WindowConfigure, WindowDecorations, WindowHandler},
-            XdgShell,
-        },
-        WaylandSurface,
-    },
+use std::{
+    collections::{hash_map::Entry, BTreeSet, HashMap, VecDeque},
+    mem::ManuallyDrop,
+    ptr::null_mut,
+    sync::Arc,
+    time::Instant,
+};
+
+use smithay_client_toolkit::shell::{xdg::window::WindowConfigure, WaylandSurface};
+use wayland_app_common::{
+    format_name, modifier_name, App, AppHandler, BaseApp, DmabufFormats, DroppedImage, GlApi,
+    GlInitError, MenuPopup, Options, Overlay, DEFAULT_SIZE,
 };
-use wayland_client::{
-    globals::registry_queue_init,
-    protocol::{wl_output, wl_seat, wl_surface},
-    Connection, Proxy, QueueHandle,
+use wayland_client::{backend::ObjectId, protocol::wl_surface, EventQueue, Proxy, QueueHandle};
+use glow_common::{
+    damage::{intersection, union, OUTLINE_COLOR},
+    info::{self, EglInfo, CONFIG_ATTRIBUTES},
+    msaa, screenshot,
+    srgb::{self, framebuffer_color},
+    best_config, builtin_renderer, config_table, depth_stencil_sizes, Canvas, ConfigInfo,
+    DamageTracker, GlDebug, ImageQuad, Quads, Renderer, RendererFactory, ResetStatus, Scene, Sheet,
+    Software, View,
 };
 use wayland_egl::WlEglSurface;
-use glow::{Context, HasContext, COLOR_BUFFER_BIT, RENDERER, VERSION};
+use platform::Platform;
+use render_thread::RenderThread;
+use glow::{Context, HasContext, COLOR_BUFFER_BIT, RENDERER, SCISSOR_TEST, VERSION};
 
-fn main() {
-    env_logger::init();
+mod device;
+mod offscreen;
+mod platform;
+mod render_thread;
+#[cfg(feature = "explicit-sync")]
+mod explicit_sync;
 
-    let conn = Connection::connect_to_env().unwrap();
-    let (globals, mut event_queue) = registry_queue_init(&conn).unwrap();
-    let qh = event_queue.handle();
+/// `EGL_BUFFER_AGE_EXT` of EGL_EXT_buffer_age, the same as `EGL_BUFFER_AGE_KHR`.
+const BUFFER_AGE: khronos_egl::Int = 0x313D;
 
-    // Initialize xdg_shell handlers
-    let compositor_state =
-        CompositorState::bind(&globals, &qh).expect("wl_compositor not available");
-    let xdg_shell_state = XdgShell::bind(&globals, &qh).expect("xdg shell not available");
+/// `eglSwapBuffersWithDamageKHR`, or the EXT version which takes the same arguments.
+type SwapBuffersWithDamage = unsafe extern "system" fn(
+    khronos_egl::EGLDisplay,
+    khronos_egl::EGLSurface,
+    *const khronos_egl::Int,
+    khronos_egl::Int,
+) -> khronos_egl::Boolean;
 
-    let surface = compositor_state.create_surface(&qh);
-    // Create the window
-    let window = xdg_shell_state.create_window(surface, WindowDecorations::ServerDefault, &qh);
-    window.set_title("glow wayland window");
-    // GitHub does not let projects use the `org.github` domain but the `io.github` domain is fine.
-    window.set_app_id("io.github.smithay.client-toolkit.GlowExample");
-    window.set_min_size(Some((256, 256)));
-    window.commit();
-
-    let mut glow_app = GlowApp {
-        registry_state: RegistryState::new(&globals),
-        seat_state: SeatState::new(&globals, &qh),
-        output_state: OutputState::new(&globals, &qh),
-
-        exit: false,
-        width: 256,
-        height: 256,
-        window,
-        conn,
-
-        egl_instance: None,
-        wl_egl_surface: None,
-        egl_display: None,
-        egl_context: None,
-        egl_surface: None,
-        gl_context: None,
-    };
+/// `eglSetDamageRegionKHR` of EGL_KHR_partial_update.
+type SetDamageRegion = SwapBuffersWithDamage;
 
-    // We don't draw immediately, the configure will notify us when to first draw.
-    loop {
-        event_queue.blocking_dispatch(&mut glow_app).unwrap();
+/// `eglQueryDmaBufFormatsEXT` of EGL_EXT_image_dma_buf_import_modifiers.
+type QueryDmaBufFormats = unsafe extern "system" fn(
+    khronos_egl::EGLDisplay,
+    khronos_egl::Int,
+    *mut khronos_egl::Int,
+    *mut khronos_egl::Int,
+) -> khronos_egl::Boolean;
 
-        if glow_app.exit {
-            println!("exiting example");
-            break;
+/// `eglQueryDmaBufModifiersEXT` of the same extension.
+type QueryDmaBufModifiers = unsafe extern "system" fn(
+    khronos_egl::EGLDisplay,
+    khronos_egl::Int,
+    khronos_egl::Int,
+    *mut u64,
+    *mut khronos_egl::Boolean,
+    *mut khronos_egl::Int,
+) -> khronos_egl::Boolean;
+
+/// `DRM_FORMAT_MOD_INVALID`, the compositor takes the format with whatever layout the driver
+/// picks without saying.
+const MODIFIER_INVALID: u64 = 0x00ff_ffff_ffff_ffff;
+
+/// How long to wait for the GPU to finish an old frame with `--max-frames-in-flight` before
+/// drawing anyway, in nanoseconds.
+const FRAME_FENCE_TIMEOUT: khronos_egl::Time = 100_000_000;
+
+/// Attributes of EGL_EXT_create_context_robustness, for a context that reports GPU resets.
+const CONTEXT_OPENGL_ROBUST_ACCESS_EXT: khronos_egl::Int = 0x30BF;
+const CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY_EXT: khronos_egl::Int = 0x3138;
+const LOSE_CONTEXT_ON_RESET_EXT: khronos_egl::Int = 0x31BF;
+
+fn main() {
+    let options = Options::from_args();
+    let mut logger = env_logger::Builder::from_default_env();
+    if let Some(level) = options.log_level {
+        logger.filter_level(level);
+    }
+    logger.init();
+    if let Some(frames) = options.offscreen {
+        if let Err(err) = offscreen::run(&options, frames) {
+            eprintln!("{err}");
+            std::process::exit(1);
         }
+        return;
     }
 
-    // Clean up in the proper order
-    drop(glow_app.gl_context);
-    // EGL surface, context and display are Copy types and don't need explicit drops
-    drop(glow_app.wl_egl_surface);
-    drop(glow_app.window);
+    wayland_app_common::run_app(options, start, |mut app| {
+        // The render thread cleans up before it ends, dropping the handler drops the GL stack,
+        // which cleans up in the proper order itself. Both go before the windows.
+        if let Some(render_thread) = app.handler.render_thread.take() {
+            render_thread.stop();
+        }
+        drop(app.handler);
+        drop(app.windows);
+    });
+}
+
+/// Connects and opens the first window, again after a reconnect.
+fn start(mut options: Options) -> (App<GlowApp>, EventQueue<App<GlowApp>>) {
+    let scene = options.scene;
+    if options.render_thread
+        && (options.overlay
+            || options.solid_background
+            || options.explicit_sync
+            || options.max_frames_in_flight.is_some())
+    {
+        println!(
+            "--render-thread draws without --overlay, --solid-background, --explicit-sync and \
+             --max-frames-in-flight"
+        );
+        options.overlay = false;
+        options.solid_background = false;
+        options.explicit_sync = false;
+        options.max_frames_in_flight = None;
+    }
+    let new_renderer: RendererFactory = Arc::new(move || builtin_renderer(scene));
+    // GitHub does not let projects use the `org.github` domain but the `io.github` domain is fine.
+    let (app, event_queue) = wayland_app_common::init_with_options(
+        "glow wayland window",
+        "io.github.smithay.client-toolkit.GlowExample",
+        GlowApp {
+            stack: None,
+            software: options.force_software.then(|| Software::new(new_renderer())),
+            new_renderer,
+            threaded: options.render_thread,
+            render_thread: None,
+            #[cfg(feature = "explicit-sync")]
+            syncobj_manager: None,
+        },
+        options,
+    );
+
+    // Only the explicit sync manager is set afterwards
+    #[cfg(feature = "explicit-sync")]
+    let mut app = app;
+    if app.shared.options.explicit_sync {
+        #[cfg(feature = "explicit-sync")]
+        {
+            app.handler.syncobj_manager =
+                explicit_sync::SyncobjManager::bind(&app, &event_queue.handle());
+        }
+        #[cfg(not(feature = "explicit-sync"))]
+        println!("built without the explicit-sync feature, staying with implicit sync");
+    }
+    (app, event_queue)
 }
 
 struct GlowApp {
-    registry_state: RegistryState,
-    seat_state: SeatState,
-    output_state: OutputState,
+    /// Created with the first window and shared by all of them.
+    stack: Option<GlStack>,
+    /// Draws all windows instead of GL with `--force-software`, or once EGL couldn't be set up
+    /// for the first window.
+    software: Option<Software>,
+    /// Makes the scene of the renderer, any [`Renderer`] will do. Again after a lost context.
+    new_renderer: RendererFactory,
+    /// Whether the windows are drawn on a render thread, `--render-thread`.
+    threaded: bool,
+    /// Started with the first window with `--render-thread`, it has the context then and
+    /// `stack` stays `None`.
+    render_thread: Option<RenderThread>,
+    /// With `--explicit-sync`, `None` when the compositor lacks it.
+    #[cfg(feature = "explicit-sync")]
+    syncobj_manager: Option<explicit_sync::SyncobjManager>,
+}
 
-    exit: bool,
-    width: u32,
-    height: u32,
-    window: Window,
-    conn: Connection,
+/// The renderer along with every surface it draws to. EGL wants them cleaned up in order: the
+/// GL objects while a surface is still around to make the context current with, then the
+/// surfaces, the context and the display. Dropping the stack does that, after a lost context
+/// as well as on exit, so nothing can be left out or go in the wrong order.
+struct GlStack {
+    /// Only taken out when the stack is dropped.
+    renderer: ManuallyDrop<GlRenderer>,
+    /// The surfaces of each window, keyed by [`BaseApp::id`].
+    windows: HashMap<ObjectId, GlWindow>,
+    /// The surfaces of the `--overlay` subsurface of each window, keyed like `windows`.
+    overlays: HashMap<ObjectId, GlWindow>,
+    /// The surfaces of the popup menu while it is open.
+    popup: Option<GlWindow>,
+}
 
-    egl_instance: Option<khronos_egl::Instance<khronos_egl::Static>>,
-    wl_egl_surface: Option<WlEglSurface>,
-    egl_display: Option<khronos_egl::Display>,
-    egl_context: Option<khronos_egl::Context>,
-    egl_surface: Option<khronos_egl::Surface>,
-    gl_context: Option<Context>,
+impl GlStack {
+    fn new(renderer: GlRenderer) -> GlStack {
+        GlStack {
+            renderer: ManuallyDrop::new(renderer),
+            windows: HashMap::new(),
+            overlays: HashMap::new(),
+            popup: None,
+        }
+    }
 }
 
-impl GlowApp {
-    fn init_gl(&mut self) {
+impl Drop for GlStack {
+    fn drop(&mut self) {
+        let windows = self.windows.drain().map(|(_, window)| window);
+        let overlays = self.overlays.drain().map(|(_, window)| window);
+        let windows = windows.chain(overlays).chain(self.popup.take()).collect();
+        // The renderer is not touched again, the stack is going away
+        let renderer = unsafe { ManuallyDrop::take(&mut self.renderer) };
+        renderer.destroy(windows);
+    }
+}
+
+/// The EGL display and the one context all windows are drawn with. The context is made current
+/// with the surface of a window before drawing to it, so the GL objects are shared for free.
+struct GlRenderer {
+    egl: khronos_egl::Instance<khronos_egl::Static>,
+    display: khronos_egl::Display,
+    config: khronos_egl::Config,
+    context: khronos_egl::Context,
+    gl: Context,
+    debug: GlDebug,
+    reset_status: ResetStatus,
+    damage: DamageExtensions,
+    /// How the display was created, the window surfaces are created the same way.
+    platform: Platform,
+    /// Whether surfaces can be sRGB, with EGL 1.5 or EGL_KHR_gl_colorspace.
+    srgb_supported: bool,
+    /// Whether the windows are paced by fences with `--max-frames-in-flight`, which needs
+    /// EGL 1.5.
+    fence_pacing: bool,
+    /// What is drawn in the content area of every window.
+    scene: Scene,
+    /// The 3D scene of `--depth`.
+    quads: Quads,
+    /// The last image dropped onto each window, keyed by [`BaseApp::id`].
+    images: HashMap<ObjectId, ImageQuad>,
+    /// What a tablet drew on the windows, and what it is drawn with.
+    canvas: Canvas,
+    sheets: HashMap<ObjectId, Sheet>,
+}
+
+/// What a window needs on top of the shared context, its surfaces.
+struct GlWindow {
+    wl_egl_surface: WlEglSurface,
+    egl_surface: khronos_egl::Surface,
+    /// What the latest frames changed, for redrawing only that.
+    damage: DamageTracker,
+    /// The fences of the frames in flight with `--max-frames-in-flight`, the oldest first.
+    fences: VecDeque<khronos_egl::Sync>,
+    /// The timelines of `--explicit-sync`, only windows have them. `None` syncs implicitly.
+    #[cfg(feature = "explicit-sync")]
+    sync: Option<explicit_sync::SurfaceSync>,
+}
+
+/// The EGL extensions for redrawing only what changed. Without them every frame is drawn
+/// completely and the whole surface is damaged.
+struct DamageExtensions {
+    /// EGL_EXT_buffer_age or EGL_KHR_partial_update, tells how old the contents of the back
+    /// buffer are.
+    buffer_age: bool,
+    swap_buffers_with_damage: Option<SwapBuffersWithDamage>,
+    set_damage_region: Option<SetDamageRegion>,
+}
+
+impl DamageExtensions {
+    fn load(
+        egl: &khronos_egl::Instance<khronos_egl::Static>,
+        extensions: &[&str],
+    ) -> DamageExtensions {
+        let has = |name: &str| extensions.contains(&name);
+
+        // The function of the first extension the display has
+        let function = |candidates: &[(&str, &str)]| {
+            candidates
+                .iter()
+                .find(|(extension, _)| has(extension))
+                .and_then(|(_, function)| egl.get_proc_address(function))
+                .map(|function| unsafe {
+                    std::mem::transmute::<extern "system" fn(), SwapBuffersWithDamage>(function)
+                })
+        };
+
+        let damage = DamageExtensions {
+            buffer_age: has("EGL_EXT_buffer_age") || has("EGL_KHR_partial_update"),
+            swap_buffers_with_damage: function(&[
+                ("EGL_KHR_swap_buffers_with_damage", "eglSwapBuffersWithDamageKHR"),
+                ("EGL_EXT_swap_buffers_with_damage", "eglSwapBuffersWithDamageEXT"),
+            ]),
+            set_damage_region: function(&[("EGL_KHR_partial_update", "eglSetDamageRegionKHR")]),
+        };
+        println!(
+            "buffer age: {}, swap with damage: {}, partial update: {}",
+            damage.buffer_age,
+            damage.swap_buffers_with_damage.is_some(),
+            damage.set_damage_region.is_some()
+        );
+        damage
+    }
+}
+
+impl GlRenderer {
+    /// Initializes EGL and creates the context. A context can only be made current with a
+    /// surface on every driver, so this also creates the surfaces of the first window.
+    fn new(
+        base: &mut BaseApp,
+        scene: Box<dyn Renderer>,
+    ) -> Result<(GlRenderer, GlWindow), GlInitError> {
         // Get the native display
-        let display_ptr = self.conn.backend().display_ptr();
-        
+        let display_ptr = base.connection().backend().display_ptr();
+
         // Initialize EGL
         let egl = khronos_egl::Instance::new(khronos_egl::Static);
-        let display = unsafe {
-            egl.get_display(display_ptr as *mut std::ffi::c_void)
-                .expect("Failed to get EGL display")
-        };
-        
-        egl.initialize(display).expect("Failed to initialize EGL");
+        let platform = Platform::detect(&egl);
+        let display_ptr = display_ptr as *mut std::ffi::c_void;
+        let (display, picked) =
+            device::get_display(&egl, platform, display_ptr, base.gpu.as_ref())?;
+
+        let egl_version = egl.initialize(display).map_err(|err| {
+            let err = GlInitError::Display(format!("eglInitialize: {err}"));
+            device::explain(err, picked.as_ref(), base)
+        })?;
+        // Cleans up again when anything below fails
+        let mut init = PartialInit::new(display);
+        match device::display_device(&egl, display) {
+            Some(device) => println!("EGL device: {}", device.name()),
+            None => println!("EGL device: unknown, EGL_EXT_device_query is missing"),
+        }
 
         let version = egl.query_string(Some(display), khronos_egl::VERSION)
-            .expect("Failed to query EGL version");
+            .map_err(|err| GlInitError::Display(format!("EGL_VERSION: {err}")))?;
         println!("EGL version: {:?}", version);
+        let extensions = egl
+            .query_string(Some(display), khronos_egl::EXTENSIONS)
+            .map(|extensions| extensions.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let extensions: Vec<&str> = extensions.split_ascii_whitespace().collect();
+        let damage = DamageExtensions::load(&egl, &extensions);
 
-        // Choose an EGL config
-        let attributes = [
-            khronos_egl::RED_SIZE, 8,
-            khronos_egl::GREEN_SIZE, 8,
-            khronos_egl::BLUE_SIZE, 8,
-            khronos_egl::ALPHA_SIZE, 8,
-            khronos_egl::SURFACE_TYPE, khronos_egl::WINDOW_BIT,
-            khronos_egl::RENDERABLE_TYPE, khronos_egl::OPENGL_ES2_BIT,
-            khronos_egl::NONE,
-        ];
-
-        let config = egl
-            .choose_first_config(display, &attributes)
-            .expect("Failed to choose EGL config")
-            .expect("No EGL config found");
-
-        // Bind OpenGL ES API
-        egl.bind_api(khronos_egl::OPENGL_ES_API).expect("Failed to bind OpenGL ES API");
-
-        // Create EGL context
-        let context_attributes = [
-            khronos_egl::CONTEXT_MAJOR_VERSION, 2,
-            khronos_egl::CONTEXT_MINOR_VERSION, 0,
-            khronos_egl::NONE,
-        ];
-
-        let context = egl
-            .create_context(display, config, None, &context_attributes)
-            .expect("Failed to create EGL context");
-
-        // Create the wayland EGL surface
-        let wl_egl_surface = WlEglSurface::new(
-            self.window.wl_surface().id(),
-            self.width as i32,
-            self.height as i32,
-        )
-        .expect("Failed to create WlEglSurface");
-
-        // Create EGL window surface
-        let egl_surface = unsafe {
-            egl.create_window_surface(
-                display,
-                config,
-                wl_egl_surface.ptr() as *mut std::ffi::c_void,
-                None,
-            )
-            .expect("Failed to create EGL surface")
+        if base.dump_configs {
+            let configs = matching_configs(&egl, display, &[khronos_egl::NONE]);
+            let configs: Vec<ConfigInfo> =
+                configs.into_iter().map(|config| config_info(&egl, display, config)).collect();
+            for line in config_table(&configs) {
+                println!("{line}");
+            }
+            drop(init);
+            std::process::exit(0);
+        }
+        let srgb_supported =
+            egl_version >= (1, 5) || extensions.contains(&"EGL_KHR_gl_colorspace");
+        let fence_pacing = match base.max_frames_in_flight {
+            Some(frames) if egl_version >= (1, 5) => {
+                println!("async frames paced by fences, at most {frames} in flight");
+                true
+            }
+            Some(_) => {
+                println!("no fences before EGL 1.5, async frames are paced by frame callbacks");
+                false
+            }
+            None => false,
         };
 
-        // Make the context current
-        egl.make_current(display, Some(egl_surface), Some(egl_surface), Some(context))
-            .expect("Failed to make EGL context current");
-
-        // Set swap interval to 0 for non-blocking rendering
-        egl.swap_interval(display, 0).ok();
+        // Try the API `--api` asks for first, then the ones below it. GLES2 works everywhere.
+        let debug = GlDebug::wanted(base);
+        let request = ConfigRequest::window(base);
+        let mut created = Err(GlInitError::Context("no API to try".to_string()));
+        for &api in base.gl_api.unwrap_or(GlApi::Gles2).fallbacks() {
+            created = create_context(&egl, display, api, &request, debug, egl_version, &extensions)
+                .map(|(config, context)| (api, config, context));
+            match &created {
+                Ok(_) => break,
+                Err(err) => log::warn!("no {api} context: {err}"),
+            }
+        }
+        let (api, config, context) = created?;
+        init.context = Some(context);
+        println!("API: {api}");
+        // The config may have more samples than asked for, or fewer after falling back
+        let samples = egl.get_config_attrib(display, config, khronos_egl::SAMPLES).unwrap_or(0);
+        println!("MSAA: {samples} samples, {} asked for", base.msaa);
+        let depth = egl.get_config_attrib(display, config, khronos_egl::DEPTH_SIZE).unwrap_or(0);
+        let stencil =
+            egl.get_config_attrib(display, config, khronos_egl::STENCIL_SIZE).unwrap_or(0);
+        println!("depth: {depth} bits, stencil: {stencil} bits");
+        if base.gl_info_only {
+            report_without_window(&egl, display, config, context, &extensions);
+        }
 
-        // Create glow context
-        let gl = unsafe {
-            Context::from_loader_function(|s| {
-                egl.get_proc_address(s).unwrap() as *const _
-            })
+        // Create glow context. The function pointers are the same for every surface, but some
+        // drivers only hand them out with a current context.
+        let window = create_window(&egl, platform, display, config, base, srgb_supported)
+            .map_err(|err| device::explain(err, picked.as_ref(), base))?;
+        let window = init.window.insert(window);
+        make_current(&egl, display, context, window).map_err(|err| {
+            let err = GlInitError::MakeCurrent(err.to_string());
+            device::explain(err, picked.as_ref(), base)
+        })?;
+        // The swap interval belongs to the surface that is current
+        egl.swap_interval(display, base.swap_interval() as i32).ok();
+        let get_proc_address = |s: &str| -> *const std::ffi::c_void {
+            egl.get_proc_address(s).map_or(std::ptr::null(), |f| f as *const _)
         };
+        let mut gl = unsafe { Context::from_loader_function(get_proc_address) };
+        let debug = GlDebug::new(&mut gl, debug);
+        let reset_status = ResetStatus::load(&gl, get_proc_address);
+        msaa::enable(&gl, samples as u8);
+        if base.gl_info {
+            info::print_report(&egl_info(&egl, display, config), &gl);
+        }
 
         println!("OpenGL version: {}", unsafe { gl.get_parameter_string(VERSION) });
-        println!("OpenGL renderer: {}", unsafe { gl.get_parameter_string(RENDERER) });
+        let renderer = unsafe { gl.get_parameter_string(RENDERER) };
+        println!("OpenGL renderer: {renderer}");
+        if ["llvmpipe", "softpipe", "swrast"].iter().any(|name| renderer.contains(name)) {
+            log::warn!("rendering in software, --dmabuf-info shows what the compositor takes");
+        }
+        println!(
+            "swap interval {}, {}",
+            base.swap_interval(),
+            if base.vsync { "vsync" } else { "async" }
+        );
+
+        let scene = Scene::new(scene, &gl)?;
+        debug.check(&gl, "setting up the scene");
+        let quads = Quads::new(&gl)?;
+        debug.check(&gl, "setting up the quads");
+        let canvas = Canvas::new(&gl)?;
+        debug.check(&gl, "setting up the canvas");
+
+        let window = init.finish();
+        let renderer = GlRenderer {
+            egl,
+            display,
+            config,
+            context,
+            gl,
+            debug,
+            reset_status,
+            damage,
+            platform,
+            srgb_supported,
+            fence_pacing,
+            scene,
+            quads,
+            images: HashMap::new(),
+            canvas,
+            sheets: HashMap::new(),
+        };
+        Ok((renderer, window))
+    }
+
+    /// Creates the surfaces of another window, the context is shared.
+    fn create_window(&self, base: &mut BaseApp) -> Result<GlWindow, GlInitError> {
+        let window = create_window(
+            &self.egl,
+            self.platform,
+            self.display,
+            self.config,
+            base,
+            self.srgb_supported,
+        )?;
+        self.init_surface(&window, base.swap_interval())?;
+        Ok(window)
+    }
+
+    /// Creates the surfaces of the popup menu, which is drawn with the same context too.
+    fn create_popup(&self, popup: &MenuPopup) -> Result<GlWindow, GlInitError> {
+        let surface = popup.popup().wl_surface();
+        // The menu colors go to the framebuffer as they are, it doesn't need an sRGB one
+        let window = create_surface(
+            &self.egl,
+            self.platform,
+            self.display,
+            self.config,
+            surface,
+            popup.buffer_size(),
+            false,
+        )?;
+        // The menu is only drawn on configures, it never has to wait for vsync
+        self.init_surface(&window, 0)?;
+        Ok(window)
+    }
+
+    /// Creates the surfaces of the overlay of a window, drawn with the same context.
+    fn create_overlay(&self, overlay: &Overlay) -> Result<GlWindow, GlInitError> {
+        let window = create_surface(
+            &self.egl,
+            self.platform,
+            self.display,
+            self.config,
+            overlay.wl_surface(),
+            overlay.buffer_size(),
+            false,
+        )?;
+        // The window waits for vsync, the overlay drawn right before it must not wait as well
+        self.init_surface(&window, 0)?;
+        Ok(window)
+    }
+
+    fn init_surface(&self, window: &GlWindow, swap_interval: u32) -> Result<(), GlInitError> {
+        make_current(&self.egl, self.display, self.context, window)
+            .map_err(|err| GlInitError::MakeCurrent(err.to_string()))?;
+        // The swap interval belongs to the surface that is current
+        self.egl.swap_interval(self.display, swap_interval as i32).ok();
+        Ok(())
+    }
+
+    /// Destroys the surfaces of a window, its image and its strokes. The context stays, along
+    /// with the other GL objects.
+    fn destroy_window(&mut self, id: &ObjectId, window: GlWindow) {
+        let image = self.images.remove(id);
+        let sheet = self.sheets.remove(id);
+        if image.is_some() || sheet.is_some() {
+            match make_current(&self.egl, self.display, self.context, &window) {
+                Ok(()) => {
+                    if let Some(image) = image {
+                        image.destroy(&self.gl);
+                    }
+                    if let Some(sheet) = sheet {
+                        sheet.destroy(&self.gl);
+                    }
+                }
+                Err(err) => log::warn!("failed to make the context current for cleanup: {err}"),
+            }
+        }
+        destroy_window(&self.egl, self.display, window);
+    }
+
+    /// Uploads an image dropped onto the window, replacing the one it showed before.
+    fn set_image(&mut self, id: &ObjectId, window: &mut GlWindow, image: &DroppedImage) {
+        if let Err(err) = make_current(&self.egl, self.display, self.context, window) {
+            log::warn!("failed to make the context current for the image: {err}");
+            return;
+        }
+
+        let quad = ImageQuad::new(&self.gl, image);
+        self.debug.check(&self.gl, "uploading the image");
+        match quad {
+            Ok(quad) => {
+                if let Some(old) = self.images.insert(id.clone(), quad) {
+                    old.destroy(&self.gl);
+                }
+                // The damage tracking doesn't know about images
+                window.damage.reset();
+            }
+            Err(err) => println!("{err}"),
+        }
+    }
+
+    /// Draws the frame of the window described by `base` into its surface and swaps. Errors,
+    /// e.g. `BAD_SURFACE` for a surface that went away, only affect this window.
+    ///
+    /// With a buffer age only what changed since the back buffer was last drawn is redrawn, the
+    /// rest of it is still right. The compositor is told about the damage as well, so it can
+    /// skip the rest too.
+    fn draw_to(
+        &mut self,
+        window: &mut GlWindow,
+        base: &mut BaseApp,
+    ) -> Result<(), khronos_egl::Error> {
+        make_current(&self.egl, self.display, self.context, window)?;
+        if self.reset_status.context_lost() {
+            return Err(khronos_egl::Error::ContextLost);
+        }
+
+        self.wait_for_frames(window, base);
+        let gl = &self.gl;
+        let draw_start = Instant::now();
+        #[cfg(feature = "explicit-sync")]
+        wait_for_release(window);
+        let sheet = self.sheets.entry(base.id()).or_default();
+        self.canvas.update(gl, sheet, base);
+        self.debug.check(gl, "drawing the strokes");
+
+        // The age is 0 for a buffer with unknown contents, e.g. after a resize. A screenshot
+        // reads the whole buffer, which is only defined everywhere when all of it is drawn.
+        let age = if self.damage.buffer_age && !base.full_damage && !base.screenshot {
+            self.egl.query_surface(self.display, window.egl_surface, BUFFER_AGE)?
+        } else {
+            0
+        };
+        let damage = window.damage.frame(base, self.scene.bounds(base), age.max(0) as u32);
+        let (buffer_width, buffer_height) = base.buffer_size();
+        let full = damage == [[0, 0, buffer_width as i32, buffer_height as i32]];
+        if !full && let Some(set_damage_region) = self.damage.set_damage_region {
+            let rects = damage.as_flattened();
+            unsafe {
+                set_damage_region(
+                    self.display.as_ptr(),
+                    window.egl_surface.as_ptr(),
+                    rects.as_ptr(),
+                    damage.len() as khronos_egl::Int,
+                );
+            }
+        }
 
-        self.egl_instance = Some(egl);
-        self.wl_egl_surface = Some(wl_egl_surface);
-        self.egl_display = Some(display);
-        self.egl_context = Some(context);
-        self.egl_surface = Some(egl_surface);
-        self.gl_context = Some(gl);
+        // Everything is drawn clipped to the damage, rectangles outside of it are skipped
+        let clip = damage.iter().copied().reduce(union).unwrap_or([0, 0, 0, 0]);
+        let srgb = base.srgb;
+        let fill_translucent = |rect: [i32; 4], color: [f32; 3], alpha: f32| {
+            if let Some([x, y, width, height]) = intersection(rect, clip) {
+                let [red, green, blue] = framebuffer_color(color, srgb);
+                unsafe {
+                    gl.scissor(x, y, width, height);
+                    gl.clear_color(red, green, blue, alpha);
+                    gl.clear(COLOR_BUFFER_BIT);
+                }
+            }
+        };
+        let fill = |rect: [i32; 4], color: [f32; 3]| fill_translucent(rect, color, 1.0);
+        srgb::set_encoding(gl, srgb);
+        unsafe {
+            gl.enable(SCISSOR_TEST);
+        }
+
+        // Clear the screen with a color following the frame callbacks and the pointer, or half
+        // transparent black with `--transparent`
+        let [red, green, blue, alpha] = base.background();
+        fill_translucent(clip, [red, green, blue], alpha);
+
+        // Draw the test pattern, it shows whether the scale and transform are right
+        for rect in base.test_pattern() {
+            fill(rect, [1.0, 1.0, 1.0]);
+        }
+
+        // Without server-side decorations we draw a title bar, the content goes below it
+        for (rect, color) in base.decoration_rects() {
+            fill(rect, color);
+        }
+
+        // The region C confines the pointer to
+        for rect in base.confinement_outline() {
+            fill(rect, [1.0, 0.5, 0.0]);
+        }
+
+        // A square under each finger touching the window
+        for (rect, color) in base.touch_rects() {
+            fill(rect, color);
+        }
+
+        // The text typed through the input method, and its preedit underlined
+        for (rect, color) in base.text_rects() {
+            fill(rect, color);
+        }
+
+        // The badge while F10 has the compositor's shortcuts
+        for (rect, color) in base.shortcuts_badge_rects() {
+            fill(rect, color);
+        }
+        self.debug.check(gl, "clearing");
+
+        unsafe {
+            let [x, y, width, height] = clip;
+            gl.scissor(x, y, width, height);
+            let [x, y, width, height] = base.content_rect();
+            gl.viewport(x, y, width, height);
+        }
+
+        let view = View::of(base);
+        if let Some(image) = self.images.get(&base.id()) {
+            image.draw(gl, &view);
+            self.debug.check(gl, "drawing the image");
+        }
+        self.scene.draw(gl, &view);
+        self.debug.check(gl, "drawing the scene");
+        if base.depth {
+            self.quads.draw(gl, &view);
+            self.debug.check(gl, "drawing the quads");
+        }
+        self.canvas.draw(gl, sheet, &view);
+        self.debug.check(gl, "drawing the canvas");
+
+        // The next frames redraw the outline along with their damage, which erases it again
+        if base.show_damage {
+            for rect in window.damage.outline() {
+                fill(rect, OUTLINE_COLOR);
+            }
+        }
+        unsafe {
+            gl.disable(SCISSOR_TEST);
+        }
+        screenshot::save_if_requested(gl, base);
+        #[cfg(feature = "explicit-sync")]
+        set_sync_points(window, gl);
+
+        let swap_start = Instant::now();
+        // Swap buffers, this also commits the surface. With vsync it may wait for the previous
+        // frame to be shown.
+        match self.damage.swap_buffers_with_damage {
+            Some(swap_buffers_with_damage) if !full => {
+                // wl_surface damage has its origin at the top left, unlike GL
+                let surface = base.window().wl_surface();
+                if surface.version() >= 4 {
+                    for &[x, y, width, height] in &damage {
+                        surface.damage_buffer(x, buffer_height as i32 - y - height, width, height);
+                    }
+                }
+
+                let rects = damage.as_flattened();
+                let swapped = unsafe {
+                    swap_buffers_with_damage(
+                        self.display.as_ptr(),
+                        window.egl_surface.as_ptr(),
+                        rects.as_ptr(),
+                        damage.len() as khronos_egl::Int,
+                    )
+                };
+                if swapped != khronos_egl::TRUE {
+                    return Err(self.egl.get_error().unwrap_or(khronos_egl::Error::BadSurface));
+                }
+            }
+            _ => self.egl.swap_buffers(self.display, window.egl_surface)?,
+        }
+        base.frame_stats.record(swap_start - draw_start, swap_start.elapsed());
+        self.insert_fence(window, base);
+        Ok(())
     }
 
-    fn draw(&self) {
-        let gl = self.gl_context.as_ref().unwrap();
-        let egl = self.egl_instance.as_ref().unwrap();
-        let display = self.egl_display.unwrap();
-        let egl_surface = self.egl_surface.unwrap();
+    /// Draws the content square of `--solid-background` into its subsurface and swaps, which
+    /// commits the subsurface. Only the image, the scene and the quads are drawn, everything
+    /// around them is the single-pixel buffer of the window. `rect` is where the square is in
+    /// the buffer of the whole window, the content is drawn as it would be there.
+    fn draw_content(
+        &mut self,
+        window: &mut GlWindow,
+        base: &mut BaseApp,
+        rect: [i32; 4],
+    ) -> Result<(), khronos_egl::Error> {
+        make_current(&self.egl, self.display, self.context, window)?;
+        if self.reset_status.context_lost() {
+            return Err(khronos_egl::Error::ContextLost);
+        }
+
+        self.wait_for_frames(window, base);
+        let gl = &self.gl;
+        let draw_start = Instant::now();
+        #[cfg(feature = "explicit-sync")]
+        wait_for_release(window);
 
-        // Clear the screen with a blue color
+        // The square follows the content area, which also changes without the window size,
+        // e.g. when D switches the decorations
+        let [square_x, square_y, width, height] = rect;
+        if window.wl_egl_surface.get_size() != (width, height) {
+            window.wl_egl_surface.resize(width, height, 0, 0);
+        }
+        let sheet = self.sheets.entry(base.id()).or_default();
+        self.canvas.update(gl, sheet, base);
+        self.debug.check(gl, "drawing the strokes");
+
+        // The same color as the pixel around it, unless the content covers it
+        srgb::set_encoding(gl, base.srgb);
+        let [red, green, blue, alpha] = base.background();
+        let [red, green, blue] = framebuffer_color([red, green, blue], base.srgb);
         unsafe {
-            gl.clear_color(0.0, 0.0, 1.0, 1.0);
+            gl.clear_color(red, green, blue, alpha);
             gl.clear(COLOR_BUFFER_BIT);
+            let [x, y, width, height] = base.content_rect();
+            gl.viewport(x - square_x, y - square_y, width, height);
+        }
+        self.debug.check(gl, "clearing");
+
+        let view = View::of(base);
+        if let Some(image) = self.images.get(&base.id()) {
+            image.draw(gl, &view);
+            self.debug.check(gl, "drawing the image");
+        }
+        self.scene.draw(gl, &view);
+        self.debug.check(gl, "drawing the scene");
+        if base.depth {
+            self.quads.draw(gl, &view);
+            self.debug.check(gl, "drawing the quads");
+        }
+        self.canvas.draw(gl, sheet, &view);
+        self.debug.check(gl, "drawing the canvas");
+
+        if base.screenshot {
+            base.screenshot = false;
+            println!("no screenshots with --solid-background, GL draws only part of the window");
         }
+        #[cfg(feature = "explicit-sync")]
+        set_sync_points(window, gl);
 
-        // Swap buffers (non-blocking due to swap interval = 0)
-        egl.swap_buffers(display, egl_surface)
-            .expect("Failed to swap buffers");
-        
-        // Commit the surface to display the changes
-        self.window.wl_surface().commit();
+        let swap_start = Instant::now();
+        self.egl.swap_buffers(self.display, window.egl_surface)?;
+        base.frame_stats.record(swap_start - draw_start, swap_start.elapsed());
+        self.insert_fence(window, base);
+        Ok(())
     }
 
-    fn resize(&mut self, width: u32, height: u32) {
-        self.width = width;
-        self.height = height;
+    /// Waits for the GPU to finish the frame `--max-frames-in-flight` frames back, once that
+    /// many are queued. Logs when it actually has to wait. Without pacing, e.g. after T turned
+    /// vsync on, the fences left are dropped.
+    fn wait_for_frames(&self, window: &mut GlWindow, base: &BaseApp) {
+        let max = match base.max_frames_in_flight {
+            Some(max) if base.paced_by_fences() => max as usize,
+            _ => 0,
+        };
+        while window.fences.len() >= max.max(1) {
+            let Some(fence) = window.fences.pop_front() else {
+                break;
+            };
+            let in_flight = window.fences.len() + 1;
+            // A zero timeout only asks whether it is done yet
+            let status = unsafe { self.egl.client_wait_sync(self.display, fence, 0, 0) };
+            if max > 0 && status == Ok(khronos_egl::TIMEOUT_EXPIRED) {
+                let start = Instant::now();
+                let flags = khronos_egl::SYNC_FLUSH_COMMANDS_BIT;
+                let status = unsafe {
+                    self.egl.client_wait_sync(self.display, fence, flags, FRAME_FENCE_TIMEOUT)
+                };
+                match status {
+                    Ok(khronos_egl::TIMEOUT_EXPIRED) => {
+                        log::warn!("window {}: the GPU is 100 ms behind, drawing anyway", base.id())
+                    }
+                    Ok(_) => log::debug!(
+                        "window {}: {in_flight} frames in flight, waited {:?}",
+                        base.id(),
+                        start.elapsed()
+                    ),
+                    Err(err) => log::warn!("failed to wait for a frame fence: {err}"),
+                }
+            }
+            if let Err(err) = unsafe { self.egl.destroy_sync(self.display, fence) } {
+                log::warn!("failed to destroy a frame fence: {err}");
+            }
+        }
+    }
 
-        if let Some(wl_egl_surface) = &self.wl_egl_surface {
-            wl_egl_surface.resize(width as i32, height as i32, 0, 0);
-            
-            if let Some(gl) = &self.gl_context {
-                unsafe {
-                    gl.viewport(0, 0, width as i32, height as i32);
+    /// Puts a fence after the frame just swapped, see [`Self::wait_for_frames`].
+    fn insert_fence(&self, window: &mut GlWindow, base: &BaseApp) {
+        if !base.paced_by_fences() {
+            return;
+        }
+        let fence = unsafe {
+            self.egl.create_sync(
+                self.display,
+                khronos_egl::SYNC_FENCE as khronos_egl::Enum,
+                &[khronos_egl::ATTRIB_NONE],
+            )
+        };
+        match fence {
+            Ok(fence) => window.fences.push_back(fence),
+            Err(err) => log::warn!("failed to put a fence after the frame: {err}"),
+        }
+    }
+
+    /// Draws the popup menu, plain rectangles for the background and the items.
+    fn draw_popup(&self, window: &GlWindow, popup: &MenuPopup) -> Result<(), khronos_egl::Error> {
+        make_current(&self.egl, self.display, self.context, window)?;
+
+        let gl = &self.gl;
+        let (width, height) = popup.buffer_size();
+        unsafe {
+            gl.viewport(0, 0, width as i32, height as i32);
+            gl.enable(SCISSOR_TEST);
+            for ([x, y, width, height], [red, green, blue]) in popup.menu_rects() {
+                gl.scissor(x, y, width, height);
+                gl.clear_color(red, green, blue, 1.0);
+                gl.clear(COLOR_BUFFER_BIT);
+            }
+            gl.disable(SCISSOR_TEST);
+        }
+        self.debug.check(gl, "drawing the popup");
+
+        // The menu is static, it is only drawn again when the compositor reconfigures it
+        self.egl.swap_buffers(self.display, window.egl_surface)
+    }
+
+    /// Draws the frame time graph into the overlay and swaps, which commits it. In sync mode the
+    /// compositor holds the commit back until the window commits too.
+    fn draw_overlay(&self, window: &GlWindow, overlay: &Overlay) -> Result<(), khronos_egl::Error> {
+        make_current(&self.egl, self.display, self.context, window)?;
+
+        let gl = &self.gl;
+        let (width, height) = overlay.buffer_size();
+        unsafe {
+            gl.viewport(0, 0, width as i32, height as i32);
+            gl.enable(SCISSOR_TEST);
+            for ([x, y, width, height], [red, green, blue]) in overlay.graph_rects() {
+                gl.scissor(x, y, width, height);
+                gl.clear_color(red, green, blue, 1.0);
+                gl.clear(COLOR_BUFFER_BIT);
+            }
+            gl.disable(SCISSOR_TEST);
+        }
+        self.debug.check(gl, "drawing the overlay");
+        self.egl.swap_buffers(self.display, window.egl_surface)
+    }
+
+    /// Frees the GL objects and the context. They can only be freed with the context current,
+    /// which takes a surface, so the remaining windows go last. Dropping the [`GlStack`] calls
+    /// this.
+    fn destroy(self, windows: Vec<GlWindow>) {
+        match windows.first() {
+            Some(window) => match make_current(&self.egl, self.display, self.context, window) {
+                Ok(()) => {
+                    self.scene.destroy(&self.gl);
+                    self.quads.destroy(&self.gl);
+                    for (_, image) in self.images {
+                        image.destroy(&self.gl);
+                    }
+                    self.canvas.destroy(&self.gl);
+                    for (_, sheet) in self.sheets {
+                        sheet.destroy(&self.gl);
+                    }
                 }
+                Err(err) => log::warn!("failed to make the context current for cleanup: {err}"),
+            },
+            // Without a window left the GL objects go along with the context
+            None => log::debug!("no surface left to free the GL objects with"),
+        }
+
+        for window in windows {
+            destroy_window(&self.egl, self.display, window);
+        }
+        tear_down(&self.egl, self.display, Some(self.context));
+    }
+}
+
+/// What [`GlRenderer::new`] set up so far. Dropped before it got to the end, it destroys the
+/// first window and the context and terminates the display, so an error halfway leaks
+/// nothing and a fallback or a reconnect starts from a clean display.
+struct PartialInit {
+    egl: khronos_egl::Instance<khronos_egl::Static>,
+    /// `None` once the renderer took over.
+    display: Option<khronos_egl::Display>,
+    context: Option<khronos_egl::Context>,
+    window: Option<GlWindow>,
+}
+
+impl PartialInit {
+    fn new(display: khronos_egl::Display) -> PartialInit {
+        PartialInit {
+            egl: khronos_egl::Instance::new(khronos_egl::Static),
+            display: Some(display),
+            context: None,
+            window: None,
+        }
+    }
+
+    /// Hands everything over to the renderer, along with the first window.
+    fn finish(mut self) -> GlWindow {
+        self.display = None;
+        self.window.take().expect("the first window is set up before the renderer")
+    }
+}
+
+impl Drop for PartialInit {
+    fn drop(&mut self) {
+        let Some(display) = self.display else {
+            return;
+        };
+        log::debug!("cleaning up the EGL state that was set up");
+        match self.window.take() {
+            Some(window) => destroy_window(&self.egl, display, window),
+            None => {
+                self.egl.make_current(display, None, None, None).ok();
             }
         }
+        tear_down(&self.egl, display, self.context);
     }
 }
 
-impl CompositorHandler for GlowApp {
-    fn scale_factor_changed(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _new_factor: i32,
-    ) {
-        // Not needed for this example.
+fn make_current(
+    egl: &khronos_egl::Instance<khronos_egl::Static>,
+    display: khronos_egl::Display,
+    context: khronos_egl::Context,
+    window: &GlWindow,
+) -> Result<(), khronos_egl::Error> {
+    let surface = Some(window.egl_surface);
+    egl.make_current(display, surface, surface, Some(context))
+}
+
+fn destroy_window(
+    egl: &khronos_egl::Instance<khronos_egl::Static>,
+    display: khronos_egl::Display,
+    window: GlWindow,
+) {
+    // A surface that is current is only destroyed once it no longer is, release it now so the
+    // wl_egl_window isn't pulled out from under it
+    if let Err(err) = egl.make_current(display, None, None, None) {
+        log::warn!("failed to release the context: {err}");
+    }
+    for fence in window.fences {
+        if let Err(err) = unsafe { egl.destroy_sync(display, fence) } {
+            log::warn!("failed to destroy a frame fence: {err}");
+        }
     }
+    if let Err(err) = egl.destroy_surface(display, window.egl_surface) {
+        log::warn!("failed to destroy the EGL surface: {err}");
+    }
+    drop(window.wl_egl_surface);
+}
 
-    fn transform_changed(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _new_transform: wl_output::Transform,
-    ) {
-        // Not needed for this example.
+/// Destroys the context and terminates the display, the end of every cleanup once the
+/// surfaces are gone and nothing is current. Debug builds check that EGL really let go of
+/// both, so valgrind and the like only report what the driver keeps around on its own.
+pub(crate) fn tear_down(
+    egl: &khronos_egl::Instance<khronos_egl::Static>,
+    display: khronos_egl::Display,
+    context: Option<khronos_egl::Context>,
+) {
+    debug_assert!(egl.get_current_context().is_none(), "tearing down a current context");
+    if let Some(context) = context {
+        match egl.destroy_context(display, context) {
+            Ok(()) => debug_assert!(
+                egl.query_context(display, context, khronos_egl::CONFIG_ID).is_err(),
+                "the EGL context outlived eglDestroyContext"
+            ),
+            Err(err) => log::warn!("failed to destroy the EGL context: {err}"),
+        }
+    }
+    // The display belongs to the connection, after a reconnect EGL would hand it out again
+    // for a new wl_display at the same address, with the state of the old one
+    match egl.terminate(display) {
+        Ok(()) => debug_assert!(
+            egl.query_string(Some(display), khronos_egl::VENDOR).is_err(),
+            "the EGL display is still initialized after eglTerminate"
+        ),
+        Err(err) => log::warn!("failed to terminate the EGL display: {err}"),
     }
+    log::debug!("EGL context destroyed and display terminated");
+}
 
-    fn frame(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _time: u32,
-    ) {
+/// Waits until the compositor released the buffer the frame may draw into, with
+/// `--explicit-sync`.
+#[cfg(feature = "explicit-sync")]
+fn wait_for_release(window: &GlWindow) {
+    if let Some(sync) = &window.sync {
+        sync.wait_for_release();
     }
+}
 
-    fn surface_enter(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _output: &wl_output::WlOutput,
-    ) {
-        // Not needed for this example.
+/// Sets the acquire and release points of the frame before swapping, with `--explicit-sync`.
+/// When that fails the window goes back to implicit sync, a commit without the points would be
+/// a protocol error.
+#[cfg(feature = "explicit-sync")]
+fn set_sync_points(window: &mut GlWindow, gl: &Context) {
+    if let Some(sync) = &mut window.sync
+        && let Err(err) = sync.set_points(gl)
+    {
+        log::warn!("no more explicit sync for the window: {err}");
+        window.sync = None;
     }
+}
 
-    fn surface_leave(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _output: &wl_output::WlOutput,
-    ) {
-        // Not needed for this example.
+/// Creates the wl_egl_window and EGL surface for a window.
+fn create_window(
+    egl: &khronos_egl::Instance<khronos_egl::Static>,
+    platform: Platform,
+    display: khronos_egl::Display,
+    config: khronos_egl::Config,
+    base: &mut BaseApp,
+    srgb_supported: bool,
+) -> Result<GlWindow, GlInitError> {
+    // Sized in physical pixels. A zero size is rejected by wayland-egl, until the window has an
+    // area use the default size. With a solid background only the content square is drawn.
+    let size = base
+        .non_zero_buffer_size()
+        .map_or((DEFAULT_SIZE, DEFAULT_SIZE), |(w, h)| (w.get(), h.get()));
+    let (surface, size) = match (base.solid_content_surface(), base.solid_content_rect()) {
+        (Some(surface), Some([_, _, width, height])) => (surface, (width as u32, height as u32)),
+        _ => (base.window().wl_surface(), size),
+    };
+
+    // Not every config can be sRGB even when the display knows about colorspaces
+    let srgb = base.srgb && srgb_supported;
+    let window = match create_surface(egl, platform, display, config, surface, size, srgb) {
+        Err(err) if srgb => {
+            log::info!("no sRGB surface ({err}), falling back to a linear one");
+            create_surface(egl, platform, display, config, surface, size, false)
+        }
+        result => result,
+    }?;
+
+    // Ask the surface rather than trusting the request
+    base.srgb = srgb_supported
+        && egl.query_surface(display, window.egl_surface, khronos_egl::GL_COLORSPACE)
+            == Ok(khronos_egl::GL_COLORSPACE_SRGB);
+    println!("colorspace: {}", if base.srgb { "sRGB" } else { "linear" });
+
+    // The quads of `--depth` need a depth buffer, which the driver may not have
+    let has_bits = |attribute| egl.get_config_attrib(display, config, attribute).unwrap_or(0) > 0;
+    base.depth &= has_bits(khronos_egl::DEPTH_SIZE);
+    base.stencil &= has_bits(khronos_egl::STENCIL_SIZE);
+    Ok(window)
+}
+
+/// Creates the wl_egl_window and EGL surface for any `wl_surface`, e.g. a popup.
+fn create_surface(
+    egl: &khronos_egl::Instance<khronos_egl::Static>,
+    platform: Platform,
+    display: khronos_egl::Display,
+    config: khronos_egl::Config,
+    surface: &wl_surface::WlSurface,
+    (buffer_width, buffer_height): (u32, u32),
+    srgb: bool,
+) -> Result<GlWindow, GlInitError> {
+    // Create the wayland EGL surface
+    let wl_egl_surface = WlEglSurface::new(surface.id(), buffer_width as i32, buffer_height as i32)
+        .map_err(|err| GlInitError::Surface(err.to_string()))?;
+
+    // Create EGL window surface, with the default colorspace unless asked for sRGB
+    let attributes = [
+        khronos_egl::GL_COLORSPACE as khronos_egl::Attrib,
+        khronos_egl::GL_COLORSPACE_SRGB as khronos_egl::Attrib,
+        khronos_egl::ATTRIB_NONE,
+    ];
+    let attributes = if srgb { &attributes[..] } else { &attributes[2..] };
+    let egl_surface = platform
+        .create_window_surface(
+            egl,
+            display,
+            config,
+            wl_egl_surface.ptr() as *mut std::ffi::c_void,
+            attributes,
+        )
+        .map_err(|err| GlInitError::Surface(err.to_string()))?;
+
+    Ok(GlWindow {
+        wl_egl_surface,
+        egl_surface,
+        damage: DamageTracker::default(),
+        fences: VecDeque::new(),
+        #[cfg(feature = "explicit-sync")]
+        sync: None,
+    })
+}
+
+/// Picks a config for the API and creates a context of it, the API stays bound. The config has
+/// as many samples as `--msaa` asks for, or fewer when the driver has none with as many, and
+/// the depth and stencil buffers of `--depth` and `--stencil`.
+/// What the config of a context has to have.
+struct ConfigRequest {
+    /// `EGL_SURFACE_TYPE` bits, 0 for a context only made current without a surface.
+    surface_type: khronos_egl::Int,
+    /// Samples per pixel, fewer are taken when no config has as many.
+    msaa: u8,
+    depth: u8,
+    stencil: u8,
+}
+
+impl ConfigRequest {
+    /// A config for the window surfaces, with the buffers `--msaa`, `--depth` and `--stencil`
+    /// ask for.
+    fn window(base: &BaseApp) -> ConfigRequest {
+        let (depth, stencil) = depth_stencil_sizes(base.depth, base.stencil);
+        ConfigRequest { surface_type: khronos_egl::WINDOW_BIT, msaa: base.msaa, depth, stencil }
+    }
+
+    fn surface_name(&self) -> &'static str {
+        match self.surface_type {
+            khronos_egl::WINDOW_BIT => "window surface",
+            khronos_egl::PBUFFER_BIT => "pbuffer",
+            _ => "surfaceless",
+        }
     }
 }
 
-impl OutputHandler for GlowApp {
-    fn output_state(&mut self) -> &mut OutputState {
-        &mut self.output_state
+fn create_context(
+    egl: &khronos_egl::Instance<khronos_egl::Static>,
+    display: khronos_egl::Display,
+    api: GlApi,
+    request: &ConfigRequest,
+    debug: bool,
+    egl_version: (khronos_egl::Int, khronos_egl::Int),
+    extensions: &[&str],
+) -> Result<(khronos_egl::Config, khronos_egl::Context), GlInitError> {
+    let (renderable_type, bound_api, (major, minor)) = match api {
+        GlApi::Gles2 => (khronos_egl::OPENGL_ES2_BIT, khronos_egl::OPENGL_ES_API, (2, 0)),
+        GlApi::Gles3 => (khronos_egl::OPENGL_ES3_BIT, khronos_egl::OPENGL_ES_API, (3, 0)),
+        GlApi::Gl => (khronos_egl::OPENGL_BIT, khronos_egl::OPENGL_API, (3, 3)),
+    };
+
+    let (depth, stencil) = (request.depth, request.stencil);
+    let config = msaa::sample_counts(request.msaa)
+        .into_iter()
+        .find_map(|samples| {
+            let attributes = [
+                khronos_egl::RED_SIZE, 8,
+                khronos_egl::GREEN_SIZE, 8,
+                khronos_egl::BLUE_SIZE, 8,
+                khronos_egl::ALPHA_SIZE, 8,
+                khronos_egl::SURFACE_TYPE, request.surface_type,
+                khronos_egl::RENDERABLE_TYPE, renderable_type,
+                khronos_egl::SAMPLE_BUFFERS, (samples > 0) as khronos_egl::Int,
+                khronos_egl::SAMPLES, samples as khronos_egl::Int,
+                khronos_egl::DEPTH_SIZE, depth as khronos_egl::Int,
+                khronos_egl::STENCIL_SIZE, stencil as khronos_egl::Int,
+                khronos_egl::NONE,
+            ];
+            // Every matching config is a candidate, the first one EGL sorts up isn't always
+            // the best one for us
+            let configs = matching_configs(egl, display, &attributes);
+            let candidates: Vec<ConfigInfo> =
+                configs.iter().map(|&config| config_info(egl, display, config)).collect();
+            best_config(&candidates, samples, depth, stencil).map(|best| configs[best])
+        })
+        .ok_or_else(|| GlInitError::Config {
+            requested: format!("RGBA8888, {}, {api}", request.surface_name()),
+            available: available_configs(egl, display),
+        })?;
+
+    egl.bind_api(bound_api)
+        .map_err(|err| GlInitError::Context(format!("eglBindAPI: {err}")))?;
+
+    // The shaders need no more than these versions, see `Dialect`
+    let mut context_attributes = vec![
+        khronos_egl::CONTEXT_MAJOR_VERSION, major,
+        khronos_egl::CONTEXT_MINOR_VERSION, minor,
+    ];
+    if api == GlApi::Gl {
+        context_attributes.extend([
+            khronos_egl::CONTEXT_OPENGL_PROFILE_MASK, khronos_egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+        ]);
+    }
+    // A debug context needs EGL 1.5, before that the flag is unknown
+    if debug && egl_version >= (1, 5) {
+        context_attributes.push(khronos_egl::CONTEXT_OPENGL_DEBUG);
+        context_attributes.push(khronos_egl::TRUE as khronos_egl::Int);
+    } else if debug {
+        log::info!("EGL 1.5 is needed for a debug context, the context may not report much");
     }
+    // A robust context is lost on a GPU reset rather than taking the process down, see
+    // `recreate_renderer`. The extension only covers GLES, EGL 1.5 has it for desktop GL.
+    if api == GlApi::Gl && egl_version >= (1, 5) {
+        context_attributes.extend([
+            khronos_egl::CONTEXT_OPENGL_ROBUST_ACCESS, khronos_egl::TRUE as khronos_egl::Int,
+            khronos_egl::CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY,
+            khronos_egl::LOSE_CONTEXT_ON_RESET,
+        ]);
+    } else if api != GlApi::Gl && extensions.contains(&"EGL_EXT_create_context_robustness") {
+        context_attributes.extend([
+            CONTEXT_OPENGL_ROBUST_ACCESS_EXT, khronos_egl::TRUE as khronos_egl::Int,
+            CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY_EXT, LOSE_CONTEXT_ON_RESET_EXT,
+        ]);
+    } else {
+        log::info!("no robust {api} contexts, GPU resets may crash");
+    }
+    context_attributes.push(khronos_egl::NONE);
 
-    fn new_output(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _output: wl_output::WlOutput,
-    ) {
+    let context = egl
+        .create_context(display, config, None, &context_attributes)
+        .map_err(|err| GlInitError::Context(err.to_string()))?;
+    Ok((config, context))
+}
+
+/// The dmabuf formats and modifiers the EGL display can import, the same the driver can hand
+/// to the compositor. External-only pairs count too, the driver only renders into the others
+/// but the compositor doesn't care about that.
+fn egl_dmabuf_formats(base: &BaseApp) -> Result<DmabufFormats, String> {
+    let egl = khronos_egl::Instance::new(khronos_egl::Static);
+    let display_ptr = base.connection().backend().display_ptr();
+    let platform = Platform::detect(&egl);
+    let display_ptr = display_ptr as *mut std::ffi::c_void;
+    let (display, _) = device::get_display(&egl, platform, display_ptr, base.gpu.as_ref())
+        .map_err(|err| err.to_string())?;
+    egl.initialize(display).map_err(|err| format!("eglInitialize: {err}"))?;
+
+    let extensions = egl
+        .query_string(Some(display), khronos_egl::EXTENSIONS)
+        .map(|extensions| extensions.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if !extensions.split_ascii_whitespace().any(|e| e == "EGL_EXT_image_dma_buf_import_modifiers")
+    {
+        return Err("EGL has no EGL_EXT_image_dma_buf_import_modifiers to ask".to_string());
     }
+    let (Some(query_formats), Some(query_modifiers)) = (
+        egl.get_proc_address("eglQueryDmaBufFormatsEXT"),
+        egl.get_proc_address("eglQueryDmaBufModifiersEXT"),
+    ) else {
+        return Err("EGL lacks the dmabuf query functions".to_string());
+    };
+    let (query_formats, query_modifiers) = unsafe {
+        (
+            std::mem::transmute::<extern "system" fn(), QueryDmaBufFormats>(query_formats),
+            std::mem::transmute::<extern "system" fn(), QueryDmaBufModifiers>(query_modifiers),
+        )
+    };
 
-    fn update_output(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _output: wl_output::WlOutput,
-    ) {
+    // Each list is asked for twice, first its length and then its contents
+    let failed = |name: &str| format!("{name}: {:?}", egl.get_error());
+    let display = display.as_ptr();
+    let mut count = 0;
+    if unsafe { query_formats(display, 0, null_mut(), &mut count) } != khronos_egl::TRUE
+    {
+        return Err(failed("eglQueryDmaBufFormatsEXT"));
     }
+    let mut formats = vec![0; count.max(0) as usize];
+    if unsafe { query_formats(display, count, formats.as_mut_ptr(), &mut count) }
+        != khronos_egl::TRUE
+    {
+        return Err(failed("eglQueryDmaBufFormatsEXT"));
+    }
+    formats.truncate(count.max(0) as usize);
 
-    fn output_destroyed(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _output: wl_output::WlOutput,
-    ) {
+    let mut pairs = DmabufFormats::new();
+    for format in formats {
+        let mut count = 0;
+        let queried =
+            unsafe { query_modifiers(display, format, 0, null_mut(), null_mut(), &mut count) };
+        let len = if queried == khronos_egl::TRUE { count.max(0) as usize } else { 0 };
+        let mut modifiers = vec![0; len];
+        if len > 0 {
+            let list = modifiers.as_mut_ptr();
+            let queried =
+                unsafe { query_modifiers(display, format, count, list, null_mut(), &mut count) };
+            let len = if queried == khronos_egl::TRUE { count.max(0) as usize } else { 0 };
+            modifiers.truncate(len);
+        }
+        pairs.insert(format as u32, modifiers);
     }
+    Ok(pairs)
 }
 
-impl WindowHandler for GlowApp {
-    fn request_close(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &Window) {
-        self.exit = true;
+/// Prints which of the pairs the compositor takes the EGL display can import, format by
+/// format. A format the compositor takes with the invalid modifier goes with any modifier.
+fn print_dmabuf_comparison(compositor: &DmabufFormats, egl: &DmabufFormats) {
+    println!("EGL imports {} formats, compared with the compositor:", egl.len());
+    let mut shared = 0;
+    for (&format, theirs) in compositor {
+        let Some(ours) = egl.get(&format) else {
+            println!("    {}: EGL can't import it", format_name(format));
+            continue;
+        };
+        let theirs: BTreeSet<u64> = theirs.iter().copied().collect();
+        let ours: BTreeSet<u64> = ours.iter().copied().collect();
+        let implicit = theirs.contains(&MODIFIER_INVALID);
+        let both: BTreeSet<u64> = if implicit { ours.clone() } else { &theirs & &ours };
+        shared += both.len() + implicit as usize;
+        println!(
+            "    {}: both {}; compositor only {}; EGL only {}",
+            format_name(format),
+            modifier_names(both.iter()),
+            modifier_names(theirs.difference(&ours)),
+            modifier_names(ours.difference(&theirs)),
+        );
     }
 
+    let missing: Vec<String> = egl
+        .keys()
+        .filter(|format| !compositor.contains_key(format))
+        .map(|&format| format_name(format))
+        .collect();
+    if !missing.is_empty() {
+        println!("EGL only: {}", missing.join(", "));
+    }
+    if shared == 0 {
+        println!("no pair both take, the driver can't hand its buffers to the compositor as is");
+    }
+}
+
+/// The modifiers as text, `none` when there are none.
+fn modifier_names<'a>(modifiers: impl Iterator<Item = &'a u64>) -> String {
+    let names: Vec<String> = modifiers.map(|&modifier| modifier_name(modifier)).collect();
+    if names.is_empty() { "none".to_string() } else { names.join(", ") }
+}
+
+/// All configs matching the attributes, in the order EGL sorts them.
+fn matching_configs(
+    egl: &khronos_egl::Instance<khronos_egl::Static>,
+    display: khronos_egl::Display,
+    attributes: &[khronos_egl::Int],
+) -> Vec<khronos_egl::Config> {
+    let count = egl.get_config_count(display).unwrap_or(0);
+    let mut configs = Vec::with_capacity(count);
+    if egl.choose_config(display, attributes, &mut configs).is_err() {
+        return Vec::new();
+    }
+    configs
+}
+
+/// What the config scoring looks at, see [`best_config`].
+fn config_info(
+    egl: &khronos_egl::Instance<khronos_egl::Static>,
+    display: khronos_egl::Display,
+    config: khronos_egl::Config,
+) -> ConfigInfo {
+    let attrib = |attribute| egl.get_config_attrib(display, config, attribute).unwrap_or(0);
+    ConfigInfo {
+        color: [
+            khronos_egl::RED_SIZE,
+            khronos_egl::GREEN_SIZE,
+            khronos_egl::BLUE_SIZE,
+            khronos_egl::ALPHA_SIZE,
+        ]
+        .map(|attribute| attrib(attribute) as u8),
+        depth: attrib(khronos_egl::DEPTH_SIZE) as u8,
+        stencil: attrib(khronos_egl::STENCIL_SIZE) as u8,
+        samples: attrib(khronos_egl::SAMPLES) as u8,
+        caveat: attrib(khronos_egl::CONFIG_CAVEAT) != khronos_egl::NONE,
+        native_renderable: Some(
+            attrib(khronos_egl::NATIVE_RENDERABLE) == khronos_egl::TRUE as khronos_egl::Int,
+        ),
+    }
+}
+
+/// What EGL tells about the display and the config, for `--gl-info`.
+fn egl_info(
+    egl: &khronos_egl::Instance<khronos_egl::Static>,
+    display: khronos_egl::Display,
+    config: khronos_egl::Config,
+) -> EglInfo {
+    let string = |display, name| {
+        egl.query_string(display, name)
+            .map(|string| string.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+    EglInfo {
+        vendor: string(Some(display), khronos_egl::VENDOR),
+        version: string(Some(display), khronos_egl::VERSION),
+        client_extensions: string(None, khronos_egl::EXTENSIONS),
+        display_extensions: string(Some(display), khronos_egl::EXTENSIONS),
+        config: CONFIG_ATTRIBUTES
+            .iter()
+            .map(|attribute| egl.get_config_attrib(display, config, attribute.id).ok())
+            .collect(),
+    }
+}
+
+/// `--gl-info-only`, prints the report with the context current without a window surface and
+/// exits. EGL_KHR_surfaceless_context needs no surface at all, without it a 1x1 pbuffer has to
+/// do, which not every window config can have.
+fn report_without_window(
+    egl: &khronos_egl::Instance<khronos_egl::Static>,
+    display: khronos_egl::Display,
+    config: khronos_egl::Config,
+    context: khronos_egl::Context,
+    extensions: &[&str],
+) -> ! {
+    let surface = if extensions.contains(&"EGL_KHR_surfaceless_context") {
+        None
+    } else {
+        let attributes = [khronos_egl::WIDTH, 1, khronos_egl::HEIGHT, 1, khronos_egl::NONE];
+        match egl.create_pbuffer_surface(display, config, &attributes) {
+            Ok(surface) => Some(surface),
+            Err(err) => {
+                eprintln!("no surfaceless contexts and no pbuffer of the config: {err}");
+                std::process::exit(1);
+            }
+        }
+    };
+    if let Err(err) = egl.make_current(display, surface, surface, Some(context)) {
+        eprintln!("failed to make the context current without a window: {err}");
+        std::process::exit(1);
+    }
+    let get_proc_address = |s: &str| -> *const std::ffi::c_void {
+        egl.get_proc_address(s).map_or(std::ptr::null(), |f| f as *const _)
+    };
+    let gl = unsafe { Context::from_loader_function(get_proc_address) };
+    info::print_report(&egl_info(egl, display, config), &gl);
+    std::process::exit(0);
+}
+
+/// Lists the configs of the display for diagnostics when none matched.
+fn available_configs(
+    egl: &khronos_egl::Instance<khronos_egl::Static>,
+    display: khronos_egl::Display,
+) -> Vec<String> {
+    let count = egl.get_config_count(display).unwrap_or(0);
+    let mut configs = Vec::with_capacity(count);
+    if egl.get_configs(display, &mut configs).is_err() {
+        return Vec::new();
+    }
+
+    let attrib = |config, attribute| egl.get_config_attrib(display, config, attribute).unwrap_or(0);
+    configs
+        .into_iter()
+        .map(|config| {
+            format!(
+                "RGBA {}{}{}{}, surface type {:#x}, renderable type {:#x}, samples {}",
+                attrib(config, khronos_egl::RED_SIZE),
+                attrib(config, khronos_egl::GREEN_SIZE),
+                attrib(config, khronos_egl::BLUE_SIZE),
+                attrib(config, khronos_egl::ALPHA_SIZE),
+                attrib(config, khronos_egl::SURFACE_TYPE),
+                attrib(config, khronos_egl::RENDERABLE_TYPE),
+                attrib(config, khronos_egl::SAMPLES),
+            )
+        })
+        .collect()
+}
+
+impl GlowApp {
+    /// Creates the surfaces of the window, and the renderer first if there is none. A window
+    /// that can't be drawn to is closed.
+    fn set_up_window(&mut self, base: &mut BaseApp) {
+        if let Some(software) = &mut self.software {
+            if let Err(err) = software.open(base) {
+                eprintln!("{err}");
+                base.close = true;
+            }
+            return;
+        }
+        if self.threaded {
+            self.set_up_threaded_window(base);
+            return;
+        }
+        let window = match &self.stack {
+            Some(stack) => stack.renderer.create_window(base),
+            None => GlRenderer::new(base, (self.new_renderer)()).map(|(renderer, window)| {
+                self.stack = Some(GlStack::new(renderer));
+                window
+            }),
+        };
+        match window {
+            Ok(window) => {
+                #[cfg(feature = "explicit-sync")]
+                let window = GlWindow {
+                    sync: self.surface_sync(base),
+                    ..window
+                };
+                if let Some(stack) = &mut self.stack {
+                    base.fence_pacing = stack.renderer.fence_pacing;
+                    stack.windows.insert(base.id(), window);
+                }
+            }
+            // Without a renderer EGL itself failed, not just the surfaces of this window
+            Err(err) if self.stack.is_none() => {
+                eprintln!("{err}");
+                self.fall_back_to_software(base);
+            }
+            Err(err) => {
+                eprintln!("{err}");
+                base.close = true;
+            }
+        }
+    }
+
+    /// Draws every window with the CPU from now on, when EGL can't be set up at all.
+    fn fall_back_to_software(&mut self, base: &mut BaseApp) {
+        println!("falling back to software rendering into wl_shm buffers");
+        self.threaded = false;
+        self.software = Some(Software::new((self.new_renderer)()));
+        self.set_up_window(base);
+    }
+
+    /// The timelines of `--explicit-sync` for the surface the window is drawn into.
+    #[cfg(feature = "explicit-sync")]
+    fn surface_sync(&self, base: &BaseApp) -> Option<explicit_sync::SurfaceSync> {
+        let (Some(manager), Some(stack)) = (&self.syncobj_manager, &self.stack) else {
+            return None;
+        };
+        let surface = base.solid_content_surface().unwrap_or(base.window().wl_surface());
+        manager.surface(&stack.renderer.egl, stack.renderer.display, surface)
+    }
+
+    /// [`Self::set_up_window`] with `--render-thread`, the thread starts with the first window.
+    fn set_up_threaded_window(&mut self, base: &mut BaseApp) {
+        let render_thread = match &mut self.render_thread {
+            Some(render_thread) => render_thread,
+            None => match RenderThread::spawn(base, self.new_renderer.clone()) {
+                Ok(render_thread) => self.render_thread.insert(render_thread),
+                Err(err) => {
+                    eprintln!("{err}");
+                    self.fall_back_to_software(base);
+                    return;
+                }
+            },
+        };
+        if let Err(err) = render_thread.open_window(base) {
+            eprintln!("{err}");
+            base.close = true;
+        }
+    }
+
+    /// Whether the surfaces of the window are set up, here or on the render thread.
+    fn is_set_up(&self, base: &BaseApp) -> bool {
+        let id = base.id();
+        self.stack.as_ref().is_some_and(|stack| stack.windows.contains_key(&id))
+            || self.software.as_ref().is_some_and(|software| software.is_open(&id))
+            || self.render_thread.as_ref().is_some_and(|render_thread| render_thread.is_open(&id))
+    }
+
+    /// Draws the window, setting up its surfaces first when a lost context took them.
+    fn draw_window(&mut self, base: &mut BaseApp) -> Result<(), khronos_egl::Error> {
+        if self.stack.as_ref().is_some_and(|stack| !stack.windows.contains_key(&base.id())) {
+            self.set_up_window(base);
+        }
+        self.draw_overlay(base);
+        let Some(stack) = &mut self.stack else {
+            return Ok(());
+        };
+        match stack.windows.get_mut(&base.id()) {
+            Some(window) => match base.solid_content_rect() {
+                Some(rect) => stack.renderer.draw_content(window, base, rect),
+                None => stack.renderer.draw_to(window, base),
+            },
+            None => Ok(()),
+        }
+    }
+
+    /// Draws the overlay of the window, setting up its surfaces with its first frame. It goes
+    /// before the window, so in sync mode the commit of the window shows both.
+    fn draw_overlay(&mut self, base: &BaseApp) {
+        let (Some(stack), Some(overlay)) = (&mut self.stack, base.overlay()) else {
+            return;
+        };
+        let window = match stack.overlays.entry(base.id()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => match stack.renderer.create_overlay(overlay) {
+                Ok(window) => entry.insert(window),
+                Err(err) => {
+                    log::warn!("failed to set up the overlay of window {}: {err}", base.id());
+                    return;
+                }
+            },
+        };
+
+        // The size follows the scale of the window
+        let (width, height) = overlay.buffer_size();
+        if window.wl_egl_surface.get_size() != (width as i32, height as i32) {
+            window.wl_egl_surface.resize(width as i32, height as i32, 0, 0);
+        }
+        if let Err(err) = stack.renderer.draw_overlay(window, overlay) {
+            log::warn!("failed to draw the overlay of window {}: {err}", base.id());
+        }
+    }
+
+    /// Throws away the context with everything in it and the surfaces of all windows, then
+    /// sets it up again for this window. The other windows follow with their next frame, the
+    /// popup when it is drawn again. Dropped images are gone.
+    fn recreate_renderer(&mut self, base: &mut BaseApp) {
+        // Dropping the stack cleans up, with the old context that still works for that
+        self.stack = None;
+        self.set_up_window(base);
+    }
+}
+
+impl AppHandler for GlowApp {
     fn configure(
         &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _window: &Window,
+        base: &mut BaseApp,
+        _qh: &QueueHandle<App<Self>>,
         configure: WindowConfigure,
         _serial: u32,
     ) {
-        let (new_width, new_height) = configure.new_size;
-        let width = new_width.map_or(256, |v| v.get());
-        let height = new_height.map_or(256, |v| v.get());
+        let (width, height) = base.configured_size(&configure);
 
-        // Initialize GL on first configure
-        if self.gl_context.is_none() {
-            self.width = width;
-            self.height = height;
-            self.init_gl();
-            self.draw();
-        } else if self.width != width || self.height != height {
-            // Only resize and redraw if dimensions actually changed
-            self.resize(width, height);
-            self.draw();
+        // Set up the surfaces on the first configure of the window, the renderer along with the
+        // first window
+        if !self.is_set_up(base) {
+            base.width = width;
+            base.height = height;
+            self.set_up_window(base);
+        } else if base.set_size(width, height) {
+            // Only resize if the buffer size actually changed
+            self.resize(base);
         }
     }
-}
 
-impl SeatHandler for GlowApp {
-    fn seat_state(&mut self) -> &mut SeatState {
-        &mut self.seat_state
+    fn resize(&mut self, base: &mut BaseApp) {
+        let Some((width, height)) = base.non_zero_buffer_size() else {
+            // Drawing is skipped until we get a usable size, keep the old surface until then.
+            log::warn!("ignoring resize to a zero size");
+            return;
+        };
+        let (width, height) = (width.get(), height.get());
+        if self.threaded {
+            // The size goes to the render thread with the next frame
+            return;
+        }
+        if base.solid_content_surface().is_some() {
+            // The content square is sized when it is drawn
+            return;
+        }
+
+        // The viewport is set with every draw, only the surface needs resizing
+        if let Some(window) = self.stack.as_ref().and_then(|stack| stack.windows.get(&base.id())) {
+            window.wl_egl_surface.resize(width as i32, height as i32, 0, 0);
+        }
     }
 
-    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
+    fn draw(&mut self, base: &mut BaseApp) {
+        if let Some(software) = &mut self.software {
+            software.draw(base);
+            return;
+        }
+        if let Some(render_thread) = &mut self.render_thread {
+            // The render thread has no canvas, the strokes would only pile up
+            base.take_strokes();
+            render_thread.draw(base);
+            return;
+        }
+        let mut result = self.draw_window(base);
+        if let Err(khronos_egl::Error::ContextLost) = result {
+            log::warn!("the GL context is lost, setting it up again");
+            self.recreate_renderer(base);
+            result = self.draw_window(base);
+        }
 
-    fn new_capability(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _seat: wl_seat::WlSeat,
-        _capability: Capability,
-    ) {
+        if let Err(err) = result {
+            // Nothing was presented, commit anyway so the frame callback request goes out and
+            // the window keeps trying. The other windows don't care.
+            log::warn!("failed to draw window {}: {err}", base.id());
+            base.window().wl_surface().commit();
+        }
     }
 
-    fn remove_capability(
-        &mut self,
-        _conn: &Connection,
-        _: &QueueHandle<Self>,
-        _: wl_seat::WlSeat,
-        _capability: Capability,
-    ) {
+    fn window_closed(&mut self, base: &mut BaseApp) {
+        let id = base.id();
+        if let Some(software) = &mut self.software {
+            software.close(&id);
+            return;
+        }
+        if let Some(render_thread) = &mut self.render_thread {
+            render_thread.close(&id);
+            return;
+        }
+        let Some(stack) = &mut self.stack else {
+            return;
+        };
+        // The overlay surfaces go first, the subsurface is destroyed before the window after this
+        if let Some(window) = stack.overlays.remove(&id) {
+            destroy_window(&stack.renderer.egl, stack.renderer.display, window);
+        }
+        if let Some(window) = stack.windows.remove(&id) {
+            stack.renderer.destroy_window(&id, window);
+        }
     }
 
-    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
-}
+    const POPUP_MENU: bool = true;
+
+    const SCREENSHOTS: bool = true;
+
+    const OVERLAY: bool = true;
+
+    const SOLID_BACKGROUND: bool = true;
+
+    fn draw_popup(&mut self, popup: &mut MenuPopup) {
+        if let Some(render_thread) = &mut self.render_thread {
+            if !render_thread.is_open(&popup.id())
+                && let Err(err) = render_thread.open_popup(popup)
+            {
+                log::warn!("failed to set up the popup: {err}");
+                return;
+            }
+            render_thread.draw_popup(popup);
+            return;
+        }
+        let Some(stack) = &mut self.stack else {
+            return;
+        };
+
+        // Created with the first configure, later ones may change the size
+        let window = match &stack.popup {
+            Some(window) => {
+                let (width, height) = popup.buffer_size();
+                window.wl_egl_surface.resize(width as i32, height as i32, 0, 0);
+                window
+            }
+            None => match stack.renderer.create_popup(popup) {
+                Ok(window) => stack.popup.insert(window),
+                Err(err) => {
+                    log::warn!("failed to set up the popup: {err}");
+                    return;
+                }
+            },
+        };
+
+        if let Err(err) = stack.renderer.draw_popup(window, popup) {
+            log::warn!("failed to draw the popup: {err}");
+        }
+    }
+
+    fn popup_closed(&mut self, popup: &mut MenuPopup) {
+        if let Some(render_thread) = &mut self.render_thread {
+            render_thread.close(&popup.id());
+            return;
+        }
+        if let Some(stack) = &mut self.stack
+            && let Some(window) = stack.popup.take()
+        {
+            stack.renderer.destroy_window(&popup.id(), window);
+        }
+    }
+
+    fn srgb_changed(&mut self, base: &mut BaseApp) {
+        // The colorspace is fixed when the surface is created, so the window gets new surfaces.
+        // Its image stays, that belongs to the context.
+        if self.threaded {
+            base.srgb = false;
+            println!("no sRGB with --render-thread");
+            return;
+        }
+        let Some(stack) = &mut self.stack else {
+            return;
+        };
+        if let Some(window) = stack.windows.remove(&base.id()) {
+            destroy_window(&stack.renderer.egl, stack.renderer.display, window);
+            self.set_up_window(base);
+        }
+    }
+
+    fn simulate_context_loss(&mut self, base: &mut BaseApp) {
+        if self.software.is_some() {
+            println!("no context to lose in software");
+            return;
+        }
+        if self.threaded {
+            println!("no simulated context loss with --render-thread");
+            return;
+        }
+        // The same as after a real reset, only the old context still works for cleaning up
+        self.recreate_renderer(base);
+    }
 
-delegate_compositor!(GlowApp);
-delegate_output!(GlowApp);
-delegate_seat!(GlowApp);
-delegate_xdg_shell!(GlowApp);
-delegate_xdg_window!(GlowApp);
-delegate_registry!(GlowApp);
+    fn vsync_changed(&mut self, base: &mut BaseApp) {
+        if let Some(render_thread) = &mut self.render_thread {
+            render_thread.set_swap_interval(base);
+            return;
+        }
+        if let Some(stack) = &self.stack
+            && let Some(window) = stack.windows.get(&base.id())
+            && let Err(err) = stack.renderer.init_surface(window, base.swap_interval())
+        {
+            log::warn!("failed to set the swap interval of window {}: {err}", base.id());
+        }
+    }
 
-impl ProvidesRegistryState for GlowApp {
-    fn registry(&mut self) -> &mut RegistryState {
-        &mut self.registry_state
+    fn dmabuf_info(&mut self, base: &mut BaseApp, formats: &DmabufFormats) {
+        match egl_dmabuf_formats(base) {
+            Ok(egl) => print_dmabuf_comparison(formats, &egl),
+            Err(err) => println!("{err}"),
+        }
+    }
+
+    fn image_dropped(&mut self, base: &mut BaseApp, image: DroppedImage) {
+        if self.threaded {
+            println!("no images with --render-thread");
+            return;
+        }
+        let id = base.id();
+        if let Some(stack) = &mut self.stack
+            && let Some(window) = stack.windows.get_mut(&id)
+        {
+            stack.renderer.set_image(&id, window, &image);
+        }
     }
-    registry_handlers![OutputState];
 }
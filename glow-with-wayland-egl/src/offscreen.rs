@@ -0,0 +1,271 @@
+//! `--offscreen <n>`, draws n frames of the animation without a window and saves them as PNGs,
+//! e.g. for checking the rendering in CI. With `WAYLAND_DISPLAY` set EGL still goes through
+//! the Wayland connection, without it the display of an EGL device needs no compositor at all.
+//!
+//! A pbuffer holds the frames when the display has configs for them, otherwise a surfaceless
+//! context draws into a framebuffer object. Either way the frames are what a window of the
+//! same size shows at the same times.
+
+use std::{error::Error, ffi::c_void, path::PathBuf};
+
+use glow::{
+    Context, HasContext, NativeFramebuffer, NativeRenderbuffer, NativeTexture, PixelUnpackData,
+    COLOR_ATTACHMENT0, COLOR_BUFFER_BIT, DEPTH24_STENCIL8, DEPTH_ATTACHMENT, DEPTH_COMPONENT16,
+    DEPTH_STENCIL_ATTACHMENT, FRAMEBUFFER, FRAMEBUFFER_COMPLETE, RENDERBUFFER, RGBA,
+    SCISSOR_TEST, STENCIL_ATTACHMENT, STENCIL_INDEX8, TEXTURE_2D, UNSIGNED_BYTE,
+};
+use glow_common::{
+    depth_stencil_sizes, info, msaa, screenshot,
+    srgb::{self, framebuffer_color},
+    builtin_renderer, GlDebug, Quads, Scene, View,
+};
+use wayland_app_common::{
+    animated_color, test_pattern_rects, GlApi, GlInitError, Options,
+};
+use wayland_client::Connection;
+
+use crate::{create_context, device, egl_info, platform::Platform, ConfigRequest};
+
+/// Milliseconds between the frames, the animation advances as it does at 60 Hz.
+const FRAME_INTERVAL: u32 = 1000 / 60;
+
+/// Draws the frames into the working directory as `offscreen-0000.png` and on.
+pub(crate) fn run(options: &Options, frames: u32) -> Result<(), Box<dyn Error>> {
+    let egl = khronos_egl::Instance::new(khronos_egl::Static);
+
+    // The display of a Wayland connection only works while it is connected
+    let connection = match std::env::var_os("WAYLAND_DISPLAY") {
+        Some(_) => Connection::connect_to_env()
+            .inspect_err(|err| log::warn!("no Wayland connection ({err}), using an EGL device"))
+            .ok(),
+        None => None,
+    };
+    let display = match &connection {
+        Some(connection) => {
+            let platform = Platform::detect(&egl);
+            let display_ptr = connection.backend().display_ptr() as *mut c_void;
+            device::get_display(&egl, platform, display_ptr, options.gpu.as_ref())?.0
+        }
+        None => {
+            let (display, device) = device::headless_display(&egl, options.gpu.as_ref())?;
+            println!("EGL device: {}", device.name());
+            display
+        }
+    };
+    let egl_version = egl
+        .initialize(display)
+        .map_err(|err| GlInitError::Display(format!("eglInitialize: {err}")))?;
+    let extensions = egl
+        .query_string(Some(display), khronos_egl::EXTENSIONS)
+        .map(|extensions| extensions.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extensions: Vec<&str> = extensions.split_ascii_whitespace().collect();
+
+    // A pbuffer has the samples and the depth and stencil buffers of its config like a window,
+    // the framebuffer object only gets the buffers
+    let (depth, stencil) = depth_stencil_sizes(options.depth, options.stencil);
+    let pbuffer_request = ConfigRequest {
+        surface_type: khronos_egl::PBUFFER_BIT,
+        msaa: options.msaa,
+        depth,
+        stencil,
+    };
+    let mut requests = vec![pbuffer_request];
+    if extensions.contains(&"EGL_KHR_surfaceless_context") {
+        requests.push(ConfigRequest { surface_type: 0, msaa: 0, depth: 0, stencil: 0 });
+    }
+
+    let debug = options.gl_debug || log::log_enabled!(log::Level::Debug);
+    let mut created = Err(GlInitError::Context("no API to try".to_string()));
+    'apis: for &api in options.gl_api.unwrap_or(GlApi::Gles2).fallbacks() {
+        for request in &requests {
+            created = create_context(&egl, display, api, request, debug, egl_version, &extensions)
+                .map(|(config, context)| (api, request, config, context));
+            match &created {
+                Ok(_) => break 'apis,
+                Err(err) => log::warn!("no {api} context, {}: {err}", request.surface_name()),
+            }
+        }
+    }
+    let (api, request, config, context) = created?;
+    println!("API: {api}, {}", request.surface_name());
+
+    let size = options.size;
+    let (width, height) = size;
+    let pbuffer = match request.surface_type {
+        khronos_egl::PBUFFER_BIT => {
+            let attributes = [
+                khronos_egl::WIDTH, width as khronos_egl::Int,
+                khronos_egl::HEIGHT, height as khronos_egl::Int,
+                khronos_egl::NONE,
+            ];
+            let pbuffer = egl.create_pbuffer_surface(display, config, &attributes);
+            Some(pbuffer.map_err(|err| GlInitError::Surface(format!("pbuffer: {err}")))?)
+        }
+        _ => None,
+    };
+    egl.make_current(display, pbuffer, pbuffer, Some(context))
+        .map_err(|err| GlInitError::MakeCurrent(err.to_string()))?;
+
+    let get_proc_address = |s: &str| -> *const c_void {
+        egl.get_proc_address(s).map_or(std::ptr::null(), |f| f as *const _)
+    };
+    let mut gl = unsafe { Context::from_loader_function(get_proc_address) };
+    let debug = GlDebug::new(&mut gl, debug);
+    let framebuffer = match pbuffer {
+        Some(_) => None,
+        None => Some(Framebuffer::new(&gl, size, options.depth, options.stencil)?),
+    };
+    let samples = egl.get_config_attrib(display, config, khronos_egl::SAMPLES).unwrap_or(0);
+    msaa::enable(&gl, samples as u8);
+    if options.gl_info {
+        info::print_report(&egl_info(&egl, display, config), &gl);
+    }
+
+    let mut scene = Scene::new(builtin_renderer(options.scene), &gl)?;
+    let quads = Quads::new(&gl)?;
+    println!("drawing {frames} frames of {width}x{height}, {FRAME_INTERVAL} ms apart");
+    for frame in 0..frames {
+        // Linear like a window with --no-srgb, the shaders encode the colors themselves
+        let view = View::offscreen(size, frame * FRAME_INTERVAL, false, options.stencil);
+        draw(&gl, &mut scene, &quads, &view, options.depth);
+        debug.check(&gl, "drawing the frame");
+
+        let path = PathBuf::from(format!("offscreen-{frame:04}.png"));
+        screenshot::save_framebuffer(&gl, size, &path)?;
+        println!("saved frame {frame} at {} ms to {}", view.time, path.display());
+    }
+
+    scene.destroy(&gl);
+    quads.destroy(&gl);
+    if let Some(framebuffer) = framebuffer {
+        framebuffer.destroy(&gl);
+    }
+    egl.make_current(display, None, None, None)?;
+    if let Some(pbuffer) = pbuffer {
+        egl.destroy_surface(display, pbuffer)?;
+    }
+    egl.destroy_context(display, context)?;
+    egl.terminate(display)?;
+    Ok(())
+}
+
+/// Draws a frame the way `GlRenderer::draw_to` draws a window with nothing over it: the
+/// background, the test pattern, the scene and with `--depth` the quads.
+fn draw(gl: &Context, scene: &mut Scene, quads: &Quads, view: &View, depth: bool) {
+    let (width, height) = view.content_size;
+    let fill = |[x, y, width, height]: [i32; 4], color: [f32; 3]| {
+        let [red, green, blue] = framebuffer_color(color, view.srgb);
+        unsafe {
+            gl.scissor(x, y, width, height);
+            gl.clear_color(red, green, blue, 1.0);
+            gl.clear(COLOR_BUFFER_BIT);
+        }
+    };
+    srgb::set_encoding(gl, view.srgb);
+    unsafe {
+        gl.viewport(0, 0, width as i32, height as i32);
+        gl.enable(SCISSOR_TEST);
+    }
+
+    fill([0, 0, width as i32, height as i32], animated_color(view.time));
+    // The rectangles count from the top, GL from the bottom
+    for [x, y, rect_width, rect_height] in test_pattern_rects(width, height) {
+        let y = height - y - rect_height;
+        fill([x as i32, y as i32, rect_width as i32, rect_height as i32], [1.0, 1.0, 1.0]);
+    }
+
+    unsafe {
+        gl.disable(SCISSOR_TEST);
+    }
+    scene.draw(gl, view);
+    if depth {
+        quads.draw(gl, view);
+    }
+}
+
+/// The framebuffer object a surfaceless context draws into, with depth and stencil buffers
+/// when `--depth` and `--stencil` ask for them.
+struct Framebuffer {
+    framebuffer: NativeFramebuffer,
+    color: NativeTexture,
+    renderbuffers: Vec<NativeRenderbuffer>,
+}
+
+impl Framebuffer {
+    /// Creates the framebuffer and binds it, it stays bound for drawing and reading.
+    fn new(
+        gl: &Context,
+        (width, height): (u32, u32),
+        depth: bool,
+        stencil: bool,
+    ) -> Result<Framebuffer, GlInitError> {
+        let (width, height) = (width as i32, height as i32);
+        unsafe {
+            let framebuffer = gl.create_framebuffer().map_err(GlInitError::Surface)?;
+            gl.bind_framebuffer(FRAMEBUFFER, Some(framebuffer));
+
+            // GLES 2 renders into RGBA8 textures, renderbuffers of it need an extension
+            let color = gl.create_texture().map_err(GlInitError::Surface)?;
+            gl.bind_texture(TEXTURE_2D, Some(color));
+            gl.tex_image_2d(
+                TEXTURE_2D,
+                0,
+                RGBA as i32,
+                width,
+                height,
+                0,
+                RGBA,
+                UNSIGNED_BYTE,
+                PixelUnpackData::Slice(None),
+            );
+            gl.bind_texture(TEXTURE_2D, None);
+            gl.framebuffer_texture_2d(FRAMEBUFFER, COLOR_ATTACHMENT0, TEXTURE_2D, Some(color), 0);
+
+            let mut renderbuffers = Vec::new();
+            let mut attach = |format, attachment| {
+                let renderbuffer = gl.create_renderbuffer().map_err(GlInitError::Surface)?;
+                gl.bind_renderbuffer(RENDERBUFFER, Some(renderbuffer));
+                gl.renderbuffer_storage(RENDERBUFFER, format, width, height);
+                gl.framebuffer_renderbuffer(
+                    FRAMEBUFFER,
+                    attachment,
+                    RENDERBUFFER,
+                    Some(renderbuffer),
+                );
+                renderbuffers.push(renderbuffer);
+                Ok::<_, GlInitError>(())
+            };
+            // Only the quads use them. GL 3 and GLES 3 have packed buffers, GLES 2 only
+            // separate ones.
+            if depth && gl.version().major >= 3 {
+                attach(DEPTH24_STENCIL8, DEPTH_STENCIL_ATTACHMENT)?;
+            } else if depth {
+                attach(DEPTH_COMPONENT16, DEPTH_ATTACHMENT)?;
+                if stencil {
+                    attach(STENCIL_INDEX8, STENCIL_ATTACHMENT)?;
+                }
+            }
+            gl.bind_renderbuffer(RENDERBUFFER, None);
+
+            let status = gl.check_framebuffer_status(FRAMEBUFFER);
+            if status != FRAMEBUFFER_COMPLETE {
+                return Err(GlInitError::Surface(format!(
+                    "the framebuffer object is incomplete, status {status:#x}"
+                )));
+            }
+            Ok(Framebuffer { framebuffer, color, renderbuffers })
+        }
+    }
+
+    fn destroy(self, gl: &Context) {
+        unsafe {
+            gl.bind_framebuffer(FRAMEBUFFER, None);
+            gl.delete_framebuffer(self.framebuffer);
+            gl.delete_texture(self.color);
+            for renderbuffer in self.renderbuffers {
+                gl.delete_renderbuffer(renderbuffer);
+            }
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! The EGL display and window surfaces of the Wayland platform. `eglGetDisplay` takes any
+//! native display and has the driver guess what it is, which goes wrong on stacks that also
+//! know GBM or X11. EGL 1.5 and EGL_EXT_platform_base say which platform the pointer belongs
+//! to, the plain calls are left for clients with neither.
+
+use std::ffi::c_void;
+
+/// `EGL_PLATFORM_WAYLAND_KHR`, the same value as `EGL_PLATFORM_WAYLAND_EXT`.
+const PLATFORM_WAYLAND: khronos_egl::Enum = 0x31D8;
+
+/// `EGL_PLATFORM_DEVICE_EXT` of EGL_EXT_platform_device.
+const PLATFORM_DEVICE: khronos_egl::Enum = 0x313F;
+
+/// `eglGetPlatformDisplayEXT` of EGL_EXT_platform_base.
+type GetPlatformDisplayExt = unsafe extern "system" fn(
+    khronos_egl::Enum,
+    *mut c_void,
+    *const khronos_egl::Int,
+) -> khronos_egl::EGLDisplay;
+
+/// `eglCreatePlatformWindowSurfaceEXT` of the same extension.
+type CreatePlatformWindowSurfaceExt = unsafe extern "system" fn(
+    khronos_egl::EGLDisplay,
+    khronos_egl::EGLConfig,
+    *mut c_void,
+    *const khronos_egl::Int,
+) -> khronos_egl::EGLSurface;
+
+/// How the display and the window surfaces are created.
+#[derive(Clone, Copy)]
+pub(crate) enum Platform {
+    /// `eglGetPlatformDisplay` and `eglCreatePlatformWindowSurface` of EGL 1.5 with
+    /// EGL_KHR_platform_wayland.
+    Core,
+    /// The functions of EGL_EXT_platform_base with EGL_EXT_platform_wayland. Their attributes
+    /// are ints, they can't carry pointers.
+    Ext {
+        get_display: GetPlatformDisplayExt,
+        create_window_surface: CreatePlatformWindowSurfaceExt,
+    },
+    /// `eglGetDisplay` and `eglCreateWindowSurface`, which guess the platform.
+    Legacy,
+}
+
+impl Platform {
+    /// The newest way the client EGL has, logging its extensions first since that is where
+    /// platform trouble shows.
+    pub(crate) fn detect(egl: &khronos_egl::Instance<khronos_egl::Static>) -> Platform {
+        // Client extensions need EGL 1.5 or EGL_EXT_client_extensions, without either there is
+        // no string and no platform extension either
+        let extensions = egl
+            .query_string(None, khronos_egl::EXTENSIONS)
+            .map(|extensions| extensions.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        log::info!("EGL client extensions: {extensions}");
+        let has = |name: &str| extensions.split_ascii_whitespace().any(|e| e == name);
+
+        let version = egl
+            .query_string(None, khronos_egl::VERSION)
+            .map(|version| version.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut numbers =
+            version.split(['.', ' ']).map(|number| number.parse::<u32>().unwrap_or(0));
+        let core = (numbers.next().unwrap_or(0), numbers.next().unwrap_or(0)) >= (1, 5);
+
+        let platform = if core && has("EGL_KHR_platform_wayland") {
+            Platform::Core
+        } else if has("EGL_EXT_platform_base")
+            && has("EGL_EXT_platform_wayland")
+            && let (Some(get_display), Some(create_window_surface)) = (
+                egl.get_proc_address("eglGetPlatformDisplayEXT"),
+                egl.get_proc_address("eglCreatePlatformWindowSurfaceEXT"),
+            )
+        {
+            unsafe {
+                Platform::Ext {
+                    get_display: std::mem::transmute::<
+                        extern "system" fn(),
+                        GetPlatformDisplayExt,
+                    >(get_display),
+                    create_window_surface: std::mem::transmute::<
+                        extern "system" fn(),
+                        CreatePlatformWindowSurfaceExt,
+                    >(create_window_surface),
+                }
+            }
+        } else {
+            Platform::Legacy
+        };
+        log::info!("EGL platform: {}", platform.name());
+        platform
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Platform::Core => "eglGetPlatformDisplay with EGL_KHR_platform_wayland",
+            Platform::Ext { .. } => "eglGetPlatformDisplayEXT with EGL_EXT_platform_wayland",
+            Platform::Legacy => "eglGetDisplay, no platform extension",
+        }
+    }
+
+    /// The display of a `wl_display`. Only [`Platform::Core`] takes `attributes` with pointers
+    /// in them, and the legacy call takes none.
+    pub(crate) fn get_display(
+        self,
+        egl: &khronos_egl::Instance<khronos_egl::Static>,
+        display_ptr: *mut c_void,
+        attributes: &[khronos_egl::Attrib],
+    ) -> Result<khronos_egl::Display, String> {
+        match self {
+            Platform::Core => {
+                unsafe { egl.get_platform_display(PLATFORM_WAYLAND, display_ptr, attributes) }
+                    .map_err(|err| format!("eglGetPlatformDisplay: {err}"))
+            }
+            Platform::Ext { get_display, .. } => {
+                let attributes = int_attributes(attributes);
+                let display =
+                    unsafe { get_display(PLATFORM_WAYLAND, display_ptr, attributes.as_ptr()) };
+                if display.is_null() {
+                    return Err(format!("eglGetPlatformDisplayEXT: {:?}", egl.get_error()));
+                }
+                Ok(unsafe { khronos_egl::Display::from_ptr(display) })
+            }
+            Platform::Legacy => unsafe { egl.get_display(display_ptr) }
+                .ok_or_else(|| "no EGL display for wl_display".to_string()),
+        }
+    }
+
+    /// The display of an EGL device, which needs no compositor. EGL_EXT_platform_device has
+    /// no counterpart in the core or legacy calls, whatever the Wayland platform is.
+    pub(crate) fn get_device_display(
+        egl: &khronos_egl::Instance<khronos_egl::Static>,
+        device: *mut c_void,
+    ) -> Result<khronos_egl::Display, String> {
+        let extensions = egl
+            .query_string(None, khronos_egl::EXTENSIONS)
+            .map(|extensions| extensions.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if !extensions.split_ascii_whitespace().any(|e| e == "EGL_EXT_platform_device") {
+            return Err("EGL has no EGL_EXT_platform_device".to_string());
+        }
+        // The extension requires EGL_EXT_platform_base
+        let Some(get_display) = egl.get_proc_address("eglGetPlatformDisplayEXT") else {
+            return Err("EGL lacks eglGetPlatformDisplayEXT".to_string());
+        };
+        let get_display = unsafe {
+            std::mem::transmute::<extern "system" fn(), GetPlatformDisplayExt>(get_display)
+        };
+        let attributes = [khronos_egl::NONE];
+        let display = unsafe { get_display(PLATFORM_DEVICE, device, attributes.as_ptr()) };
+        if display.is_null() {
+            return Err(format!("eglGetPlatformDisplayEXT: {:?}", egl.get_error()));
+        }
+        Ok(unsafe { khronos_egl::Display::from_ptr(display) })
+    }
+
+    /// The window surface of a `wl_egl_window`.
+    pub(crate) fn create_window_surface(
+        self,
+        egl: &khronos_egl::Instance<khronos_egl::Static>,
+        display: khronos_egl::Display,
+        config: khronos_egl::Config,
+        window: *mut c_void,
+        attributes: &[khronos_egl::Attrib],
+    ) -> Result<khronos_egl::Surface, khronos_egl::Error> {
+        match self {
+            Platform::Core => unsafe {
+                egl.create_platform_window_surface(display, config, window, attributes)
+            },
+            Platform::Ext {
+                create_window_surface,
+                ..
+            } => {
+                let attributes = int_attributes(attributes);
+                let surface = unsafe {
+                    create_window_surface(
+                        display.as_ptr(),
+                        config.as_ptr(),
+                        window,
+                        attributes.as_ptr(),
+                    )
+                };
+                if surface.is_null() {
+                    return Err(egl.get_error().unwrap_or(khronos_egl::Error::BadSurface));
+                }
+                Ok(unsafe { khronos_egl::Surface::from_ptr(surface) })
+            }
+            Platform::Legacy => {
+                let attributes = int_attributes(attributes);
+                let attributes = (attributes.len() > 1).then_some(&attributes[..]);
+                unsafe { egl.create_window_surface(display, config, window, attributes) }
+            }
+        }
+    }
+}
+
+/// The attributes as ints for the functions of before EGL 1.5, ending with `EGL_NONE`.
+fn int_attributes(attributes: &[khronos_egl::Attrib]) -> Vec<khronos_egl::Int> {
+    // Pairs of name and value, the `EGL_NONE` at the end is left over
+    attributes
+        .chunks_exact(2)
+        .flatten()
+        .map(|&attribute| attribute as khronos_egl::Int)
+        .chain([khronos_egl::NONE])
+        .collect()
+}
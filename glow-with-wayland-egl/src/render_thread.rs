@@ -0,0 +1,581 @@
+//! `--render-thread`, the EGL display, the context and everything GL live on a thread of their
+//! own, so a heavy scene doesn't hold up the dispatch of input and configures.
+//!
+//! The dispatch thread still does all the Wayland state: it requests the frame callback and
+//! the presentation feedback and sets the regions and the viewport, then hands the frame over
+//! as a [`Frame`]. Those requests are double buffered, they take effect with the commit the
+//! render thread does when it swaps, which comes after them. The buffer size travels with the
+//! frame, the render thread resizes the `wl_egl_window` right before drawing at the new size
+//! so a resize never shows up half done. It draws only the latest frame of each surface, the
+//! older ones still waiting are skipped, and answers with the times of each swap.
+//!
+//! Only the windows and the popup menu are drawn this way: no overlay, no solid background, no
+//! fences or explicit sync, no images, screenshots or damage tracking. The surfaces are linear.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::c_void,
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use glow::{Context, HasContext, COLOR_BUFFER_BIT, SCISSOR_TEST};
+use smithay_client_toolkit::shell::WaylandSurface;
+use glow_common::{msaa, srgb, GlDebug, Quads, RendererFactory, Scene, View};
+use wayland_app_common::{BaseApp, GlApi, GlInitError, GpuChoice, MenuPopup, DEFAULT_SIZE};
+use wayland_client::{backend::ObjectId, protocol::wl_surface::WlSurface, Proxy};
+use wayland_egl::WlEglSurface;
+
+use crate::{create_context, device, platform::Platform, tear_down, ConfigRequest};
+
+/// The render thread as the dispatch thread sees it.
+pub(crate) struct RenderThread {
+    commands: Sender<Command>,
+    replies: Receiver<Reply>,
+    thread: Option<JoinHandle<()>>,
+    /// The surfaces the render thread has set up, windows and the popup.
+    open: HashSet<ObjectId>,
+    /// The swap times of the windows, until they are recorded with their next frame.
+    swaps: HashMap<ObjectId, Vec<(Duration, Duration)>>,
+    /// Whether the config has a depth and a stencil buffer.
+    depth: bool,
+    stencil: bool,
+}
+
+/// What the dispatch thread asks for, in order.
+enum Command {
+    /// Set up the EGL surface for a surface. The `wl_egl_window` is created by the dispatch
+    /// thread, it takes the `wl_surface`.
+    Open { id: ObjectId, surface: WlSurface, wl_egl_surface: WlEglSurface, swap_interval: u32 },
+    Draw { id: ObjectId, frame: Frame },
+    SwapInterval { id: ObjectId, interval: u32 },
+    /// Destroy the EGL surface and the `wl_egl_window`, before the `wl_surface` goes.
+    Close(ObjectId),
+}
+
+/// What the render thread answers.
+enum Reply {
+    /// The context and config are set up, or couldn't be. Whether the config has a depth and
+    /// a stencil buffer.
+    Ready(Result<(bool, bool), GlInitError>),
+    Opened(Result<(), GlInitError>),
+    /// A frame was swapped, with the time drawing and swapping took.
+    Swapped { id: ObjectId, draw: Duration, swap: Duration },
+    Closed,
+}
+
+/// Everything the render thread needs for a frame, taken from the window state on the dispatch
+/// thread. Rectangles are in GL buffer coordinates, see [`BaseApp::buffer_rect`].
+struct Frame {
+    /// The buffer size to draw at, the surface is resized to it first.
+    size: (u32, u32),
+    /// Rectangles cleared to a color in order, the background first.
+    fills: Vec<([i32; 4], [f32; 4])>,
+    /// The content area with what the scene is drawn from, `None` for the popup.
+    content: Option<([i32; 4], View)>,
+    /// Draw the quads of `--depth` over the scene.
+    depth: bool,
+}
+
+impl Frame {
+    /// The frame of a window as `GlRenderer::draw_to` draws it, without an image.
+    fn of(base: &BaseApp) -> Frame {
+        let (width, height) = base.buffer_size();
+        let [red, green, blue, alpha] = base.background();
+        let mut fills = vec![([0, 0, width as i32, height as i32], [red, green, blue, alpha])];
+        let opaque =
+            |(rect, [red, green, blue]): ([i32; 4], [f32; 3])| (rect, [red, green, blue, 1.0]);
+        let outline = base.confinement_outline();
+        fills.extend(base.test_pattern().map(|rect| opaque((rect, [1.0, 1.0, 1.0]))));
+        fills.extend(base.decoration_rects().into_iter().map(opaque));
+        fills.extend(outline.into_iter().map(|rect| opaque((rect, [1.0, 0.5, 0.0]))));
+        fills.extend(base.touch_rects().into_iter().map(opaque));
+        fills.extend(base.text_rects().into_iter().map(opaque));
+        fills.extend(base.shortcuts_badge_rects().into_iter().map(opaque));
+        Frame {
+            size: (width, height),
+            fills,
+            content: Some((base.content_rect(), View::of(base))),
+            depth: base.depth,
+        }
+    }
+
+    /// The frame of the popup menu.
+    fn of_popup(popup: &MenuPopup) -> Frame {
+        let fills = popup
+            .menu_rects()
+            .into_iter()
+            .map(|(rect, [red, green, blue])| (rect, [red, green, blue, 1.0]))
+            .collect();
+        Frame { size: popup.buffer_size(), fills, content: None, depth: false }
+    }
+}
+
+impl RenderThread {
+    /// Starts the thread and waits until it has a context, the scenes come from the factory
+    /// there.
+    pub(crate) fn spawn(
+        base: &BaseApp,
+        new_renderer: RendererFactory,
+    ) -> Result<RenderThread, GlInitError> {
+        let setup = Setup {
+            // The pointer only goes to EGL, which is fine with any thread
+            display: base.connection().backend().display_ptr() as usize,
+            gpu: base.gpu.clone(),
+            gl_api: base.gl_api.unwrap_or(GlApi::Gles2),
+            request: ConfigRequest::window(base),
+            debug: GlDebug::wanted(base),
+            new_renderer,
+        };
+        let (commands, command_receiver) = mpsc::channel();
+        let (reply_sender, replies) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("render".to_string())
+            .spawn(move || setup.run(command_receiver, reply_sender))
+            .map_err(|err| GlInitError::Context(format!("can't start the render thread: {err}")))?;
+
+        let mut render_thread = RenderThread {
+            commands,
+            replies,
+            thread: Some(thread),
+            open: HashSet::new(),
+            swaps: HashMap::new(),
+            depth: false,
+            stencil: false,
+        };
+        match render_thread.wait() {
+            Some(Reply::Ready(Ok((depth, stencil)))) => {
+                render_thread.depth = depth;
+                render_thread.stencil = stencil;
+                Ok(render_thread)
+            }
+            Some(Reply::Ready(Err(err))) => Err(err),
+            _ => Err(GlInitError::Context("the render thread quit".to_string())),
+        }
+    }
+
+    /// Whether the surface is set up.
+    pub(crate) fn is_open(&self, id: &ObjectId) -> bool {
+        self.open.contains(id)
+    }
+
+    /// Sets up the surfaces of a window, in its buffer size or the default size until it has
+    /// one. The window follows the config and the linear surface.
+    pub(crate) fn open_window(&mut self, base: &mut BaseApp) -> Result<(), GlInitError> {
+        let size = base
+            .non_zero_buffer_size()
+            .map_or((DEFAULT_SIZE, DEFAULT_SIZE), |(w, h)| (w.get(), h.get()));
+        self.open(base.window().wl_surface().clone(), size, base.swap_interval())?;
+        base.srgb = false;
+        base.depth &= self.depth;
+        base.stencil &= self.stencil;
+        println!("colorspace: linear, drawing on the render thread");
+        Ok(())
+    }
+
+    /// Sets up the surfaces of the popup menu, which never waits for vsync.
+    pub(crate) fn open_popup(&mut self, popup: &MenuPopup) -> Result<(), GlInitError> {
+        self.open(popup.popup().wl_surface().clone(), popup.buffer_size(), 0)
+    }
+
+    fn open(
+        &mut self,
+        surface: WlSurface,
+        (width, height): (u32, u32),
+        swap_interval: u32,
+    ) -> Result<(), GlInitError> {
+        let wl_egl_surface = WlEglSurface::new(surface.id(), width as i32, height as i32)
+            .map_err(|err| GlInitError::Surface(err.to_string()))?;
+        let id = surface.id();
+        self.send(Command::Open { id: id.clone(), surface, wl_egl_surface, swap_interval });
+        match self.wait() {
+            Some(Reply::Opened(Ok(()))) => {
+                self.open.insert(id);
+                Ok(())
+            }
+            Some(Reply::Opened(Err(err))) => Err(err),
+            _ => Err(GlInitError::Surface("the render thread quit".to_string())),
+        }
+    }
+
+    /// Hands the frame of the window to the render thread and records the swaps that finished
+    /// since its last one.
+    pub(crate) fn draw(&mut self, base: &mut BaseApp) {
+        self.receive_swaps();
+        for (draw, swap) in self.swaps.remove(&base.id()).unwrap_or_default() {
+            base.frame_stats.record(draw, swap);
+        }
+        if base.screenshot {
+            base.screenshot = false;
+            println!("no screenshots with --render-thread");
+        }
+        self.send(Command::Draw { id: base.id(), frame: Frame::of(base) });
+    }
+
+    /// Hands the menu to the render thread, it is drawn for every configure of the popup.
+    pub(crate) fn draw_popup(&mut self, popup: &MenuPopup) {
+        self.send(Command::Draw { id: popup.id(), frame: Frame::of_popup(popup) });
+    }
+
+    pub(crate) fn set_swap_interval(&mut self, base: &BaseApp) {
+        self.send(Command::SwapInterval { id: base.id(), interval: base.swap_interval() });
+    }
+
+    /// Destroys the surfaces and waits for it, the `wl_surface` must outlive them.
+    pub(crate) fn close(&mut self, id: &ObjectId) {
+        if !self.open.remove(id) {
+            return;
+        }
+        self.swaps.remove(id);
+        self.send(Command::Close(id.clone()));
+        if !matches!(self.wait(), Some(Reply::Closed)) {
+            log::warn!("the render thread quit before closing {id}");
+        }
+    }
+
+    /// Lets the thread free everything and waits for it to end. The Wayland windows may only go
+    /// after this.
+    pub(crate) fn stop(mut self) {
+        let (commands, _) = mpsc::channel();
+        // The thread ends once the sender is gone
+        drop(std::mem::replace(&mut self.commands, commands));
+        if let Some(thread) = self.thread.take()
+            && thread.join().is_err()
+        {
+            log::warn!("the render thread panicked");
+        }
+    }
+
+    fn send(&self, command: Command) {
+        if self.commands.send(command).is_err() {
+            log::warn!("the render thread is gone, nothing is drawn");
+        }
+    }
+
+    /// The next reply that isn't a swap, the swaps are kept for [`Self::draw`].
+    fn wait(&mut self) -> Option<Reply> {
+        loop {
+            match self.replies.recv().ok()? {
+                Reply::Swapped { id, draw, swap } => {
+                    self.swaps.entry(id).or_default().push((draw, swap));
+                }
+                reply => return Some(reply),
+            }
+        }
+    }
+
+    fn receive_swaps(&mut self) {
+        loop {
+            match self.replies.try_recv() {
+                Ok(Reply::Swapped { id, draw, swap }) => {
+                    self.swaps.entry(id).or_default().push((draw, swap));
+                }
+                Ok(_) => log::warn!("unexpected reply from the render thread"),
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => return,
+            }
+        }
+    }
+}
+
+/// What the render thread starts from, everything it takes from the window state.
+struct Setup {
+    /// The `wl_display` pointer.
+    display: usize,
+    gpu: Option<GpuChoice>,
+    gl_api: GlApi,
+    request: ConfigRequest,
+    debug: bool,
+    new_renderer: RendererFactory,
+}
+
+/// The EGL and GL state of the render thread.
+struct RenderState {
+    egl: khronos_egl::Instance<khronos_egl::Static>,
+    platform: Platform,
+    display: khronos_egl::Display,
+    config: khronos_egl::Config,
+    context: khronos_egl::Context,
+    surfaces: HashMap<ObjectId, RenderSurface>,
+    /// Set up with the first surface, the context can only be made current with one.
+    gl: Option<Gl>,
+    debug: bool,
+    new_renderer: RendererFactory,
+}
+
+struct RenderSurface {
+    /// For a commit when a frame fails, so the frame callback still goes out.
+    surface: WlSurface,
+    wl_egl_surface: WlEglSurface,
+    egl_surface: khronos_egl::Surface,
+}
+
+struct Gl {
+    gl: Context,
+    debug: GlDebug,
+    scene: Scene,
+    quads: Quads,
+}
+
+impl Setup {
+    /// The body of the render thread, until the dispatch thread drops its sender.
+    fn run(self, commands: Receiver<Command>, replies: Sender<Reply>) {
+        let mut renderer = match self.create() {
+            Ok((renderer, depth, stencil)) => {
+                let _ = replies.send(Reply::Ready(Ok((depth, stencil))));
+                renderer
+            }
+            Err(err) => {
+                let _ = replies.send(Reply::Ready(Err(err)));
+                return;
+            }
+        };
+
+        while let Ok(command) = commands.recv() {
+            // Take whatever else is waiting, of the frames only the latest of each surface
+            let mut frames: Vec<(ObjectId, Frame)> = Vec::new();
+            let mut next = Some(command);
+            while let Some(command) = next {
+                match command {
+                    Command::Draw { id, frame } => {
+                        frames.retain(|(other, _)| *other != id);
+                        frames.push((id, frame));
+                    }
+                    Command::Close(id) => {
+                        frames.retain(|(other, _)| *other != id);
+                        renderer.close(&id);
+                        let _ = replies.send(Reply::Closed);
+                    }
+                    command => renderer.handle(command, &replies),
+                }
+                next = commands.try_recv().ok();
+            }
+            for (id, frame) in frames {
+                renderer.draw(&id, &frame, &replies);
+            }
+        }
+        renderer.destroy();
+    }
+
+    /// Initializes EGL on the display of the connection and creates the context, in the first
+    /// API of `--api` that works.
+    fn create(self) -> Result<(RenderState, bool, bool), GlInitError> {
+        let egl = khronos_egl::Instance::new(khronos_egl::Static);
+        let platform = Platform::detect(&egl);
+        let display_ptr = self.display as *mut c_void;
+        let (display, _) = device::get_display(&egl, platform, display_ptr, self.gpu.as_ref())?;
+        let egl_version = egl
+            .initialize(display)
+            .map_err(|err| GlInitError::Display(format!("eglInitialize: {err}")))?;
+        let extensions = egl
+            .query_string(Some(display), khronos_egl::EXTENSIONS)
+            .map(|extensions| extensions.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let extensions: Vec<&str> = extensions.split_ascii_whitespace().collect();
+
+        let mut created = Err(GlInitError::Context("no API to try".to_string()));
+        let (request, debug) = (&self.request, self.debug);
+        for &api in self.gl_api.fallbacks() {
+            created = create_context(&egl, display, api, request, debug, egl_version, &extensions)
+                .map(|(config, context)| (api, config, context));
+            match &created {
+                Ok(_) => break,
+                Err(err) => log::warn!("no {api} context: {err}"),
+            }
+        }
+        let (api, config, context) = match created {
+            Ok(created) => created,
+            Err(err) => {
+                tear_down(&egl, display, None);
+                return Err(err);
+            }
+        };
+        println!("API: {api}, on the render thread");
+        let has_bits =
+            |attribute| egl.get_config_attrib(display, config, attribute).unwrap_or(0) > 0;
+        let depth = has_bits(khronos_egl::DEPTH_SIZE);
+        let stencil = has_bits(khronos_egl::STENCIL_SIZE);
+
+        let renderer = RenderState {
+            egl,
+            platform,
+            display,
+            config,
+            context,
+            surfaces: HashMap::new(),
+            gl: None,
+            debug: self.debug,
+            new_renderer: self.new_renderer,
+        };
+        Ok((renderer, depth, stencil))
+    }
+}
+
+impl RenderState {
+    fn handle(&mut self, command: Command, replies: &Sender<Reply>) {
+        match command {
+            Command::Open { id, surface, wl_egl_surface, swap_interval } => {
+                let result = self.open(id, surface, wl_egl_surface, swap_interval);
+                let _ = replies.send(Reply::Opened(result));
+            }
+            Command::SwapInterval { id, interval } => {
+                if let Err(err) = self.make_current(&id) {
+                    log::warn!("failed to set the swap interval of {id}: {err}");
+                    return;
+                }
+                // The swap interval belongs to the surface that is current
+                self.egl.swap_interval(self.display, interval as i32).ok();
+            }
+            Command::Draw { .. } | Command::Close(_) => unreachable!("handled by the loop"),
+        }
+    }
+
+    /// Creates the EGL surface, and the GL state along with the first one.
+    fn open(
+        &mut self,
+        id: ObjectId,
+        surface: WlSurface,
+        wl_egl_surface: WlEglSurface,
+        swap_interval: u32,
+    ) -> Result<(), GlInitError> {
+        let egl_surface = self
+            .platform
+            .create_window_surface(
+                &self.egl,
+                self.display,
+                self.config,
+                wl_egl_surface.ptr() as *mut c_void,
+                &[khronos_egl::ATTRIB_NONE],
+            )
+            .map_err(|err| GlInitError::Surface(err.to_string()))?;
+        self.surfaces.insert(id.clone(), RenderSurface { surface, wl_egl_surface, egl_surface });
+        self.make_current(&id).map_err(|err| GlInitError::MakeCurrent(err.to_string()))?;
+        self.egl.swap_interval(self.display, swap_interval as i32).ok();
+
+        if self.gl.is_none() {
+            let egl = &self.egl;
+            let get_proc_address = |s: &str| -> *const c_void {
+                egl.get_proc_address(s).map_or(std::ptr::null(), |f| f as *const _)
+            };
+            let mut gl = unsafe { Context::from_loader_function(get_proc_address) };
+            let debug = GlDebug::new(&mut gl, self.debug);
+            let samples =
+                egl.get_config_attrib(self.display, self.config, khronos_egl::SAMPLES).unwrap_or(0);
+            msaa::enable(&gl, samples as u8);
+            let scene = Scene::new((self.new_renderer)(), &gl)?;
+            debug.check(&gl, "setting up the scene");
+            let quads = Quads::new(&gl)?;
+            debug.check(&gl, "setting up the quads");
+            self.gl = Some(Gl { gl, debug, scene, quads });
+        }
+        Ok(())
+    }
+
+    fn make_current(&self, id: &ObjectId) -> Result<(), khronos_egl::Error> {
+        let surface = self.surfaces.get(id).map(|surface| surface.egl_surface);
+        if surface.is_none() {
+            return Err(khronos_egl::Error::BadSurface);
+        }
+        self.egl.make_current(self.display, surface, surface, Some(self.context))
+    }
+
+    /// Draws and swaps, which commits the surface. A frame that fails is committed without a
+    /// new buffer, so the frame callback still comes.
+    fn draw(&mut self, id: &ObjectId, frame: &Frame, replies: &Sender<Reply>) {
+        let draw_start = Instant::now();
+        match self.draw_frame(id, frame) {
+            Ok(swap_start) => {
+                let (draw, swap) = (swap_start - draw_start, swap_start.elapsed());
+                let _ = replies.send(Reply::Swapped { id: id.clone(), draw, swap });
+            }
+            Err(err) => {
+                log::warn!("failed to draw {id} on the render thread: {err}");
+                if let Some(surface) = self.surfaces.get(id) {
+                    surface.surface.commit();
+                }
+            }
+        }
+    }
+
+    /// Returns when the swap started.
+    fn draw_frame(&mut self, id: &ObjectId, frame: &Frame) -> Result<Instant, khronos_egl::Error> {
+        self.make_current(id)?;
+        let (Some(surface), Some(gl)) = (self.surfaces.get(id), &mut self.gl) else {
+            return Err(khronos_egl::Error::BadSurface);
+        };
+
+        // The new size goes with the frame drawn at it
+        let (width, height) = (frame.size.0 as i32, frame.size.1 as i32);
+        if surface.wl_egl_surface.get_size() != (width, height) {
+            surface.wl_egl_surface.resize(width, height, 0, 0);
+        }
+
+        let Gl { gl, debug, scene, quads } = gl;
+        srgb::set_encoding(gl, false);
+        unsafe {
+            gl.viewport(0, 0, width, height);
+            gl.enable(SCISSOR_TEST);
+            for &([x, y, width, height], [red, green, blue, alpha]) in &frame.fills {
+                gl.scissor(x, y, width, height);
+                gl.clear_color(red, green, blue, alpha);
+                gl.clear(COLOR_BUFFER_BIT);
+            }
+            gl.disable(SCISSOR_TEST);
+        }
+        debug.check(gl, "clearing");
+
+        if let Some(([x, y, width, height], view)) = &frame.content {
+            unsafe {
+                gl.viewport(*x, *y, *width, *height);
+            }
+            scene.draw(gl, view);
+            debug.check(gl, "drawing the scene");
+            if frame.depth {
+                quads.draw(gl, view);
+                debug.check(gl, "drawing the quads");
+            }
+        }
+
+        let swap_start = Instant::now();
+        self.egl.swap_buffers(self.display, surface.egl_surface)?;
+        Ok(swap_start)
+    }
+
+    /// Destroys the surfaces of the window or popup.
+    fn close(&mut self, id: &ObjectId) {
+        let Some(surface) = self.surfaces.remove(id) else {
+            return;
+        };
+        // A surface that is current is only destroyed once it no longer is
+        if let Err(err) = self.egl.make_current(self.display, None, None, None) {
+            log::warn!("failed to release the context: {err}");
+        }
+        if let Err(err) = self.egl.destroy_surface(self.display, surface.egl_surface) {
+            log::warn!("failed to destroy the EGL surface: {err}");
+        }
+        drop(surface.wl_egl_surface);
+    }
+
+    /// Frees the GL objects with one of the surfaces left, then the surfaces, the context and
+    /// the display.
+    fn destroy(mut self) {
+        if let Some(Gl { gl, scene, quads, .. }) = self.gl.take() {
+            let id = self.surfaces.keys().next().cloned();
+            match id.map(|id| self.make_current(&id)) {
+                Some(Ok(())) => {
+                    scene.destroy(&gl);
+                    quads.destroy(&gl);
+                }
+                Some(Err(err)) => {
+                    log::warn!("failed to make the context current for cleanup: {err}");
+                }
+                None => log::debug!("no surface left to free the GL objects with"),
+            }
+        }
+        let ids: Vec<ObjectId> = self.surfaces.keys().cloned().collect();
+        for id in ids {
+            self.close(&id);
+        }
+        tear_down(&self.egl, self.display, Some(self.context));
+    }
+}
@@ -0,0 +1,593 @@
+// A status bar style overlay through wlr-layer-shell: a 32 pixel tall strip along the top edge
+// of an output with an animated GL gradient in it. The compositor keeps other windows out of
+// the strip through the exclusive zone.
+//
+// Pass `--output <name>` (e.g. `DP-1`) to pick the output, otherwise the compositor picks one.
+// `--exit-after-frames <n>` prints a line per frame callback and exits after n of them,
+// `--exit-after-seconds <s>` exits after s seconds. Both print a summary on exit.
+// `--reconnect <n>` connects again up to n times when the connection breaks.
+
+use smithay_client_toolkit::{
+    compositor::{CompositorHandler, CompositorState},
+    delegate_compositor, delegate_layer, delegate_output, delegate_registry,
+    output::{OutputHandler, OutputState},
+    registry::{ProvidesRegistryState, RegistryState},
+    registry_handlers,
+    shell::{
+        wlr_layer::{
+            Anchor, Layer, LayerShell, LayerShellHandler, LayerSurface, LayerSurfaceConfigure,
+        },
+        WaylandSurface,
+    },
+};
+use std::time::{Duration, Instant};
+
+use wayland_app_common::{ArgsError, BasicOptions, ConnectionError, GlInitError, Requirement};
+use wayland_client::{
+    globals::registry_queue_init,
+    protocol::{wl_output, wl_surface},
+    Connection, Proxy, QueueHandle,
+};
+use wayland_egl::WlEglSurface;
+use glow::{Context, HasContext, FRAGMENT_SHADER, RENDERER, TRIANGLES, VERSION, VERTEX_SHADER};
+
+/// The globals the bar needs, checked before binding them.
+const REQUIREMENTS: &[Requirement] = &[
+    Requirement::required("wl_compositor", 1, "surfaces"),
+    Requirement::required("zwlr_layer_shell_v1", 1, "the bar"),
+    Requirement::optional("wl_output", "--output"),
+];
+
+/// Height of the bar in logical pixels.
+const BAR_HEIGHT: u32 = 32;
+
+const VERTEX_SHADER_SOURCE: &str = r#"#version 100
+// A triangle covering the whole surface, no vertex buffer needed
+attribute float index;
+varying float v_x;
+
+void main() {
+    vec2 position = vec2(index == 1.0 ? 3.0 : -1.0, index == 2.0 ? 3.0 : -1.0);
+    v_x = position.x * 0.5 + 0.5;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
+"#;
+
+const FRAGMENT_SHADER_SOURCE: &str = r#"#version 100
+precision mediump float;
+varying float v_x;
+// Seconds, scrolls the gradient along the bar
+uniform float time;
+
+void main() {
+    float t = fract(v_x - time * 0.1);
+    vec3 left = vec3(0.1, 0.3, 0.8);
+    vec3 right = vec3(0.8, 0.2, 0.5);
+    // Back and forth so the wrap around doesn't show
+    gl_FragColor = vec4(mix(left, right, abs(t * 2.0 - 1.0)), 1.0);
+}
+"#;
+
+fn main() {
+    let args = parse_args();
+    let mut logger = env_logger::Builder::from_default_env();
+    if let Some(level) = args.options.log_level {
+        logger.filter_level(level);
+    }
+    logger.init();
+
+    let mut reconnects = 0;
+    loop {
+        let Err(err) = run(&args) else {
+            return;
+        };
+        // Like the other examples, a protocol error would only happen again
+        eprintln!("{err}");
+        if !err.is_broken() || reconnects == args.options.reconnect {
+            std::process::exit(1);
+        }
+        reconnects += 1;
+        println!("reconnecting, attempt {reconnects} of {}", args.options.reconnect);
+        if !wayland_app_common::wait_for_compositor() {
+            eprintln!("no compositor to reconnect to");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Shows the bar until it exits or the connection fails.
+fn run(args: &Args) -> Result<(), ConnectionError> {
+    let conn = Connection::connect_to_env().unwrap();
+    let (globals, mut event_queue) = registry_queue_init(&conn).unwrap();
+    let qh = event_queue.handle();
+    wayland_app_common::check_globals(&globals, REQUIREMENTS);
+
+    let compositor_state =
+        CompositorState::bind(&globals, &qh).expect("wl_compositor not available");
+    let layer_shell = LayerShell::bind(&globals, &qh).expect("zwlr_layer_shell_v1 not available");
+
+    let mut bar = LayerBar {
+        registry_state: RegistryState::new(&globals),
+        output_state: OutputState::new(&globals, &qh),
+        exit: false,
+        exit_after_frames: args.options.exit_after_frames,
+        exit_after_seconds: args.options.exit_after_seconds,
+        started: Instant::now(),
+        frames: 0,
+        frames_drawn: 0,
+        width: 0,
+        height: BAR_HEIGHT,
+        scale: 1,
+        layer: None,
+        gl: None,
+        conn: conn.clone(),
+    };
+
+    // The output names arrive with the output events, the second roundtrip gets those of
+    // xdg-output on compositors that still send them that way
+    for _ in 0..2 {
+        event_queue.roundtrip(&mut bar).map_err(|err| ConnectionError::dispatch(&conn, err))?;
+    }
+
+    let output = match &args.output_name {
+        Some(name) => match bar.find_output(name) {
+            Some(output) => Some(output),
+            None => {
+                eprintln!("no output named {name:?}, the outputs are:");
+                for name in bar.output_names() {
+                    eprintln!("  {name}");
+                }
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let surface = compositor_state.create_surface(&qh);
+    let layer = layer_shell.create_layer_surface(
+        &qh,
+        surface,
+        Layer::Top,
+        Some("gl-bar"),
+        output.as_ref(),
+    );
+    // Stretched across the whole width, the compositor tells us how wide that is
+    layer.set_anchor(Anchor::TOP | Anchor::LEFT | Anchor::RIGHT);
+    layer.set_size(0, BAR_HEIGHT);
+    layer.set_exclusive_zone(BAR_HEIGHT as i32);
+    // Nothing is drawn until the first configure
+    layer.commit();
+    bar.layer = Some(layer);
+
+    let mut result = Ok(());
+    while !bar.exit {
+        if let Err(err) = event_queue.blocking_dispatch(&mut bar) {
+            result = Err(ConnectionError::dispatch(&conn, err));
+            break;
+        }
+    }
+    if args.options.exit_after_frames.is_some() || args.options.exit_after_seconds.is_some() {
+        let seconds = bar.started.elapsed().as_secs_f64();
+        println!(
+            "bar: {} frames drawn, {} shown in {seconds:.2} s, {:.1} fps, {}x{}",
+            bar.frames_drawn,
+            bar.frames,
+            bar.frames as f64 / seconds,
+            bar.width,
+            bar.height
+        );
+    }
+
+    // Clean up in the proper order, the GL stack goes before the surface it renders into
+    if let Some(gl) = bar.gl.take() {
+        gl.destroy();
+    }
+    drop(bar.layer.take());
+    result
+}
+
+struct Args {
+    output_name: Option<String>,
+    options: BasicOptions,
+}
+
+/// What `--help` prints, the bar has none of the options of the window examples.
+const USAGE: &str = "\
+Options:
+  --output <name>                put the bar on the output of that name
+  --exit-after-frames <n>        print every frame callback, exit after n
+  --exit-after-seconds <s>       exit after this long
+  --reconnect <n>                connect again this often when it breaks
+  --log-level <level>            off, error, warn, info, debug or trace
+  -h, --help                     print this
+";
+
+/// Parses `--output <name>` along with the [`BasicOptions`]. `--help` prints the usage and
+/// exits, an argument that isn't known or a value that doesn't fit exits with an error.
+fn parse_args() -> Args {
+    let mut output_name = None;
+    let options = BasicOptions::from_args(USAGE, |arg, value| {
+        if arg != "--output" {
+            return Ok(false);
+        }
+        match value() {
+            Some(name) => output_name = Some(name),
+            None => return Err(ArgsError::Invalid("--output needs the name of an output".into())),
+        }
+        Ok(true)
+    });
+    Args { output_name, options }
+}
+
+struct LayerBar {
+    registry_state: RegistryState,
+    output_state: OutputState,
+
+    exit: bool,
+    /// `--exit-after-frames` and `--exit-after-seconds`, for the smoke tests and benchmarks.
+    exit_after_frames: Option<u64>,
+    exit_after_seconds: Option<Duration>,
+    started: Instant,
+    /// Frame callbacks received so far, each for a frame with new content.
+    frames: u64,
+    frames_drawn: u64,
+    /// Logical size from the latest configure.
+    width: u32,
+    height: u32,
+    /// Integer buffer scale, follows the output the bar is on.
+    scale: i32,
+    layer: Option<LayerSurface>,
+    gl: Option<Gl>,
+    conn: Connection,
+}
+
+impl LayerBar {
+    fn find_output(&self, name: &str) -> Option<wl_output::WlOutput> {
+        self.output_state.outputs().find(|output| {
+            self.output_state
+                .info(output)
+                .is_some_and(|info| info.name.as_deref() == Some(name))
+        })
+    }
+
+    fn output_names(&self) -> Vec<String> {
+        self.output_state
+            .outputs()
+            .filter_map(|output| self.output_state.info(&output)?.name)
+            .collect()
+    }
+
+    /// Size of the bar in physical pixels.
+    fn buffer_size(&self) -> (i32, i32) {
+        (self.width as i32 * self.scale, self.height as i32 * self.scale)
+    }
+
+    fn draw(&mut self, qh: &QueueHandle<Self>, time: u32) {
+        let (Some(layer), Some(gl)) = (&self.layer, &self.gl) else {
+            return;
+        };
+
+        // Keep the animation going, the request goes out with the swap
+        let surface = layer.wl_surface();
+        surface.frame(qh, surface.clone());
+        let (width, height) = self.buffer_size();
+        match gl.draw(width, height, time) {
+            Ok(()) => self.frames_drawn += 1,
+            Err(err) => {
+                log::warn!("failed to draw the bar: {err}");
+                surface.commit();
+            }
+        }
+    }
+}
+
+/// The EGL setup of the wayland-egl example, cut down to a single surface.
+struct Gl {
+    egl: khronos_egl::Instance<khronos_egl::Static>,
+    display: khronos_egl::Display,
+    context: khronos_egl::Context,
+    wl_egl_surface: WlEglSurface,
+    egl_surface: khronos_egl::Surface,
+    gl: Context,
+    program: glow::NativeProgram,
+}
+
+impl Gl {
+    fn new(
+        conn: &Connection,
+        surface: &wl_surface::WlSurface,
+        (width, height): (i32, i32),
+    ) -> Result<Gl, GlInitError> {
+        let egl = khronos_egl::Instance::new(khronos_egl::Static);
+        let display = unsafe {
+            egl.get_display(conn.backend().display_ptr() as *mut std::ffi::c_void)
+                .ok_or_else(|| GlInitError::Display("no EGL display for wl_display".to_string()))?
+        };
+        egl.initialize(display)
+            .map_err(|err| GlInitError::Display(format!("eglInitialize: {err}")))?;
+
+        let attributes = [
+            khronos_egl::RED_SIZE, 8,
+            khronos_egl::GREEN_SIZE, 8,
+            khronos_egl::BLUE_SIZE, 8,
+            khronos_egl::SURFACE_TYPE, khronos_egl::WINDOW_BIT,
+            khronos_egl::RENDERABLE_TYPE, khronos_egl::OPENGL_ES2_BIT,
+            khronos_egl::NONE,
+        ];
+        let config = egl
+            .choose_first_config(display, &attributes)
+            .ok()
+            .flatten()
+            .ok_or_else(|| GlInitError::Config {
+                requested: "RGB888, window surface, OpenGL ES 2".to_string(),
+                available: Vec::new(),
+            })?;
+
+        egl.bind_api(khronos_egl::OPENGL_ES_API)
+            .map_err(|err| GlInitError::Context(format!("eglBindAPI: {err}")))?;
+        let context_attributes = [khronos_egl::CONTEXT_CLIENT_VERSION, 2, khronos_egl::NONE];
+        let context = egl
+            .create_context(display, config, None, &context_attributes)
+            .map_err(|err| GlInitError::Context(err.to_string()))?;
+
+        let wl_egl_surface = WlEglSurface::new(surface.id(), width, height)
+            .map_err(|err| GlInitError::Surface(err.to_string()))?;
+        let egl_surface = unsafe {
+            egl.create_window_surface(
+                display,
+                config,
+                wl_egl_surface.ptr() as *mut std::ffi::c_void,
+                None,
+            )
+            .map_err(|err| GlInitError::Surface(err.to_string()))?
+        };
+
+        egl.make_current(display, Some(egl_surface), Some(egl_surface), Some(context))
+            .map_err(|err| GlInitError::MakeCurrent(err.to_string()))?;
+        // The frame callbacks pace us, the swap doesn't have to
+        egl.swap_interval(display, 0).ok();
+
+        let gl = unsafe {
+            Context::from_loader_function(|s| {
+                egl.get_proc_address(s).map_or(std::ptr::null(), |f| f as *const _)
+            })
+        };
+        println!("OpenGL version: {}", unsafe { gl.get_parameter_string(VERSION) });
+        println!("OpenGL renderer: {}", unsafe { gl.get_parameter_string(RENDERER) });
+
+        let program = unsafe { create_program(&gl)? };
+
+        Ok(Gl {
+            egl,
+            display,
+            context,
+            wl_egl_surface,
+            egl_surface,
+            gl,
+            program,
+        })
+    }
+
+    fn resize(&self, (width, height): (i32, i32)) {
+        self.wl_egl_surface.resize(width, height, 0, 0);
+    }
+
+    /// Draws the gradient and swaps, which commits the surface.
+    fn draw(&self, width: i32, height: i32, time: u32) -> Result<(), khronos_egl::Error> {
+        let gl = &self.gl;
+        unsafe {
+            gl.viewport(0, 0, width, height);
+            gl.use_program(Some(self.program));
+            let location = gl.get_uniform_location(self.program, "time");
+            gl.uniform_1_f32(location.as_ref(), time as f32 / 1000.0);
+            // The vertex shader only needs the index of the corner
+            gl.vertex_attrib_1_f32(0, 0.0);
+            gl.draw_arrays(TRIANGLES, 0, 3);
+        }
+        self.egl.swap_buffers(self.display, self.egl_surface)
+    }
+
+    fn destroy(self) {
+        unsafe {
+            self.gl.delete_program(self.program);
+        }
+        if let Err(err) = self.egl.make_current(self.display, None, None, None) {
+            log::warn!("failed to release the context: {err}");
+        }
+        if let Err(err) = self.egl.destroy_surface(self.display, self.egl_surface) {
+            log::warn!("failed to destroy the EGL surface: {err}");
+        }
+        if let Err(err) = self.egl.destroy_context(self.display, self.context) {
+            log::warn!("failed to destroy the EGL context: {err}");
+        }
+        drop(self.wl_egl_surface);
+        // The display may be handed out again for the next connection, it must start afresh
+        if let Err(err) = self.egl.terminate(self.display) {
+            log::warn!("failed to terminate the EGL display: {err}");
+        }
+    }
+}
+
+/// Compiles and links the gradient shaders. The corner index is fed in as a vertex attribute
+/// array, GLSL ES 1.00 has no `gl_VertexID`.
+unsafe fn create_program(gl: &Context) -> Result<glow::NativeProgram, GlInitError> {
+    unsafe {
+        let program = gl.create_program().map_err(GlInitError::Shader)?;
+        let mut shaders = Vec::new();
+        for (kind, source) in [
+            (VERTEX_SHADER, VERTEX_SHADER_SOURCE),
+            (FRAGMENT_SHADER, FRAGMENT_SHADER_SOURCE),
+        ] {
+            let shader = gl.create_shader(kind).map_err(GlInitError::Shader)?;
+            gl.shader_source(shader, source);
+            gl.compile_shader(shader);
+            if !gl.get_shader_compile_status(shader) {
+                return Err(GlInitError::Shader(gl.get_shader_info_log(shader)));
+            }
+            gl.attach_shader(program, shader);
+            shaders.push(shader);
+        }
+        gl.bind_attrib_location(program, 0, "index");
+        gl.link_program(program);
+        let linked = gl.get_program_link_status(program);
+        for shader in shaders {
+            gl.detach_shader(program, shader);
+            gl.delete_shader(shader);
+        }
+        if !linked {
+            return Err(GlInitError::Shader(gl.get_program_info_log(program)));
+        }
+        Ok(program)
+    }
+}
+
+impl LayerShellHandler for LayerBar {
+    fn closed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _layer: &LayerSurface) {
+        // E.g. the output went away, the surface is of no use anymore
+        println!("layer surface closed");
+        self.exit = true;
+    }
+
+    fn configure(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        layer: &LayerSurface,
+        configure: LayerSurfaceConfigure,
+        _serial: u32,
+    ) {
+        // Anchored to both sides the width comes from the compositor, the height is ours
+        let (width, height) = configure.new_size;
+        self.width = width.max(1);
+        self.height = if height == 0 { BAR_HEIGHT } else { height };
+        log::info!("layer surface configured to {}x{}", self.width, self.height);
+
+        match &self.gl {
+            Some(gl) => gl.resize(self.buffer_size()),
+            None => match Gl::new(&self.conn, layer.wl_surface(), self.buffer_size()) {
+                Ok(gl) => self.gl = Some(gl),
+                Err(err) => {
+                    eprintln!("{err}");
+                    self.exit = true;
+                    return;
+                }
+            },
+        }
+        self.draw(qh, 0);
+    }
+}
+
+impl CompositorHandler for LayerBar {
+    fn scale_factor_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        new_factor: i32,
+    ) {
+        if new_factor == self.scale {
+            return;
+        }
+
+        // Applied with the next buffer, which is drawn at the new size
+        log::info!("scale factor changed to {new_factor}");
+        self.scale = new_factor;
+        surface.set_buffer_scale(new_factor);
+        if let Some(gl) = &self.gl {
+            gl.resize(self.buffer_size());
+        }
+    }
+
+    fn transform_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _new_transform: wl_output::Transform,
+    ) {
+    }
+
+    fn frame(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        time: u32,
+    ) {
+        self.frames += 1;
+        if let Some(frames) = self.exit_after_frames {
+            // `frame <n> <time>` like the other examples, the compositor showed the frame
+            println!("frame {} {time}", self.frames);
+            if self.frames >= frames {
+                self.exit = true;
+                return;
+            }
+        }
+        // The callbacks keep coming while the bar animates, no timer needed
+        if self.exit_after_seconds.is_some_and(|after| self.started.elapsed() >= after) {
+            self.exit = true;
+            return;
+        }
+        self.draw(qh, time);
+    }
+
+    fn surface_enter(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _output: &wl_output::WlOutput,
+    ) {
+    }
+
+    fn surface_leave(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _output: &wl_output::WlOutput,
+    ) {
+    }
+}
+
+impl OutputHandler for LayerBar {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn update_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn output_destroyed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+}
+
+delegate_compositor!(LayerBar);
+delegate_output!(LayerBar);
+delegate_layer!(LayerBar);
+delegate_registry!(LayerBar);
+
+impl ProvidesRegistryState for LayerBar {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+    registry_handlers![OutputState];
+}
@@ -0,0 +1,906 @@
+// A lock screen through ext-session-lock: the session is locked and every output gets a lock
+// surface with an animated GL gradient and the time of day on it. Enter unlocks.
+//
+// Outputs plugged in while the session is locked get a lock surface of their own, and the
+// surfaces of unplugged ones go away with them. When another client holds the lock already the
+// compositor refuses ours with `finished`, and the example exits.
+//
+// `--exit-after-frames <n>` prints a line per frame callback and unlocks after n of them,
+// `--exit-after-seconds <s>` unlocks after s seconds, a way out should the keyboard not work.
+// Both print a summary on exit.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use glow::{
+    Context, HasContext, ARRAY_BUFFER, COLOR_BUFFER_BIT, FLOAT, FRAGMENT_SHADER, RENDERER,
+    SCISSOR_TEST, STATIC_DRAW, TRIANGLES, VERSION, VERTEX_SHADER,
+};
+use smithay_client_toolkit::{
+    compositor::{CompositorHandler, CompositorState},
+    delegate_compositor, delegate_keyboard, delegate_output, delegate_registry, delegate_seat,
+    delegate_session_lock,
+    output::{OutputHandler, OutputState},
+    registry::{ProvidesRegistryState, RegistryState},
+    registry_handlers,
+    seat::{
+        keyboard::{KeyEvent, KeyboardHandler, Keysym, Modifiers, RawModifiers},
+        Capability, SeatHandler, SeatState,
+    },
+    session_lock::{
+        SessionLock, SessionLockHandler, SessionLockState, SessionLockSurface,
+        SessionLockSurfaceConfigure,
+    },
+};
+use wayland_app_common::{ArgsError, BasicOptions, ConnectionError, GlInitError, Requirement};
+use wayland_client::{
+    globals::registry_queue_init,
+    protocol::{wl_keyboard, wl_output, wl_seat, wl_surface},
+    Connection, Proxy, QueueHandle,
+};
+use wayland_egl::WlEglSurface;
+
+/// The globals the lock screen needs, checked before binding them.
+const REQUIREMENTS: &[Requirement] = &[
+    Requirement::required("wl_compositor", 1, "surfaces"),
+    Requirement::required("ext_session_lock_manager_v1", 1, "the lock"),
+    Requirement::required("wl_output", 1, "a lock surface for each output"),
+    Requirement::optional("wl_seat", "unlocking with Enter"),
+];
+
+/// Segments of the seven-segment digits 0 to 9, from the top (lowest bit) clockwise around to
+/// the upper left, then the middle.
+const DIGIT_SEGMENTS: [u8; 10] = [0x3f, 0x06, 0x5b, 0x4f, 0x66, 0x6d, 0x7d, 0x07, 0x7f, 0x6f];
+
+const VERTEX_SHADER_SOURCE: &str = r#"#version 100
+// A triangle covering the whole surface, only the index of the corner comes from the buffer
+attribute float index;
+varying vec2 v_position;
+
+void main() {
+    vec2 position = vec2(index == 1.0 ? 3.0 : -1.0, index == 2.0 ? 3.0 : -1.0);
+    v_position = position * 0.5 + 0.5;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
+"#;
+
+const FRAGMENT_SHADER_SOURCE: &str = r#"#version 100
+precision mediump float;
+varying vec2 v_position;
+// Seconds, moves the bands of the gradient
+uniform float time;
+
+void main() {
+    float t = 0.5 + 0.5 * sin(v_position.x * 2.0 + v_position.y * 3.0 + time * 0.5);
+    vec3 dark = vec3(0.05, 0.08, 0.2);
+    vec3 light = vec3(0.3, 0.1, 0.35);
+    gl_FragColor = vec4(mix(dark, light, t), 1.0);
+}
+"#;
+
+fn main() {
+    let args = parse_args();
+    let mut logger = env_logger::Builder::from_default_env();
+    if let Some(level) = args.log_level {
+        logger.filter_level(level);
+    }
+    logger.init();
+
+    // A lock screen that reconnects would find the session locked by the compositor and
+    // nobody to unlock it, so unlike the other examples a broken connection just exits
+    if let Err(err) = run(&args) {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
+
+/// Locks the session until Enter, or until the compositor refuses or the connection fails.
+fn run(args: &BasicOptions) -> Result<(), ConnectionError> {
+    let conn = Connection::connect_to_env().unwrap();
+    let (globals, mut event_queue) = registry_queue_init(&conn).unwrap();
+    let qh = event_queue.handle();
+    wayland_app_common::check_globals(&globals, REQUIREMENTS);
+
+    let mut locker = Locker {
+        registry_state: RegistryState::new(&globals),
+        output_state: OutputState::new(&globals, &qh),
+        seat_state: SeatState::new(&globals, &qh),
+        compositor_state: CompositorState::bind(&globals, &qh)
+            .expect("wl_compositor not available"),
+        lock: None,
+        surfaces: Vec::new(),
+        gl: None,
+        keyboards: HashMap::new(),
+        exit: false,
+        exit_after_frames: args.exit_after_frames,
+        exit_after_seconds: args.exit_after_seconds,
+        started: Instant::now(),
+        frames: 0,
+        frames_drawn: 0,
+        conn: conn.clone(),
+    };
+
+    // The outputs arrive with the first roundtrip, later ones with `new_output`
+    event_queue.roundtrip(&mut locker).map_err(|err| ConnectionError::dispatch(&conn, err))?;
+
+    let session_lock_state = SessionLockState::new(&globals, &qh);
+    let lock = session_lock_state.lock(&qh).expect("ext_session_lock_manager_v1 not available");
+    locker.lock = Some(lock);
+    // Every output needs a lock surface, the compositor shows nothing of the session meanwhile
+    let outputs: Vec<wl_output::WlOutput> = locker.output_state.outputs().collect();
+    for output in outputs {
+        locker.add_surface(&qh, output);
+    }
+
+    let mut result = Ok(());
+    while !locker.exit {
+        if let Err(err) = event_queue.blocking_dispatch(&mut locker) {
+            result = Err(ConnectionError::dispatch(&conn, err));
+            break;
+        }
+    }
+    if args.exit_after_frames.is_some() || args.exit_after_seconds.is_some() {
+        let seconds = locker.started.elapsed().as_secs_f64();
+        println!(
+            "lock: {} frames drawn, {} shown in {seconds:.2} s, {:.1} fps, {} outputs",
+            locker.frames_drawn,
+            locker.frames,
+            locker.frames as f64 / seconds,
+            locker.surfaces.len()
+        );
+    }
+
+    // Clean up in the proper order, the GL stack goes before the surfaces it renders into
+    for surface in locker.surfaces.drain(..) {
+        if let (Some(gl), Some(egl)) = (&locker.gl, surface.egl) {
+            gl.destroy_surface(egl);
+        }
+    }
+    if let Some(gl) = locker.gl.take() {
+        gl.destroy();
+    }
+    drop(locker.lock.take());
+    // Make sure the unlock arrived before we go, the compositor may not take a client that
+    // just disappears for one that unlocked
+    if result.is_ok() {
+        event_queue.roundtrip(&mut locker).map_err(|err| ConnectionError::dispatch(&conn, err))?;
+    }
+    result
+}
+
+/// What `--help` prints, the lock has none of the options of the window examples.
+const USAGE: &str = "\
+Options:
+  --exit-after-frames <n>        print every frame callback, exit after n
+  --exit-after-seconds <s>       exit after this long
+  --log-level <level>            off, error, warn, info, debug or trace
+  -h, --help                     print this
+";
+
+/// Parses the [`BasicOptions`] but `--reconnect`. `--help` prints the usage and exits, an
+/// argument that isn't known or a value that doesn't fit exits with an error.
+fn parse_args() -> BasicOptions {
+    BasicOptions::from_args(USAGE, |arg, _| match arg {
+        "--reconnect" => Err(ArgsError::Invalid("the session lock never reconnects".into())),
+        _ => Ok(false),
+    })
+}
+
+struct Locker {
+    registry_state: RegistryState,
+    output_state: OutputState,
+    seat_state: SeatState,
+    compositor_state: CompositorState,
+    /// The lock until the example exits. Dropped without unlocking it stays locked.
+    lock: Option<SessionLock>,
+    /// A lock surface for each output.
+    surfaces: Vec<LockSurface>,
+    /// Created with the first configure, one context draws all surfaces.
+    gl: Option<Gl>,
+    /// The keyboard of each seat, Enter on any of them unlocks.
+    keyboards: HashMap<wl_seat::WlSeat, wl_keyboard::WlKeyboard>,
+
+    exit: bool,
+    /// `--exit-after-frames` and `--exit-after-seconds`, for the smoke tests and benchmarks.
+    exit_after_frames: Option<u64>,
+    exit_after_seconds: Option<Duration>,
+    started: Instant,
+    /// Frame callbacks received so far, of all surfaces.
+    frames: u64,
+    frames_drawn: u64,
+    conn: Connection,
+}
+
+/// The lock surface of an output.
+struct LockSurface {
+    output: wl_output::WlOutput,
+    /// Logical size from the latest configure.
+    width: u32,
+    height: u32,
+    /// Integer buffer scale, follows the output.
+    scale: i32,
+    /// Created on the first configure.
+    egl: Option<EglSurface>,
+    lock_surface: SessionLockSurface,
+}
+
+impl LockSurface {
+    /// Size of the surface in physical pixels.
+    fn buffer_size(&self) -> (i32, i32) {
+        (self.width as i32 * self.scale, self.height as i32 * self.scale)
+    }
+}
+
+impl Locker {
+    /// Gives the output a lock surface, it is drawn once the compositor configures it.
+    fn add_surface(&mut self, qh: &QueueHandle<Self>, output: wl_output::WlOutput) {
+        let Some(lock) = &self.lock else {
+            return;
+        };
+        let surface = self.compositor_state.create_surface(qh);
+        let lock_surface = lock.create_lock_surface(surface, &output, qh);
+        log::info!("lock surface {} for output {}", lock_surface.wl_surface().id(), output.id());
+        self.surfaces.push(LockSurface {
+            output,
+            width: 0,
+            height: 0,
+            scale: 1,
+            egl: None,
+            lock_surface,
+        });
+    }
+
+    /// The index of the lock surface in `self.surfaces`.
+    fn find_surface(&self, surface: &wl_surface::WlSurface) -> Option<usize> {
+        self.surfaces.iter().position(|lock| lock.lock_surface.wl_surface() == surface)
+    }
+
+    /// Unlocks the session if it is locked, and leaves the loop.
+    fn unlock(&mut self) {
+        if let Some(lock) = &self.lock {
+            lock.unlock();
+        }
+        self.exit = true;
+    }
+
+    fn draw(&mut self, qh: &QueueHandle<Self>, index: usize, time: u32) {
+        let (Some(gl), Some(surface)) = (&self.gl, self.surfaces.get(index)) else {
+            return;
+        };
+        let Some(egl) = &surface.egl else {
+            return;
+        };
+
+        // Keep the animation going, the request goes out with the swap
+        let wl_surface = surface.lock_surface.wl_surface();
+        wl_surface.frame(qh, wl_surface.clone());
+        match gl.draw(egl, surface.buffer_size(), time, &time_of_day()) {
+            Ok(()) => self.frames_drawn += 1,
+            Err(err) => {
+                log::warn!("failed to draw the lock surface: {err}");
+                wl_surface.commit();
+            }
+        }
+    }
+}
+
+/// The EGL window surface of a lock surface.
+struct EglSurface {
+    wl_egl_surface: WlEglSurface,
+    egl_surface: khronos_egl::Surface,
+}
+
+/// The EGL setup of the layer-shell example, with one context for all surfaces.
+struct Gl {
+    egl: khronos_egl::Instance<khronos_egl::Static>,
+    display: khronos_egl::Display,
+    config: khronos_egl::Config,
+    context: khronos_egl::Context,
+    gl: Context,
+    program: glow::NativeProgram,
+    /// The corner indices 0, 1 and 2, shared by all surfaces like the program.
+    corners: glow::NativeBuffer,
+}
+
+impl Gl {
+    /// Sets up EGL along with the surface of the first lock surface, the program is compiled
+    /// with its surface current.
+    fn new(
+        conn: &Connection,
+        surface: &wl_surface::WlSurface,
+        size: (i32, i32),
+    ) -> Result<(Gl, EglSurface), GlInitError> {
+        let egl = khronos_egl::Instance::new(khronos_egl::Static);
+        let display = unsafe {
+            egl.get_display(conn.backend().display_ptr() as *mut std::ffi::c_void)
+                .ok_or_else(|| GlInitError::Display("no EGL display for wl_display".to_string()))?
+        };
+        egl.initialize(display)
+            .map_err(|err| GlInitError::Display(format!("eglInitialize: {err}")))?;
+
+        let attributes = [
+            khronos_egl::RED_SIZE, 8,
+            khronos_egl::GREEN_SIZE, 8,
+            khronos_egl::BLUE_SIZE, 8,
+            khronos_egl::SURFACE_TYPE, khronos_egl::WINDOW_BIT,
+            khronos_egl::RENDERABLE_TYPE, khronos_egl::OPENGL_ES2_BIT,
+            khronos_egl::NONE,
+        ];
+        let config = egl
+            .choose_first_config(display, &attributes)
+            .ok()
+            .flatten()
+            .ok_or_else(|| GlInitError::Config {
+                requested: "RGB888, window surface, OpenGL ES 2".to_string(),
+                available: Vec::new(),
+            })?;
+
+        egl.bind_api(khronos_egl::OPENGL_ES_API)
+            .map_err(|err| GlInitError::Context(format!("eglBindAPI: {err}")))?;
+        let context_attributes = [khronos_egl::CONTEXT_CLIENT_VERSION, 2, khronos_egl::NONE];
+        let context = egl
+            .create_context(display, config, None, &context_attributes)
+            .map_err(|err| GlInitError::Context(err.to_string()))?;
+
+        let gl = unsafe {
+            Context::from_loader_function(|s| {
+                egl.get_proc_address(s).map_or(std::ptr::null(), |f| f as *const _)
+            })
+        };
+        // Makes the context current with the surface
+        let first = create_egl_surface(&egl, display, config, context, surface, size)?;
+        println!("OpenGL version: {}", unsafe { gl.get_parameter_string(VERSION) });
+        println!("OpenGL renderer: {}", unsafe { gl.get_parameter_string(RENDERER) });
+        let program = unsafe { create_program(&gl)? };
+        let corners = unsafe { create_corners(&gl)? };
+
+        Ok((
+            Gl {
+                egl,
+                display,
+                config,
+                context,
+                gl,
+                program,
+                corners,
+            },
+            first,
+        ))
+    }
+
+    /// Creates the EGL surface of another lock surface and makes it current.
+    fn create_surface(
+        &self,
+        surface: &wl_surface::WlSurface,
+        size: (i32, i32),
+    ) -> Result<EglSurface, GlInitError> {
+        create_egl_surface(&self.egl, self.display, self.config, self.context, surface, size)
+    }
+
+    fn make_current(&self, surface: khronos_egl::Surface) -> Result<(), khronos_egl::Error> {
+        self.egl.make_current(self.display, Some(surface), Some(surface), Some(self.context))
+    }
+
+    /// Draws the gradient and the clock and swaps, which commits the surface.
+    fn draw(
+        &self,
+        target: &EglSurface,
+        (width, height): (i32, i32),
+        time: u32,
+        digits: &[u8; 6],
+    ) -> Result<(), khronos_egl::Error> {
+        self.make_current(target.egl_surface)?;
+        let gl = &self.gl;
+        unsafe {
+            gl.viewport(0, 0, width, height);
+            gl.use_program(Some(self.program));
+            let location = gl.get_uniform_location(self.program, "time");
+            gl.uniform_1_f32(location.as_ref(), time as f32 / 1000.0);
+            gl.bind_buffer(ARRAY_BUFFER, Some(self.corners));
+            gl.enable_vertex_attrib_array(0);
+            gl.vertex_attrib_pointer_f32(0, 1, FLOAT, false, 4, 0);
+            gl.draw_arrays(TRIANGLES, 0, 3);
+
+            // The segments of the digits are cleared rectangles, no font needed
+            gl.enable(SCISSOR_TEST);
+            gl.clear_color(0.9, 0.9, 0.95, 1.0);
+            for [x, y, width, height] in clock_rects(digits, (width, height)) {
+                gl.scissor(x, y, width, height);
+                gl.clear(COLOR_BUFFER_BIT);
+            }
+            gl.disable(SCISSOR_TEST);
+        }
+        self.egl.swap_buffers(self.display, target.egl_surface)
+    }
+
+    /// Destroys the EGL surface of a lock surface before the lock surface goes.
+    fn destroy_surface(&self, target: EglSurface) {
+        // A surface that is still current is only destroyed once it no longer is
+        if let Err(err) = self.egl.make_current(self.display, None, None, Some(self.context)) {
+            log::warn!("failed to release the EGL surface: {err}");
+        }
+        if let Err(err) = self.egl.destroy_surface(self.display, target.egl_surface) {
+            log::warn!("failed to destroy the EGL surface: {err}");
+        }
+        drop(target.wl_egl_surface);
+    }
+
+    fn destroy(self) {
+        unsafe {
+            self.gl.delete_buffer(self.corners);
+            self.gl.delete_program(self.program);
+        }
+        if let Err(err) = self.egl.make_current(self.display, None, None, None) {
+            log::warn!("failed to release the context: {err}");
+        }
+        if let Err(err) = self.egl.destroy_context(self.display, self.context) {
+            log::warn!("failed to destroy the EGL context: {err}");
+        }
+        if let Err(err) = self.egl.terminate(self.display) {
+            log::warn!("failed to terminate the EGL display: {err}");
+        }
+    }
+}
+
+/// Creates the EGL window surface of a lock surface and makes it current with the context.
+fn create_egl_surface(
+    egl: &khronos_egl::Instance<khronos_egl::Static>,
+    display: khronos_egl::Display,
+    config: khronos_egl::Config,
+    context: khronos_egl::Context,
+    surface: &wl_surface::WlSurface,
+    (width, height): (i32, i32),
+) -> Result<EglSurface, GlInitError> {
+    let wl_egl_surface = WlEglSurface::new(surface.id(), width, height)
+        .map_err(|err| GlInitError::Surface(err.to_string()))?;
+    let egl_surface = unsafe {
+        egl.create_window_surface(
+            display,
+            config,
+            wl_egl_surface.ptr() as *mut std::ffi::c_void,
+            None,
+        )
+        .map_err(|err| GlInitError::Surface(err.to_string()))?
+    };
+    egl.make_current(display, Some(egl_surface), Some(egl_surface), Some(context))
+        .map_err(|err| GlInitError::MakeCurrent(err.to_string()))?;
+    // The frame callbacks pace us, the swap doesn't have to. The interval belongs to the
+    // surface current when it is set.
+    egl.swap_interval(display, 0).ok();
+    Ok(EglSurface { wl_egl_surface, egl_surface })
+}
+
+/// Compiles and links the gradient shaders. The corner index is fed in as a vertex attribute
+/// array, GLSL ES 1.00 has no `gl_VertexID`.
+unsafe fn create_program(gl: &Context) -> Result<glow::NativeProgram, GlInitError> {
+    unsafe {
+        let program = gl.create_program().map_err(GlInitError::Shader)?;
+        let mut shaders = Vec::new();
+        for (kind, source) in [
+            (VERTEX_SHADER, VERTEX_SHADER_SOURCE),
+            (FRAGMENT_SHADER, FRAGMENT_SHADER_SOURCE),
+        ] {
+            let shader = gl.create_shader(kind).map_err(GlInitError::Shader)?;
+            gl.shader_source(shader, source);
+            gl.compile_shader(shader);
+            if !gl.get_shader_compile_status(shader) {
+                return Err(GlInitError::Shader(gl.get_shader_info_log(shader)));
+            }
+            gl.attach_shader(program, shader);
+            shaders.push(shader);
+        }
+        gl.bind_attrib_location(program, 0, "index");
+        gl.link_program(program);
+        let linked = gl.get_program_link_status(program);
+        for shader in shaders {
+            gl.detach_shader(program, shader);
+            gl.delete_shader(shader);
+        }
+        if !linked {
+            return Err(GlInitError::Shader(gl.get_program_info_log(program)));
+        }
+        Ok(program)
+    }
+}
+
+/// Uploads the corner indices of the triangle.
+unsafe fn create_corners(gl: &Context) -> Result<glow::NativeBuffer, GlInitError> {
+    unsafe {
+        let buffer = gl.create_buffer().map_err(GlInitError::Shader)?;
+        let corners: Vec<u8> = [0.0f32, 1.0, 2.0].iter().flat_map(|c| c.to_ne_bytes()).collect();
+        gl.bind_buffer(ARRAY_BUFFER, Some(buffer));
+        gl.buffer_data_u8_slice(ARRAY_BUFFER, &corners, STATIC_DRAW);
+        Ok(buffer)
+    }
+}
+
+/// The digits of the local time, HH:MM:SS. UTC when the time zone is unknown.
+fn time_of_day() -> [u8; 6] {
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    if unsafe { libc::localtime_r(&now, &mut tm) }.is_null() {
+        return [0; 6];
+    }
+    let [hours, minutes, seconds] = [tm.tm_hour, tm.tm_min, tm.tm_sec].map(|n| n as u8);
+    [hours / 10, hours % 10, minutes / 10, minutes % 10, seconds / 10, seconds % 10]
+}
+
+/// The rectangles of the segments of HH:MM:SS, centered on a buffer of the size. In GL window
+/// coordinates, `[x, y, width, height]` from the lower left corner.
+fn clock_rects(digits: &[u8; 6], (width, height): (i32, i32)) -> Vec<[i32; 4]> {
+    // Laid out from the top left, the eight characters take about five digit heights
+    let digit_height = (height / 6).min(width / 5).max(10);
+    let digit_width = digit_height / 2;
+    let thickness = (digit_width / 5).max(1);
+    let gap = digit_width / 2;
+    let half = digit_height / 2;
+    let total = 6 * digit_width + 7 * gap + 2 * thickness;
+    let top = (height - digit_height) / 2;
+    let mut x = (width - total) / 2;
+
+    let (w, h, t) = (digit_width, digit_height, thickness);
+    let segments = [
+        [0, 0, w, t],
+        [w - t, 0, t, half],
+        [w - t, half, t, h - half],
+        [0, h - t, w, t],
+        [0, half, t, h - half],
+        [0, 0, t, half],
+        [0, half - t / 2, w, t],
+    ];
+    let mut rects = Vec::new();
+    for (index, &digit) in digits.iter().enumerate() {
+        if index == 2 || index == 4 {
+            // The colon, two dots a third of the way down and up
+            for y in [top + h / 3, top + h * 2 / 3] {
+                rects.push([x, y - t / 2, t, t]);
+            }
+            x += t + gap;
+        }
+        let lit = DIGIT_SEGMENTS[digit as usize % 10];
+        for (bit, [dx, dy, dw, dh]) in segments.into_iter().enumerate() {
+            if lit & (1 << bit) != 0 {
+                rects.push([x + dx, top + dy, dw, dh]);
+            }
+        }
+        x += w + gap;
+    }
+    rects.into_iter().map(|[x, y, w, h]| [x, height - y - h, w, h]).collect()
+}
+
+impl SessionLockHandler for Locker {
+    fn locked(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _lock: SessionLock) {
+        println!("session locked, press Enter to unlock");
+    }
+
+    fn finished(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _lock: SessionLock) {
+        // Sent instead of `locked` when another client holds the lock already, or when the
+        // compositor ends the lock on its own. Either way the session isn't ours to unlock.
+        println!("the compositor refused or ended the lock, another client may hold it");
+        self.exit = true;
+    }
+
+    fn configure(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        surface: SessionLockSurface,
+        configure: SessionLockSurfaceConfigure,
+        _serial: u32,
+    ) {
+        let Some(index) = self.find_surface(surface.wl_surface()) else {
+            return;
+        };
+        let lock_surface = &mut self.surfaces[index];
+        // The size of the output, the lock surface has no say in it
+        (lock_surface.width, lock_surface.height) = configure.new_size;
+        log::info!(
+            "lock surface {} configured to {}x{}",
+            surface.wl_surface().id(),
+            lock_surface.width,
+            lock_surface.height
+        );
+        let size = lock_surface.buffer_size();
+
+        let created = match (&self.gl, &lock_surface.egl) {
+            (Some(_), Some(egl)) => {
+                egl.wl_egl_surface.resize(size.0, size.1, 0, 0);
+                Ok(None)
+            }
+            (Some(gl), None) => {
+                gl.create_surface(surface.wl_surface(), size).map(|egl| Some((None, egl)))
+            }
+            // The first configure of all, the surface comes along with the context
+            (None, _) => Gl::new(&self.conn, surface.wl_surface(), size)
+                .map(|(gl, egl)| Some((Some(gl), egl))),
+        };
+        match created {
+            Ok(Some((gl, egl))) => {
+                if gl.is_some() {
+                    self.gl = gl;
+                }
+                self.surfaces[index].egl = Some(egl);
+            }
+            Ok(None) => {}
+            Err(err) => {
+                // Nothing to show, better not keep the session locked behind a blank screen
+                eprintln!("{err}");
+                self.unlock();
+                return;
+            }
+        }
+        self.draw(qh, index, 0);
+    }
+}
+
+impl CompositorHandler for Locker {
+    fn scale_factor_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        new_factor: i32,
+    ) {
+        let Some(lock_surface) =
+            self.find_surface(surface).map(|index| &mut self.surfaces[index])
+        else {
+            return;
+        };
+        if new_factor == lock_surface.scale {
+            return;
+        }
+
+        // Applied with the next buffer, which is drawn at the new size
+        log::info!("scale factor of lock surface {} changed to {new_factor}", surface.id());
+        lock_surface.scale = new_factor;
+        surface.set_buffer_scale(new_factor);
+        if let Some(egl) = &lock_surface.egl {
+            let (width, height) = lock_surface.buffer_size();
+            egl.wl_egl_surface.resize(width, height, 0, 0);
+        }
+    }
+
+    fn transform_changed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _new_transform: wl_output::Transform,
+    ) {
+    }
+
+    fn frame(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        time: u32,
+    ) {
+        self.frames += 1;
+        if let Some(frames) = self.exit_after_frames {
+            // `frame <n> <time>` like the other examples, the compositor showed the frame
+            println!("frame {} {time}", self.frames);
+            if self.frames >= frames {
+                self.unlock();
+                return;
+            }
+        }
+        // The callbacks keep coming while the gradient animates, no timer needed
+        if self.exit_after_seconds.is_some_and(|after| self.started.elapsed() >= after) {
+            self.unlock();
+            return;
+        }
+        if let Some(index) = self.find_surface(surface) {
+            self.draw(qh, index, time);
+        }
+    }
+
+    fn surface_enter(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _output: &wl_output::WlOutput,
+    ) {
+    }
+
+    fn surface_leave(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+        _output: &wl_output::WlOutput,
+    ) {
+    }
+}
+
+impl OutputHandler for Locker {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        output: wl_output::WlOutput,
+    ) {
+        // Plugged in while locked, before the lock the initial outputs are handled in `run`
+        if self.lock.is_some() {
+            log::info!("output {} plugged in while locked", output.id());
+            self.add_surface(qh, output);
+        }
+    }
+
+    fn update_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _output: wl_output::WlOutput,
+    ) {
+    }
+
+    fn output_destroyed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        output: wl_output::WlOutput,
+    ) {
+        let Some(index) = self.surfaces.iter().position(|surface| surface.output == output) else {
+            return;
+        };
+        log::info!("output {} unplugged, dropping its lock surface", output.id());
+        let surface = self.surfaces.remove(index);
+        if let (Some(gl), Some(egl)) = (&self.gl, surface.egl) {
+            gl.destroy_surface(egl);
+        }
+        // Dropping the last reference destroys the lock surface and its wl_surface
+        drop(surface.lock_surface);
+    }
+}
+
+impl Locker {
+    /// The name of the seat for the log, its id until the compositor named it.
+    fn seat_name(&self, seat: &wl_seat::WlSeat) -> String {
+        self.seat_state
+            .info(seat)
+            .and_then(|info| info.name)
+            .unwrap_or_else(|| seat.id().to_string())
+    }
+
+    fn release_keyboard(&mut self, seat: &wl_seat::WlSeat) {
+        if let Some(keyboard) = self.keyboards.remove(seat) {
+            keyboard.release();
+        }
+    }
+}
+
+impl SeatHandler for Locker {
+    fn seat_state(&mut self) -> &mut SeatState {
+        &mut self.seat_state
+    }
+
+    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, seat: wl_seat::WlSeat) {
+        log::info!("new seat {}", self.seat_name(&seat));
+    }
+
+    fn new_capability(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        seat: wl_seat::WlSeat,
+        capability: Capability,
+    ) {
+        log::info!("seat {} gained its {capability}", self.seat_name(&seat));
+        if capability == Capability::Keyboard && !self.keyboards.contains_key(&seat) {
+            let keyboard =
+                self.seat_state.get_keyboard(qh, &seat, None).expect("Failed to create keyboard");
+            self.keyboards.insert(seat, keyboard);
+        }
+    }
+
+    fn remove_capability(
+        &mut self,
+        _conn: &Connection,
+        _: &QueueHandle<Self>,
+        seat: wl_seat::WlSeat,
+        capability: Capability,
+    ) {
+        log::info!("seat {} lost its {capability}", self.seat_name(&seat));
+        if capability == Capability::Keyboard {
+            self.release_keyboard(&seat);
+        }
+    }
+
+    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, seat: wl_seat::WlSeat) {
+        log::info!("seat {} removed", self.seat_name(&seat));
+        self.release_keyboard(&seat);
+    }
+}
+
+impl KeyboardHandler for Locker {
+    fn enter(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &wl_keyboard::WlKeyboard,
+        _surface: &wl_surface::WlSurface,
+        _serial: u32,
+        _raw: &[u32],
+        _keysyms: &[Keysym],
+    ) {
+    }
+
+    fn leave(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &wl_keyboard::WlKeyboard,
+        _surface: &wl_surface::WlSurface,
+        _serial: u32,
+    ) {
+    }
+
+    fn press_key(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &wl_keyboard::WlKeyboard,
+        _serial: u32,
+        event: KeyEvent,
+    ) {
+        // A real lock screen would ask for a password here
+        if matches!(event.keysym, Keysym::Return | Keysym::KP_Enter) {
+            println!("unlocking");
+            self.unlock();
+        }
+    }
+
+    fn repeat_key(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &wl_keyboard::WlKeyboard,
+        _serial: u32,
+        _event: KeyEvent,
+    ) {
+    }
+
+    fn release_key(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &wl_keyboard::WlKeyboard,
+        _serial: u32,
+        _event: KeyEvent,
+    ) {
+    }
+
+    fn update_modifiers(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &wl_keyboard::WlKeyboard,
+        _serial: u32,
+        _modifiers: Modifiers,
+        _raw_modifiers: RawModifiers,
+        _layout: u32,
+    ) {
+    }
+}
+
+delegate_compositor!(Locker);
+delegate_output!(Locker);
+delegate_seat!(Locker);
+delegate_keyboard!(Locker);
+delegate_session_lock!(Locker);
+delegate_registry!(Locker);
+
+impl ProvidesRegistryState for Locker {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+    registry_handlers![OutputState, SeatState];
+}
@@ -0,0 +1,188 @@
+// Software rendering into wl_shm buffers, for machines without a working EGL stack.
+//
+// The window shows a static gradient with a bar sweeping across it. Only the columns the bar
+// moved through are repainted and damaged, which keeps the CPU work per frame small.
+
+use std::collections::{hash_map::Entry, HashMap};
+
+use smithay_client_toolkit::shell::{xdg::window::WindowConfigure, WaylandSurface};
+use wayland_app_common::{App, AppHandler, BaseApp, Options, ShmBuffers};
+use wayland_client::{backend::ObjectId, QueueHandle};
+
+fn main() {
+    let options = Options::from_args();
+    let mut logger = env_logger::Builder::from_default_env();
+    if let Some(level) = options.log_level {
+        logger.filter_level(level);
+    }
+    logger.init();
+
+    wayland_app_common::run_app(
+        options,
+        |options| {
+            // GitHub does not let projects use the `org.github` domain but the `io.github`
+            // domain is fine.
+            wayland_app_common::init_with_options(
+                "shm wayland window",
+                "io.github.smithay.client-toolkit.ShmExample",
+                ShmApp {
+                    windows: HashMap::new(),
+                },
+                options,
+            )
+        },
+        |app| {
+            // Buffers have to go before the pool and the window
+            drop(app.handler);
+            drop(app.windows);
+        },
+    );
+}
+
+struct ShmApp {
+    /// The buffers of each window, keyed by [`BaseApp::id`].
+    windows: HashMap<ObjectId, ShmWindow>,
+}
+
+struct ShmWindow {
+    /// Each with where the bar was painted into it when it was last used.
+    buffers: ShmBuffers<Option<Columns>>,
+    /// Columns of the bar in the buffer the compositor currently shows.
+    shown_bar: Option<Columns>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Columns {
+    x: u32,
+    width: u32,
+}
+
+impl Columns {
+    fn union(self, other: Columns) -> Columns {
+        let x = self.x.min(other.x);
+        let right = (self.x + self.width).max(other.x + other.width);
+        Columns { x, width: right - x }
+    }
+}
+
+impl AppHandler for ShmApp {
+    fn configure(
+        &mut self,
+        base: &mut BaseApp,
+        _qh: &QueueHandle<App<Self>>,
+        configure: WindowConfigure,
+        _serial: u32,
+    ) {
+        let (width, height) = base.configured_size(&configure);
+
+        // Create the pool on the first configure of the window
+        if let Entry::Vacant(entry) = self.windows.entry(base.id()) {
+            base.width = width;
+            base.height = height;
+            match ShmBuffers::new(base) {
+                Ok(buffers) => {
+                    entry.insert(ShmWindow {
+                        buffers,
+                        shown_bar: None,
+                    });
+                }
+                Err(err) => {
+                    eprintln!("no wl_shm pool: {err}");
+                    base.close = true;
+                }
+            }
+        } else if base.set_size(width, height) {
+            self.resize(base);
+        }
+    }
+
+    fn resize(&mut self, base: &mut BaseApp) {
+        // The buffers of the old size are dropped with the next draw, which has to damage all
+        // of the new one
+        if let Some(window) = self.windows.get_mut(&base.id()) {
+            window.shown_bar = None;
+        }
+    }
+
+    fn draw(&mut self, base: &mut BaseApp) {
+        let (width, height) = base.buffer_size();
+        // Dialogs and `--self-check` are all bar, in the clear color
+        let bar = if base.plain() {
+            Columns { x: 0, width }
+        } else {
+            Columns {
+                x: base.time / 4 % width,
+                width: (width / 10).max(1).min(width - base.time / 4 % width),
+            }
+        };
+
+        let Some(window) = self.windows.get_mut(&base.id()) else {
+            return;
+        };
+        let surface = base.window().wl_surface();
+        let frame = match window.buffers.acquire(width, height) {
+            Ok(frame) => frame,
+            Err(err) => {
+                // Commit anyway, so the frame callback request goes out and the window keeps
+                // trying
+                log::warn!("no buffer for window {}: {err}", base.id());
+                surface.commit();
+                return;
+            }
+        };
+
+        // Bring the buffer up to date, a new one has no gradient yet and an old one may be a
+        // couple of frames old
+        if frame.fresh {
+            paint_gradient(frame.canvas, width, height, Columns { x: 0, width });
+        } else if let Some(old_bar) = *frame.data {
+            paint_gradient(frame.canvas, width, height, old_bar);
+        }
+        let [red, green, blue] = base.clear_color();
+        paint_bar(frame.canvas, width, height, bar, [red, green, blue]);
+        *frame.data = Some(bar);
+
+        // Only the columns that differ from what is on screen need to be damaged
+        let damage = match window.shown_bar {
+            Some(shown_bar) => shown_bar.union(bar),
+            None => Columns { x: 0, width },
+        };
+        window.shown_bar = Some(bar);
+
+        surface.damage_buffer(damage.x as i32, 0, damage.width as i32, height as i32);
+        if let Err(err) = frame.buffer.attach_to(surface) {
+            log::warn!("failed to attach the buffer of window {}: {err}", base.id());
+        }
+        surface.commit();
+    }
+
+    fn window_closed(&mut self, base: &mut BaseApp) {
+        self.windows.remove(&base.id());
+    }
+}
+
+/// Paints the background gradient into the given columns.
+fn paint_gradient(canvas: &mut [u8], width: u32, height: u32, columns: Columns) {
+    for y in 0..height {
+        for x in columns.x..columns.x + columns.width {
+            let red = (x * 255 / width) as u8;
+            let green = (y * 255 / height) as u8;
+            put_pixel(canvas, width, x, y, [red, green, 128]);
+        }
+    }
+}
+
+fn paint_bar(canvas: &mut [u8], width: u32, height: u32, columns: Columns, color: [f32; 3]) {
+    let color = color.map(|c| (c * 255.0) as u8);
+    for y in 0..height {
+        for x in columns.x..columns.x + columns.width {
+            put_pixel(canvas, width, x, y, color);
+        }
+    }
+}
+
+fn put_pixel(canvas: &mut [u8], width: u32, x: u32, y: u32, [red, green, blue]: [u8; 3]) {
+    // Argb8888 is stored little endian, so the bytes are in BGRA order
+    let offset = ((y * width + x) * 4) as usize;
+    canvas[offset..offset + 4].copy_from_slice(&[blue, green, red, 255]);
+}
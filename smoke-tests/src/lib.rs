@@ -0,0 +1 @@
+//! Nothing to link against, the smoke tests of the examples live in `tests/smoke.rs`.
@@ -0,0 +1,220 @@
+//! Runs each example against a headless compositor for 60 frames and checks that it exits
+//! cleanly after drawing all of them. The compositor comes from `SMOKE_COMPOSITOR`, so the
+//! tests are ignored by default:
+//!
+//! ```text
+//! SMOKE_COMPOSITOR="weston --backend=headless" cargo test -p smoke-tests -- --ignored
+//! SMOKE_COMPOSITOR="env WLR_BACKENDS=headless sway" cargo test -p smoke-tests -- --ignored
+//! ```
+//!
+//! Every test starts a compositor of its own in a fresh `XDG_RUNTIME_DIR` and runs the example
+//! on the first socket showing up there. The layer-shell example needs wlr-layer-shell, which
+//! Weston doesn't have, the session-lock example needs ext-session-lock.
+//!
+//! Only [`unknown_argument`] runs without a compositor, the examples look at their arguments
+//! before connecting.
+
+use std::{
+    fs::{self, DirBuilder},
+    io::{BufRead, BufReader},
+    os::unix::fs::DirBuilderExt,
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How many frames the examples draw, they exit after the frame callback of the last one.
+const FRAMES: u64 = 60;
+
+/// How long the compositor may take to open its socket.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long an example may take for all of its frames, GL setup included.
+const RUN_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[test]
+#[ignore = "needs a headless compositor in SMOKE_COMPOSITOR"]
+fn glow_with_glutin() {
+    smoke_test("glow-with-glutin");
+}
+
+#[test]
+#[ignore = "needs a headless compositor in SMOKE_COMPOSITOR"]
+fn glow_with_wayland_egl() {
+    smoke_test("glow-with-wayland-egl");
+}
+
+#[test]
+#[ignore = "needs a headless compositor in SMOKE_COMPOSITOR"]
+fn wgpu_27() {
+    smoke_test("wgpu-27");
+}
+
+#[test]
+#[ignore = "needs a headless compositor in SMOKE_COMPOSITOR"]
+fn shm_example() {
+    smoke_test("shm-example");
+}
+
+#[test]
+#[ignore = "needs a headless compositor with wlr-layer-shell in SMOKE_COMPOSITOR"]
+fn layer_shell_example() {
+    smoke_test("layer-shell-example");
+}
+
+#[test]
+#[ignore = "needs a headless compositor with ext-session-lock in SMOKE_COMPOSITOR"]
+fn session_lock_example() {
+    smoke_test("session-lock-example");
+}
+
+#[test]
+fn unknown_argument() {
+    let binary = build("shm-example");
+    let status = Command::new(&binary)
+        .arg("--no-such-option")
+        .env_remove("WAYLAND_DISPLAY")
+        .stderr(Stdio::null())
+        .status()
+        .unwrap_or_else(|err| panic!("failed to run {}: {err}", binary.display()));
+    assert_eq!(status.code(), Some(2), "shm-example took an unknown argument, {status}");
+}
+
+/// Builds the example, runs it on a compositor of its own and checks its `frame <n> <time>`
+/// lines count up to [`FRAMES`] before it exits with success.
+fn smoke_test(example: &str) {
+    let binary = build(example);
+    let compositor = Compositor::start(example);
+
+    let mut child = Command::new(&binary)
+        .arg("--exit-after-frames")
+        .arg(FRAMES.to_string())
+        .env("XDG_RUNTIME_DIR", &compositor.runtime_dir)
+        .env("WAYLAND_DISPLAY", &compositor.socket)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|err| panic!("failed to run {}: {err}", binary.display()));
+    // Read along, the pipe would fill up before the example exits otherwise
+    let stdout = child.stdout.take().unwrap();
+    let reader = thread::spawn(move || {
+        BufReader::new(stdout).lines().map_while(Result::ok).collect::<Vec<String>>()
+    });
+
+    let status = wait_timeout(&mut child, RUN_TIMEOUT);
+    let lines = reader.join().unwrap();
+    let Some(status) = status else {
+        panic!("{example} didn't exit within {RUN_TIMEOUT:?}, {} frames", frames(&lines).len());
+    };
+    assert!(status.success(), "{example} exited with {status}");
+
+    let frames = frames(&lines);
+    let expected: Vec<u64> = (1..=FRAMES).collect();
+    assert_eq!(frames, expected, "{example} didn't report every frame once");
+}
+
+/// The numbers of the `frame <n> <time>` lines of the output.
+fn frames(lines: &[String]) -> Vec<u64> {
+    lines
+        .iter()
+        .filter_map(|line| {
+            let mut words = line.split_ascii_whitespace();
+            match (words.next(), words.next(), words.next()) {
+                (Some("frame"), Some(frame), Some(_)) => frame.parse().ok(),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Builds the example in the profile of the tests and returns the path of its binary, which
+/// ends up next to the directory the test binary is in.
+fn build(example: &str) -> PathBuf {
+    let test_binary = std::env::current_exe().unwrap();
+    let profile_dir = test_binary.parent().and_then(Path::parent).unwrap().to_path_buf();
+    let mut cargo = Command::new(env!("CARGO"));
+    cargo.args(["build", "--quiet", "--package", example, "--bin", example]);
+    if profile_dir.file_name().is_some_and(|name| name == "release") {
+        cargo.arg("--release");
+    }
+    let status = cargo.status().expect("failed to run cargo");
+    assert!(status.success(), "building {example} failed with {status}");
+    profile_dir.join(example)
+}
+
+/// Waits for the child to exit, killing it after the timeout.
+fn wait_timeout(child: &mut Child, timeout: Duration) -> Option<ExitStatus> {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        if let Some(status) = child.try_wait().unwrap() {
+            return Some(status);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    None
+}
+
+/// The compositor of a test, killed along with its runtime directory when dropped.
+struct Compositor {
+    child: Child,
+    runtime_dir: PathBuf,
+    /// The name of its socket in the runtime directory, for `WAYLAND_DISPLAY`.
+    socket: String,
+}
+
+impl Compositor {
+    fn start(example: &str) -> Compositor {
+        let command = std::env::var("SMOKE_COMPOSITOR")
+            .expect("SMOKE_COMPOSITOR needs the command of a headless compositor");
+        let mut words = command.split_ascii_whitespace();
+        let program = words.next().expect("SMOKE_COMPOSITOR is empty");
+
+        // Wayland wants the runtime directory to be private
+        let runtime_dir =
+            std::env::temp_dir().join(format!("smoke-{example}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&runtime_dir);
+        DirBuilder::new().mode(0o700).create(&runtime_dir).unwrap();
+
+        let child = Command::new(program)
+            .args(words)
+            .env("XDG_RUNTIME_DIR", &runtime_dir)
+            .env_remove("WAYLAND_DISPLAY")
+            .env_remove("DISPLAY")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap_or_else(|err| panic!("failed to start {command:?}: {err}"));
+        let mut compositor = Compositor { child, runtime_dir, socket: String::new() };
+
+        let start = Instant::now();
+        compositor.socket = loop {
+            if let Some(socket) = compositor.find_socket() {
+                break socket;
+            }
+            if let Some(status) = compositor.child.try_wait().unwrap() {
+                panic!("{command:?} exited with {status} before opening a socket");
+            }
+            assert!(start.elapsed() < STARTUP_TIMEOUT, "{command:?} opened no socket");
+            thread::sleep(Duration::from_millis(50));
+        };
+        compositor
+    }
+
+    /// A `wayland-*` socket in the runtime directory, not the lock file next to it.
+    fn find_socket(&self) -> Option<String> {
+        fs::read_dir(&self.runtime_dir)
+            .ok()?
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .find(|name| name.starts_with("wayland-") && !name.ends_with(".lock"))
+    }
+}
+
+impl Drop for Compositor {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.runtime_dir);
+    }
+}
@@ -0,0 +1,246 @@
+// Pretty-prints a trace of `--trace <file>`, or diffs two of them:
+//
+//     trace-dump run.jsonl
+//     trace-dump good.jsonl bad.jsonl
+//
+// `--only configure,frame` keeps only those events. The diff compares the events without their
+// `time` and the fields that differ from run to run anyway, the serials and the timestamps of
+// the compositor. `--ignore scale,output` leaves out more fields.
+
+use std::{fs, process::ExitCode};
+
+use serde_json::{Map, Value};
+
+/// Fields that differ between any two runs.
+const ALWAYS_IGNORED: [&str; 4] = ["time", "serial", "callback_time", "key_time"];
+
+/// Equal events shown around each change of a diff.
+const CONTEXT: usize = 3;
+
+struct Args {
+    files: Vec<String>,
+    only: Vec<String>,
+    ignore: Vec<String>,
+}
+
+/// An event of a trace.
+struct Event {
+    time: f64,
+    name: String,
+    /// The rest of the fields.
+    fields: Map<String, Value>,
+}
+
+fn main() -> ExitCode {
+    let Some(args) = parse_args() else {
+        eprintln!("usage: trace-dump [--only <events>] [--ignore <fields>] <trace> [<other>]");
+        return ExitCode::FAILURE;
+    };
+    let mut traces = Vec::new();
+    for file in &args.files {
+        match read_trace(file, &args.only) {
+            Ok(events) => traces.push(events),
+            Err(err) => {
+                eprintln!("can't read {file}: {err}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    match &traces[..] {
+        [events] => {
+            for event in events {
+                println!("{}", format_event(event, &[]));
+            }
+            ExitCode::SUCCESS
+        }
+        [old, new] => {
+            let ignore: Vec<&str> =
+                ALWAYS_IGNORED.into_iter().chain(args.ignore.iter().map(String::as_str)).collect();
+            let old_lines: Vec<String> = old.iter().map(|event| compared(event, &ignore)).collect();
+            let new_lines: Vec<String> = new.iter().map(|event| compared(event, &ignore)).collect();
+            let changes = diff(&old_lines, &new_lines);
+            print_diff(&changes, old, new, &ignore);
+            // Like diff(1), 1 when the traces differ
+            if changes.iter().all(|change| matches!(change, Change::Same(_))) {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::from(1)
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// One or two files, `--only` and `--ignore` with comma separated names.
+fn parse_args() -> Option<Args> {
+    let mut parsed = Args { files: Vec::new(), only: Vec::new(), ignore: Vec::new() };
+    let mut args = std::env::args().skip(1);
+    let list = |value: Option<String>| -> Option<Vec<String>> {
+        Some(value?.split(',').map(str::to_string).collect())
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--only" => parsed.only.extend(list(args.next())?),
+            "--ignore" => parsed.ignore.extend(list(args.next())?),
+            _ if arg.starts_with("--") => return None,
+            _ => parsed.files.push(arg),
+        }
+    }
+    (1..=2).contains(&parsed.files.len()).then_some(parsed)
+}
+
+/// The events of the file, only those named with `only` unless it is empty. Lines that aren't
+/// events are reported and skipped, e.g. the last one of an app that crashed mid-write.
+fn read_trace(file: &str, only: &[String]) -> Result<Vec<Event>, std::io::Error> {
+    let text = fs::read_to_string(file)?;
+    let mut events = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let Ok(Value::Object(mut fields)) = serde_json::from_str::<Value>(line) else {
+            eprintln!("{file}:{}: not an event, skipped", number + 1);
+            continue;
+        };
+        let time = fields.shift_remove("time").and_then(|time| time.as_f64()).unwrap_or(0.0);
+        let name = match fields.shift_remove("event") {
+            Some(Value::String(name)) => name,
+            _ => "?".to_string(),
+        };
+        if only.is_empty() || only.contains(&name) {
+            events.push(Event { time, name, fields });
+        }
+    }
+    Ok(events)
+}
+
+/// `  1.234567  configure  window=wl_surface@3 width=800 ...`, without the ignored fields.
+fn format_event(event: &Event, ignore: &[&str]) -> String {
+    format!("{:>10.6}  {}", event.time, compared(event, ignore))
+}
+
+/// The event as it is compared, without its time and the ignored fields.
+fn compared(event: &Event, ignore: &[&str]) -> String {
+    let mut line = event.name.clone();
+    for (name, value) in &event.fields {
+        if ignore.contains(&name.as_str()) {
+            continue;
+        }
+        match value {
+            // Strings read better without the quotes
+            Value::String(value) => line.push_str(&format!(" {name}={value}")),
+            value => line.push_str(&format!(" {name}={value}")),
+        }
+    }
+    line
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Change {
+    /// The index in the new trace.
+    Same(usize),
+    Removed(usize),
+    Added(usize),
+}
+
+/// The shortest edit script turning `old` into `new`, by Myers' algorithm. Only the furthest
+/// points of the diagonals each round reached are kept, which is little for traces that mostly
+/// agree.
+fn diff<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Change> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = n + m;
+    let offset = max + 1;
+    let at = |k: isize| (k + offset) as usize;
+    let mut furthest = vec![0; 2 * max as usize + 3];
+    // The furthest x on the diagonals -d..=d after each round d
+    let mut rounds: Vec<Vec<isize>> = Vec::new();
+    'rounds: for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && furthest[at(k - 1)] < furthest[at(k + 1)]) {
+                furthest[at(k + 1)]
+            } else {
+                furthest[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            furthest[at(k)] = x;
+            if x >= n && y >= m {
+                rounds.push(furthest[at(-d)..=at(d)].to_vec());
+                break 'rounds;
+            }
+        }
+        rounds.push(furthest[at(-d)..=at(d)].to_vec());
+    }
+
+    // Walk back from the end, each round took one edit and the equal events after it
+    let mut changes = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..rounds.len() as isize).rev() {
+        let previous = &rounds[d as usize - 1];
+        let reached = |k: isize| previous[(k + d - 1) as usize];
+        let k = x - y;
+        let previous_k = if k == -d || (k != d && reached(k - 1) < reached(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let previous_x = reached(previous_k);
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            changes.push(Change::Same(y as usize));
+        }
+        if x == previous_x {
+            y -= 1;
+            changes.push(Change::Added(y as usize));
+        } else {
+            x -= 1;
+            changes.push(Change::Removed(x as usize));
+        }
+    }
+    while x > 0 && y > 0 {
+        x -= 1;
+        y -= 1;
+        changes.push(Change::Same(y as usize));
+    }
+    changes.reverse();
+    changes
+}
+
+/// Prints the changes with [`CONTEXT`] equal events around them, longer runs of equal events
+/// are cut short.
+fn print_diff(changes: &[Change], old: &[Event], new: &[Event], ignore: &[&str]) {
+    let changed: Vec<usize> = changes
+        .iter()
+        .enumerate()
+        .filter(|(_, change)| !matches!(change, Change::Same(_)))
+        .map(|(index, _)| index)
+        .collect();
+    let near_change = |index: usize| {
+        changed.iter().any(|&changed| changed.abs_diff(index) <= CONTEXT)
+    };
+
+    let mut skipped = 0;
+    for (index, change) in changes.iter().enumerate() {
+        if let Change::Same(_) = change
+            && !near_change(index)
+        {
+            skipped += 1;
+            continue;
+        }
+        if skipped > 0 {
+            println!("  ... {skipped} equal events");
+            skipped = 0;
+        }
+        match *change {
+            Change::Same(new_index) => println!("  {}", format_event(&new[new_index], ignore)),
+            Change::Removed(old_index) => println!("- {}", format_event(&old[old_index], ignore)),
+            Change::Added(new_index) => println!("+ {}", format_event(&new[new_index], ignore)),
+        }
+    }
+    if skipped > 0 {
+        println!("  ... {skipped} equal events");
+    }
+}
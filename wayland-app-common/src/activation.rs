@@ -0,0 +1,55 @@
+//! xdg-activation, so that one instance can hand its focus over to another. Pressing A prints a
+//! token, a new instance started with it raises its window:
+//!
+//! ```text
+//! XDG_ACTIVATION_TOKEN=<token> cargo run --bin glow-with-wayland-egl
+//! ```
+
+use smithay_client_toolkit::{
+    activation::{ActivationHandler, RequestData},
+    shell::WaylandSurface,
+};
+use wayland_client::{backend::ObjectId, protocol::wl_seat, QueueHandle};
+
+use crate::{App, AppHandler};
+
+impl<H: AppHandler> App<H> {
+    /// Asks for a token that lets another client take the focus. The key press serial shows
+    /// the compositor that the user asked for it, without one the token may be useless.
+    pub(crate) fn request_activation_token(
+        &self,
+        qh: &QueueHandle<Self>,
+        id: &ObjectId,
+        seat: &wl_seat::WlSeat,
+        serial: u32,
+    ) {
+        let Some(activation) = &self.shared.activation else {
+            println!("activation tokens need xdg_activation_v1, which the compositor lacks");
+            return;
+        };
+        let Some(base) = self.windows.get(id) else {
+            return;
+        };
+
+        activation.request_token(
+            qh,
+            RequestData {
+                app_id: Some(self.shared.app_id.clone()),
+                seat_and_serial: Some((seat.clone(), serial)),
+                surface: Some(base.window.wl_surface().clone()),
+            },
+        );
+    }
+}
+
+impl<H: AppHandler> ActivationHandler for App<H> {
+    type RequestData = RequestData;
+
+    fn new_token(&mut self, token: String, data: &Self::RequestData) {
+        if self.take_focus_token(&token, data) {
+            return;
+        }
+        println!("activation token: {token}");
+        println!("start another instance with XDG_ACTIVATION_TOKEN={token} or --activate-token {token}");
+    }
+}
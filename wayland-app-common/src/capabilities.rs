@@ -0,0 +1,119 @@
+//! What the compositor offers, checked before anything is bound. A compositor lacking a global
+//! the examples can't do without, or having only an old version of it, gets a table of what is
+//! there and what isn't rather than a panic about the first missing one.
+
+use wayland_client::globals::GlobalList;
+
+/// A global an app binds, with the least version it works with.
+pub struct Requirement {
+    pub interface: &'static str,
+    pub version: u32,
+    /// Whether the app can't start without it, the others only turn features off.
+    pub required: bool,
+    /// What it is for, shown in the table.
+    pub purpose: &'static str,
+}
+
+impl Requirement {
+    /// A global the app can't start without, in at least this version.
+    pub const fn required(
+        interface: &'static str,
+        version: u32,
+        purpose: &'static str,
+    ) -> Requirement {
+        Requirement { interface, version, required: true, purpose }
+    }
+
+    /// A global that turns on a feature when it is there, in any version.
+    pub const fn optional(interface: &'static str, purpose: &'static str) -> Requirement {
+        Requirement { interface, version: 1, required: false, purpose }
+    }
+}
+
+/// The globals of the xdg-shell examples.
+pub const REQUIREMENTS: &[Requirement] = {
+    use Requirement as R;
+    &[
+        R::required("wl_compositor", 5, "surfaces, buffer scale events"),
+        R::required("xdg_wm_base", 1, "windows"),
+        R::required("wl_shm", 1, "cursors and client-side decorations"),
+        R::required("wl_seat", 1, "input"),
+        R::optional("wl_subcompositor", "subsurfaces of the decorations"),
+        R::optional("zxdg_decoration_manager_v1", "server-side decorations"),
+        R::optional("xdg_activation_v1", "activating windows"),
+        R::optional("xdg_wm_dialog_v1", "modal dialogs"),
+        R::optional("zxdg_exporter_v2", "--export"),
+        R::optional("zxdg_importer_v2", "--import"),
+        R::optional("ext_image_copy_capture_manager_v1", "--self-check"),
+        R::optional("ext_output_image_capture_source_manager_v1", "--self-check"),
+        R::optional("zwlr_screencopy_manager_v1", "--self-check without the two above"),
+        R::optional("wp_viewporter", "the fixed resolution mode, --solid-background"),
+        R::optional("wp_single_pixel_buffer_manager_v1", "--solid-background"),
+        R::optional("wp_fractional_scale_manager_v1", "fractional scaling"),
+        R::optional("zwp_linux_dmabuf_v1", "--dmabuf-info and the GPU choice"),
+        R::optional("zwp_idle_inhibit_manager_v1", "keeping the screen on"),
+        R::optional("wp_content_type_manager_v1", "--content-type"),
+        R::optional("wp_tearing_control_manager_v1", "tearing with T"),
+        R::optional("wp_presentation", "presentation feedback"),
+        R::optional("wp_linux_drm_syncobj_manager_v1", "--explicit-sync"),
+        R::optional("wl_data_device_manager", "the clipboard, drag and drop"),
+        R::optional("zwp_primary_selection_device_manager_v1", "the primary selection"),
+        R::optional("zwp_text_input_manager_v3", "input methods"),
+        R::optional("zwp_keyboard_shortcuts_inhibit_manager_v1", "capturing shortcuts with F10"),
+        R::optional("zwp_pointer_constraints_v1", "mouse look"),
+        R::optional("zwp_relative_pointer_manager_v1", "mouse look"),
+        R::optional("zwp_pointer_gestures_v1", "touchpad gestures"),
+        R::optional("zwp_tablet_manager_v2", "drawing with a tablet"),
+    ]
+};
+
+/// Checks the globals against the requirements. When a required one is missing or too old
+/// this prints the table of all of them along with the socket of the compositor and exits with
+/// status 1, otherwise the table goes to the debug log.
+pub fn check_globals(globals: &GlobalList, requirements: &[Requirement]) {
+    let offered = globals.contents().clone_list();
+    let mut lacking = false;
+    let mut table = String::new();
+    for requirement in requirements {
+        let version = offered
+            .iter()
+            .filter(|global| global.interface == requirement.interface)
+            .map(|global| global.version)
+            .max();
+        let (found, status) = match (version, requirement.required) {
+            (Some(version), true) if version >= requirement.version => {
+                (version.to_string(), "ok".to_string())
+            }
+            (Some(version), true) => {
+                lacking = true;
+                (version.to_string(), format!("too old, needs {}", requirement.version))
+            }
+            (None, true) => {
+                lacking = true;
+                ("-".to_string(), "missing".to_string())
+            }
+            (Some(version), false) => (version.to_string(), "available: yes".to_string()),
+            (None, false) => ("-".to_string(), "available: no".to_string()),
+        };
+        table.push_str(&format!(
+            "  {:<40} {:>7}  {:<20} {}\n",
+            requirement.interface, found, status, requirement.purpose
+        ));
+    }
+    let header = format!("  {:<40} {:>7}  {:<20} {}\n", "global", "version", "status", "for");
+
+    if lacking {
+        eprintln!("the compositor on {} lacks globals the app needs:\n", socket_name());
+        eprint!("{header}{table}");
+        std::process::exit(1);
+    }
+    log::debug!("globals of the compositor on {}:\n{header}{table}", socket_name());
+}
+
+/// Where the connection went, as `connect_to_env` picks it.
+fn socket_name() -> String {
+    if let Ok(socket) = std::env::var("WAYLAND_SOCKET") {
+        return format!("the inherited socket {socket}");
+    }
+    std::env::var("WAYLAND_DISPLAY").unwrap_or_else(|_| "wayland-0".to_string())
+}
@@ -0,0 +1,246 @@
+//! Copy and paste of the clear color through the clipboard. Ctrl+C offers the color of the
+//! focused window as text, e.g. `#3366ff`, Ctrl+V parses the clipboard text as a color and
+//! keeps the window at it.
+
+use std::io::{ErrorKind, Read, Write};
+
+use calloop::PostAction;
+use smithay_client_toolkit::data_device_manager::{
+    data_source::DataSourceHandler, ReadPipe, WritePipe,
+};
+use wayland_client::{
+    backend::ObjectId,
+    protocol::{wl_data_device_manager::DndAction, wl_data_source::WlDataSource, wl_seat},
+    Connection, QueueHandle,
+};
+
+use crate::{App, AppHandler};
+
+/// The text types we offer and accept, the first one is preferred.
+pub(crate) const TEXT_MIME_TYPES: [&str; 2] = ["text/plain;charset=utf-8", "text/plain"];
+
+/// More than any color needs, a longer selection is not read any further.
+const MAX_PASTE_LEN: usize = 1024;
+
+impl<H: AppHandler> App<H> {
+    /// Puts the clear color of the window on the clipboard of the seat. The source stays ours
+    /// until another client sets the selection.
+    pub(crate) fn copy_color(
+        &mut self,
+        qh: &QueueHandle<Self>,
+        id: &ObjectId,
+        seat: &wl_seat::WlSeat,
+        serial: u32,
+    ) {
+        let shared = &mut self.shared;
+        let device = shared.seats.get(seat).and_then(|seat| seat.data_device.as_ref());
+        let (Some(manager), Some(device)) = (&shared.data_device_manager, device) else {
+            println!("copying needs wl_data_device_manager, which the compositor lacks");
+            return;
+        };
+        let Some(base) = self.windows.get(id) else {
+            return;
+        };
+
+        let text = format_color(base.clear_color());
+        let source = manager.create_copy_paste_source(qh, TEXT_MIME_TYPES);
+        source.set_selection(device, serial);
+        println!("copied {text}");
+        // Replacing the old source destroys it, the compositor cancels it anyway
+        shared.copy_source = Some((source, text));
+    }
+
+    /// Reads the clipboard of the seat and, when it holds a color, keeps the window at it. The
+    /// read goes through the event loop, a slow source must not hold up the rendering.
+    pub(crate) fn paste_color(&mut self, id: &ObjectId, seat: &wl_seat::WlSeat) {
+        let Some(device) = self.shared.seats.get(seat).and_then(|seat| seat.data_device.as_ref())
+        else {
+            println!("pasting needs wl_data_device_manager, which the compositor lacks");
+            return;
+        };
+        let Some(offer) = device.data().selection_offer() else {
+            println!("nothing to paste, the clipboard is empty");
+            return;
+        };
+        let Some(mime_type) = offer.with_mime_types(text_mime_type) else {
+            println!("nothing to paste, the clipboard holds no text");
+            return;
+        };
+        if !self.can_read_pipes() {
+            return;
+        }
+
+        match offer.receive(mime_type.to_string()) {
+            Ok(read_pipe) => self.read_pasted_color(id, read_pipe, "clipboard"),
+            Err(err) => log::warn!("failed to receive the selection: {err}"),
+        }
+    }
+
+    /// Whether pipes can be read without blocking, i.e. the calloop loop is running.
+    pub(crate) fn can_read_pipes(&self) -> bool {
+        if self.loop_handle.is_none() {
+            // A blocking read would hang when pasting our own color, we'd never get to send it
+            println!("pasting needs the calloop event loop, it's not available with --poll-loop");
+        }
+        self.loop_handle.is_some()
+    }
+
+    /// Reads the text from the pipe as it arrives and applies it as the color of the window.
+    /// `from` names the selection for messages.
+    pub(crate) fn read_pasted_color(&self, id: &ObjectId, read_pipe: ReadPipe, from: &'static str) {
+        let Some(loop_handle) = &self.loop_handle else {
+            return;
+        };
+
+        let id = id.clone();
+        let mut text = Vec::new();
+        let result = loop_handle.insert_source(read_pipe, move |_, file, app| {
+            let mut buffer = [0; 256];
+            // Level triggered, so a single read per wake up never blocks
+            match unsafe { file.get_mut() }.read(&mut buffer) {
+                Ok(0) => {
+                    app.apply_pasted(&id, &text, from);
+                    PostAction::Remove
+                }
+                Ok(len) if text.len() + len > MAX_PASTE_LEN => {
+                    println!("{from} text is too long to be a color");
+                    PostAction::Remove
+                }
+                Ok(len) => {
+                    text.extend_from_slice(&buffer[..len]);
+                    PostAction::Continue
+                }
+                Err(err) if err.kind() == ErrorKind::Interrupted => PostAction::Continue,
+                Err(err) => {
+                    log::warn!("failed to read the {from}: {err}");
+                    PostAction::Remove
+                }
+            }
+        });
+        if let Err(err) = result {
+            log::warn!("failed to watch the {from} pipe: {err}");
+        }
+    }
+
+    fn apply_pasted(&mut self, id: &ObjectId, text: &[u8], from: &str) {
+        let text = String::from_utf8_lossy(text);
+        let Some(color) = parse_color(&text) else {
+            println!("{from} text {:?} is not a color", text.trim());
+            return;
+        };
+        // The window may have been closed while the paste was underway
+        if let Some(base) = self.windows.get_mut(id) {
+            println!("pasted {} from the {from}", format_color(color));
+            base.pasted_color = Some(color);
+        }
+    }
+}
+
+/// The text type to ask for out of the offered ones, if there is one we take.
+pub(crate) fn text_mime_type(offered: &[String]) -> Option<&'static str> {
+    TEXT_MIME_TYPES
+        .into_iter()
+        .find(|mime_type| offered.iter().any(|offered| offered == mime_type))
+}
+
+/// Formats the color as `#rrggbb`.
+pub(crate) fn format_color(color: [f32; 3]) -> String {
+    let [red, green, blue] = color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+    format!("#{red:02x}{green:02x}{blue:02x}")
+}
+
+/// Parses `#rrggbb` or `#rgb`, the `#` is optional.
+fn parse_color(text: &str) -> Option<[f32; 3]> {
+    let text = text.trim();
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |digits: &str| u8::from_str_radix(digits, 16).unwrap_or_default();
+    let rgb = match hex.len() {
+        6 => [&hex[0..2], &hex[2..4], &hex[4..6]].map(channel),
+        // Every digit doubled, #36f is #3366ff
+        3 => [&hex[0..1], &hex[1..2], &hex[2..3]].map(|digit| channel(digit) * 17),
+        _ => return None,
+    };
+    Some(rgb.map(|c| c as f32 / 255.0))
+}
+
+impl<H: AppHandler> DataSourceHandler for App<H> {
+    fn accept_mime(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _source: &WlDataSource,
+        _mime: Option<String>,
+    ) {
+    }
+
+    fn send_request(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        source: &WlDataSource,
+        mime: String,
+        mut fd: WritePipe,
+    ) {
+        let copied = self
+            .shared
+            .copy_source
+            .as_ref()
+            .filter(|(copied, _)| copied.inner() == source)
+            .map(|(_, text)| text.as_str());
+        let dragged = self.shared.drag.as_ref().and_then(|drag| drag.text_for(source));
+        let Some(text) = copied.or(dragged) else {
+            return;
+        };
+        if !TEXT_MIME_TYPES.contains(&mime.as_str()) {
+            return;
+        }
+
+        // A color fits in the pipe buffer many times over, the write doesn't block. The reader
+        // sees the end of it when the pipe is dropped.
+        if let Err(err) = fd.write_all(text.as_bytes()) {
+            log::warn!("failed to send the selection: {err}");
+        }
+    }
+
+    fn cancelled(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, source: &WlDataSource) {
+        // The drop was refused, or the drag didn't end on a target at all
+        if self.end_drag(source) {
+            println!("drag cancelled");
+            return;
+        }
+
+        // Another client took over the clipboard
+        if self
+            .shared
+            .copy_source
+            .as_ref()
+            .is_some_and(|(copied, _)| copied.inner() == source)
+        {
+            self.shared.copy_source = None;
+        }
+    }
+
+    fn dnd_dropped(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _source: &WlDataSource) {
+        // The source has to stay until the target is done reading
+        log::info!("dropped, waiting for the target to finish");
+    }
+
+    fn dnd_finished(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, source: &WlDataSource) {
+        if self.end_drag(source) {
+            println!("drag finished");
+        }
+    }
+
+    fn action(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _source: &WlDataSource,
+        action: DndAction,
+    ) {
+        log::debug!("drag action is now {action:?}");
+    }
+}
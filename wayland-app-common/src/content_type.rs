@@ -0,0 +1,58 @@
+//! The content type hint of `--content-type`. It tells the compositor what the window shows,
+//! e.g. that it is a game, so the compositor can pick variable refresh rate or skip its own
+//! smoothing of the frame timing.
+
+use smithay_client_toolkit::{
+    reexports::protocols::wp::content_type::v1::client::{
+        wp_content_type_manager_v1::WpContentTypeManagerV1,
+        wp_content_type_v1::{self, WpContentTypeV1},
+    },
+    shell::WaylandSurface,
+};
+use wayland_client::{delegate_noop, globals::GlobalList, QueueHandle};
+
+use crate::{App, AppHandler};
+
+/// The values `--content-type` takes.
+pub(crate) const CONTENT_TYPES: &str = "game, video, photo or none";
+
+/// Parses a value of `--content-type`.
+pub(crate) fn parse_content_type(value: &str) -> Option<wp_content_type_v1::Type> {
+    match value {
+        "none" => Some(wp_content_type_v1::Type::None),
+        "photo" => Some(wp_content_type_v1::Type::Photo),
+        "video" => Some(wp_content_type_v1::Type::Video),
+        "game" => Some(wp_content_type_v1::Type::Game),
+        _ => None,
+    }
+}
+
+/// Binds the content type manager when there is a hint to give, and the compositor has it.
+pub(crate) fn bind_manager<H: AppHandler>(
+    globals: &GlobalList,
+    qh: &QueueHandle<App<H>>,
+    content_type: Option<wp_content_type_v1::Type>,
+) -> Option<WpContentTypeManagerV1> {
+    content_type?;
+    let manager = globals.bind::<WpContentTypeManagerV1, _, _>(qh, 1..=1, ()).ok();
+    if manager.is_none() {
+        log::info!("wp_content_type_manager_v1 not available, ignoring --content-type");
+    }
+    manager
+}
+
+/// Gives the window surface the content type hint. Like the other surface state it takes
+/// effect with the next commit, the first one for a new window.
+pub(crate) fn set_content_type<H: AppHandler>(
+    manager: &WpContentTypeManagerV1,
+    qh: &QueueHandle<App<H>>,
+    surface: &impl WaylandSurface,
+    content_type: wp_content_type_v1::Type,
+) -> WpContentTypeV1 {
+    let object = manager.get_surface_content_type(surface.wl_surface(), qh, ());
+    object.set_content_type(content_type);
+    object
+}
+
+delegate_noop!(@<H: AppHandler> App<H>: ignore WpContentTypeManagerV1);
+delegate_noop!(@<H: AppHandler> App<H>: ignore WpContentTypeV1);
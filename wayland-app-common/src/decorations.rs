@@ -0,0 +1,176 @@
+use smithay_client_toolkit::{
+    reexports::{csd_frame::WindowState, protocols::xdg::shell::client::xdg_toplevel::ResizeEdge},
+    seat::pointer::CursorIcon,
+    shell::xdg::window::DecorationMode,
+};
+
+use wayland_client::backend::ObjectId;
+
+use crate::{App, AppHandler, BaseApp};
+
+/// Height of the client-side title bar in logical pixels.
+pub const TITLE_BAR_HEIGHT: u32 = 24;
+
+/// Width of the band along the window edges that starts an interactive resize, in logical
+/// pixels.
+pub const RESIZE_BORDER: u32 = 8;
+
+pub(crate) const TITLE_BAR_COLOR: [f32; 3] = [0.2, 0.2, 0.2];
+pub(crate) const CLOSE_BUTTON_COLOR: [f32; 3] = [0.8, 0.2, 0.2];
+
+/// What a point of the client-side title bar does when clicked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TitleBarHit {
+    Close,
+    Move,
+}
+
+impl BaseApp {
+    /// Whether the compositor left drawing the window decorations to us.
+    pub fn client_side_decorations(&self) -> bool {
+        self.decoration_mode == Some(DecorationMode::Client)
+    }
+
+    /// Height of the title bar we draw ourselves, zero with server-side decorations.
+    pub fn title_bar_height(&self) -> u32 {
+        if self.client_side_decorations() {
+            TITLE_BAR_HEIGHT.min(self.height)
+        } else {
+            0
+        }
+    }
+
+    /// The part of the window the content goes into, as `[x, y, width, height]` in logical
+    /// coordinates. That is below the title bar, and with `--fixed-aspect` the largest centered
+    /// rectangle of the ratio, the clear color shows in the bars around it. xdg-shell has no
+    /// way to make the compositor keep the ratio of the window itself.
+    pub fn content_area(&self) -> [u32; 4] {
+        let bar = self.title_bar_height();
+        let (width, height) = (self.width, self.height - bar);
+        let Some((ratio_width, ratio_height)) = self.fixed_aspect else {
+            return [0, bar, width, height];
+        };
+
+        // Computed in u64, the products of sizes and ratios may not fit in u32
+        let (w, h) = (u64::from(width), u64::from(height));
+        let (rw, rh) = (u64::from(ratio_width), u64::from(ratio_height));
+        let (fit_width, fit_height) = if w * rh > h * rw {
+            // Wider than the ratio, bars left and right
+            ((h * rw / rh) as u32, height)
+        } else {
+            (width, (w * rh / rw) as u32)
+        };
+        [
+            (width - fit_width) / 2,
+            bar + (height - fit_height) / 2,
+            fit_width,
+            fit_height,
+        ]
+    }
+
+    /// [`Self::content_area`] in GL buffer coordinates. This is the viewport the renderers draw
+    /// their content into.
+    pub fn content_rect(&self) -> [i32; 4] {
+        let [x, y, width, height] = self.content_area();
+        self.buffer_rect(x, y, width, height)
+    }
+
+    /// The rectangles making up the client-side decorations with their fill colors, in GL buffer
+    /// coordinates. Empty with server-side decorations.
+    pub fn decoration_rects(&self) -> Vec<([i32; 4], [f32; 3])> {
+        let bar = self.title_bar_height();
+        if bar == 0 {
+            return Vec::new();
+        }
+
+        let close = bar.min(self.width);
+        vec![
+            (self.buffer_rect(0, 0, self.width, bar), TITLE_BAR_COLOR),
+            (self.buffer_rect(self.width - close, 0, close, bar), CLOSE_BUTTON_COLOR),
+        ]
+    }
+
+    /// What the client-side title bar has at the given surface-local position, if anything.
+    pub fn title_bar_hit(&self, x: f64, y: f64) -> Option<TitleBarHit> {
+        let bar = self.title_bar_height() as f64;
+        if y < 0.0 || y >= bar {
+            None
+        } else if x >= self.width as f64 - bar {
+            Some(TitleBarHit::Close)
+        } else {
+            Some(TitleBarHit::Move)
+        }
+    }
+
+    /// The edge an interactive resize would grab at the given surface-local position, if any.
+    ///
+    /// Pointer positions are surface-local and so already in logical pixels, which keeps the
+    /// border [`RESIZE_BORDER`] logical pixels wide at any buffer scale. Within the border of two
+    /// edges, i.e. in a corner, the diagonal edge is returned.
+    pub fn resize_edge(&self, x: f64, y: f64) -> Option<ResizeEdge> {
+        // The compositor handles resizing server-side decorated windows, and windows that fill
+        // the output or a tile can't be resized by the user.
+        if !self.client_side_decorations()
+            || self.window_state.intersects(
+                WindowState::MAXIMIZED | WindowState::FULLSCREEN | WindowState::TILED,
+            )
+        {
+            return None;
+        }
+
+        let border = RESIZE_BORDER as f64;
+        let left = x < border;
+        let right = x >= self.width as f64 - border;
+        let top = y < border;
+        let bottom = y >= self.height as f64 - border;
+
+        match (left, right, top, bottom) {
+            (true, _, true, _) => Some(ResizeEdge::TopLeft),
+            (_, true, true, _) => Some(ResizeEdge::TopRight),
+            (true, _, _, true) => Some(ResizeEdge::BottomLeft),
+            (_, true, _, true) => Some(ResizeEdge::BottomRight),
+            (true, ..) => Some(ResizeEdge::Left),
+            (_, true, ..) => Some(ResizeEdge::Right),
+            (_, _, true, _) => Some(ResizeEdge::Top),
+            (.., true) => Some(ResizeEdge::Bottom),
+            _ => None,
+        }
+    }
+}
+
+/// The cursor shown while the pointer is over the given resize edge.
+pub(crate) fn resize_cursor(edge: Option<ResizeEdge>) -> CursorIcon {
+    match edge {
+        Some(ResizeEdge::Top) => CursorIcon::NResize,
+        Some(ResizeEdge::Bottom) => CursorIcon::SResize,
+        Some(ResizeEdge::Left) => CursorIcon::WResize,
+        Some(ResizeEdge::Right) => CursorIcon::EResize,
+        Some(ResizeEdge::TopLeft) => CursorIcon::NwResize,
+        Some(ResizeEdge::TopRight) => CursorIcon::NeResize,
+        Some(ResizeEdge::BottomLeft) => CursorIcon::SwResize,
+        Some(ResizeEdge::BottomRight) => CursorIcon::SeResize,
+        _ => CursorIcon::Default,
+    }
+}
+
+impl<H: AppHandler> App<H> {
+    /// Asks the compositor to switch the window between server-side and client-side
+    /// decorations. The mode changes with the configure that answers it, if at all.
+    pub fn toggle_decorations(&self, id: &ObjectId) {
+        let Some(base) = self.windows.get(id) else {
+            return;
+        };
+        if !self.shared.decoration_manager {
+            println!("switching decorations needs zxdg_decoration_manager_v1, which the compositor lacks");
+            return;
+        }
+
+        let mode = if base.client_side_decorations() {
+            DecorationMode::Server
+        } else {
+            DecorationMode::Client
+        };
+        println!("requesting {mode:?} decorations");
+        base.window.request_decoration_mode(Some(mode));
+    }
+}
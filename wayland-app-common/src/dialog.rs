@@ -0,0 +1,138 @@
+//! Dialogs, Shift+O opens a child window of the focused window. The compositor keeps it above
+//! its parent, it is modal when the compositor has xdg-dialog-v1 and closes along with the
+//! parent. The examples fill it with a plain color instead of drawing their scene.
+//!
+//! Closing a dialog hands the focus back to the parent with an xdg-activation token asked for
+//! when the dialog opened, while the key press that opened it still counts as user intent.
+
+use smithay_client_toolkit::{
+    activation::RequestData,
+    reexports::protocols::xdg::dialog::v1::client::{
+        xdg_dialog_v1::XdgDialogV1, xdg_wm_dialog_v1::XdgWmDialogV1,
+    },
+    shell::WaylandSurface,
+};
+use wayland_client::{
+    backend::ObjectId, delegate_noop, globals::GlobalList, protocol::wl_seat, Proxy, QueueHandle,
+};
+
+use crate::{title::limit_title, App, AppHandler, BaseApp};
+
+/// The color dialogs are filled with.
+pub(crate) const DIALOG_COLOR: [f32; 3] = [0.85, 0.84, 0.8];
+
+/// Binds the dialog manager, without it dialogs are only children of their parent.
+pub(crate) fn bind_manager<H: AppHandler>(
+    globals: &GlobalList,
+    qh: &QueueHandle<App<H>>,
+) -> Option<XdgWmDialogV1> {
+    globals.bind::<XdgWmDialogV1, _, _>(qh, 1..=1, ()).ok()
+}
+
+impl BaseApp {
+    /// Whether the window is a dialog of another window, see [`Self::parent`].
+    pub fn is_dialog(&self) -> bool {
+        self.parent.is_some()
+    }
+
+    /// The window this one is a dialog of.
+    pub fn parent(&self) -> Option<&ObjectId> {
+        self.parent.as_ref()
+    }
+}
+
+impl<H: AppHandler> App<H> {
+    /// Opens a dialog of the window. The key press serial lets us ask for a token to focus the
+    /// parent again once the dialog closes.
+    pub(crate) fn open_dialog(
+        &mut self,
+        qh: &QueueHandle<Self>,
+        parent: &ObjectId,
+        seat: &wl_seat::WlSeat,
+        serial: u32,
+    ) {
+        let Some(parent_window) = self.windows.get(parent).map(|base| base.window.clone()) else {
+            return;
+        };
+        let id = self.open_window(qh);
+        let base = self.windows.get_mut(&id).unwrap();
+        // Like the rest of the toplevel state this only matters once the window maps
+        base.window.set_parent(Some(&parent_window));
+        base.title = limit_title(format!("{} dialog", self.shared.title));
+        base.window.set_title(base.title.clone());
+        base.parent = Some(parent.clone());
+        // The fill doesn't change, there is nothing to animate
+        base.paused = true;
+
+        match &self.shared.wm_dialog {
+            Some(wm_dialog) => {
+                let dialog = wm_dialog.get_xdg_dialog(base.window.xdg_toplevel(), qh, ());
+                dialog.set_modal();
+                base.dialog = Some(dialog);
+            }
+            None => log::info!("xdg_wm_dialog_v1 not available, the dialog is not modal"),
+        }
+        if let Some(activation) = &self.shared.activation {
+            activation.request_token(
+                qh,
+                RequestData {
+                    app_id: Some(self.shared.app_id.clone()),
+                    seat_and_serial: Some((seat.clone(), serial)),
+                    surface: Some(parent_window.wl_surface().clone()),
+                },
+            );
+            self.shared.focus_token_requests.push_back((id.clone(), parent.clone()));
+        }
+        println!("opened dialog {id} of window {parent}");
+    }
+
+    /// Takes a token asked for by [`Self::open_dialog`], false when the token is for something
+    /// else. Tokens come in the order they were asked for, so it goes to the dialog of the
+    /// parent that asked first. A dialog that closed meanwhile still takes its token.
+    pub(crate) fn take_focus_token(&mut self, token: &str, data: &RequestData) -> bool {
+        let Some(surface) = &data.surface else {
+            return false;
+        };
+        let parent = surface.id();
+        let requests = &mut self.shared.focus_token_requests;
+        let Some(index) = requests.iter().position(|(_, waiting)| *waiting == parent) else {
+            return false;
+        };
+        let (dialog, _) = requests.remove(index).unwrap();
+        if let Some(dialog) = self.windows.get_mut(&dialog) {
+            dialog.focus_token = Some(token.to_string());
+        }
+        true
+    }
+
+    /// Closes the dialogs of the window, before the window itself goes.
+    pub(crate) fn close_dialogs_of(&mut self, id: &ObjectId) {
+        let dialogs: Vec<ObjectId> = self
+            .windows
+            .iter()
+            .filter(|(_, base)| base.parent.as_ref() == Some(id))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for dialog in dialogs {
+            self.close_window(&dialog);
+        }
+    }
+
+    /// Focuses the parent of a dialog that is closing, if the dialog had the focus.
+    pub(crate) fn refocus_parent(&self, dialog: &mut BaseApp) {
+        let (Some(parent), Some(token)) = (&dialog.parent, dialog.focus_token.take()) else {
+            return;
+        };
+        if !self.shared.has_keyboard_focus(&dialog.id()) {
+            return;
+        }
+        if let Some(activation) = &self.shared.activation
+            && let Some(parent) = self.windows.get(parent)
+        {
+            activation.activate::<Self>(parent.window.wl_surface(), token);
+        }
+    }
+}
+
+delegate_noop!(@<H: AppHandler> App<H>: ignore XdgWmDialogV1);
+delegate_noop!(@<H: AppHandler> App<H>: ignore XdgDialogV1);
@@ -0,0 +1,252 @@
+//! linux-dmabuf feedback, which formats and modifiers the compositor takes buffers in and which
+//! GPU it wants them from. The GL drivers hand their buffers over this way, when that goes wrong
+//! they fall back to slower copies or software rendering without saying why.
+//!
+//! Every window asks for the feedback of its surface and logs it, `RUST_LOG=debug` adds the
+//! format/modifier pairs. `--dmabuf-info` prints it all for the first window, has the renderer
+//! compare it with what it can import and exits.
+
+use std::{collections::BTreeMap, os::unix::fs::MetadataExt};
+
+use smithay_client_toolkit::{
+    dmabuf::{DmabufFeedback, DmabufFormat, DmabufHandler, DmabufState},
+    reexports::protocols::wp::linux_dmabuf::zv1::client::{
+        zwp_linux_buffer_params_v1::ZwpLinuxBufferParamsV1,
+        zwp_linux_dmabuf_feedback_v1::{TrancheFlags, ZwpLinuxDmabufFeedbackV1},
+    },
+};
+use wayland_client::{
+    backend::ObjectId, globals::GlobalList, protocol::wl_buffer::WlBuffer, Connection,
+    QueueHandle, WEnum,
+};
+
+use crate::{App, AppHandler, BaseApp};
+
+/// `DRM_FORMAT_MOD_INVALID`, the driver picks the layout and doesn't say which.
+const MODIFIER_INVALID: u64 = 0x00ff_ffff_ffff_ffff;
+
+/// Format/modifier pairs grouped by format, as the renderers get them with `--dmabuf-info`.
+pub type DmabufFormats = BTreeMap<u32, Vec<u64>>;
+
+/// The fourcc code of a DRM format as text, e.g. `XR24`.
+pub fn format_name(format: u32) -> String {
+    let code: String = format
+        .to_le_bytes()
+        .iter()
+        .map(|&byte| if byte.is_ascii_graphic() { byte as char } else { '?' })
+        .collect();
+    format!("{code} ({format:#010x})")
+}
+
+/// A DRM format modifier with the vendor it belongs to, e.g. `INTEL 0x2`.
+pub fn modifier_name(modifier: u64) -> String {
+    let vendor = match modifier >> 56 {
+        _ if modifier == MODIFIER_INVALID => return "INVALID (implicit)".to_string(),
+        _ if modifier == 0 => return "LINEAR".to_string(),
+        0 => "NONE",
+        1 => "INTEL",
+        2 => "AMD",
+        3 => "NVIDIA",
+        4 => "SAMSUNG",
+        5 => "QCOM",
+        6 => "VIVANTE",
+        7 => "BROADCOM",
+        8 => "ARM",
+        9 => "ALLWINNER",
+        10 => "AMLOGIC",
+        _ => "unknown vendor",
+    };
+    format!("{vendor} {:#x}", modifier & MODIFIER_INVALID)
+}
+
+/// The node of the device under /dev/dri along with its numbers, e.g.
+/// `/dev/dri/renderD128 (226:128)`.
+pub fn device_name(device: u64) -> String {
+    // The glibc encoding of dev_t
+    let major = ((device >> 32) & 0xffff_f000) | ((device >> 8) & 0xfff);
+    let minor = ((device >> 12) & 0xffff_ff00) | (device & 0xff);
+    let node = std::fs::read_dir("/dev/dri").ok().and_then(|entries| {
+        entries
+            .flatten()
+            .find(|entry| entry.metadata().is_ok_and(|metadata| metadata.rdev() == device))
+    });
+    match node {
+        Some(node) => format!("{} ({major}:{minor})", node.path().display()),
+        None => format!("{major}:{minor}"),
+    }
+}
+
+/// Groups the pairs by format.
+fn group<'a>(pairs: impl IntoIterator<Item = &'a DmabufFormat>) -> DmabufFormats {
+    let mut formats = DmabufFormats::new();
+    for pair in pairs {
+        let modifiers = formats.entry(pair.format).or_default();
+        if !modifiers.contains(&pair.modifier) {
+            modifiers.push(pair.modifier);
+        }
+    }
+    formats
+}
+
+/// One line per format with its modifiers, indented.
+fn format_lines(formats: &DmabufFormats) -> Vec<String> {
+    formats
+        .iter()
+        .map(|(&format, modifiers)| {
+            let modifiers: Vec<String> = modifiers.iter().map(|&m| modifier_name(m)).collect();
+            format!("    {}: {}", format_name(format), modifiers.join(", "))
+        })
+        .collect()
+}
+
+/// What the feedback says, a line for the main device and one for each tranche, each followed
+/// by its pairs. The pairs are marked as details.
+fn feedback_lines(feedback: &DmabufFeedback) -> Vec<(bool, String)> {
+    let table = feedback.format_table();
+    let mut lines = vec![(false, format!("main device: {}", device_name(feedback.main_device())))];
+    for (index, tranche) in feedback.tranches().iter().enumerate() {
+        let scanout = match tranche.flags {
+            WEnum::Value(flags) if flags.contains(TrancheFlags::Scanout) => ", scanout",
+            _ => "",
+        };
+        let pairs = group(tranche.formats.iter().filter_map(|&i| table.get(i as usize)));
+        lines.push((
+            false,
+            format!(
+                "tranche {index}: device {}{scanout}, {} formats",
+                device_name(tranche.device),
+                pairs.len()
+            ),
+        ));
+        lines.extend(format_lines(&pairs).into_iter().map(|line| (true, line)));
+    }
+    lines
+}
+
+/// Binds zwp_linux_dmabuf_v1, which never fails, and says what it can tell.
+pub(crate) fn bind<H: AppHandler>(
+    globals: &GlobalList,
+    qh: &QueueHandle<App<H>>,
+) -> DmabufState {
+    let state = DmabufState::new(globals, qh);
+    match state.version() {
+        None => log::info!("zwp_linux_dmabuf_v1 not available"),
+        Some(version) if version < 4 => {
+            log::info!("zwp_linux_dmabuf_v1 version {version} has no feedback")
+        }
+        Some(_) => {}
+    }
+    state
+}
+
+impl BaseApp {
+    /// The GPU the compositor renders with as a `dev_t`, the main device of the latest dmabuf
+    /// feedback of the window. `None` until the feedback came, and without version 4.
+    pub fn compositor_device(&self) -> Option<u64> {
+        self.compositor_device
+    }
+}
+
+impl<H: AppHandler> App<H> {
+    /// Prints the formats of `--dmabuf-info` when the compositor has no feedback to give, the
+    /// formats of version 3 or none at all. The feedback of version 4 is printed as it comes.
+    pub(crate) fn print_dmabuf_formats(&mut self, id: &ObjectId) {
+        let formats = match self.shared.dmabuf_state.version() {
+            None => {
+                println!("the compositor has no zwp_linux_dmabuf_v1, buffers go through shm");
+                DmabufFormats::new()
+            }
+            Some(version) => {
+                println!("zwp_linux_dmabuf_v1 version {version}, no feedback. Formats:");
+                let formats = group(self.shared.dmabuf_state.modifiers());
+                for line in format_lines(&formats) {
+                    println!("{line}");
+                }
+                formats
+            }
+        };
+        self.compare_dmabuf_formats(id, &formats);
+    }
+
+    /// Has the renderer compare the formats with its own and exits, `--dmabuf-info` is done.
+    fn compare_dmabuf_formats(&mut self, id: &ObjectId, formats: &DmabufFormats) {
+        if let Some(base) = self.windows.get_mut(id) {
+            self.handler.dmabuf_info(base, formats);
+        }
+        self.shared.exit = true;
+    }
+}
+
+impl<H: AppHandler> DmabufHandler for App<H> {
+    fn dmabuf_state(&mut self) -> &mut DmabufState {
+        &mut self.shared.dmabuf_state
+    }
+
+    fn dmabuf_feedback(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        proxy: &ZwpLinuxDmabufFeedbackV1,
+        feedback: DmabufFeedback,
+    ) {
+        // It comes again whenever it changes, e.g. when a fullscreen window could be scanned out
+        let Some(id) = self
+            .windows
+            .iter()
+            .find(|(_, base)| base.dmabuf_feedback.as_ref() == Some(proxy))
+            .map(|(id, _)| id.clone())
+        else {
+            return;
+        };
+
+        if let Some(base) = self.windows.get_mut(&id) {
+            base.compositor_device = Some(feedback.main_device());
+        }
+
+        let lines = feedback_lines(&feedback);
+        if !self.shared.options.dmabuf_info {
+            log::info!("dmabuf feedback of window {id}:");
+            for (detail, line) in lines {
+                if detail {
+                    log::debug!("{line}");
+                } else {
+                    log::info!("{line}");
+                }
+            }
+            return;
+        }
+        if self.shared.exit {
+            return;
+        }
+
+        println!("dmabuf feedback of window {id}:");
+        for (_, line) in lines {
+            println!("{line}");
+        }
+        let table = feedback.format_table();
+        let indices = feedback.tranches().iter().flat_map(|tranche| &tranche.formats);
+        let formats = group(indices.filter_map(|&i| table.get(i as usize)));
+        self.compare_dmabuf_formats(&id, &formats);
+    }
+
+    // The drivers create their dmabuf buffers with proxies of their own, none of ours come here
+
+    fn created(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _params: &ZwpLinuxBufferParamsV1,
+        _buffer: WlBuffer,
+    ) {
+    }
+
+    fn failed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _params: &ZwpLinuxBufferParamsV1,
+    ) {
+    }
+
+    fn released(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _buffer: &WlBuffer) {}
+}
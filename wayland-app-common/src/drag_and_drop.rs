@@ -0,0 +1,254 @@
+//! Dropping files onto the windows. File managers offer them as a `text/uri-list`, the paths
+//! are printed and when the first one is a PNG it is handed to the renderer, see
+//! [`AppHandler::image_dropped`].
+
+use std::{
+    ffi::OsString,
+    io::{ErrorKind, Read},
+    os::unix::ffi::OsStringExt,
+    path::PathBuf,
+};
+
+use calloop::PostAction;
+use smithay_client_toolkit::data_device_manager::{
+    data_device::{DataDeviceData, DataDeviceHandler},
+    data_offer::{DataOfferHandler, DragOffer},
+};
+use wayland_client::{
+    backend::ObjectId,
+    protocol::{
+        wl_data_device::WlDataDevice, wl_data_device_manager::DndAction, wl_surface::WlSurface,
+    },
+    Connection, Proxy, QueueHandle,
+};
+
+use crate::{App, AppHandler};
+
+const URI_LIST_MIME_TYPE: &str = "text/uri-list";
+
+/// Plenty for a few thousand paths, a longer list is not read any further.
+const MAX_URI_LIST_LEN: usize = 1024 * 1024;
+
+/// An image dropped onto a window, RGBA with 8 bits per channel and the top row first.
+pub struct DroppedImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl<H: AppHandler> App<H> {
+    /// Reads the URI list of a drop. Like pasting the read goes through the event loop, the
+    /// offer is finished once all of it arrived.
+    fn receive_drop(&mut self, offer: DragOffer) {
+        let Some(loop_handle) = &self.loop_handle else {
+            println!("dropping needs the calloop event loop, it's not available with --poll-loop");
+            offer.destroy();
+            return;
+        };
+        let read_pipe = match offer.receive(URI_LIST_MIME_TYPE.to_string()) {
+            Ok(read_pipe) => read_pipe,
+            Err(err) => {
+                log::warn!("failed to receive the drop: {err}");
+                offer.destroy();
+                return;
+            }
+        };
+
+        let id = offer.surface.id();
+        let mut uri_list = Vec::new();
+        let result = loop_handle.insert_source(read_pipe, move |_, file, app| {
+            let mut buffer = [0; 4096];
+            match unsafe { file.get_mut() }.read(&mut buffer) {
+                Ok(0) => {
+                    // Tells the source the data arrived, only then may it e.g. delete moved
+                    // files. The offer is ours to destroy after a drop.
+                    offer.finish();
+                    offer.destroy();
+                    app.uris_dropped(&id, &uri_list);
+                    PostAction::Remove
+                }
+                Ok(len) if uri_list.len() + len > MAX_URI_LIST_LEN => {
+                    println!("the dropped URI list is too long");
+                    offer.destroy();
+                    PostAction::Remove
+                }
+                Ok(len) => {
+                    uri_list.extend_from_slice(&buffer[..len]);
+                    PostAction::Continue
+                }
+                Err(err) if err.kind() == ErrorKind::Interrupted => PostAction::Continue,
+                Err(err) => {
+                    log::warn!("failed to read the drop: {err}");
+                    offer.destroy();
+                    PostAction::Remove
+                }
+            }
+        });
+        if let Err(err) = result {
+            log::warn!("failed to watch the drop pipe: {err}");
+        }
+    }
+
+    fn uris_dropped(&mut self, id: &ObjectId, uri_list: &[u8]) {
+        // Lines end in CRLF, comments start with #
+        let text = String::from_utf8_lossy(uri_list);
+        let uris: Vec<&str> = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect();
+        for uri in &uris {
+            match file_path(uri) {
+                Some(path) => println!("dropped file {}", path.display()),
+                None => println!("dropped {uri}"),
+            }
+        }
+
+        let Some(path) = uris.first().and_then(|uri| file_path(uri)) else {
+            return;
+        };
+        let is_png = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+        if !is_png {
+            return;
+        }
+
+        let image = match image::open(&path) {
+            Ok(image) => image.into_rgba8(),
+            Err(err) => {
+                println!("failed to load {}: {err}", path.display());
+                return;
+            }
+        };
+        // The window may have been closed while the list was underway
+        if let Some(base) = self.windows.get_mut(id) {
+            println!("showing {}x{} image", image.width(), image.height());
+            let image = DroppedImage {
+                width: image.width(),
+                height: image.height(),
+                pixels: image.into_raw(),
+            };
+            self.handler.image_dropped(base, image);
+        }
+    }
+}
+
+/// The drag offer of the data device, if a drag is on one of our surfaces.
+fn drag_offer(data_device: &WlDataDevice) -> Option<DragOffer> {
+    data_device.data::<DataDeviceData>()?.drag_offer()
+}
+
+/// The local path of a `file://` URI, percent-decoded. Other URIs give `None`.
+fn file_path(uri: &str) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("file://")?;
+    // The host part is usually empty or localhost, either way the path starts at the next slash
+    let path = &rest[rest.find('/')?..];
+
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut iter = path.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    Some(OsString::from_vec(bytes).into())
+}
+
+impl<H: AppHandler> DataDeviceHandler for App<H> {
+    fn enter(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        data_device: &WlDataDevice,
+        x: f64,
+        y: f64,
+        surface: &WlSurface,
+    ) {
+        let Some(offer) = drag_offer(data_device) else {
+            return;
+        };
+
+        // Tell the source right away whether a drop would be taken, it shows that with the
+        // cursor. Only the windows take drops, not the popup.
+        let accepted = self.windows.contains_key(&surface.id())
+            && offer.with_mime_types(|types| types.iter().any(|t| t == URI_LIST_MIME_TYPE));
+        if accepted {
+            log::info!("drag entered window {} at ({x:.0}, {y:.0})", surface.id());
+            offer.accept_mime_type(offer.serial, Some(URI_LIST_MIME_TYPE.to_string()));
+            offer.set_actions(DndAction::Copy, DndAction::Copy);
+        } else {
+            log::info!("drag entered window {} without anything we take", surface.id());
+            offer.accept_mime_type(offer.serial, None);
+            offer.set_actions(DndAction::empty(), DndAction::empty());
+        }
+    }
+
+    fn leave(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _data_device: &WlDataDevice) {
+        log::info!("drag left");
+    }
+
+    fn motion(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _data_device: &WlDataDevice,
+        x: f64,
+        y: f64,
+    ) {
+        // The whole window takes the drop, what was accepted on enter holds everywhere
+        log::debug!("drag at ({x:.0}, {y:.0})");
+    }
+
+    fn selection(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _data_device: &WlDataDevice) {
+        // The offer is kept by the data device, it's looked at when pasting
+        log::debug!("the selection changed");
+    }
+
+    fn drop_performed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        data_device: &WlDataDevice,
+    ) {
+        let Some(offer) = drag_offer(data_device) else {
+            return;
+        };
+
+        let has_uri_list =
+            offer.with_mime_types(|types| types.iter().any(|t| t == URI_LIST_MIME_TYPE));
+        if has_uri_list && self.windows.contains_key(&offer.surface.id()) {
+            self.receive_drop(offer);
+        } else {
+            // Nothing was accepted, there is nothing to finish either
+            offer.destroy();
+        }
+    }
+}
+
+impl<H: AppHandler> DataOfferHandler for App<H> {
+    fn source_actions(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _offer: &mut DragOffer,
+        actions: DndAction,
+    ) {
+        // Copy is all we take, a source that only moves would delete the files after the drop
+        log::debug!("drag source offers {actions:?}");
+    }
+
+    fn selected_action(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _offer: &mut DragOffer,
+        actions: DndAction,
+    ) {
+        log::debug!("compositor picked {actions:?} for the drag");
+    }
+}
@@ -0,0 +1,171 @@
+//! Dragging the clear color out of a window. Pressing the left button in the content area and
+//! moving past a threshold starts a drag that offers the color as text, e.g. `#3366ff`, with a
+//! swatch of it as the drag icon. With Shift held the same gesture selects the color instead,
+//! see the primary selection.
+
+use smithay_client_toolkit::{
+    compositor::Surface,
+    data_device_manager::data_source::DragSource,
+    shell::WaylandSurface,
+    shm::slot::{Buffer, SlotPool},
+};
+use wayland_client::{
+    backend::ObjectId,
+    protocol::{
+        wl_data_device_manager::DndAction, wl_data_source::WlDataSource, wl_seat, wl_shm,
+        wl_surface,
+    },
+    QueueHandle,
+};
+
+use crate::{
+    clipboard::{format_color, TEXT_MIME_TYPES},
+    App, AppHandler, BaseApp, SharedState,
+};
+
+/// How far the pointer has to move with the button down before a drag starts, in logical
+/// pixels. Below it the press is just a click.
+const DRAG_THRESHOLD: f64 = 8.0;
+
+/// Size of the drag icon in logical pixels.
+const ICON_SIZE: u32 = 32;
+const ICON_BORDER_COLOR: [u8; 3] = [40, 40, 40];
+
+/// A drag we started, kept until the compositor finishes or cancels it.
+pub(crate) struct Drag {
+    /// Only kept alive for the drag. Declared first so the icon goes before the source.
+    _icon: Option<DragIcon>,
+    source: DragSource,
+    /// The color as sent to the target.
+    text: String,
+}
+
+/// The surface under the pointer during the drag and its contents. The surface is declared
+/// first, it goes before the buffer it shows.
+struct DragIcon {
+    _surface: Surface,
+    _buffer: Buffer,
+    _pool: SlotPool,
+}
+
+impl Drag {
+    /// What to send for the source, if it's the source of this drag.
+    pub(crate) fn text_for(&self, source: &WlDataSource) -> Option<&str> {
+        (self.source.inner() == source).then_some(self.text.as_str())
+    }
+}
+
+impl BaseApp {
+    /// Whether the pointer moved far enough from the press to start a drag.
+    pub(crate) fn drag_threshold_passed(&self, x: f64, y: f64) -> bool {
+        self.drag_start
+            .is_some_and(|(start_x, start_y, _)| (x - start_x).hypot(y - start_y) > DRAG_THRESHOLD)
+    }
+}
+
+impl<H: AppHandler> App<H> {
+    /// Starts dragging the clear color of the window with the pointer of the seat, `serial` is
+    /// that of the button press.
+    pub(crate) fn start_drag(
+        &mut self,
+        qh: &QueueHandle<Self>,
+        id: &ObjectId,
+        seat: &wl_seat::WlSeat,
+        serial: u32,
+    ) {
+        let shared = &mut self.shared;
+        let device = shared.seats.get(seat).and_then(|seat| seat.data_device.as_ref());
+        let (Some(manager), Some(device)) = (&shared.data_device_manager, device) else {
+            println!("dragging needs wl_data_device_manager, which the compositor lacks");
+            return;
+        };
+        let Some(base) = self.windows.get(id) else {
+            return;
+        };
+        if shared.drag.is_some() {
+            return;
+        }
+
+        let color = base.clear_color();
+        let text = format_color(color);
+        let source = manager.create_drag_and_drop_source(qh, TEXT_MIME_TYPES, DndAction::Copy);
+
+        // The icon gets its role from start_drag, its contents follow with the next commit
+        let icon_surface = match Surface::new(&shared.compositor_state, qh) {
+            Ok(surface) => Some(surface),
+            Err(err) => {
+                log::warn!("failed to create the drag icon surface: {err}");
+                None
+            }
+        };
+        let origin = base.window.wl_surface();
+        source.start_drag(device, origin, icon_surface.as_ref().map(Surface::wl_surface), serial);
+        let icon = icon_surface.and_then(|surface| shared.paint_drag_icon(surface, color, base.scale));
+
+        println!("dragging {text}");
+        shared.drag = Some(Drag {
+            _icon: icon,
+            source,
+            text,
+        });
+    }
+
+    /// Forgets the drag when `source` is its source, which destroys the icon surface and the
+    /// source. Returns false for other sources.
+    pub(crate) fn end_drag(&mut self, source: &WlDataSource) -> bool {
+        self.shared.drag.take_if(|drag| drag.source.inner() == source).is_some()
+    }
+}
+
+impl SharedState {
+    /// Fills a buffer with the color, framed so it stands out on any background, and shows it
+    /// on the icon surface.
+    fn paint_drag_icon(&self, surface: Surface, color: [f32; 3], scale: i32) -> Option<DragIcon> {
+        let size = ICON_SIZE * scale as u32;
+        let stride = size * 4;
+        let mut pool = match SlotPool::new((stride * size) as usize, &self.shm) {
+            Ok(pool) => pool,
+            Err(err) => {
+                log::warn!("failed to create the drag icon pool: {err}");
+                return None;
+            }
+        };
+        let (buffer, canvas) = match pool.create_buffer(
+            size as i32,
+            size as i32,
+            stride as i32,
+            wl_shm::Format::Argb8888,
+        ) {
+            Ok(buffer) => buffer,
+            Err(err) => {
+                log::warn!("failed to create the drag icon buffer: {err}");
+                return None;
+            }
+        };
+
+        let border = scale as u32;
+        let [red, green, blue] = color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+        for (i, pixel) in canvas.chunks_exact_mut(4).enumerate() {
+            let (x, y) = (i as u32 % size, i as u32 / size);
+            let edge = x < border || y < border || x >= size - border || y >= size - border;
+            let [red, green, blue] = if edge { ICON_BORDER_COLOR } else { [red, green, blue] };
+            // Little endian ARGB
+            pixel.copy_from_slice(&[blue, green, red, 255]);
+        }
+
+        let wl_surface: &wl_surface::WlSurface = surface.wl_surface();
+        if let Err(err) = buffer.attach_to(wl_surface) {
+            log::warn!("failed to attach the drag icon buffer: {err}");
+            return None;
+        }
+        wl_surface.set_buffer_scale(scale);
+        wl_surface.damage_buffer(0, 0, size as i32, size as i32);
+        wl_surface.commit();
+
+        Some(DragIcon {
+            _surface: surface,
+            _buffer: buffer,
+            _pool: pool,
+        })
+    }
+}
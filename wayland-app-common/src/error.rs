@@ -0,0 +1,99 @@
+use std::{fmt, io};
+
+use wayland_client::backend::protocol::ProtocolError;
+
+/// Everything that can go wrong while setting up a GL stack for a window. The payloads are
+/// strings since the examples use different GL setup crates with their own error types.
+#[derive(Debug)]
+pub enum GlInitError {
+    /// No display could be acquired for the Wayland connection.
+    Display(String),
+    /// None of the configs matches what we asked for.
+    Config {
+        requested: String,
+        available: Vec<String>,
+    },
+    /// The context could not be created with the chosen config.
+    Context(String),
+    /// The window surface could not be created.
+    Surface(String),
+    /// The context could not be made current on the window surface.
+    MakeCurrent(String),
+    /// A shader failed to compile or link, or a GL object could not be created. Compile and
+    /// link failures carry the info log.
+    Shader(String),
+}
+
+impl fmt::Display for GlInitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GlInitError::Display(err) => write!(f, "failed to acquire a GL display: {err}"),
+            GlInitError::Config {
+                requested,
+                available,
+            } => {
+                writeln!(f, "no GL config matches the requested attributes")?;
+                writeln!(f, "  requested: {requested}")?;
+                if available.is_empty() {
+                    write!(f, "  available: none")
+                } else {
+                    write!(f, "  available:")?;
+                    for config in available {
+                        write!(f, "\n    {config}")?;
+                    }
+                    Ok(())
+                }
+            }
+            GlInitError::Context(err) => write!(f, "failed to create the GL context: {err}"),
+            GlInitError::Surface(err) => write!(f, "failed to create the GL surface: {err}"),
+            GlInitError::MakeCurrent(err) => {
+                write!(f, "failed to make the GL context current: {err}")
+            }
+            GlInitError::Shader(err) => write!(f, "failed to set up the shaders: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for GlInitError {}
+
+/// Why the event loop stopped before the app was done.
+#[derive(Debug)]
+pub enum ConnectionError {
+    /// The compositor ended the connection over a request it didn't accept.
+    Protocol(ProtocolError),
+    /// Reading or writing the socket failed, e.g. because the compositor went away.
+    Io(io::Error),
+}
+
+impl ConnectionError {
+    /// Whether the connection just broke, so a new one may work. A protocol error would only
+    /// happen again.
+    pub fn is_broken(&self) -> bool {
+        match self {
+            ConnectionError::Protocol(_) => false,
+            ConnectionError::Io(err) => matches!(
+                err.kind(),
+                io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::NotConnected
+                    | io::ErrorKind::UnexpectedEof
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionError::Protocol(err) => write!(
+                f,
+                "protocol error {} on {}@{}: {}",
+                err.code, err.object_interface, err.object_id, err.message
+            ),
+            ConnectionError::Io(err) => write!(f, "the Wayland connection broke: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ConnectionError {}
@@ -0,0 +1,107 @@
+//! A 5x7 bitmap font for the text the windows draw, e.g. [`crate::BaseApp::text_rects`]. Each
+//! glyph is five columns, bit 0 the top row, so a run of lit pixels down a column becomes one
+//! rectangle and the renderers fill them like the other rectangles, with scissored clears.
+
+/// Width of a glyph in font pixels.
+pub(crate) const GLYPH_WIDTH: u32 = 5;
+/// Height of a glyph in font pixels.
+pub(crate) const GLYPH_HEIGHT: u32 = 7;
+/// Advance from one character to the next in font pixels, a column is left blank between them.
+pub(crate) const CELL_WIDTH: u32 = GLYPH_WIDTH + 1;
+
+/// Drawn for the characters the font lacks, everything outside of printable ASCII.
+const MISSING: [u8; 5] = [0x7f, 0x41, 0x41, 0x41, 0x7f];
+
+/// The glyphs of ' ' to '~'.
+#[rustfmt::skip]
+const GLYPHS: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], [0x00, 0x00, 0x5f, 0x00, 0x00], // ' ' !
+    [0x00, 0x07, 0x00, 0x07, 0x00], [0x14, 0x7f, 0x14, 0x7f, 0x14], // " #
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], [0x23, 0x13, 0x08, 0x64, 0x62], // $ %
+    [0x36, 0x49, 0x55, 0x22, 0x50], [0x00, 0x05, 0x03, 0x00, 0x00], // & '
+    [0x00, 0x1c, 0x22, 0x41, 0x00], [0x00, 0x41, 0x22, 0x1c, 0x00], // ( )
+    [0x08, 0x2a, 0x1c, 0x2a, 0x08], [0x08, 0x08, 0x3e, 0x08, 0x08], // * +
+    [0x00, 0x50, 0x30, 0x00, 0x00], [0x08, 0x08, 0x08, 0x08, 0x08], // , -
+    [0x00, 0x60, 0x60, 0x00, 0x00], [0x20, 0x10, 0x08, 0x04, 0x02], // . /
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], [0x00, 0x42, 0x7f, 0x40, 0x00], // 0 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], [0x21, 0x41, 0x45, 0x4b, 0x31], // 2 3
+    [0x18, 0x14, 0x12, 0x7f, 0x10], [0x27, 0x45, 0x45, 0x45, 0x39], // 4 5
+    [0x3c, 0x4a, 0x49, 0x49, 0x30], [0x01, 0x71, 0x09, 0x05, 0x03], // 6 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], [0x06, 0x49, 0x49, 0x29, 0x1e], // 8 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], [0x00, 0x56, 0x36, 0x00, 0x00], // : ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], [0x14, 0x14, 0x14, 0x14, 0x14], // < =
+    [0x00, 0x41, 0x22, 0x14, 0x08], [0x02, 0x01, 0x51, 0x09, 0x06], // > ?
+    [0x32, 0x49, 0x79, 0x41, 0x3e], [0x7e, 0x11, 0x11, 0x11, 0x7e], // @ A
+    [0x7f, 0x49, 0x49, 0x49, 0x36], [0x3e, 0x41, 0x41, 0x41, 0x22], // B C
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], [0x7f, 0x49, 0x49, 0x49, 0x41], // D E
+    [0x7f, 0x09, 0x09, 0x01, 0x01], [0x3e, 0x41, 0x41, 0x51, 0x32], // F G
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], [0x00, 0x41, 0x7f, 0x41, 0x00], // H I
+    [0x20, 0x40, 0x41, 0x3f, 0x01], [0x7f, 0x08, 0x14, 0x22, 0x41], // J K
+    [0x7f, 0x40, 0x40, 0x40, 0x40], [0x7f, 0x02, 0x04, 0x02, 0x7f], // L M
+    [0x7f, 0x04, 0x08, 0x10, 0x7f], [0x3e, 0x41, 0x41, 0x41, 0x3e], // N O
+    [0x7f, 0x09, 0x09, 0x09, 0x06], [0x3e, 0x41, 0x51, 0x21, 0x5e], // P Q
+    [0x7f, 0x09, 0x19, 0x29, 0x46], [0x46, 0x49, 0x49, 0x49, 0x31], // R S
+    [0x01, 0x01, 0x7f, 0x01, 0x01], [0x3f, 0x40, 0x40, 0x40, 0x3f], // T U
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], [0x7f, 0x20, 0x18, 0x20, 0x7f], // V W
+    [0x63, 0x14, 0x08, 0x14, 0x63], [0x03, 0x04, 0x78, 0x04, 0x03], // X Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], [0x00, 0x7f, 0x41, 0x41, 0x00], // Z [
+    [0x02, 0x04, 0x08, 0x10, 0x20], [0x00, 0x41, 0x41, 0x7f, 0x00], // \ ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], [0x40, 0x40, 0x40, 0x40, 0x40], // ^ _
+    [0x00, 0x01, 0x02, 0x04, 0x00], [0x20, 0x54, 0x54, 0x54, 0x78], // ` a
+    [0x7f, 0x48, 0x44, 0x44, 0x38], [0x38, 0x44, 0x44, 0x44, 0x20], // b c
+    [0x38, 0x44, 0x44, 0x48, 0x7f], [0x38, 0x54, 0x54, 0x54, 0x18], // d e
+    [0x08, 0x7e, 0x09, 0x01, 0x02], [0x08, 0x54, 0x54, 0x54, 0x3c], // f g
+    [0x7f, 0x08, 0x04, 0x04, 0x78], [0x00, 0x44, 0x7d, 0x40, 0x00], // h i
+    [0x20, 0x40, 0x44, 0x3d, 0x00], [0x00, 0x7f, 0x10, 0x28, 0x44], // j k
+    [0x00, 0x41, 0x7f, 0x40, 0x00], [0x7c, 0x04, 0x18, 0x04, 0x78], // l m
+    [0x7c, 0x08, 0x04, 0x04, 0x78], [0x38, 0x44, 0x44, 0x44, 0x38], // n o
+    [0x7c, 0x14, 0x14, 0x14, 0x08], [0x08, 0x14, 0x14, 0x18, 0x7c], // p q
+    [0x7c, 0x08, 0x04, 0x04, 0x08], [0x48, 0x54, 0x54, 0x54, 0x20], // r s
+    [0x04, 0x3f, 0x44, 0x40, 0x20], [0x3c, 0x40, 0x40, 0x20, 0x7c], // t u
+    [0x1c, 0x20, 0x40, 0x20, 0x1c], [0x3c, 0x40, 0x30, 0x40, 0x3c], // v w
+    [0x44, 0x28, 0x10, 0x28, 0x44], [0x0c, 0x50, 0x50, 0x50, 0x3c], // x y
+    [0x44, 0x64, 0x54, 0x4c, 0x44], [0x00, 0x08, 0x36, 0x41, 0x00], // z {
+    [0x00, 0x00, 0x7f, 0x00, 0x00], [0x00, 0x41, 0x36, 0x08, 0x00], // | }
+    [0x02, 0x01, 0x02, 0x04, 0x02],                                 // ~
+];
+
+/// The columns of the glyph of the character.
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        ' '..='~' => GLYPHS[c as usize - ' ' as usize],
+        _ => MISSING,
+    }
+}
+
+/// The lit pixels of the character as `[x, y, width, height]` in font pixels, one rectangle
+/// per run of them down a column.
+pub(crate) fn glyph_rects(c: char) -> Vec<[u32; 4]> {
+    let mut rects = Vec::new();
+    for (x, column) in (0..).zip(glyph(c)) {
+        let mut y = 0;
+        while y < GLYPH_HEIGHT {
+            if column >> y & 1 == 0 {
+                y += 1;
+                continue;
+            }
+            let top = y;
+            while y < GLYPH_HEIGHT && column >> y & 1 == 1 {
+                y += 1;
+            }
+            rects.push([x, top, 1, y - top]);
+        }
+    }
+    rects
+}
+
+/// The lit pixels of a line of text like [`glyph_rects`], and its width, in font pixels.
+pub(crate) fn line_rects(text: &str) -> (Vec<[u32; 4]>, u32) {
+    let mut rects = Vec::new();
+    let mut left = 0;
+    for c in text.chars() {
+        let glyph = glyph_rects(c).into_iter();
+        rects.extend(glyph.map(|[x, y, width, height]| [left + x, y, width, height]));
+        left += CELL_WIDTH;
+    }
+    (rects, left.saturating_sub(1))
+}
@@ -0,0 +1,88 @@
+use smithay_client_toolkit::{
+    reexports::protocols::wp::fractional_scale::v1::client::{
+        wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1,
+        wp_fractional_scale_v1::{self, WpFractionalScaleV1},
+    },
+    shell::WaylandSurface,
+};
+use wayland_client::{
+    backend::ObjectId, delegate_noop, globals::GlobalList, Connection, Dispatch, Proxy,
+    QueueHandle,
+};
+
+use crate::{App, AppHandler, BaseApp};
+
+/// Fractional scales are sent in 120ths.
+const SCALE_DENOMINATOR: u32 = 120;
+
+/// Binds the fractional scale manager, or nothing when the compositor lacks it. Without the
+/// protocol the integer buffer scale path is used.
+pub(crate) fn bind_manager<H: AppHandler>(
+    globals: &GlobalList,
+    qh: &QueueHandle<App<H>>,
+) -> Option<WpFractionalScaleManagerV1> {
+    globals.bind::<WpFractionalScaleManagerV1, _, _>(qh, 1..=1, ()).ok()
+}
+
+/// Asks the compositor for the preferred fractional scale of a window surface. Only useful
+/// together with a viewport, which maps the buffer rendered at the scaled size back to the
+/// logical size.
+pub(crate) fn get_fractional_scale<H: AppHandler>(
+    manager: &WpFractionalScaleManagerV1,
+    qh: &QueueHandle<App<H>>,
+    surface: &impl WaylandSurface,
+) -> WpFractionalScaleV1 {
+    // The surface id tells the events of the windows apart
+    manager.get_fractional_scale(surface.wl_surface(), qh, surface.wl_surface().id())
+}
+
+impl BaseApp {
+    /// The scale factor the content is rendered at, fractional when the compositor prefers so.
+    pub fn scale_factor(&self) -> f64 {
+        match self.fractional_scale {
+            Some(scale) => scale as f64 / SCALE_DENOMINATOR as f64,
+            None => self.scale as f64,
+        }
+    }
+
+    /// Converts a logical length to physical pixels. Fractional scales round to the nearest
+    /// pixel, the same way the compositor computes the size of the viewport source.
+    pub(crate) fn to_physical(&self, logical: u32) -> u32 {
+        match self.fractional_scale {
+            Some(scale) => (logical * scale + SCALE_DENOMINATOR / 2) / SCALE_DENOMINATOR,
+            None => logical * self.scale as u32,
+        }
+    }
+}
+
+impl<H: AppHandler> Dispatch<WpFractionalScaleV1, ObjectId> for App<H> {
+    fn event(
+        app: &mut Self,
+        _proxy: &WpFractionalScaleV1,
+        event: wp_fractional_scale_v1::Event,
+        id: &ObjectId,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        let wp_fractional_scale_v1::Event::PreferredScale { scale } = event else {
+            return;
+        };
+        app.shared.trace.record("preferred_fractional_scale", &[("window", id), ("scale", &scale)]);
+        let Some(base) = app.windows.get_mut(id) else {
+            return;
+        };
+        if base.fractional_scale == Some(scale) {
+            return;
+        }
+
+        // From now on the viewport does the scaling, the buffer is in physical pixels.
+        base.fractional_scale = Some(scale);
+        base.update_buffer_scale();
+        log::info!("preferred fractional scale of window {id} changed to {}", base.scale_factor());
+
+        app.handler.resize(base);
+        app.redraw(qh, id);
+    }
+}
+
+delegate_noop!(@<H: AppHandler> App<H>: ignore WpFractionalScaleManagerV1);
@@ -0,0 +1,85 @@
+//! `--max-fps`, at most that many frames a second also when the frame callbacks or the fences
+//! would allow more, to save power. A frame that is due too early is held back by a timer for
+//! the rest of the interval.
+//!
+//! With presentation feedback the interval counts from when the last frame was actually shown,
+//! so the cap follows the display rather than our own timing. Without it, or after a frame was
+//! discarded, it counts from when the last frame was drawn.
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use calloop::timer::{TimeoutAction, Timer};
+use wayland_client::{backend::ObjectId, QueueHandle};
+
+use crate::{App, AppHandler};
+
+/// Delays shorter than this aren't worth a timer, the frame is drawn right away.
+const MIN_DELAY: Duration = Duration::from_micros(500);
+
+impl<H: AppHandler> App<H> {
+    /// How long the next frame of the window has to wait for `--max-fps`, zero when it may be
+    /// drawn right away.
+    pub(crate) fn frame_cap_delay(&self, id: &ObjectId) -> Duration {
+        let (Some(fps), Some(base)) = (self.shared.options.max_fps, self.windows.get(id)) else {
+            return Duration::ZERO;
+        };
+        let interval = Duration::from_secs(1) / fps;
+        let presented = base.presentation_delay(interval, self.shared.presentation_clock);
+        let delay = presented.unwrap_or_else(|| {
+            let due = base.last_frame_at.map_or_else(Instant::now, |at| at + interval);
+            due.saturating_duration_since(Instant::now())
+        });
+        if delay < MIN_DELAY { Duration::ZERO } else { delay }
+    }
+
+    /// Holds the next frame of the window back until `--max-fps` allows it. Returns whether a
+    /// timer draws it later, otherwise it may be drawn now.
+    pub(crate) fn cap_frame_rate(&mut self, qh: &QueueHandle<Self>, id: &ObjectId) -> bool {
+        let delay = self.frame_cap_delay(id);
+        if delay.is_zero() {
+            return false;
+        }
+        let Some(handle) = &self.loop_handle else {
+            // The poll loop has no timers, it sleeps the rest of the interval
+            thread::sleep(delay);
+            return false;
+        };
+        let Some(base) = self.windows.get_mut(id) else {
+            return false;
+        };
+
+        let (qh, id) = (qh.clone(), id.clone());
+        let inserted = handle.insert_source(Timer::from_duration(delay), move |_, _, app| {
+            app.capped_redraw(&qh, &id);
+            TimeoutAction::Drop
+        });
+        match inserted {
+            Ok(_) => {
+                base.capped_redraw_pending = true;
+                true
+            }
+            Err(err) => {
+                log::warn!("failed to hold the frame back: {err}");
+                false
+            }
+        }
+    }
+
+    /// The frame [`Self::cap_frame_rate`] held back. A window that paused or got suspended in
+    /// the meantime is left alone until it resumes, one that drew for a configure in between
+    /// already has its next frame callback coming.
+    fn capped_redraw(&mut self, qh: &QueueHandle<Self>, id: &ObjectId) {
+        let Some(base) = self.windows.get_mut(id) else {
+            return;
+        };
+        base.capped_redraw_pending = false;
+        if !base.animating() || base.suspended() || base.frame_callback_pending {
+            return;
+        }
+        base.advance_paced_time();
+        self.redraw(qh, id);
+    }
+}
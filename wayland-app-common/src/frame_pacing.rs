@@ -0,0 +1,81 @@
+//! `--max-frames-in-flight`, pacing async windows with fences rather than frame callbacks.
+//! Without vsync the swap returns right away and the driver queues up whatever it is given,
+//! each queued frame adding latency. Renderers that can wait for the GPU set
+//! [`BaseApp::fence_pacing`], then a frame follows the last one as soon as the loop gets to it
+//! and the renderer blocks on the fence of an older frame once that many are in flight.
+//!
+//! With vsync the swap waits for the compositor anyway, and renderers without fences stay with
+//! the frame callbacks.
+
+use std::time::Instant;
+
+use calloop::timer::{TimeoutAction, Timer};
+use wayland_client::{backend::ObjectId, QueueHandle};
+
+use crate::{App, AppHandler, BaseApp};
+
+impl BaseApp {
+    /// Whether frames are paced by the fences of the renderer, which then waits before drawing
+    /// when [`Self::max_frames_in_flight`] are queued. Only async windows are.
+    pub fn paced_by_fences(&self) -> bool {
+        self.fence_pacing && !self.vsync && self.max_frames_in_flight.is_some()
+    }
+
+    /// Notes the time of a frame callback for [`Self::advance_paced_time`]. Returns whether the
+    /// paced frames go on without the callback drawing another.
+    pub(crate) fn frame_done_paced(&mut self, time: u32) -> bool {
+        self.last_frame_done = Some((time, Instant::now()));
+        self.paced_by_fences() && self.paced_redraw_pending
+    }
+
+    /// Advances the animation by the time since the last frame callback, the paced frames
+    /// come in between them.
+    pub(crate) fn advance_paced_time(&mut self) {
+        if let Some((time, at)) = self.last_frame_done {
+            self.advance_time(time.wrapping_add(at.elapsed().as_millis() as u32));
+        }
+    }
+}
+
+impl<H: AppHandler> App<H> {
+    /// Draws the next frame of a window paced by fences once the loop is idle, unless one is
+    /// coming already. Only the calloop loop paces, the poll loop keeps using frame callbacks.
+    pub(crate) fn schedule_paced_redraw(&mut self, qh: &QueueHandle<Self>, id: &ObjectId) {
+        // `--max-fps` holds async frames back too
+        let delay = self.frame_cap_delay(id);
+        let Some(base) = self.windows.get_mut(id) else {
+            return;
+        };
+        if !base.paced_by_fences() || base.paced_redraw_pending || !base.animating() {
+            return;
+        }
+        let Some(handle) = &self.loop_handle else {
+            return;
+        };
+
+        let (qh, id) = (qh.clone(), id.clone());
+        let timer = if delay.is_zero() { Timer::immediate() } else { Timer::from_duration(delay) };
+        let inserted = handle.insert_source(timer, move |_, _, app| {
+            app.paced_redraw(&qh, &id);
+            TimeoutAction::Drop
+        });
+        match inserted {
+            Ok(_) => base.paced_redraw_pending = true,
+            Err(err) => log::warn!("failed to schedule the next frame: {err}"),
+        }
+    }
+
+    /// The frame [`Self::schedule_paced_redraw`] scheduled. A window that switched to vsync or
+    /// paused in the meantime is left to its frame callbacks.
+    fn paced_redraw(&mut self, qh: &QueueHandle<Self>, id: &ObjectId) {
+        let Some(base) = self.windows.get_mut(id) else {
+            return;
+        };
+        base.paced_redraw_pending = false;
+        if !base.paced_by_fences() || !base.animating() {
+            return;
+        }
+        base.advance_paced_time();
+        self.redraw(qh, id);
+    }
+}
@@ -0,0 +1,137 @@
+use std::{collections::VecDeque, fmt::Write as _, fs, io, path::Path, time::Duration};
+
+/// Frames `--bench` draws before it exits.
+pub const BENCH_FRAMES: u64 = 1000;
+
+/// Frame rate and frame time statistics, logged once a second when enabled with `--stats`.
+///
+/// The frame rate is counted even without `--stats`, the window title shows it. It counts frame
+/// callbacks, i.e. frames the compositor actually showed, over a
+/// sliding one second window. The times are reported by the renderers through [`Self::record`].
+#[derive(Debug, Default)]
+pub struct FrameStats {
+    enabled: bool,
+    /// Timestamps of the frame callbacks within the last second, in milliseconds.
+    callbacks: VecDeque<u32>,
+    last_report: Option<u32>,
+    samples: u32,
+    draw: Duration,
+    swap: Duration,
+    max_draw: Duration,
+    /// The draw and swap time of every frame with `--bench`.
+    bench: Option<Vec<(Duration, Duration)>>,
+}
+
+impl FrameStats {
+    pub(crate) fn new(enabled: bool, bench: bool) -> FrameStats {
+        FrameStats {
+            enabled,
+            bench: bench.then(|| Vec::with_capacity(BENCH_FRAMES as usize)),
+            ..Default::default()
+        }
+    }
+
+    /// Records the CPU time spent building a frame and the time the swap took.
+    pub fn record(&mut self, draw: Duration, swap: Duration) {
+        if let Some(bench) = &mut self.bench {
+            bench.push((draw, swap));
+        }
+        if !self.enabled {
+            return;
+        }
+
+        self.samples += 1;
+        self.draw += draw;
+        self.swap += swap;
+        self.max_draw = self.max_draw.max(draw);
+    }
+
+    /// Frame callbacks within the last second.
+    pub fn fps(&self) -> usize {
+        self.callbacks.len()
+    }
+
+    /// Counts a frame callback with the given timestamp, logs the statistics once a second.
+    pub(crate) fn frame_done(&mut self, time: u32) {
+        self.callbacks.push_back(time);
+        while let Some(&oldest) = self.callbacks.front() {
+            if time.wrapping_sub(oldest) < 1000 {
+                break;
+            }
+            self.callbacks.pop_front();
+        }
+
+        if !self.enabled {
+            return;
+        }
+
+        let last_report = *self.last_report.get_or_insert(time);
+        if time.wrapping_sub(last_report) < 1000 {
+            return;
+        }
+        self.last_report = Some(time);
+
+        let average = |total: Duration| total.as_secs_f64() * 1000.0 / self.samples.max(1) as f64;
+        log::info!(
+            "{} fps, draw {:.2} ms (max {:.2} ms), swap {:.2} ms",
+            self.fps(),
+            average(self.draw),
+            self.max_draw.as_secs_f64() * 1000.0,
+            average(self.swap),
+        );
+
+        self.samples = 0;
+        self.draw = Duration::ZERO;
+        self.swap = Duration::ZERO;
+        self.max_draw = Duration::ZERO;
+    }
+
+    /// Prints the percentiles of the `--bench` frames and writes every frame to the CSV file.
+    /// Renderers that don't [`Self::record`] have nothing to print.
+    pub(crate) fn print_bench(&self, csv: &Path) {
+        let Some(bench) = &self.bench else {
+            return;
+        };
+        if bench.is_empty() {
+            println!("bench: this renderer doesn't record frame times");
+            return;
+        }
+
+        let milliseconds = |time: Duration| time.as_secs_f64() * 1000.0;
+        let draws: Vec<f64> = bench.iter().map(|&(draw, _)| milliseconds(draw)).collect();
+        let swaps: Vec<f64> = bench.iter().map(|&(_, swap)| milliseconds(swap)).collect();
+        println!("bench: {} frames", bench.len());
+        for (what, times) in [("submit", draws), ("swap", swaps)] {
+            let [p50, p95, p99] = percentiles(times, [0.5, 0.95, 0.99]);
+            println!("  {what:<6} p50 {p50:.3} ms, p95 {p95:.3} ms, p99 {p99:.3} ms");
+        }
+
+        match write_bench_csv(bench, csv) {
+            Ok(()) => println!("bench: every frame written to {}", csv.display()),
+            Err(err) => eprintln!("can't write {}: {err}", csv.display()),
+        }
+    }
+}
+
+/// The values at the fractions of the way through the sorted times, nearest rank.
+fn percentiles<const N: usize>(mut times: Vec<f64>, fractions: [f64; N]) -> [f64; N] {
+    times.sort_by(f64::total_cmp);
+    fractions.map(|fraction| {
+        let rank = (fraction * times.len() as f64).ceil() as usize;
+        times[rank.clamp(1, times.len()) - 1]
+    })
+}
+
+/// One line per frame with the draw and swap times in milliseconds.
+fn write_bench_csv(bench: &[(Duration, Duration)], path: &Path) -> io::Result<()> {
+    let mut csv = String::from("frame,submit_ms,swap_ms\n");
+    for (frame, (draw, swap)) in bench.iter().enumerate() {
+        let _ = writeln!(
+            csv,
+            "{frame},{:.4},{:.4}",
+            draw.as_secs_f64() * 1000.0,
+            swap.as_secs_f64() * 1000.0
+        );
+    }
+    fs::write(path, csv)
+}
@@ -0,0 +1,119 @@
+//! Keeping the screen from blanking while the animation runs. Space pauses the animation of a
+//! window, then the screen may blank as usual, as it may while the window is suspended.
+//! `--pause-inactive` pauses it too while the window isn't activated, to save power.
+
+use smithay_client_toolkit::{
+    reexports::protocols::wp::idle_inhibit::zv1::client::{
+        zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1,
+        zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1,
+    },
+    shell::WaylandSurface,
+};
+use wayland_client::{backend::ObjectId, delegate_noop, globals::GlobalList, QueueHandle};
+
+use crate::{App, AppHandler, BaseApp};
+
+/// Binds the idle inhibit manager, or nothing when the compositor lacks it.
+pub(crate) fn bind_manager<H: AppHandler>(
+    globals: &GlobalList,
+    qh: &QueueHandle<App<H>>,
+) -> Option<ZwpIdleInhibitManagerV1> {
+    let manager = globals.bind::<ZwpIdleInhibitManagerV1, _, _>(qh, 1..=1, ()).ok();
+    if manager.is_none() {
+        log::info!("zwp_idle_inhibit_manager_v1 not available, the screen may blank anytime");
+    }
+    manager
+}
+
+impl BaseApp {
+    /// Whether the triangle turns and the colors cycle, Space toggles it.
+    pub fn animating(&self) -> bool {
+        !self.paused && !self.inactive
+    }
+
+    /// Pauses or resumes the animation. The animation time doesn't jump over the pause.
+    fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        if !paused {
+            self.resuming = true;
+        }
+    }
+
+    /// Pauses the animation while the window isn't activated, with `--pause-inactive`. A
+    /// configure or an input event that ends it draws again.
+    pub(crate) fn set_inactive(&mut self, inactive: bool) {
+        if inactive == self.inactive {
+            return;
+        }
+        let what = if inactive { "inactive, paused" } else { "resumed" };
+        log::info!("window {} {what}", self.id());
+        self.inactive = inactive;
+        if !inactive {
+            self.resuming = true;
+        }
+    }
+
+    /// Takes the time of a frame callback, less the time spent paused.
+    pub(crate) fn advance_time(&mut self, time: u32) {
+        if self.resuming {
+            self.time_offset = time.wrapping_sub(self.time);
+            self.resuming = false;
+        }
+        self.time = time.wrapping_sub(self.time_offset);
+    }
+
+    /// Creates or destroys the inhibitor to match the state of the window. Safe to call any
+    /// number of times, there is at most one inhibitor per window.
+    pub(crate) fn update_idle_inhibitor<H: AppHandler>(
+        &mut self,
+        manager: Option<&ZwpIdleInhibitManagerV1>,
+        qh: &QueueHandle<App<H>>,
+    ) {
+        let wanted = self.animating() && !self.suspended();
+        match (&self.idle_inhibitor, manager) {
+            (None, Some(manager)) if wanted => {
+                log::info!("inhibiting idle while window {} animates", self.id());
+                let inhibitor = manager.create_inhibitor(self.window.wl_surface(), qh, ());
+                self.idle_inhibitor = Some(inhibitor);
+            }
+            (Some(inhibitor), _) if !wanted => {
+                log::info!("no longer inhibiting idle for window {}", self.id());
+                inhibitor.destroy();
+                self.idle_inhibitor = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<H: AppHandler> App<H> {
+    /// Pauses the animation of the window, or resumes it.
+    pub(crate) fn toggle_animation(&mut self, qh: &QueueHandle<Self>, id: &ObjectId) {
+        let Some(base) = self.windows.get_mut(id) else {
+            return;
+        };
+        base.set_paused(base.animating());
+        println!("animation {}", if base.animating() { "resumed" } else { "paused" });
+        base.update_idle_inhibitor(self.shared.idle_inhibit_manager.as_ref(), qh);
+
+        // A paused window lets its frame loop run out, resuming starts it again
+        self.redraw(qh, id);
+    }
+
+    /// Input on a window paused by `--pause-inactive` resumes it, the compositor may take a
+    /// while to activate it, or never does for a pointer passing over.
+    pub(crate) fn wake_inactive(&mut self, qh: &QueueHandle<Self>, id: &ObjectId) {
+        let Some(base) = self.windows.get_mut(id) else {
+            return;
+        };
+        if !base.inactive {
+            return;
+        }
+        base.set_inactive(false);
+        base.update_idle_inhibitor(self.shared.idle_inhibit_manager.as_ref(), qh);
+        self.redraw(qh, id);
+    }
+}
+
+delegate_noop!(@<H: AppHandler> App<H>: ignore ZwpIdleInhibitManagerV1);
+delegate_noop!(@<H: AppHandler> App<H>: ignore ZwpIdleInhibitorV1);
@@ -0,0 +1,84 @@
+//! Click-through, `--click-through` or I. The input region of the window shrinks to a circle in
+//! the middle of the content, clicks and pointer motion outside of it go to whatever is below
+//! the window. The pointer enter and leave messages in the log show where the window ends.
+
+use smithay_client_toolkit::{
+    compositor::{CompositorState, Region},
+    shell::WaylandSurface,
+};
+use wayland_client::{backend::ObjectId, QueueHandle};
+
+use crate::{App, AppHandler, BaseApp};
+
+/// Height of the strips the circle is made of, in logical pixels.
+const STRIP_HEIGHT: u32 = 4;
+
+impl BaseApp {
+    /// The circle in the middle of the content area as strips, and the client-side title bar
+    /// so the window can still be moved and closed. In logical coordinates.
+    fn click_through_rects(&self) -> Vec<[i32; 4]> {
+        let mut rects = Vec::new();
+        let bar = self.title_bar_height();
+        if bar > 0 {
+            rects.push([0, 0, self.width as i32, bar as i32]);
+        }
+
+        let [x, y, width, height] = self.content_area();
+        let radius = width.min(height) as f64 / 2.0;
+        let center_x = x as f64 + width as f64 / 2.0;
+        let center_y = y as f64 + height as f64 / 2.0;
+        for top in (y..y + height).step_by(STRIP_HEIGHT as usize) {
+            let strip_height = STRIP_HEIGHT.min(y + height - top);
+            // As wide as the circle is in the middle of the strip
+            let distance = (top as f64 + strip_height as f64 / 2.0 - center_y).abs();
+            if distance >= radius {
+                continue;
+            }
+            let half_width = (radius * radius - distance * distance).sqrt();
+            let (left, right) = ((center_x - half_width).round(), (center_x + half_width).round());
+            if right > left {
+                rects.push([left as i32, top as i32, (right - left) as i32, strip_height as i32]);
+            }
+        }
+        rects
+    }
+
+    /// Sets the input region for the next commit when it changed, the circle with click-through
+    /// and the whole surface without. Called for every frame, so it follows resizes. The region
+    /// is in logical coordinates, a new scale leaves it as it is.
+    pub(crate) fn update_input_region(&mut self, compositor: &CompositorState) {
+        let rects = self.click_through.then(|| self.click_through_rects());
+        if rects == self.input_region {
+            return;
+        }
+
+        // No region at all means the whole surface
+        let region = match &rects {
+            Some(rects) => {
+                let Ok(region) = Region::new(compositor) else {
+                    return;
+                };
+                for &[x, y, width, height] in rects {
+                    region.add(x, y, width, height);
+                }
+                Some(region)
+            }
+            None => None,
+        };
+        self.window.wl_surface().set_input_region(region.as_ref().map(Region::wl_region));
+        self.input_region = rects;
+    }
+}
+
+impl<H: AppHandler> App<H> {
+    /// Shrinks the input region of the window to the circle, or gives it the whole surface
+    /// back.
+    pub(crate) fn toggle_click_through(&mut self, qh: &QueueHandle<Self>, id: &ObjectId) {
+        let Some(base) = self.windows.get_mut(id) else {
+            return;
+        };
+        base.click_through = !base.click_through;
+        println!("click-through: {}", if base.click_through { "on" } else { "off" });
+        self.redraw(qh, id);
+    }
+}
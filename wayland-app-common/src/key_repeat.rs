@@ -0,0 +1,128 @@
+//! Repeating held keys at the rate and delay the compositor advertises with
+//! `wl_keyboard.repeat_info`. Holding an arrow key keeps nudging the triangle.
+//!
+//! The repeat runs on a calloop timer. In `--poll-loop` mode there are no timers, a held key
+//! acts only once there.
+
+use std::time::Duration;
+
+use calloop::{
+    timer::{TimeoutAction, Timer},
+    RegistrationToken,
+};
+use smithay_client_toolkit::seat::keyboard::{Keysym, RepeatInfo};
+use wayland_client::backend::ObjectId;
+
+use crate::{App, AppHandler, BaseApp};
+
+/// How far a press of an arrow key moves the triangle, in logical pixels.
+const NUDGE_STEP: f32 = 10.0;
+
+/// The repeat settings and the key being repeated.
+pub(crate) struct KeyRepeat {
+    /// Until the compositor says otherwise keys don't repeat. Compositors that know the event
+    /// send it right after the keymap.
+    info: RepeatInfo,
+    /// The raw code of the repeating key and the timer repeating it.
+    current: Option<(u32, RegistrationToken)>,
+}
+
+impl Default for KeyRepeat {
+    fn default() -> KeyRepeat {
+        KeyRepeat {
+            info: RepeatInfo::Disable,
+            current: None,
+        }
+    }
+}
+
+impl BaseApp {
+    /// Moves the triangle a step in the direction of an arrow key. Returns false for other keys.
+    pub(crate) fn nudge_triangle(&mut self, keysym: Keysym) -> bool {
+        let (dx, dy) = match keysym {
+            Keysym::Left => (-NUDGE_STEP, 0.0),
+            Keysym::Right => (NUDGE_STEP, 0.0),
+            Keysym::Up => (0.0, -NUDGE_STEP),
+            Keysym::Down => (0.0, NUDGE_STEP),
+            _ => return false,
+        };
+        self.move_triangle(dx, dy);
+        true
+    }
+
+    /// Moves the triangle by that many logical pixels, its center stays inside the content
+    /// area.
+    pub(crate) fn move_triangle(&mut self, dx: f32, dy: f32) {
+        let [_, _, width, height] = self.content_area();
+        let (half_width, half_height) = (width as f32 / 2.0, height as f32 / 2.0);
+        let (x, y) = self.triangle_offset;
+        self.triangle_offset = (
+            (x + dx).clamp(-half_width, half_width),
+            (y + dy).clamp(-half_height, half_height),
+        );
+    }
+}
+
+impl<H: AppHandler> App<H> {
+    /// Takes the settings of a `repeat_info` event. A key already repeating stops, it would
+    /// otherwise keep the old rate.
+    pub(crate) fn set_repeat_info(&mut self, info: RepeatInfo) {
+        match info {
+            RepeatInfo::Repeat { rate, delay } => {
+                log::info!("key repeat: {rate} per second after {delay} ms");
+            }
+            RepeatInfo::Disable => log::info!("key repeat disabled"),
+        }
+        self.stop_key_repeat();
+        self.shared.key_repeat.info = info;
+    }
+
+    /// Repeats the key with the given raw code on the window until it is released, another key
+    /// is pressed or the window loses the focus.
+    pub(crate) fn start_key_repeat(&mut self, id: &ObjectId, raw_code: u32, keysym: Keysym) {
+        self.stop_key_repeat();
+
+        let RepeatInfo::Repeat { rate, delay } = self.shared.key_repeat.info else {
+            return;
+        };
+        let Some(handle) = &self.loop_handle else {
+            return;
+        };
+
+        let interval = Duration::from_micros(1_000_000 / u64::from(rate.get()));
+        let id = id.clone();
+        let timer = Timer::from_duration(Duration::from_millis(u64::from(delay)));
+        let token = handle
+            .insert_source(timer, move |_, _, app| {
+                match app.windows.get_mut(&id) {
+                    Some(base) => {
+                        base.nudge_triangle(keysym);
+                        TimeoutAction::ToDuration(interval)
+                    }
+                    // The window closed while the key was held
+                    None => {
+                        app.shared.key_repeat.current = None;
+                        TimeoutAction::Drop
+                    }
+                }
+            })
+            .expect("Failed to insert the key repeat timer");
+        self.shared.key_repeat.current = Some((raw_code, token));
+    }
+
+    /// Stops the repeat if it is this key that was released.
+    pub(crate) fn key_released(&mut self, raw_code: u32) {
+        if matches!(self.shared.key_repeat.current, Some((code, _)) if code == raw_code) {
+            self.stop_key_repeat();
+        }
+    }
+
+    pub(crate) fn stop_key_repeat(&mut self) {
+        let Some((_, token)) = self.shared.key_repeat.current.take() else {
+            return;
+        };
+        if let Some(handle) = &self.loop_handle {
+            handle.remove(token);
+        }
+    }
+}
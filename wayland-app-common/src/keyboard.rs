@@ -0,0 +1,228 @@
+use smithay_client_toolkit::seat::keyboard::{
+    KeyEvent, KeyboardHandler, Keysym, Modifiers, RawModifiers, RepeatInfo,
+};
+use wayland_client::{
+    protocol::{wl_keyboard, wl_surface},
+    Connection, Proxy, QueueHandle,
+};
+
+use crate::{trace::Debugged, App, AppHandler, FIXED_RESOLUTION};
+
+impl<H: AppHandler> KeyboardHandler for App<H> {
+    fn enter(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        keyboard: &wl_keyboard::WlKeyboard,
+        surface: &wl_surface::WlSurface,
+        serial: u32,
+        _raw: &[u32],
+        _keysyms: &[Keysym],
+    ) {
+        let id = surface.id();
+        self.shared.trace.record("keyboard_enter", &[("surface", &id), ("serial", &serial)]);
+        if !self.windows.contains_key(&id) {
+            return;
+        }
+        let Some((seat, resources)) = self.shared.keyboard_seat::<H>(keyboard) else {
+            return;
+        };
+        resources.keyboard_focus = Some(id.clone());
+        log::info!("keyboard focus of seat {} gained by window {id}", self.shared.seat_name(&seat));
+        self.restore_shortcuts_inhibit(qh, &id, &seat);
+    }
+
+    fn leave(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        keyboard: &wl_keyboard::WlKeyboard,
+        surface: &wl_surface::WlSurface,
+        serial: u32,
+    ) {
+        let id = surface.id();
+        self.shared.trace.record("keyboard_leave", &[("surface", &id), ("serial", &serial)]);
+        let Some((seat, resources)) = self.shared.keyboard_seat::<H>(keyboard) else {
+            return;
+        };
+        if resources.keyboard_focus.as_ref() != Some(&id) {
+            return;
+        }
+        resources.keyboard_focus = None;
+        log::info!("keyboard focus of seat {} lost", self.shared.seat_name(&seat));
+        self.stop_key_repeat();
+        self.unlock_pointer_of(&seat);
+        self.suspend_shortcuts_inhibit(qh, &id);
+    }
+
+    fn press_key(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        keyboard: &wl_keyboard::WlKeyboard,
+        serial: u32,
+        event: KeyEvent,
+    ) {
+        self.trace_key("press_key", &event);
+        // A new key takes over the repeat from the one held so far
+        self.stop_key_repeat();
+
+        // The popup grabs the keyboard, Escape dismisses it
+        if self.popup.is_some() && event.keysym == Keysym::Escape {
+            self.close_popup();
+            return;
+        }
+
+        // Escape leaves mouse look before it would quit
+        if self.shared.pointer_lock.is_some() && event.keysym == Keysym::Escape {
+            self.unlock_pointer();
+            return;
+        }
+
+        // Keys act on the window the keyboard of their seat is focused on
+        let Some((seat, resources)) = self.shared.keyboard_seat::<H>(keyboard) else {
+            return;
+        };
+        let Some(id) = resources.keyboard_focus.clone() else {
+            return;
+        };
+        let modifiers = resources.modifiers;
+        self.wake_inactive(qh, &id);
+
+        if modifiers.ctrl {
+            match event.keysym {
+                Keysym::c | Keysym::C => self.copy_color(qh, &id, &seat, serial),
+                Keysym::v | Keysym::V => self.paste_color(&id, &seat),
+                _ => {}
+            }
+            return;
+        }
+
+        // The keysym is already translated with the keymap the compositor sent us, so this also
+        // works for layouts where Q or F live somewhere else.
+        match event.keysym {
+            Keysym::Escape | Keysym::q | Keysym::Q => {
+                self.shared.exit = true;
+            }
+            Keysym::n | Keysym::N => {
+                self.open_window(qh);
+            }
+            Keysym::f | Keysym::F => self.toggle_fullscreen(&id),
+            Keysym::x | Keysym::X => self.toggle_maximized(&id),
+            Keysym::h | Keysym::H => self.minimize(&id),
+            Keysym::space => self.toggle_animation(qh, &id),
+            Keysym::t | Keysym::T => self.toggle_vsync(qh, &id),
+            Keysym::m | Keysym::M => self.toggle_mouse_look(qh, &id, &seat),
+            Keysym::c | Keysym::C => self.toggle_confinement(qh, &id, &seat),
+            Keysym::d | Keysym::D => self.toggle_decorations(&id),
+            Keysym::r | Keysym::R => self.simulate_context_loss(qh, &id),
+            Keysym::u | Keysym::U => self.remap_window(qh, &id),
+            Keysym::s | Keysym::S => self.take_screenshot(qh, &id),
+            Keysym::g | Keysym::G => self.toggle_srgb(qh, &id),
+            Keysym::i | Keysym::I => self.toggle_click_through(qh, &id),
+            Keysym::o | Keysym::O if modifiers.shift => self.open_dialog(qh, &id, &seat, serial),
+            Keysym::o | Keysym::O => self.toggle_overlay_sync(qh, &id),
+            Keysym::F10 => self.toggle_shortcuts_inhibit(qh, &id, &seat),
+            Keysym::a | Keysym::A => self.request_activation_token(qh, &id, &seat, serial),
+            Keysym::Left | Keysym::Right | Keysym::Up | Keysym::Down => {
+                if let Some(base) = self.windows.get_mut(&id) {
+                    base.nudge_triangle(event.keysym);
+                    self.start_key_repeat(&id, event.raw_code, event.keysym);
+                }
+            }
+            Keysym::l | Keysym::L => {
+                let Some(base) = self.windows.get_mut(&id) else {
+                    return;
+                };
+                let resolution = match base.fixed_resolution() {
+                    Some(_) => None,
+                    None => Some(FIXED_RESOLUTION),
+                };
+                if base.set_fixed_resolution(resolution) {
+                    println!("fixed resolution: {resolution:?}");
+                    self.handler.resize(base);
+                    self.redraw(qh, &id);
+                } else {
+                    println!("fixed resolution needs wp_viewporter and no --solid-background");
+                }
+            }
+            keysym => {
+                println!("key pressed: {:?} {:?}", keysym, event.utf8.unwrap_or_default());
+            }
+        }
+    }
+
+    /// Only compositors that repeat keys themselves send these, we repeat with our own timer
+    /// otherwise.
+    fn repeat_key(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        keyboard: &wl_keyboard::WlKeyboard,
+        _serial: u32,
+        event: KeyEvent,
+    ) {
+        if let Some((_, resources)) = self.shared.keyboard_seat::<H>(keyboard)
+            && let Some(id) = &resources.keyboard_focus
+            && let Some(base) = self.windows.get_mut(id)
+        {
+            base.nudge_triangle(event.keysym);
+        }
+    }
+
+    fn release_key(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &wl_keyboard::WlKeyboard,
+        _serial: u32,
+        event: KeyEvent,
+    ) {
+        self.trace_key("release_key", &event);
+        self.key_released(event.raw_code);
+    }
+
+    fn update_repeat_info(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _keyboard: &wl_keyboard::WlKeyboard,
+        info: RepeatInfo,
+    ) {
+        self.set_repeat_info(info);
+    }
+
+    fn update_modifiers(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        keyboard: &wl_keyboard::WlKeyboard,
+        _serial: u32,
+        modifiers: Modifiers,
+        _raw_modifiers: RawModifiers,
+        layout: u32,
+    ) {
+        self.shared.trace.record(
+            "update_modifiers",
+            &[("modifiers", &Debugged(&modifiers)), ("layout", &layout)],
+        );
+        if let Some((_, resources)) = self.shared.keyboard_seat::<H>(keyboard) {
+            resources.modifiers = modifiers;
+        }
+    }
+}
+
+impl<H: AppHandler> App<H> {
+    /// Records a key event in the `--trace`.
+    fn trace_key(&self, event_name: &str, event: &KeyEvent) {
+        self.shared.trace.record(
+            event_name,
+            &[
+                ("key_time", &event.time),
+                ("raw_code", &event.raw_code),
+                ("keysym", &Debugged(event.keysym)),
+                ("utf8", &event.utf8),
+            ],
+        );
+    }
+}
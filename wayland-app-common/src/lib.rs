@@ -0,0 +1,2022 @@
+//! Shared smithay-client-toolkit scaffolding for the examples in this workspace.
+//!
+//! Every example needs the same registry/seat/output state, the same xdg windows and the same
+//! pile of handler impls and delegate macros. This crate owns all of that in [`App`], and the
+//! examples only provide an [`AppHandler`] that knows how to set up their renderer for a window
+//! on its first configure and how to draw a frame. Pressing N opens another window, and a right
+//! click opens a popup menu in the examples that draw one.
+
+mod activation;
+mod capabilities;
+mod clipboard;
+mod content_type;
+mod decorations;
+mod dialog;
+mod dmabuf;
+mod drag_and_drop;
+mod drag_source;
+mod error;
+mod font;
+mod fractional_scale;
+mod frame_cap;
+mod frame_pacing;
+mod frame_stats;
+mod idle_inhibit;
+mod input_region;
+mod key_repeat;
+mod keyboard;
+mod options;
+mod output;
+mod overlay;
+mod pointer;
+mod pointer_constraints;
+mod pointer_gestures;
+mod poll_loop;
+mod popup;
+mod presentation;
+mod primary_selection;
+mod reconnect;
+mod refresh;
+mod remap;
+mod seat;
+mod self_check;
+mod shm_buffers;
+mod shortcuts_inhibit;
+mod solid_background;
+mod tablet;
+mod tearing_control;
+mod text_input;
+mod title;
+mod touch;
+mod trace;
+mod transparency;
+mod viewport;
+mod xdg_foreign;
+
+pub use capabilities::{check_globals, Requirement, REQUIREMENTS};
+pub use decorations::{TitleBarHit, RESIZE_BORDER, TITLE_BAR_HEIGHT};
+pub use dmabuf::{device_name, format_name, modifier_name, DmabufFormats};
+pub use drag_and_drop::DroppedImage;
+pub use error::{ConnectionError, GlInitError};
+pub use frame_stats::FrameStats;
+pub use options::{
+    ArgsError, BasicOptions, BuiltinScene, GlApi, GpuChoice, Options, TakeValue, USAGE,
+};
+pub use overlay::{Overlay, OVERLAY_HEIGHT, OVERLAY_WIDTH};
+pub use popup::{MenuPopup, MENU_ITEMS, MENU_ITEM_HEIGHT, MENU_ITEM_WIDTH};
+pub use reconnect::{run_app, wait_for_compositor};
+pub use shm_buffers::{ShmBuffers, ShmFrame};
+pub use tablet::StrokeSegment;
+pub use viewport::FIXED_RESOLUTION;
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    num::NonZeroU32,
+    path::Path,
+    time::Instant,
+};
+
+use calloop::{
+    signals::{Signal, Signals},
+    timer::{TimeoutAction, Timer},
+    EventLoop, LoopHandle,
+};
+
+use presentation::Presentation;
+use solid_background::SolidBackground;
+use viewport::Viewport;
+
+use smithay_client_toolkit::{
+    activation::ActivationState,
+    compositor::{CompositorHandler, CompositorState},
+    data_device_manager::{data_source::CopyPasteSource, DataDeviceManagerState},
+    delegate_activation, delegate_compositor, delegate_data_device, delegate_dmabuf,
+    delegate_keyboard,
+    delegate_output, delegate_pointer, delegate_pointer_constraints, delegate_primary_selection,
+    delegate_registry, delegate_relative_pointer, delegate_seat, delegate_shm,
+    delegate_subcompositor, delegate_touch, delegate_xdg_popup, delegate_xdg_shell,
+    delegate_xdg_window,
+    dmabuf::DmabufState,
+    output::{OutputHandler, OutputState},
+    primary_selection::{selection::PrimarySelectionSource, PrimarySelectionManagerState},
+    error::GlobalError,
+    globals::ProvidesBoundGlobal,
+    registry::{ProvidesRegistryState, RegistryState},
+    registry_handlers,
+    reexports::{
+        calloop_wayland_source::WaylandSource,
+        csd_frame::{WindowManagerCapabilities, WindowState},
+        protocols::wp::{
+            content_type::v1::client::{
+                wp_content_type_manager_v1::WpContentTypeManagerV1,
+                wp_content_type_v1::WpContentTypeV1,
+            },
+            fractional_scale::v1::client::{
+                wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1,
+                wp_fractional_scale_v1::WpFractionalScaleV1,
+            },
+            idle_inhibit::zv1::client::{
+                zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1,
+                zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1,
+            },
+            keyboard_shortcuts_inhibit::zv1::client::{
+                zwp_keyboard_shortcuts_inhibit_manager_v1::ZwpKeyboardShortcutsInhibitManagerV1,
+                zwp_keyboard_shortcuts_inhibitor_v1::ZwpKeyboardShortcutsInhibitorV1,
+            },
+            linux_dmabuf::zv1::client::zwp_linux_dmabuf_feedback_v1::ZwpLinuxDmabufFeedbackV1,
+            pointer_gestures::zv1::client::zwp_pointer_gestures_v1::ZwpPointerGesturesV1,
+            tablet::zv2::client::zwp_tablet_manager_v2::ZwpTabletManagerV2,
+            presentation_time::client::wp_presentation::WpPresentation,
+            tearing_control::v1::client::{
+                wp_tearing_control_manager_v1::WpTearingControlManagerV1,
+                wp_tearing_control_v1::WpTearingControlV1,
+            },
+            text_input::zv3::client::zwp_text_input_manager_v3::ZwpTextInputManagerV3,
+            single_pixel_buffer::v1::client::wp_single_pixel_buffer_manager_v1::{
+                WpSinglePixelBufferManagerV1,
+            },
+            viewporter::client::wp_viewporter::WpViewporter,
+        },
+        protocols::xdg::foreign::zv2::client::{
+            zxdg_exported_v2::ZxdgExportedV2, zxdg_exporter_v2::ZxdgExporterV2,
+            zxdg_imported_v2::ZxdgImportedV2, zxdg_importer_v2::ZxdgImporterV2,
+        },
+        protocols::xdg::dialog::v1::client::{
+            xdg_dialog_v1::XdgDialogV1, xdg_wm_dialog_v1::XdgWmDialogV1,
+        },
+    },
+    seat::{
+        pointer_constraints::PointerConstraintsState, relative_pointer::RelativePointerState,
+        SeatState,
+    },
+    shm::{Shm, ShmHandler},
+    subcompositor::SubcompositorState,
+    shell::{
+        xdg::{
+            window::{DecorationMode, Window, WindowConfigure, WindowDecorations, WindowHandler},
+            XdgShell,
+        },
+        WaylandSurface,
+    },
+};
+use wayland_client::{
+    backend::ObjectId,
+    globals::registry_queue_init,
+    protocol::{wl_output, wl_seat, wl_shm, wl_surface},
+    Connection, EventQueue, Proxy, QueueHandle,
+};
+
+/// Size used until the compositor tells us otherwise.
+pub const DEFAULT_SIZE: u32 = 256;
+
+/// The Wayland state shared by all windows of an example.
+pub struct SharedState {
+    pub registry_state: RegistryState,
+    pub seat_state: SeatState,
+    pub output_state: OutputState,
+    pub shm: Shm,
+    pub options: Options,
+    /// Leaves the event loop, which also happens once the last window is closed.
+    pub exit: bool,
+    /// Windows unmapped with U that are opened again, see [`remap`].
+    unmapped: u32,
+
+    title: String,
+    app_id: String,
+    compositor_state: CompositorState,
+    /// For the `--overlay` and `--solid-background` subsurfaces, `None` when the compositor
+    /// lacks wl_subcompositor.
+    subcompositor: Option<SubcompositorState>,
+    xdg_shell: XdgShell,
+    /// Whether the compositor has zxdg_decoration_manager_v1. Without it the decorations are
+    /// always ours to draw.
+    decoration_manager: bool,
+    activation: Option<ActivationState>,
+    /// For making dialogs modal.
+    wm_dialog: Option<XdgWmDialogV1>,
+    /// The dialogs waiting for a token to focus their parent again, each with its parent, in
+    /// the order they asked for one.
+    focus_token_requests: VecDeque<(ObjectId, ObjectId)>,
+    /// Only bound with `--export`.
+    exporter: Option<ZxdgExporterV2>,
+    /// Only bound with `--import`.
+    importer: Option<ZxdgImporterV2>,
+    viewporter: Option<WpViewporter>,
+    single_pixel_buffer_manager: Option<WpSinglePixelBufferManagerV1>,
+    /// For the dmabuf feedback of the windows, which only version 4 gives.
+    dmabuf_state: DmabufState,
+    fractional_scale_manager: Option<WpFractionalScaleManagerV1>,
+    idle_inhibit_manager: Option<ZwpIdleInhibitManagerV1>,
+    /// Only bound with `--content-type`.
+    content_type_manager: Option<WpContentTypeManagerV1>,
+    tearing_control_manager: Option<WpTearingControlManagerV1>,
+    presentation: Option<WpPresentation>,
+    /// The clock the compositor reports presentation times in, sent right after binding.
+    presentation_clock: Option<u32>,
+    data_device_manager: Option<DataDeviceManagerState>,
+    /// What we put on the clipboard with Ctrl+C, and the text it holds.
+    copy_source: Option<(CopyPasteSource, String)>,
+    primary_selection_manager: Option<PrimarySelectionManagerState>,
+    /// What we selected with Shift and a drag, and the text it holds.
+    primary_source: Option<(PrimarySelectionSource, String)>,
+    /// The drag we started, if one is underway.
+    drag: Option<drag_source::Drag>,
+    /// The input devices and focus of each seat.
+    seats: HashMap<wl_seat::WlSeat, seat::SeatResources>,
+    text_input_manager: Option<ZwpTextInputManagerV3>,
+    shortcuts_inhibit_manager: Option<ZwpKeyboardShortcutsInhibitManagerV1>,
+    /// The key held down on any seat, only one repeats at a time.
+    key_repeat: key_repeat::KeyRepeat,
+    pointer_constraints: PointerConstraintsState,
+    relative_pointer_state: RelativePointerState,
+    pointer_gestures: Option<ZwpPointerGesturesV1>,
+    tablet_manager: Option<ZwpTabletManagerV2>,
+    /// The lock of mouse look, while it is on.
+    pointer_lock: Option<pointer_constraints::PointerLock>,
+    pointer_confinement: Option<pointer_constraints::PointerConfinement>,
+    /// `--trace`, records the events as they come.
+    trace: trace::Trace,
+    /// `--self-check`, `None` without it or when the compositor can't capture the screen.
+    self_check: Option<self_check::SelfCheck>,
+    conn: Connection,
+}
+
+/// The wl_shm global, for creating shm pools with e.g. `SlotPool::new`.
+#[derive(Clone)]
+pub struct ShmGlobal(wl_shm::WlShm);
+
+impl ProvidesBoundGlobal<wl_shm::WlShm, 1> for ShmGlobal {
+    fn bound_global(&self) -> Result<wl_shm::WlShm, GlobalError> {
+        Ok(self.0.clone())
+    }
+}
+
+/// The state of one window.
+pub struct BaseApp {
+    pub shm: ShmGlobal,
+
+    /// Closes the window, e.g. when its renderer failed to initialize. The app exits with its
+    /// last window.
+    pub close: bool,
+    /// Logical size of the window.
+    pub width: u32,
+    pub height: u32,
+    /// Integer buffer scale of the surface, the renderer draws `width * scale` pixels wide.
+    /// Unused once the compositor sends a fractional scale, see [`BaseApp::scale_factor`].
+    pub scale: i32,
+    /// The scale wl_surface version 6 sends, replaces the guess from the outputs in `scale`.
+    preferred_scale: Option<i32>,
+    /// Transform of the buffer relative to the surface, matches the output we are on so the
+    /// compositor doesn't have to rotate our buffers.
+    pub transform: wl_output::Transform,
+    /// Timestamp of the latest frame callback in milliseconds, drives the animation.
+    pub time: u32,
+    /// Surface-local position of the pointer while it is over the window.
+    pub pointer_position: Option<(f64, f64)>,
+    /// Where the arrow keys moved the triangle from the center of the content area, in
+    /// logical pixels.
+    pub triangle_offset: (f32, f32),
+    /// Whether the window waits for vsync or presents right away, T switches. Renderers follow
+    /// it with [`Self::swap_interval`].
+    pub vsync: bool,
+    /// Whether renderers that only redraw what changed outline it, `--show-damage`.
+    pub show_damage: bool,
+    /// Whether renderers damage the whole surface with every frame, `--full-damage`. A way out
+    /// when partial damage shows glitches.
+    pub full_damage: bool,
+    /// Whether the GL renderers debug their contexts, `--gl-debug`.
+    pub gl_debug: bool,
+    /// Samples per pixel the GL renderers ask for, `--msaa`. They take fewer when the driver
+    /// has no config with as many.
+    pub msaa: u8,
+    /// Whether the GL renderers ask for a depth buffer, `--depth`.
+    pub depth: bool,
+    /// Whether the GL renderers ask for a stencil buffer, `--stencil`.
+    pub stencil: bool,
+    /// Whether the window draws into an sRGB framebuffer, G switches. Renderers clear it when
+    /// the driver has none, the colors look the same either way then.
+    pub srgb: bool,
+    /// The GL API the GL renderers try first, `--api`. Without it they use their default.
+    pub gl_api: Option<GlApi>,
+    /// The GPU the EGL renderer asks for, `--device` or `--gpu`. Without it EGL picks.
+    pub gpu: Option<GpuChoice>,
+    /// Whether the GL renderers print the configs of the display and exit, `--dump-configs`.
+    pub dump_configs: bool,
+    /// Whether the GL renderers print what EGL and GL tell about themselves, `--gl-info`.
+    pub gl_info: bool,
+    /// Whether they exit after that without a window surface, `--gl-info-only`.
+    pub gl_info_only: bool,
+    /// Whether the background is half transparent, `--transparent`. Renderers clear it when the
+    /// config has no alpha, see [`Self::background`].
+    pub transparent: bool,
+    /// Whether clicks outside of a circle in the middle go through the window, I switches.
+    pub click_through: bool,
+    /// How many frames renderers let the GPU queue up for an async window before waiting,
+    /// `--max-frames-in-flight`. `None` leaves the pacing to the frame callbacks.
+    pub max_frames_in_flight: Option<u32>,
+    /// Set by renderers that wait on fences for [`Self::max_frames_in_flight`], see
+    /// [`Self::paced_by_fences`].
+    pub fence_pacing: bool,
+    /// Set by S and SIGUSR1, the renderer saves the next frame as a PNG and clears it. Only
+    /// renderers with [`AppHandler::SCREENSHOTS`] look at it.
+    pub screenshot: bool,
+    /// How far mouse look turned the triangle, in radians.
+    pub look_angle: f32,
+    /// How much two fingers have pinched the triangle larger or smaller, on the screen or the
+    /// touchpad, 1 for its normal size.
+    pub zoom: f32,
+    /// The states of the latest configure.
+    pub window_state: WindowState,
+    /// Renderers record their frame times here, see [`FrameStats::record`].
+    pub frame_stats: FrameStats,
+
+    configured: bool,
+    /// The latest configure and its serial, until [`App::apply_configure`] gets to it.
+    pending_configure: Option<(WindowConfigure, u32)>,
+    frame_callback_pending: bool,
+    frames_drawn: u64,
+    /// When the first frame was drawn, for the summary of scripted runs.
+    first_frame_at: Option<Instant>,
+    /// When the last frame was drawn, `--max-fps` paces by it without presentation feedback.
+    last_frame_at: Option<Instant>,
+    /// Whether a frame held back by `--max-fps` is scheduled, see `App::cap_frame_rate`.
+    capped_redraw_pending: bool,
+    frames_with_callback: u64,
+    frame_callbacks: u64,
+    /// Whether a frame paced by fences is scheduled, see `App::schedule_paced_redraw`.
+    paced_redraw_pending: bool,
+    /// The time of the last frame callback and when it came, for the paced frames in between.
+    last_frame_done: Option<(u32, Instant)>,
+    /// The refresh rate of the outputs the window is on, see [`refresh`].
+    refresh: refresh::Refresh,
+    /// Frames not drawn because the window was suspended, shows that it actually idles.
+    frames_skipped: u64,
+    /// Preferred scale from fractional-scale-v1 in 120ths, takes precedence over `scale`.
+    fractional_scale: Option<u32>,
+    /// The main device of the latest dmabuf feedback, see [`Self::compositor_device`].
+    compositor_device: Option<u64>,
+    /// Kept alive so the preferred scale keeps coming.
+    fractional_scale_object: Option<WpFractionalScaleV1>,
+    viewport: Option<Viewport>,
+    /// Internal resolution while the viewport scales a fixed size buffer to the window.
+    fixed_resolution: Option<(u32, u32)>,
+    /// The buffer size kept during an interactive resize and since when, see
+    /// [`Self::update_resize_hold`].
+    resize_hold: Option<((u32, u32), Instant)>,
+    resize_throttle: bool,
+    /// The opaque region last sent with `--transparent`, in logical coordinates.
+    opaque_region: Vec<[i32; 4]>,
+    /// The input region last sent, in logical coordinates. `None` for the whole surface.
+    input_region: Option<Vec<[i32; 4]>>,
+    /// The `--overlay` subsurface, see [`Self::overlay`].
+    overlay: Option<Overlay>,
+    /// The surfaces of `--solid-background`, see [`Self::solid_content_surface`].
+    solid_background: Option<SolidBackground>,
+    /// Sends the dmabuf feedback of the surface whenever it changes.
+    dmabuf_feedback: Option<ZwpLinuxDmabufFeedbackV1>,
+    presentation: Presentation,
+    floating_size: (u32, u32),
+    /// The largest size the compositor suggests for a floating window, e.g. the work area of
+    /// its output, from the latest configure that had it.
+    bounds: Option<(u32, u32)>,
+    /// What the compositor lets us do with the window, from the latest configure.
+    capabilities: WindowManagerCapabilities,
+    /// Whether we asked to maximize (true) or unmaximize (false) and the compositor has yet to
+    /// answer. Tells our own requests apart from maximizing the compositor does on its own.
+    maximize_request: Option<bool>,
+    /// Set by a minimize request until the window is suspended or active again. xdg-shell has
+    /// no minimized state, suspended is the closest.
+    minimize_requested: bool,
+    /// The `--fixed-aspect` ratio, see [`Self::content_area`].
+    fixed_aspect: Option<(u32, u32)>,
+    /// The title last sent, and the frame time of when, see [`Self::update_title`].
+    title: String,
+    title_updated: Option<u32>,
+    /// The outputs the window is on, in the order it entered them.
+    outputs: Vec<wl_output::WlOutput>,
+    /// Time and position of the last left click, for detecting double clicks.
+    last_click: Option<(u32, f64, f64)>,
+    /// The active touch points on the window.
+    touches: touch::Touches,
+    /// Set by Space, stops the frame loop and the animation time.
+    paused: bool,
+    /// Like `paused`, set with `--pause-inactive` while the window isn't activated.
+    inactive: bool,
+    pause_inactive: bool,
+    /// The first frame after a pause picks the time up where it stopped.
+    resuming: bool,
+    /// How far the animation time lags the frame callback time, the sum of the pauses.
+    time_offset: u32,
+    /// Keeps the screen from blanking while the window animates.
+    idle_inhibitor: Option<ZwpIdleInhibitorV1>,
+    /// Holds the `--content-type` hint of the surface.
+    content_type: Option<WpContentTypeV1>,
+    /// Carries the presentation hint T switches.
+    tearing_control: Option<WpTearingControlV1>,
+    /// The handle of the window with `--export`.
+    exported: Option<ZxdgExportedV2>,
+    /// The window of another client this one is a dialog of, `--import`. Gone along with that
+    /// window.
+    imported: Option<ZxdgImportedV2>,
+    /// The window this one is a dialog of, see [`Self::is_dialog`].
+    parent: Option<ObjectId>,
+    /// Makes the dialog modal.
+    dialog: Option<XdgDialogV1>,
+    /// The token for focusing the parent again, once it came.
+    focus_token: Option<String>,
+    /// Whether the pointer is confined to the window, see [`Self::confinement_outline`].
+    confined: bool,
+    /// Position and serial of a left button press in the content area, a drag starts once the
+    /// pointer moves far enough from it.
+    drag_start: Option<(f64, f64, u32)>,
+    decoration_mode: Option<DecorationMode>,
+    /// Color pasted from the clipboard, replaces the animated clear color.
+    pasted_color: Option<[f32; 3]>,
+    /// What the input method typed, see [`Self::text_rects`].
+    text_field: text_input::TextField,
+    /// What a tablet drew since the renderer last took it, see [`Self::take_strokes`].
+    sketch: tablet::Sketch,
+    /// Whether F10 asked for the compositor's shortcuts, the inhibitor is only there while the
+    /// window has keyboard focus.
+    capture_shortcuts: bool,
+    shortcuts_inhibitor: Option<ZwpKeyboardShortcutsInhibitorV1>,
+    /// Whether the compositor activated the inhibitor, see [`Self::shortcuts_badge_rects`].
+    shortcuts_inhibited: bool,
+    /// Whether the window fills with the color `--self-check` looks for, see [`Self::plain`].
+    self_check: bool,
+    window: Window,
+    conn: Connection,
+}
+
+impl Drop for BaseApp {
+    fn drop(&mut self) {
+        // The surface extensions go before the surface itself, which is destroyed with `window`,
+        // and so do the subsurfaces on top of it. The renderer let go of its surfaces already.
+        if let Some(overlay) = &self.overlay {
+            overlay.destroy();
+        }
+        if let Some(solid_background) = &self.solid_background {
+            solid_background.destroy();
+        }
+        if let Some(viewport) = &self.viewport {
+            viewport.destroy();
+        }
+        if let Some(fractional_scale) = &self.fractional_scale_object {
+            fractional_scale.destroy();
+        }
+        if let Some(idle_inhibitor) = &self.idle_inhibitor {
+            idle_inhibitor.destroy();
+        }
+        if let Some(content_type) = &self.content_type {
+            content_type.destroy();
+        }
+        if let Some(shortcuts_inhibitor) = &self.shortcuts_inhibitor {
+            shortcuts_inhibitor.destroy();
+        }
+        if let Some(tearing_control) = &self.tearing_control {
+            tearing_control.destroy();
+        }
+        if let Some(exported) = &self.exported {
+            exported.destroy();
+        }
+        if let Some(imported) = &self.imported {
+            imported.destroy();
+        }
+        if let Some(dialog) = &self.dialog {
+            dialog.destroy();
+        }
+        if let Some(dmabuf_feedback) = &self.dmabuf_feedback {
+            dmabuf_feedback.destroy();
+        }
+    }
+}
+
+impl BaseApp {
+    /// The xdg toplevel, e.g. for getting at the `wl_surface` to render into.
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+
+    /// The id of the window's `wl_surface`, which is its key in [`App::windows`]. Renderers use
+    /// it to key their per-window state.
+    pub fn id(&self) -> ObjectId {
+        self.window.wl_surface().id()
+    }
+
+    /// The outputs the window is currently on, most recently entered last.
+    pub fn outputs(&self) -> &[wl_output::WlOutput] {
+        &self.outputs
+    }
+
+    /// The connection, e.g. for getting the `wl_display` pointer for EGL.
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Updates the logical size of the window from a configure. Returns whether the buffer size
+    /// changed with it, i.e. whether the renderer has to resize. At a fixed resolution, and for
+    /// a while during an interactive resize, only the viewport follows the window.
+    pub fn set_size(&mut self, width: u32, height: u32) -> bool {
+        let buffer_size = self.buffer_size();
+        let unrotated = self.unrotated_buffer_size();
+        self.width = width;
+        self.height = height;
+        self.update_resize_hold(unrotated);
+        self.buffer_size() != buffer_size
+    }
+
+    /// Size of the window in physical pixels, this is what the renderer should allocate. With a
+    /// 90 or 270 degree transform the buffer is taller than wide for a wide window. At a fixed
+    /// resolution this is the internal resolution, whatever the window size.
+    pub fn buffer_size(&self) -> (u32, u32) {
+        let (width, height) = self.unrotated_buffer_size();
+        if transform_swaps_axes(self.transform) {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// Maps a rectangle in logical surface coordinates (origin top left) to a rectangle in GL
+    /// buffer coordinates (origin bottom left), taking the scale and transform into account.
+    /// Returned as `[x, y, width, height]`, ready for `glScissor`.
+    pub fn buffer_rect(&self, x: u32, y: u32, width: u32, height: u32) -> [i32; 4] {
+        let (x0, y0) = self.buffer_point(x, y);
+        let (x1, y1) = self.buffer_point(x + width, y + height);
+        let (_, buffer_height) = self.buffer_size();
+        let (left, right) = (x0.min(x1), x0.max(x1));
+        let (top, bottom) = (y0.min(y1), y0.max(y1));
+        [
+            left as i32,
+            buffer_height as i32 - bottom as i32,
+            (right - left) as i32,
+            (bottom - top) as i32,
+        ]
+    }
+
+    /// A column-major 2x2 matrix taking normalized device coordinates of the upright surface to
+    /// those of the buffer, i.e. it pre-rotates content for the buffer transform. Scaling is left
+    /// out, the viewport covers that.
+    pub fn ndc_transform(&self) -> [f32; 4] {
+        use wl_output::Transform;
+
+        match self.transform {
+            Transform::_90 => [0.0, 1.0, -1.0, 0.0],
+            Transform::_180 => [-1.0, 0.0, 0.0, -1.0],
+            Transform::_270 => [0.0, -1.0, 1.0, 0.0],
+            Transform::Flipped => [-1.0, 0.0, 0.0, 1.0],
+            Transform::Flipped90 => [0.0, -1.0, -1.0, 0.0],
+            Transform::Flipped180 => [1.0, 0.0, 0.0, -1.0],
+            Transform::Flipped270 => [0.0, 1.0, 1.0, 0.0],
+            _ => [1.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    /// The buffer size before the transform is applied.
+    fn unrotated_buffer_size(&self) -> (u32, u32) {
+        match (self.fixed_resolution, self.resize_hold) {
+            (Some(resolution), _) => resolution,
+            (None, Some((held, _))) => held,
+            (None, None) => (self.to_physical(self.width), self.to_physical(self.height)),
+        }
+    }
+
+    /// Maps a point in logical surface coordinates to buffer coordinates (origin top left).
+    fn buffer_point(&self, x: u32, y: u32) -> (u32, u32) {
+        use wl_output::Transform;
+
+        // First scale into physical pixels, then rotate within the buffer
+        let (x, y) = (x.min(self.width), y.min(self.height));
+        let (x, y) = match (self.fixed_source(), self.resize_hold) {
+            (Some([source_x, source_y, source_width, source_height]), _) => (
+                source_x + x * source_width / self.width.max(1),
+                source_y + y * source_height / self.height.max(1),
+            ),
+            // The held buffer is stretched over the whole window
+            (None, Some(((held_width, held_height), _))) => {
+                (x * held_width / self.width.max(1), y * held_height / self.height.max(1))
+            }
+            (None, None) => (self.to_physical(x), self.to_physical(y)),
+        };
+
+        let (w, h) = self.unrotated_buffer_size();
+        match self.transform {
+            Transform::Flipped => (w - x, y),
+            Transform::_90 => (y, w - x),
+            Transform::Flipped90 => (y, x),
+            Transform::_180 => (w - x, h - y),
+            Transform::Flipped180 => (x, h - y),
+            Transform::_270 => (h - y, x),
+            Transform::Flipped270 => (h - y, w - x),
+            _ => (x, y),
+        }
+    }
+
+    /// An asymmetric pattern for telling whether the content is upright: a bar along the top
+    /// edge and a block in the top left corner of the content area. In buffer coordinates, see
+    /// [`Self::buffer_rect`]. Empty rectangles for [`Self::plain`] windows.
+    pub fn test_pattern(&self) -> [[i32; 4]; 2] {
+        if self.plain() {
+            return [[0; 4]; 2];
+        }
+        let top = self.title_bar_height();
+        test_pattern_rects(self.width, self.height - top)
+            .map(|[x, y, width, height]| self.buffer_rect(x, top + y, width, height))
+    }
+
+    /// The color the examples clear to. While the pointer is over the window its position picks
+    /// red and green, otherwise the hue cycles with the frame callbacks. A color pasted with
+    /// Ctrl+V replaces both. Dialogs are a plain color. Inactive windows are dimmed.
+    pub fn clear_color(&self) -> [f32; 3] {
+        if self.self_check {
+            // `--self-check` finds the window on the screen by this color, as it is
+            return self_check::CHECK_COLOR;
+        }
+        let [red, green, blue] = animated_color(self.time);
+        let color = match (self.pasted_color, self.pointer_position) {
+            _ if self.is_dialog() => dialog::DIALOG_COLOR,
+            (Some(color), _) => color,
+            (None, Some((x, y))) => [
+                (x / self.width as f64).clamp(0.0, 1.0) as f32,
+                (y / self.height as f64).clamp(0.0, 1.0) as f32,
+                blue,
+            ],
+            (None, None) => [red, green, blue],
+        };
+
+        if self.window_state.contains(WindowState::ACTIVATED) {
+            color
+        } else {
+            color.map(|c| c * 0.6)
+        }
+    }
+
+    /// The buffer size for APIs that take `NonZeroU32`, `None` while the window has no area.
+    pub fn non_zero_buffer_size(&self) -> Option<(NonZeroU32, NonZeroU32)> {
+        let (width, height) = self.buffer_size();
+        Some((NonZeroU32::new(width)?, NonZeroU32::new(height)?))
+    }
+
+    /// The size to use for a configure. Maximized, fullscreen and tiled windows get their size
+    /// from the compositor, when it leaves the size up to us we go back to the floating size.
+    ///
+    /// A zero width or height in the configure (which sctk hands us as `None`) means the same,
+    /// including on the very first configure, so this never returns a zero size. The floating
+    /// size starts out as `--size` and is only ever updated from non-zero sizes. It is
+    /// kept within the bounds the compositor suggests, so the window doesn't start out larger
+    /// than the work area.
+    pub fn configured_size(&self, configure: &WindowConfigure) -> (u32, u32) {
+        let (new_width, new_height) = configure.new_size;
+        let (mut floating_width, mut floating_height) = self.floating_size;
+        if let Some((bound_width, bound_height)) = self.bounds {
+            floating_width = floating_width.min(bound_width);
+            floating_height = floating_height.min(bound_height);
+        }
+        (
+            new_width.map_or(floating_width, |v| v.get()).max(1),
+            new_height.map_or(floating_height, |v| v.get()).max(1),
+        )
+    }
+
+    /// Whether the compositor suspended the window, e.g. because it is fully occluded or on
+    /// another workspace. Nothing is drawn until it is active again.
+    pub fn suspended(&self) -> bool {
+        self.window_state.contains(WindowState::SUSPENDED)
+    }
+
+    /// Logs the states that changed compared to the previous configure and remembers them.
+    fn update_window_state(&mut self, configure: &WindowConfigure) {
+        let changed = self.window_state ^ configure.state;
+        for (flag, name) in [
+            (WindowState::MAXIMIZED, "maximized"),
+            (WindowState::FULLSCREEN, "fullscreen"),
+            (WindowState::TILED, "tiled"),
+            (WindowState::ACTIVATED, "activated"),
+            (WindowState::SUSPENDED, "suspended"),
+        ] {
+            if changed.intersects(flag) {
+                let now = if configure.state.intersects(flag) { "now" } else { "no longer" };
+                log::info!("window is {now} {name}");
+            }
+        }
+        if changed.contains(WindowState::SUSPENDED)
+            && !configure.state.contains(WindowState::SUSPENDED)
+        {
+            log::info!("{} frames skipped while suspended so far", self.frames_skipped);
+            // Like after a pause, the animation goes on from where it stopped
+            self.resuming = true;
+        }
+        if self.pause_inactive && changed.contains(WindowState::ACTIVATED) {
+            self.set_inactive(!configure.state.contains(WindowState::ACTIVATED));
+        }
+        self.window_state = configure.state;
+        if self.capabilities != configure.capabilities {
+            log::info!("window manager capabilities: {:?}", configure.capabilities);
+            self.capabilities = configure.capabilities;
+        }
+        // Zero means the compositor doesn't know the bounds, e.g. while the output is unknown
+        let bounds = configure.suggested_bounds.filter(|&(width, height)| width > 0 && height > 0);
+        if bounds.is_some() && bounds != self.bounds {
+            log::info!("suggested bounds: {bounds:?}");
+            self.bounds = bounds;
+        }
+
+        // A configure answers the request, whether or not the state changed with it
+        let maximized = configure.state.contains(WindowState::MAXIMIZED);
+        match self.maximize_request.take() {
+            Some(wanted) if wanted != maximized => {
+                log::info!("the compositor ignored the request to {}", maximize_verb(wanted));
+            }
+            Some(_) => {}
+            None if changed.contains(WindowState::MAXIMIZED) => {
+                log::info!("the compositor chose to {}", maximize_verb(maximized));
+            }
+            None => {}
+        }
+        if self.minimize_requested && changed.contains(WindowState::SUSPENDED) {
+            log::info!("window minimized");
+        }
+        if configure.state.intersects(WindowState::SUSPENDED | WindowState::ACTIVATED) {
+            self.minimize_requested = false;
+        }
+
+        if self.decoration_mode != Some(configure.decoration_mode) {
+            log::info!("decoration mode: {:?}", configure.decoration_mode);
+            self.decoration_mode = Some(configure.decoration_mode);
+        }
+    }
+}
+
+/// The part of an example that differs between the renderers. Every window gets its own
+/// renderer state, the handler keys it by [`BaseApp::id`].
+pub trait AppHandler: Sized + 'static {
+    /// Called for the latest configure of a window once the events are dispatched, those it
+    /// superseded are skipped. The first one is where the renderer should set up its state for
+    /// the window. A redraw always follows, so this should not draw itself.
+    fn configure(
+        &mut self,
+        base: &mut BaseApp,
+        qh: &QueueHandle<App<Self>>,
+        configure: WindowConfigure,
+        serial: u32,
+    );
+
+    /// The buffer size changed because of a new scale factor or a configure, the renderer should
+    /// resize its surface to [`BaseApp::buffer_size`]. May be called before the first configure.
+    fn resize(&mut self, base: &mut BaseApp);
+
+    /// Render a frame into the window. The frame callback for the next frame has already been
+    /// requested, it is sent with whatever commit the renderer does.
+    fn draw(&mut self, base: &mut BaseApp);
+
+    /// The window is about to be destroyed, the renderer should free whatever it holds for it.
+    /// Not called for the windows still open when the event loop exits.
+    fn window_closed(&mut self, base: &mut BaseApp);
+
+    /// Whether a right click opens the popup menu, which is then drawn with
+    /// [`Self::draw_popup`].
+    const POPUP_MENU: bool = false;
+
+    /// Whether [`Self::draw`] saves the frame as a PNG while [`BaseApp::screenshot`] is set.
+    const SCREENSHOTS: bool = false;
+
+    /// Whether [`Self::draw`] draws [`BaseApp::overlay`] too. Without it `--overlay` does
+    /// nothing.
+    const OVERLAY: bool = false;
+
+    /// Whether [`Self::draw`] draws into [`BaseApp::solid_content_surface`] while the window
+    /// has one. Without it `--solid-background` does nothing.
+    const SOLID_BACKGROUND: bool = false;
+
+    /// Render the menu into its surface and commit, called for every configure of the popup.
+    fn draw_popup(&mut self, _popup: &mut MenuPopup) {}
+
+    /// The menu is about to be destroyed, the renderer should free whatever it holds for it.
+    fn popup_closed(&mut self, _popup: &mut MenuPopup) {}
+
+    /// The window switched between vsync and async presentation, renderers with a swap
+    /// interval should set it to [`BaseApp::swap_interval`].
+    fn vsync_changed(&mut self, _base: &mut BaseApp) {}
+
+    /// G switched [`BaseApp::srgb`]. The colorspace belongs to the surface, so renderers that
+    /// follow it create the surface again. A redraw follows.
+    fn srgb_changed(&mut self, base: &mut BaseApp) {
+        println!("this renderer has no sRGB framebuffer");
+        base.srgb = false;
+    }
+
+    /// R asks the renderer to act as if the GPU was reset, so the recovery from a lost context
+    /// can be tried without crashing a real GPU. A redraw follows.
+    fn simulate_context_loss(&mut self, _base: &mut BaseApp) {
+        println!("this renderer has no context to lose");
+    }
+
+    /// `--dmabuf-info` printed the formats the compositor takes dmabufs in, renderers that
+    /// know which ones they can import compare the two.
+    fn dmabuf_info(&mut self, _base: &mut BaseApp, _formats: &DmabufFormats) {
+        println!("this renderer has no dmabuf formats to compare with");
+    }
+
+    /// A PNG was dropped onto the window. Renderers that show it keep drawing it from then on,
+    /// the others just say they can't.
+    fn image_dropped(&mut self, _base: &mut BaseApp, image: DroppedImage) {
+        println!("this renderer can't show the {}x{} image", image.width, image.height);
+    }
+}
+
+pub struct App<H> {
+    // Declared before `windows` so the renderer is dropped before the windows it renders into,
+    // and the popup before the window it belongs to.
+    pub handler: H,
+    popup: Option<MenuPopup>,
+    /// Handle of the calloop loop while it runs, for watching the pipes of clipboard transfers.
+    loop_handle: Option<LoopHandle<'static, Self>>,
+    /// The open windows, keyed by the id of their `wl_surface`.
+    pub windows: HashMap<ObjectId, BaseApp>,
+    pub shared: SharedState,
+}
+
+/// Connects to the compositor and creates the first window, nothing is drawn until its first
+/// configure.
+pub fn init<H: AppHandler>(title: &str, app_id: &str, handler: H) -> (App<H>, EventQueue<App<H>>) {
+    init_with_options(title, app_id, handler, Options::from_args())
+}
+
+/// [`init`] with options parsed already, for examples that look at them before connecting.
+pub fn init_with_options<H: AppHandler>(
+    title: &str,
+    app_id: &str,
+    handler: H,
+    options: Options,
+) -> (App<H>, EventQueue<App<H>>) {
+    let conn = Connection::connect_to_env().unwrap();
+    let (globals, mut event_queue) = registry_queue_init(&conn).unwrap();
+    let qh = event_queue.handle();
+    capabilities::check_globals(&globals, REQUIREMENTS);
+
+    // Initialize xdg_shell handlers, the check above makes sure they are there
+    let compositor_state =
+        CompositorState::bind(&globals, &qh).expect("wl_compositor not available");
+    let subcompositor =
+        SubcompositorState::bind(compositor_state.wl_compositor().clone(), &globals, &qh).ok();
+    let xdg_shell = XdgShell::bind(&globals, &qh).expect("xdg shell not available");
+    let shm = Shm::bind(&globals, &qh).expect("wl_shm not available");
+    let decoration_manager = globals.contents().with_list(|list| {
+        list.iter().any(|global| global.interface == "zxdg_decoration_manager_v1")
+    });
+    if !decoration_manager {
+        log::info!("zxdg_decoration_manager_v1 not available, drawing client-side decorations");
+    }
+    let mouse_look = globals.contents().with_list(|list| {
+        ["zwp_pointer_constraints_v1", "zwp_relative_pointer_manager_v1"]
+            .iter()
+            .all(|interface| list.iter().any(|global| &global.interface == interface))
+    });
+    if !mouse_look {
+        log::info!("pointer constraints or relative pointers not available, no mouse look");
+    }
+
+    let content_type_manager = content_type::bind_manager(&globals, &qh, options.content_type);
+    let exporter = xdg_foreign::bind_exporter(&globals, &qh, options.export);
+    let self_check = self_check::bind(&globals, &qh, options.self_check);
+    let importer = xdg_foreign::bind_importer(&globals, &qh, options.import.as_deref());
+    let title = options.title.clone().unwrap_or_else(|| title.to_string());
+    let app_id = options.app_id.clone().unwrap_or_else(|| app_id.to_string());
+
+    let trace = trace::Trace::open(options.trace.as_deref());
+    let mut app = App {
+        handler,
+        popup: None,
+        loop_handle: None,
+        windows: HashMap::new(),
+        shared: SharedState {
+            registry_state: RegistryState::new(&globals),
+            seat_state: SeatState::new(&globals, &qh),
+            output_state: OutputState::new(&globals, &qh),
+            shm,
+            options,
+            exit: false,
+            unmapped: 0,
+
+            title,
+            app_id,
+            compositor_state,
+            subcompositor,
+            xdg_shell,
+            decoration_manager,
+            activation: ActivationState::bind(&globals, &qh).ok(),
+            wm_dialog: dialog::bind_manager(&globals, &qh),
+            focus_token_requests: VecDeque::new(),
+            exporter,
+            importer,
+            viewporter: viewport::bind_viewporter(&globals, &qh),
+            single_pixel_buffer_manager: solid_background::bind_manager(&globals, &qh),
+            dmabuf_state: dmabuf::bind(&globals, &qh),
+            fractional_scale_manager: fractional_scale::bind_manager(&globals, &qh),
+            idle_inhibit_manager: idle_inhibit::bind_manager(&globals, &qh),
+            content_type_manager,
+            tearing_control_manager: tearing_control::bind_manager(&globals, &qh),
+            presentation: presentation::bind(&globals, &qh),
+            presentation_clock: None,
+            data_device_manager: DataDeviceManagerState::bind(&globals, &qh).ok(),
+            copy_source: None,
+            primary_selection_manager: PrimarySelectionManagerState::bind(&globals, &qh).ok(),
+            primary_source: None,
+            drag: None,
+            seats: HashMap::new(),
+            text_input_manager: text_input::bind_manager(&globals, &qh),
+            shortcuts_inhibit_manager: shortcuts_inhibit::bind_manager(&globals, &qh),
+            key_repeat: Default::default(),
+            pointer_constraints: PointerConstraintsState::bind(&globals, &qh),
+            relative_pointer_state: RelativePointerState::bind(&globals, &qh),
+            pointer_gestures: pointer_gestures::bind_manager(&globals, &qh),
+            tablet_manager: tablet::bind_manager(&globals, &qh),
+            pointer_lock: None,
+            pointer_confinement: None,
+            trace,
+            self_check,
+            conn,
+        },
+    };
+    // The outputs are bound along with the registry, their names come with their first events
+    if let Some(name) = app.shared.options.fullscreen_output.clone() {
+        event_queue.roundtrip(&mut app).expect("Failed to wait for the outputs");
+        if app.shared.find_output(&name).is_none() {
+            eprintln!("--fullscreen: no output is named {name:?} or of that model, there are:");
+            for output in app.shared.output_names() {
+                eprintln!("  {output}");
+            }
+            std::process::exit(2);
+        }
+    }
+    app.open_window(&qh);
+
+    (app, event_queue)
+}
+
+impl<H: AppHandler> App<H> {
+    /// Runs the event loop until the last window is closed or we get SIGINT. SIGUSR1 saves a
+    /// screenshot of every window.
+    ///
+    /// Besides the Wayland socket the loop drives a timer that redraws while no frame callback
+    /// is in flight, so the animation recovers should the callbacks ever stop coming. With
+    /// `--poll-loop` a hand written `poll(2)` loop is used instead.
+    ///
+    /// Fails when the connection does, the windows are of no use then and [`run_app`] builds
+    /// the app again on a new connection.
+    pub fn run(&mut self, event_queue: EventQueue<Self>) -> Result<(), ConnectionError> {
+        let result = if self.shared.options.poll_loop {
+            self.run_poll_loop(event_queue)
+        } else {
+            self.run_event_loop(event_queue)
+        };
+        self.finish();
+        result
+    }
+
+    /// Prints the summaries of `--exit-after-frames`, `--exit-after-seconds` and `--bench`, and
+    /// closes the popup. For loops of the application, [`Self::run`] does this itself.
+    pub fn finish(&mut self) {
+        let options = &self.shared.options;
+        if options.exit_after_frames.is_some() || options.exit_after_seconds.is_some() {
+            self.print_summary();
+        }
+        if options.bench {
+            self.print_bench();
+        }
+        self.shared.trace.finish();
+
+        // The menu has to go before its window, and while the renderer is still around
+        self.close_popup();
+    }
+
+    /// The connection to the compositor.
+    pub fn connection(&self) -> &Connection {
+        &self.shared.conn
+    }
+
+    /// What has to happen after every dispatch: closing the windows that asked for it and
+    /// applying the configures that came in. Loops of the application call this, then leave
+    /// once [`SharedState::exit`] is set.
+    pub fn after_dispatch(&mut self, qh: &QueueHandle<Self>) {
+        self.close_requested_windows();
+        self.apply_configures(qh, false);
+    }
+
+    fn run_event_loop(&mut self, event_queue: EventQueue<Self>) -> Result<(), ConnectionError> {
+        let qh = event_queue.handle();
+        let mut event_loop: EventLoop<Self> =
+            EventLoop::try_new().expect("Failed to create the event loop");
+        let handle = event_loop.handle();
+        self.loop_handle = Some(handle.clone());
+
+        // We don't draw immediately, the configure will notify us when to first draw.
+        WaylandSource::new(self.shared.conn.clone(), event_queue)
+            .insert(handle.clone())
+            .expect("Failed to insert the Wayland source");
+
+        let interval = self.shared.options.redraw_interval;
+        let timer_qh = qh.clone();
+        handle
+            .insert_source(Timer::from_duration(interval), move |_, _, app| {
+                app.redraw_idle_windows(&timer_qh);
+                TimeoutAction::ToDuration(interval)
+            })
+            .expect("Failed to insert the redraw timer");
+
+        // SIGUSR1 saves a screenshot of every window, e.g. `pkill -USR1 glow-with-glutin`
+        let configure_qh = qh.clone();
+        let signals = Signals::new(&[Signal::SIGINT, Signal::SIGUSR1])
+            .expect("Failed to block SIGINT and SIGUSR1");
+        handle
+            .insert_source(signals, move |event, _, app| match event.signal() {
+                Signal::SIGUSR1 => {
+                    let ids: Vec<ObjectId> = app.windows.keys().cloned().collect();
+                    for id in ids {
+                        app.take_screenshot(&qh, &id);
+                    }
+                }
+                _ => {
+                    println!("interrupted");
+                    app.shared.exit = true;
+                }
+            })
+            .expect("Failed to insert the signal source");
+
+        if let Some(duration) = self.shared.options.exit_after_seconds {
+            handle
+                .insert_source(Timer::from_duration(duration), |_, _, app| {
+                    app.shared.exit = true;
+                    TimeoutAction::Drop
+                })
+                .expect("Failed to insert the exit timer");
+        }
+
+        let signal = event_loop.get_signal();
+        let result = event_loop.run(None, self, |app| {
+            app.after_dispatch(&configure_qh);
+            if app.shared.exit {
+                println!("exiting example");
+                signal.stop();
+            }
+        });
+        // Drops the sources of unfinished pastes along with the loop
+        self.loop_handle = None;
+        // The Wayland source reports every failure as an IO error, EPROTO for protocol errors
+        result.map_err(|err| match err {
+            calloop::Error::IoError(err) => ConnectionError::io(&self.shared.conn, err),
+            err => ConnectionError::io(&self.shared.conn, io::Error::other(err)),
+        })
+    }
+
+    /// Prints what each window drew, for `--exit-after-frames` and `--exit-after-seconds`.
+    fn print_summary(&self) {
+        for (id, base) in &self.windows {
+            let seconds = base.first_frame_at.map_or(0.0, |at| at.elapsed().as_secs_f64());
+            let fps = if seconds > 0.0 { base.frame_callbacks as f64 / seconds } else { 0.0 };
+            println!(
+                "window {id}: {} frames drawn, {} shown in {seconds:.2} s, {fps:.1} fps, {}x{}",
+                base.frames_drawn, base.frame_callbacks, base.width, base.height
+            );
+        }
+    }
+
+    /// Prints the `--bench` results of each window, the CSV file is named after the binary so
+    /// the runs of the examples don't overwrite each other.
+    fn print_bench(&self) {
+        let binary = std::env::current_exe()
+            .ok()
+            .and_then(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "example".to_string());
+        for (index, base) in self.windows.values().enumerate() {
+            let csv = match index {
+                0 => format!("bench-{binary}.csv"),
+                _ => format!("bench-{binary}-{index}.csv"),
+            };
+            base.frame_stats.print_bench(Path::new(&csv));
+        }
+    }
+
+    /// Opens another window. Like the first one it is set up on its first configure.
+    pub fn open_window(&mut self, qh: &QueueHandle<Self>) -> ObjectId {
+        // Only the first window is raised with the token, it's good for one use
+        let activate_token = self.shared.options.activate_token.take();
+        // Likewise only the first window is a dialog of the imported one, and starts fullscreen
+        // or maximized
+        let import = self.shared.options.import.take();
+        let fullscreen = std::mem::take(&mut self.shared.options.fullscreen);
+        let maximized = std::mem::take(&mut self.shared.options.maximized);
+        let shared = &self.shared;
+        let surface = shared.compositor_state.create_surface(qh);
+        // Only a preference, the configure tells which mode the compositor picked
+        let window = shared.xdg_shell.create_window(surface, WindowDecorations::RequestServer, qh);
+        let title = title::limit_title(shared.title.clone());
+        window.set_title(title.clone());
+        window.set_app_id(shared.app_id.clone());
+        window.set_min_size(Some(shared.options.min_size));
+        window.set_max_size(shared.options.max_size);
+        let viewport = shared
+            .viewporter
+            .as_ref()
+            .map(|viewporter| Viewport::new(viewporter, qh, &window));
+        // Fractional scales are only usable through a viewport
+        let fractional_scale_object = viewport
+            .as_ref()
+            .and(shared.fractional_scale_manager.as_ref())
+            .map(|manager| fractional_scale::get_fractional_scale(manager, qh, &window));
+        let content_type = shared
+            .content_type_manager
+            .as_ref()
+            .zip(shared.options.content_type)
+            .map(|(manager, content_type)| {
+                content_type::set_content_type(manager, qh, &window, content_type)
+            });
+        let tearing_control = shared
+            .tearing_control_manager
+            .as_ref()
+            .map(|manager| {
+                tearing_control::get_tearing_control(manager, qh, &window, shared.options.vsync)
+            });
+        let dmabuf_feedback =
+            shared.dmabuf_state.get_surface_feedback(window.wl_surface(), qh).ok();
+        let exported =
+            shared.exporter.as_ref().map(|exporter| xdg_foreign::export(exporter, qh, &window));
+        let imported = shared
+            .importer
+            .as_ref()
+            .zip(import)
+            .map(|(importer, handle)| xdg_foreign::import(importer, qh, &window, handle));
+        // Before the overlay, so the overlay stacks above the content
+        let solid_background = match (
+            &shared.single_pixel_buffer_manager,
+            &shared.subcompositor,
+            &shared.viewporter,
+        ) {
+            _ if !shared.options.solid_background => None,
+            _ if !H::SOLID_BACKGROUND => {
+                println!("this renderer draws no solid background");
+                None
+            }
+            (Some(manager), Some(subcompositor), Some(viewporter)) => Some(SolidBackground::new(
+                manager,
+                subcompositor,
+                &shared.compositor_state,
+                viewporter,
+                window.wl_surface(),
+                qh,
+            )),
+            _ => {
+                println!(
+                    "--solid-background needs wp_single_pixel_buffer_manager_v1, \
+                     wl_subcompositor and wp_viewporter, clearing with GL instead"
+                );
+                None
+            }
+        };
+        let overlay = match &shared.subcompositor {
+            _ if !shared.options.overlay => None,
+            Some(subcompositor) if H::OVERLAY => Some(Overlay::new(
+                subcompositor,
+                &shared.compositor_state,
+                window.wl_surface(),
+                qh,
+            )),
+            Some(_) => {
+                println!("this renderer draws no overlay");
+                None
+            }
+            None => {
+                println!("--overlay needs wl_subcompositor, which the compositor lacks");
+                None
+            }
+        };
+        // The token goes with the initial commit, so the window is raised as soon as it maps
+        if let Some(token) = activate_token {
+            match &shared.activation {
+                Some(activation) => {
+                    log::info!("activating the window with token {token}");
+                    activation.activate::<Self>(window.wl_surface(), token);
+                }
+                None => log::info!("xdg_activation_v1 not available, ignoring the activation token"),
+            }
+        }
+        // Before the initial commit, so the first configure has the state already
+        if fullscreen {
+            let name = shared.options.fullscreen_output.as_deref();
+            let output = name.and_then(|name| shared.find_output(name));
+            window.set_fullscreen(output.as_ref());
+        }
+        if maximized {
+            window.set_maximized();
+        }
+        window.commit();
+
+        let base = BaseApp {
+            shm: ShmGlobal(shared.shm.wl_shm().clone()),
+
+            close: false,
+            width: DEFAULT_SIZE,
+            height: DEFAULT_SIZE,
+            scale: 1,
+            preferred_scale: None,
+            transform: wl_output::Transform::Normal,
+            time: 0,
+            pointer_position: None,
+            triangle_offset: (0.0, 0.0),
+            vsync: shared.options.vsync,
+            show_damage: shared.options.show_damage,
+            full_damage: shared.options.full_damage,
+            gl_debug: shared.options.gl_debug,
+            msaa: shared.options.msaa,
+            depth: shared.options.depth,
+            stencil: shared.options.stencil,
+            srgb: shared.options.srgb,
+            gl_api: shared.options.gl_api,
+            gpu: shared.options.gpu.clone(),
+            dump_configs: shared.options.dump_configs,
+            gl_info: shared.options.gl_info,
+            gl_info_only: shared.options.gl_info_only,
+            transparent: shared.options.transparent,
+            click_through: shared.options.click_through,
+            max_frames_in_flight: shared.options.max_frames_in_flight,
+            fence_pacing: false,
+            screenshot: false,
+            look_angle: 0.0,
+            zoom: 1.0,
+            window_state: WindowState::empty(),
+            frame_stats: FrameStats::new(shared.options.stats, shared.options.bench),
+
+            configured: false,
+            pending_configure: None,
+            frame_callback_pending: false,
+            frames_drawn: 0,
+            first_frame_at: None,
+            last_frame_at: None,
+            capped_redraw_pending: false,
+            frames_with_callback: 0,
+            frame_callbacks: 0,
+            paced_redraw_pending: false,
+            last_frame_done: None,
+            refresh: Default::default(),
+            frames_skipped: 0,
+            fractional_scale: None,
+            compositor_device: None,
+            fractional_scale_object,
+            viewport,
+            fixed_resolution: None,
+            resize_hold: None,
+            resize_throttle: shared.options.resize_throttle,
+            opaque_region: Vec::new(),
+            input_region: None,
+            overlay,
+            solid_background,
+            dmabuf_feedback,
+            presentation: Presentation::default(),
+            floating_size: initial_size(&shared.options),
+            bounds: None,
+            capabilities: WindowManagerCapabilities::all(),
+            maximize_request: maximized.then_some(true),
+            minimize_requested: false,
+            fixed_aspect: shared.options.fixed_aspect,
+            title,
+            title_updated: None,
+            outputs: Vec::new(),
+            last_click: None,
+            drag_start: None,
+            touches: Default::default(),
+            paused: false,
+            inactive: false,
+            pause_inactive: shared.options.pause_inactive,
+            resuming: false,
+            time_offset: 0,
+            idle_inhibitor: None,
+            content_type,
+            tearing_control,
+            exported,
+            imported,
+            parent: None,
+            dialog: None,
+            focus_token: None,
+            confined: false,
+            decoration_mode: None,
+            pasted_color: None,
+            text_field: Default::default(),
+            sketch: Default::default(),
+            capture_shortcuts: false,
+            shortcuts_inhibitor: None,
+            shortcuts_inhibited: false,
+            self_check: shared.self_check.is_some(),
+            window,
+            conn: shared.conn.clone(),
+        };
+
+        let id = base.id();
+        log::info!("opened window {id}");
+        self.windows.insert(id.clone(), base);
+        id
+    }
+
+    /// Destroys the window after the renderer had a chance to free its state for it, and its
+    /// dialogs before it. Closing the last window exits the app, unless one comes back after
+    /// being unmapped.
+    pub fn close_window(&mut self, id: &ObjectId) {
+        self.close_dialogs_of(id);
+        self.close_popup_of(id);
+        self.release_pointer_of(id);
+        let Some(mut base) = self.windows.remove(id) else {
+            return;
+        };
+
+        self.handler.window_closed(&mut base);
+        self.refocus_parent(&mut base);
+        for seat in self.shared.seats.values_mut() {
+            if seat.keyboard_focus.as_ref() == Some(id) {
+                seat.keyboard_focus = None;
+            }
+            if seat.pointer_focus.as_ref() == Some(id) {
+                seat.pointer_focus = None;
+            }
+        }
+        drop(base);
+        log::info!("closed window {id}");
+
+        if self.windows.is_empty() && self.shared.unmapped == 0 {
+            self.shared.exit = true;
+        }
+    }
+
+    /// Closes the windows that had [`BaseApp::close`] set since the last dispatch.
+    pub(crate) fn close_requested_windows(&mut self) {
+        let closing: Vec<ObjectId> = self
+            .windows
+            .iter()
+            .filter(|(_, base)| base.close)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in closing {
+            self.close_window(&id);
+        }
+    }
+
+    /// Makes the window fullscreen on the output it is on, or back to how it was before. The
+    /// floating size is remembered, so leaving fullscreen restores it. Before the window is on
+    /// any output the one with the highest refresh rate is used.
+    pub fn toggle_fullscreen(&self, id: &ObjectId) {
+        let Some(base) = self.windows.get(id) else {
+            return;
+        };
+
+        // The configure that follows tells whether the compositor went along with it
+        if base.window_state.contains(WindowState::FULLSCREEN) {
+            base.window.unset_fullscreen();
+        } else {
+            let output = base.outputs.last().cloned().or_else(|| self.shared.fastest_output());
+            base.window.set_fullscreen(output.as_ref());
+        }
+    }
+
+    /// Maximizes the window, or restores it to the floating size it had before.
+    pub fn toggle_maximized(&mut self, id: &ObjectId) {
+        let Some(base) = self.windows.get_mut(id) else {
+            return;
+        };
+        if !base.capabilities.contains(WindowManagerCapabilities::MAXIMIZE) {
+            println!("the compositor doesn't maximize windows");
+            return;
+        }
+
+        // The configure that follows tells whether the compositor went along with it. Its size
+        // is zero when restoring, then the remembered floating size is used.
+        let maximize = !base.window_state.contains(WindowState::MAXIMIZED);
+        if maximize {
+            base.window.set_maximized();
+        } else {
+            base.window.unset_maximized();
+        }
+        base.maximize_request = Some(maximize);
+    }
+
+    /// Asks the compositor to minimize the window. There is no configure for it, compositors
+    /// that know xdg-shell version 6 suspend the window.
+    pub fn minimize(&mut self, id: &ObjectId) {
+        let Some(base) = self.windows.get_mut(id) else {
+            return;
+        };
+        if !base.capabilities.contains(WindowManagerCapabilities::MINIMIZE) {
+            println!("the compositor doesn't minimize windows");
+            return;
+        }
+        base.window.set_minimized();
+        base.minimize_requested = true;
+    }
+
+    /// Saves the next frame of the window as a PNG, drawing it right away.
+    pub(crate) fn take_screenshot(&mut self, qh: &QueueHandle<Self>, id: &ObjectId) {
+        if !H::SCREENSHOTS {
+            println!("this renderer can't take screenshots");
+            return;
+        }
+        let Some(base) = self.windows.get_mut(id) else {
+            return;
+        };
+        base.screenshot = true;
+        if !self.redraw(qh, id) {
+            // E.g. while suspended, the next frame that is drawn is saved
+            println!("the window isn't drawn right now, the screenshot waits for its next frame");
+        }
+    }
+
+    /// Switches the window between an sRGB and a plain framebuffer, for comparing the two.
+    pub(crate) fn toggle_srgb(&mut self, qh: &QueueHandle<Self>, id: &ObjectId) {
+        let Some(base) = self.windows.get_mut(id) else {
+            return;
+        };
+        base.srgb = !base.srgb;
+        self.handler.srgb_changed(base);
+        self.redraw(qh, id);
+    }
+
+    /// Drops the GL context of the window and sets it up again, see
+    /// [`AppHandler::simulate_context_loss`].
+    pub(crate) fn simulate_context_loss(&mut self, qh: &QueueHandle<Self>, id: &ObjectId) {
+        let Some(base) = self.windows.get_mut(id) else {
+            return;
+        };
+        println!("simulating a lost context");
+        self.handler.simulate_context_loss(base);
+        self.redraw(qh, id);
+    }
+
+    /// Sets the buffer scale to the largest integer scale of the outputs the window is on, so it
+    /// is sharp on all of them. Without any outputs, e.g. while the window is moved off-screen,
+    /// the last scale is kept rather than dropping back to 1.
+    ///
+    /// Once the compositor sent a preferred buffer scale the outputs no longer matter, it knows
+    /// better which scale suits the window.
+    fn update_scale_from_outputs(&mut self, qh: &QueueHandle<Self>, id: &ObjectId) {
+        let Some(base) = self.windows.get(id) else {
+            return;
+        };
+        if base.preferred_scale.is_some() {
+            return;
+        }
+        let scale = base
+            .outputs
+            .iter()
+            .filter_map(|output| self.shared.output_state.info(output))
+            .map(|info| info.scale_factor)
+            .max();
+
+        if let Some(scale) = scale {
+            self.set_scale(qh, id, scale);
+        }
+    }
+
+    fn set_scale(&mut self, qh: &QueueHandle<Self>, id: &ObjectId, scale: i32) {
+        let Some(base) = self.windows.get_mut(id) else {
+            return;
+        };
+        if scale == base.scale {
+            return;
+        }
+
+        log::info!("scale factor of window {id} changed to {scale}");
+        base.scale = scale;
+
+        if base.fractional_scale.is_some() || base.fixed_resolution.is_some() {
+            // The fractional scale is more precise, or we render at a fixed resolution. Either
+            // way the viewport does the scaling. A solid background only stretches its pixel,
+            // its content is still drawn at this scale.
+            return;
+        }
+
+        // The buffer scale is applied with the next commit, which is the one carrying the buffer
+        // of the new size, so the compositor never sees a mismatched pair.
+        base.update_buffer_scale();
+        self.handler.resize(base);
+        self.redraw(qh, id);
+    }
+
+    /// Redraws the windows without a frame callback in flight, except the paused ones. Suspended
+    /// windows count the frame as skipped instead. Loops of the application call this every
+    /// [`Options::redraw_interval`].
+    pub fn redraw_idle_windows(&mut self, qh: &QueueHandle<Self>) {
+        let idle: Vec<ObjectId> = self
+            .windows
+            .iter()
+            .filter(|(_, base)| {
+                !base.frame_callback_pending && !base.capped_redraw_pending && base.animating()
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in idle {
+            if let Some(base) = self.windows.get_mut(&id)
+                && !base.suspended()
+            {
+                // No callback says the time, move on by the refreshes that passed
+                base.extrapolate_time();
+            }
+            self.redraw(qh, &id);
+        }
+        // Configures waiting for a frame callback that never came
+        self.apply_configures(qh, true);
+    }
+
+    /// Draws a frame of the window and asks the compositor to tell us when it is a good time to
+    /// draw the next. Returns false when nothing was drawn, in that case nothing was committed
+    /// either.
+    pub fn redraw(&mut self, qh: &QueueHandle<Self>, id: &ObjectId) -> bool {
+        let Some(base) = self.windows.get_mut(id) else {
+            return false;
+        };
+
+        if self.shared.exit || base.close {
+            // The window is going away, let the frame loop die out.
+            return false;
+        }
+
+        if !base.configured {
+            // The renderer is only set up by the first configure.
+            return false;
+        }
+
+        if base.non_zero_buffer_size().is_none() {
+            // Nothing to render into, wait for a configure with an actual size.
+            return false;
+        }
+
+        if base.suspended() {
+            // Nobody sees the frame, the configure that ends the suspension draws again.
+            base.frames_skipped += 1;
+            return false;
+        }
+
+        // The frame request is double buffered state, so it has to happen before the renderer
+        // commits. Only one callback is kept in flight, a redraw due to a configure must not
+        // start a second loop. A paused window draws the frame without asking for the next.
+        if !base.frame_callback_pending && base.animating() {
+            let surface = base.window.wl_surface();
+            surface.frame(qh, surface.clone());
+            base.frame_callback_pending = true;
+            base.frames_with_callback += 1;
+        }
+
+        base.update_viewport();
+        base.update_opaque_region(&self.shared.compositor_state);
+        base.update_input_region(&self.shared.compositor_state);
+        base.update_overlay();
+        base.update_solid_background(qh);
+        let frame = base.request_presentation_feedback(self.shared.presentation.as_ref(), qh);
+
+        // Swapping the buffers commits the surface, there must not be another commit after it
+        // or the compositor sees a commit without a buffer. With a solid background the swap
+        // commits the content subsurface, and the window surface holding the pixel follows.
+        self.handler.draw(base);
+        base.commit_solid_background();
+        base.frame_swapped(frame, self.shared.presentation_clock);
+        base.frames_drawn += 1;
+        base.first_frame_at.get_or_insert_with(Instant::now);
+        base.last_frame_at = Some(Instant::now());
+        if self.shared.options.bench && base.frames_drawn >= frame_stats::BENCH_FRAMES {
+            self.shared.exit = true;
+        }
+        self.schedule_paced_redraw(qh, id);
+        true
+    }
+}
+
+/// The size a window starts out with, `--size` unless the size limits rule it out.
+fn initial_size(options: &Options) -> (u32, u32) {
+    let (width, height) = options.size;
+    let (min_width, min_height) = options.min_size;
+    let (max_width, max_height) = options.max_size.unwrap_or((u32::MAX, u32::MAX));
+    (width.max(min_width).min(max_width), height.max(min_height).min(max_height))
+}
+
+fn maximize_verb(maximize: bool) -> &'static str {
+    if maximize { "maximize" } else { "unmaximize" }
+}
+
+fn transform_swaps_axes(transform: wl_output::Transform) -> bool {
+    use wl_output::Transform;
+
+    matches!(
+        transform,
+        Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270
+    )
+}
+
+/// The bar and the block of [`BaseApp::test_pattern`] in a content area of the size, as
+/// `[x, y, width, height]` from its top left corner.
+pub fn test_pattern_rects(width: u32, height: u32) -> [[u32; 4]; 2] {
+    let bar = (height / 16).max(1);
+    let block = width.min(height) / 4;
+    [[0, 0, width, bar], [0, 0, block, block]]
+}
+
+/// Slowly cycles the hue around the color wheel, `time` is in milliseconds.
+pub fn animated_color(time: u32) -> [f32; 3] {
+    let hue = (time % 6000) as f32 / 1000.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    match hue as u32 {
+        0 => [1.0, x, 0.0],
+        1 => [x, 1.0, 0.0],
+        2 => [0.0, 1.0, x],
+        3 => [0.0, x, 1.0],
+        4 => [x, 0.0, 1.0],
+        _ => [1.0, 0.0, x],
+    }
+}
+
+impl<H: AppHandler> CompositorHandler for App<H> {
+    fn scale_factor_changed(
+        &mut self,
+        conn: &Connection,
+        qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        new_factor: i32,
+    ) {
+        let cursor = self.shared.is_cursor_surface(surface);
+        self.shared.trace.record(
+            "scale_factor_changed",
+            &[("surface", &surface.id()), ("factor", &new_factor), ("cursor", &cursor)],
+        );
+
+        // The cursor surface of the themed pointer gets its scale tracked too, its image is
+        // loaded at the scale of that surface so set it again.
+        if cursor {
+            log::info!("cursor scale factor changed to {new_factor}");
+            self.reload_cursor(conn);
+            return;
+        }
+
+        // Before wl_surface version 6 sctk guesses the factor from the outputs the same way we do,
+        // but ours survives the window leaving all outputs, see `update_scale_from_outputs`.
+        let id = surface.id();
+        if surface.version() < 6 {
+            log::debug!("compositor scale factor of window {id} is {new_factor}");
+            return;
+        }
+
+        // The preferred scale comes whenever it changes, which can be before the first configure.
+        // Then the renderer is set up with it right away.
+        let Some(base) = self.windows.get_mut(&id) else {
+            return;
+        };
+        log::info!("preferred buffer scale of window {id} is {new_factor}");
+        base.preferred_scale = Some(new_factor);
+        self.set_scale(qh, &id, new_factor);
+    }
+
+    fn transform_changed(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        new_transform: wl_output::Transform,
+    ) {
+        // The cursor surface is none of our windows
+        let id = surface.id();
+        self.shared.trace.record(
+            "transform_changed",
+            &[("window", &id), ("transform", &trace::Debugged(new_transform))],
+        );
+        let Some(base) = self.windows.get_mut(&id) else {
+            return;
+        };
+        if new_transform == base.transform {
+            return;
+        }
+
+        log::info!("transform of window {id} changed to {new_transform:?}");
+        base.transform = new_transform;
+
+        // Like the scale, this only takes effect with the commit of the next buffer, which is
+        // already rendered rotated.
+        surface.set_buffer_transform(new_transform);
+        self.handler.resize(base);
+        self.redraw(qh, &id);
+    }
+
+    fn frame(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        time: u32,
+    ) {
+        let id = surface.id();
+        let Some(base) = self.windows.get_mut(&id) else {
+            return;
+        };
+        base.frame_callback_pending = false;
+        base.frame_callbacks += 1;
+        base.frame_stats.frame_done(time);
+        self.shared.trace.record(
+            "frame",
+            &[("window", &id), ("callback_time", &time), ("callbacks", &base.frame_callbacks)],
+        );
+        if let Some(frames) = self.shared.options.exit_after_frames {
+            // `frame <n> <time>` for the smoke tests to count, the compositor showed the frame.
+            // Only frames with new content ask for a callback, configure acks and the redraws
+            // replacing a frame before it was shown don't count.
+            println!("frame {} {time}", base.frame_callbacks);
+            if base.frame_callbacks >= frames {
+                self.shared.exit = true;
+                return;
+            }
+        }
+        base.update_title(&self.shared.title, time);
+        self.shared.start_self_check(qh, base);
+        if base.pending_configure.is_some() {
+            // The configures since the last frame, drawn in one go
+            if base.animating() && !base.suspended() {
+                base.advance_time(time);
+            }
+            self.apply_configure(qh, &id);
+            return;
+        }
+        if base.suspended() {
+            // A callback that was in flight when the window got suspended, the loop stops here
+            base.frames_skipped += 1;
+            return;
+        }
+        if !base.animating() {
+            // Nothing moves, the next frame is drawn once something changes
+            return;
+        }
+        base.check_refresh(time, self.shared.options.max_fps);
+        base.advance_time(time);
+        if base.frame_done_paced(time) {
+            // The next frame is coming anyway, the callback only keeps the time
+            return;
+        }
+
+        // Every frame that asked for a callback must get exactly one. Frames drawn in between
+        // (for configures) replace a buffer before it was shown, anything else is a dropped frame.
+        if base.frame_callbacks.is_multiple_of(600) {
+            log::debug!(
+                "window {id}: {} frames drawn, {} requested a callback, {} callbacks received, \
+                 {} skipped while suspended",
+                base.frames_drawn,
+                base.frames_with_callback,
+                base.frame_callbacks,
+                base.frames_skipped
+            );
+        }
+
+        if self.cap_frame_rate(qh, &id) {
+            // `--max-fps` holds the frame back, a timer draws it
+            return;
+        }
+        self.redraw(qh, &id);
+    }
+
+    fn surface_enter(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        output: &wl_output::WlOutput,
+    ) {
+        let id = surface.id();
+        let name = self.shared.output_name(output);
+        self.shared.trace.record("surface_enter", &[("window", &id), ("output", &name)]);
+        let Some(base) = self.windows.get_mut(&id) else {
+            return;
+        };
+        if base.outputs.contains(output) {
+            return;
+        }
+
+        log::info!("window {id} entered output {}", self.shared.output_name(output));
+        base.outputs.push(output.clone());
+        self.update_scale_from_outputs(qh, &id);
+        self.update_refresh(&id);
+    }
+
+    fn surface_leave(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        output: &wl_output::WlOutput,
+    ) {
+        let id = surface.id();
+        let name = self.shared.output_name(output);
+        self.shared.trace.record("surface_leave", &[("window", &id), ("output", &name)]);
+        let Some(base) = self.windows.get_mut(&id) else {
+            return;
+        };
+
+        log::info!("window {id} left output {name}");
+        base.outputs.retain(|entered| entered != output);
+        self.update_scale_from_outputs(qh, &id);
+        self.update_refresh(&id);
+    }
+}
+
+impl<H: AppHandler> OutputHandler for App<H> {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.shared.output_state
+    }
+
+    fn new_output(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        output: wl_output::WlOutput,
+    ) {
+        self.shared.trace_output("new_output", &output);
+        self.shared.print_output("added", &output);
+    }
+
+    fn update_output(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        output: wl_output::WlOutput,
+    ) {
+        self.shared.trace_output("update_output", &output);
+        self.shared.print_output("changed", &output);
+
+        // The scale or mode of an output a window is on may have changed
+        for id in self.windows_on(&output) {
+            self.update_scale_from_outputs(qh, &id);
+            self.update_refresh(&id);
+        }
+    }
+
+    fn output_destroyed(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        output: wl_output::WlOutput,
+    ) {
+        self.shared.trace_output("output_destroyed", &output);
+        self.shared.print_output("removed", &output);
+
+        // An unplugged output doesn't necessarily send a leave first
+        for id in self.windows_on(&output) {
+            if let Some(base) = self.windows.get_mut(&id) {
+                base.outputs.retain(|entered| entered != &output);
+            }
+            self.update_scale_from_outputs(qh, &id);
+            self.update_refresh(&id);
+        }
+    }
+}
+
+impl<H: AppHandler> App<H> {
+    /// The windows that are at least partly on the output.
+    fn windows_on(&self, output: &wl_output::WlOutput) -> Vec<ObjectId> {
+        self.windows
+            .iter()
+            .filter(|(_, base)| base.outputs.contains(output))
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+}
+
+impl<H: AppHandler> WindowHandler for App<H> {
+    fn request_close(&mut self, _: &Connection, _: &QueueHandle<Self>, window: &Window) {
+        self.close_window(&window.wl_surface().id());
+    }
+
+    fn configure(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        window: &Window,
+        configure: WindowConfigure,
+        serial: u32,
+    ) {
+        let id = window.wl_surface().id();
+        let (width, height) = configure.new_size;
+        self.shared.trace.record(
+            "configure",
+            &[
+                ("window", &id),
+                ("serial", &serial),
+                ("width", &width.map(NonZeroU32::get)),
+                ("height", &height.map(NonZeroU32::get)),
+                ("bounds", &configure.suggested_bounds),
+                ("state", &trace::Debugged(configure.state)),
+                ("decoration", &trace::Debugged(configure.decoration_mode)),
+            ],
+        );
+        let Some(base) = self.windows.get_mut(&id) else {
+            return;
+        };
+
+        base.update_window_state(&configure);
+        base.update_idle_inhibitor(self.shared.idle_inhibit_manager.as_ref(), qh);
+
+        // sctk acked the configure already, the resize and the frame that commit the ack come
+        // once the dispatch is done. A configure still waiting by then is superseded, during an
+        // interactive resize there is one redraw per frame rather than one per configure.
+        if let Some((_, superseded)) = base.pending_configure.replace((configure, serial)) {
+            log::debug!("window {id}: configure {superseded} superseded by {serial}");
+        }
+        if !self.shared.options.resize_throttle {
+            // Every configure as it comes, for comparing
+            self.apply_configure(qh, &id);
+        }
+    }
+}
+
+impl<H: AppHandler> App<H> {
+    /// Applies the configures that came in with the last dispatch. A window with a frame in
+    /// flight waits for its callback, unless `all` says not to wait, e.g. because the
+    /// callbacks stopped coming.
+    pub(crate) fn apply_configures(&mut self, qh: &QueueHandle<Self>, all: bool) {
+        let ready: Vec<ObjectId> = self
+            .windows
+            .iter()
+            .filter(|(_, base)| base.pending_configure.is_some())
+            .filter(|(_, base)| all || !base.frame_callback_pending || base.suspended())
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in ready {
+            self.apply_configure(qh, &id);
+        }
+    }
+
+    /// Hands the latest configure of the window to the renderer and draws a frame for it, its
+    /// commit goes with the ack. The acks of the configures it superseded went out before, only
+    /// the last ack before a commit counts.
+    fn apply_configure(&mut self, qh: &QueueHandle<Self>, id: &ObjectId) {
+        let Some(base) = self.windows.get_mut(id) else {
+            return;
+        };
+        let Some((configure, serial)) = base.pending_configure.take() else {
+            return;
+        };
+        let window = base.window.clone();
+
+        // `--dmabuf-info` needs no renderer, only the feedback of the surface or the formats
+        // without it. The feedback may still be on its way, it is printed when it comes.
+        if self.shared.options.dmabuf_info {
+            if base.dmabuf_feedback.is_none() && !self.shared.exit {
+                self.print_dmabuf_formats(id);
+            }
+            window.commit();
+            return;
+        }
+
+        self.handler.configure(base, qh, configure, serial);
+        if base.close {
+            // The renderer gave up on the window
+            self.close_window(id);
+            return;
+        }
+        base.configured = true;
+
+        // Remember the size of the floating window so it can be restored afterwards
+        let constrained = WindowState::MAXIMIZED | WindowState::FULLSCREEN | WindowState::TILED;
+        if !base.window_state.intersects(constrained) {
+            base.floating_size = (base.width, base.height);
+        }
+
+        self.update_pointer_constraint_regions(id);
+
+        // The configure has been acked, the compositor expects a new buffer to go with it. When we
+        // can't draw, the ack still has to be committed on its own.
+        if !self.redraw(qh, id) {
+            window.commit();
+        }
+    }
+}
+
+delegate_compositor!(@<H: AppHandler> App<H>);
+delegate_subcompositor!(@<H: AppHandler> App<H>);
+delegate_output!(@<H: AppHandler> App<H>);
+delegate_seat!(@<H: AppHandler> App<H>);
+delegate_touch!(@<H: AppHandler> App<H>);
+delegate_pointer_constraints!(@<H: AppHandler> App<H>);
+delegate_relative_pointer!(@<H: AppHandler> App<H>);
+delegate_shm!(@<H: AppHandler> App<H>);
+delegate_keyboard!(@<H: AppHandler> App<H>);
+delegate_pointer!(@<H: AppHandler> App<H>);
+delegate_xdg_shell!(@<H: AppHandler> App<H>);
+delegate_xdg_window!(@<H: AppHandler> App<H>);
+delegate_xdg_popup!(@<H: AppHandler> App<H>);
+delegate_activation!(@<H: AppHandler> App<H>);
+delegate_data_device!(@<H: AppHandler> App<H>);
+delegate_dmabuf!(@<H: AppHandler> App<H>);
+delegate_primary_selection!(@<H: AppHandler> App<H>);
+delegate_registry!(@<H: AppHandler> App<H>);
+
+impl<H: AppHandler> ShmHandler for App<H> {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shared.shm
+    }
+}
+
+impl<H: AppHandler> ProvidesRegistryState for App<H> {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.shared.registry_state
+    }
+    registry_handlers![OutputState];
+}
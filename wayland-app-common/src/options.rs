@@ -0,0 +1,829 @@
+use std::{fmt, path::PathBuf, time::Duration};
+
+use smithay_client_toolkit::reexports::protocols::wp::content_type::v1::client::wp_content_type_v1;
+
+use crate::{
+    content_type::{parse_content_type, CONTENT_TYPES},
+    DEFAULT_SIZE,
+};
+
+/// What `--help` prints. Every example takes all of the options, the ones about GL only change
+/// the GL examples.
+pub const USAGE: &str = "\
+Options, a value also goes after an equals sign like --size=640x480:
+
+Windows
+  --size <width>x<height>        size the windows start out with, 256x256
+  --min-size <width>x<height>    smallest size, 256x256 or --size when smaller
+  --max-size <width>x<height>    largest size
+  --fixed-aspect <n>:<m>         keep the content at this ratio, letterboxed
+  --fullscreen[=<output>]        open fullscreen, on the output of that name or model
+  --maximized                    open maximized
+  --title <title>                replace the name of the example in the title
+  --app-id <id>                  replace the app id of the example
+  --content-type <type>          content type hint, game, video, photo or none
+  --transparent                  clear the GL windows to half transparent black
+  --click-through                only take input in a circle in the middle
+  --overlay                      show a frame time graph on a subsurface
+  --solid-background             single-pixel buffer around a GL subsurface
+  --activate-token <token>       raise the first window with xdg-activation
+  --export                       export the windows with xdg-foreign
+  --import <handle>              open the first window as a dialog of another
+
+Frames
+  --vsync, --no-vsync            wait for the swap or not, vsync by default
+  --max-fps <n>                  draw at most this many frames a second
+  --max-frames-in-flight <n>     pace async windows by fences
+  --redraw-interval <ms>         redraw while no frame callback comes, 1000
+  --pause-inactive               pause the animation of inactive windows
+  --no-resize-throttle           apply every configure of a resize at once
+  --show-damage                  outline what each frame redrew
+  --full-damage                  redraw the whole window every frame
+
+GL
+  --api <gles2|gles3|gl>         the API to try first
+  --msaa <0|2|4|8>               samples per pixel
+  --depth                        depth buffer and a small 3D scene
+  --stencil                      stencil buffer
+  --srgb, --no-srgb              draw into sRGB framebuffers, on by default
+  --scene <triangle|blue-clear>  what the content area shows
+  --gl-debug                     debug context, log what the driver reports
+  --gl-info, --gl-info-only      print what EGL and GL tell, and maybe exit
+  --dump-configs                 print every usable config and exit
+  --device <node>, --gpu <n>     the GPU of the raw EGL example
+  --render-thread                draw the raw EGL example on its own thread
+  --explicit-sync                sync with DRM timelines, raw EGL example
+  --offscreen <n>                save n frames as PNGs without a window
+  --force-software               draw into wl_shm buffers with the CPU
+  --dmabuf-info                  print the linux-dmabuf feedback and exit
+
+Testing
+  --stats                        log frame rate and frame times
+  --bench                        1000 frames, then the draw and swap times
+  --exit-after-frames <n>        print every frame callback, exit after n
+  --exit-after-seconds <s>       exit after this long
+  --self-check                   check that the first window reaches the screen
+  --trace <file>                 write a JSON line for every Wayland event
+  --reconnect <n>                connect again this often when it breaks
+  --poll-loop                    dispatch with poll(2) instead of calloop
+  --log-level <level>            off, error, warn, info, debug or trace
+  -h, --help                     print this
+";
+
+/// Command line options shared by the examples.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Log frame rate and frame time statistics, `--stats`.
+    pub stats: bool,
+    /// What is logged, `--log-level <off|error|warn|info|debug|trace>`. It goes over
+    /// `RUST_LOG` for everything `RUST_LOG` doesn't name a module for.
+    pub log_level: Option<log::LevelFilter>,
+    /// How often to redraw while no frame callback is in flight, `--redraw-interval <ms>`.
+    pub redraw_interval: Duration,
+    /// Drive the Wayland queue from a plain `poll(2)` loop instead of calloop, `--poll-loop`.
+    pub poll_loop: bool,
+    /// xdg-activation token for raising the first window, `--activate-token <token>` or the
+    /// `XDG_ACTIVATION_TOKEN` environment variable of the launcher.
+    pub activate_token: Option<String>,
+    /// Export the windows with xdg-foreign and print their handles, `--export`.
+    pub export: bool,
+    /// Handle of a window exported by another client, `--import <handle>`. The first window
+    /// opens as a dialog of it.
+    pub import: Option<String>,
+    /// Whether the windows start with vsync, `--vsync` (the default) or `--no-vsync`. Either
+    /// way the frame callbacks pace the drawing, without vsync nothing waits for the swap.
+    pub vsync: bool,
+    /// Size the windows start out with, `--size <width>x<height>`. The compositor may pick
+    /// another.
+    pub size: (u32, u32),
+    /// Smallest size the windows may get, `--min-size <width>x<height>`. The default size, or
+    /// `size` when smaller.
+    pub min_size: (u32, u32),
+    /// Largest size the windows may get, `--max-size <width>x<height>`.
+    pub max_size: Option<(u32, u32)>,
+    /// Open the first window fullscreen, `--fullscreen`. The compositor picks the output unless
+    /// `fullscreen_output` names one, `--fullscreen=<output>`.
+    pub fullscreen: bool,
+    /// The name, e.g. `DP-1`, or the model of the output to go fullscreen on.
+    pub fullscreen_output: Option<String>,
+    /// Open the first window maximized, `--maximized`.
+    pub maximized: bool,
+    /// Ratio the content keeps, letterboxed within the window, `--fixed-aspect <n>:<m>`.
+    pub fixed_aspect: Option<(u32, u32)>,
+    /// Replaces the name of the example in the window title, `--title <title>`.
+    pub title: Option<String>,
+    /// Replaces the app id of the example, `--app-id <id>`.
+    pub app_id: Option<String>,
+    /// Content type hint for the window surfaces, `--content-type <game|video|photo|none>`.
+    pub content_type: Option<wp_content_type_v1::Type>,
+    /// Outline what each frame redrew, for renderers that track damage, `--show-damage`.
+    pub show_damage: bool,
+    /// Damage the whole surface with every frame instead of what changed, `--full-damage`.
+    pub full_damage: bool,
+    /// Pause the animation of a window while it isn't activated, until it is again or gets
+    /// input, `--pause-inactive`.
+    pub pause_inactive: bool,
+    /// Hold the buffer size during interactive resizes and apply configures once per frame,
+    /// `--no-resize-throttle` turns it off for comparison.
+    pub resize_throttle: bool,
+    /// Ask for a debug GL context and log what the driver reports, `--gl-debug`.
+    pub gl_debug: bool,
+    /// Samples per pixel of the GL configs, `--msaa <0|2|4|8>`. 0 turns multisampling off.
+    pub msaa: u8,
+    /// Ask the GL configs for a 24 bit depth buffer, `--depth`. The GL examples draw a small 3D
+    /// scene with it.
+    pub depth: bool,
+    /// Ask the GL configs for an 8 bit stencil buffer, `--stencil`.
+    pub stencil: bool,
+    /// Whether the GL examples draw into sRGB framebuffers, `--srgb` (the default) or
+    /// `--no-srgb`. G switches at runtime.
+    pub srgb: bool,
+    /// The GL API the GL examples try first, `--api <gles2|gles3|gl>`. They fall back to the
+    /// ones below it.
+    pub gl_api: Option<GlApi>,
+    /// What the GL examples draw in the content area, `--scene <triangle|blue-clear>`.
+    pub scene: BuiltinScene,
+    /// Print every config the GL examples could draw with and exit, `--dump-configs`.
+    pub dump_configs: bool,
+    /// Print what EGL and GL tell about themselves once the GL examples have a context,
+    /// `--gl-info`. `--gl-info-only` exits after it, without drawing into a window.
+    pub gl_info: bool,
+    pub gl_info_only: bool,
+    /// Clear the GL windows to half transparent black, `--transparent`.
+    pub transparent: bool,
+    /// Only take input in a circle in the middle of the windows, `--click-through`. I switches
+    /// at runtime.
+    pub click_through: bool,
+    /// Show a frame time graph on a subsurface of the windows, `--overlay`.
+    pub overlay: bool,
+    /// Fill the windows with a single-pixel buffer and only render the middle into a
+    /// subsurface, `--solid-background`.
+    pub solid_background: bool,
+    /// Pace async windows by fences, with at most this many frames queued on the GPU,
+    /// `--max-frames-in-flight <n>`. Renderers without fences pace with frame callbacks.
+    pub max_frames_in_flight: Option<u32>,
+    /// Draw at most this many frames a second, `--max-fps <n>`. Holds with vsync off too.
+    pub max_fps: Option<u32>,
+    /// The GPU the raw EGL example draws with, `--device <node>` or `--gpu <n>`. Without it
+    /// EGL picks.
+    pub gpu: Option<GpuChoice>,
+    /// Print the linux-dmabuf feedback of the first window and exit, `--dmabuf-info`.
+    pub dmabuf_info: bool,
+    /// Sync the buffers of the raw EGL example with DRM timelines, `--explicit-sync`. Needs
+    /// its `explicit-sync` cargo feature.
+    pub explicit_sync: bool,
+    /// Draw with the raw EGL example on a render thread of its own, `--render-thread`. The
+    /// dispatch thread only hands it the frames.
+    pub render_thread: bool,
+    /// Draw this many frames of the raw EGL example without a window and save them as PNGs,
+    /// `--offscreen <n>`. Needs no compositor.
+    pub offscreen: Option<u32>,
+    /// Draw the GL examples with the CPU into wl_shm buffers, the way they fall back to when GL
+    /// can't be set up, `--force-software`.
+    pub force_software: bool,
+    /// Print a line per frame callback and exit after this many, `--exit-after-frames <n>`.
+    /// For the smoke tests and benchmarks.
+    pub exit_after_frames: Option<u64>,
+    /// Exit after this long, `--exit-after-seconds <s>`. Either exit prints a summary.
+    pub exit_after_seconds: Option<Duration>,
+    /// Write a JSON line for every Wayland event handled to the file, `--trace <file>`.
+    pub trace: Option<PathBuf>,
+    /// Draw 1000 frames with full damage, then print the percentiles of the draw and swap
+    /// times and write them to `bench-<binary>.csv`, `--bench`.
+    pub bench: bool,
+    /// Fill the windows with a known color, capture the screen and check that the first window
+    /// shows it, `--self-check`. Needs ext-image-copy-capture or wlr-screencopy.
+    pub self_check: bool,
+    /// How many times to connect again when the connection to the compositor breaks,
+    /// `--reconnect <n>`. Without it a broken connection exits.
+    pub reconnect: u32,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            stats: false,
+            log_level: None,
+            redraw_interval: Duration::from_secs(1),
+            poll_loop: false,
+            activate_token: None,
+            export: false,
+            import: None,
+            vsync: true,
+            size: (DEFAULT_SIZE, DEFAULT_SIZE),
+            min_size: (DEFAULT_SIZE, DEFAULT_SIZE),
+            max_size: None,
+            fullscreen: false,
+            fullscreen_output: None,
+            maximized: false,
+            fixed_aspect: None,
+            title: None,
+            app_id: None,
+            content_type: None,
+            show_damage: false,
+            full_damage: false,
+            pause_inactive: false,
+            resize_throttle: true,
+            gl_debug: false,
+            msaa: 0,
+            depth: false,
+            stencil: false,
+            srgb: true,
+            gl_api: None,
+            scene: BuiltinScene::Triangle,
+            dump_configs: false,
+            gl_info: false,
+            gl_info_only: false,
+            transparent: false,
+            click_through: false,
+            overlay: false,
+            solid_background: false,
+            max_frames_in_flight: None,
+            max_fps: None,
+            gpu: None,
+            dmabuf_info: false,
+            explicit_sync: false,
+            render_thread: false,
+            offscreen: None,
+            force_software: false,
+            exit_after_frames: None,
+            exit_after_seconds: None,
+            trace: None,
+            bench: false,
+            self_check: false,
+            reconnect: 0,
+        }
+    }
+}
+
+impl Options {
+    /// Parses the options from the command line. `--help` prints the usage and exits, an
+    /// argument that isn't known or a value that doesn't fit exits with an error.
+    pub fn from_args() -> Options {
+        let mut options = match Options::parse(std::env::args().skip(1)) {
+            Ok(options) => options,
+            Err(err) => exit_with(err, USAGE),
+        };
+        if options.activate_token.is_none() {
+            options.activate_token = std::env::var("XDG_ACTIVATION_TOKEN").ok();
+        }
+        options
+    }
+
+    /// Parses the options from the arguments, without the name of the binary in front. Unlike
+    /// [`Options::from_args`] it neither exits nor looks at the environment, so tests can build
+    /// options from the same arguments the examples take.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Options, ArgsError> {
+        let mut options = Options::default();
+        let mut common = BasicOptions::default();
+        let mut min_size = None;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (arg, mut inline) = split_inline(arg);
+            let mut value = || inline.take().or_else(|| args.next());
+            match arg.as_str() {
+                "-h" | "--help" => return Err(ArgsError::Help),
+                "--log-level" | "--exit-after-frames" | "--exit-after-seconds" | "--reconnect" => {
+                    common.parse_common(&arg, &mut value)?;
+                }
+                "--stats" => options.stats = true,
+                "--poll-loop" => options.poll_loop = true,
+                "--vsync" => options.vsync = true,
+                "--no-vsync" => options.vsync = false,
+                "--show-damage" => options.show_damage = true,
+                "--full-damage" => options.full_damage = true,
+                "--no-resize-throttle" => options.resize_throttle = false,
+                "--pause-inactive" => options.pause_inactive = true,
+                "--gl-debug" => options.gl_debug = true,
+                "--dump-configs" => options.dump_configs = true,
+                "--gl-info" => options.gl_info = true,
+                "--gl-info-only" => (options.gl_info, options.gl_info_only) = (true, true),
+                // Redrawing only the damage would make the frames of the examples incomparable
+                "--bench" => (options.bench, options.full_damage) = (true, true),
+                "--depth" => options.depth = true,
+                "--stencil" => options.stencil = true,
+                "--transparent" => options.transparent = true,
+                "--click-through" => options.click_through = true,
+                "--overlay" => options.overlay = true,
+                "--solid-background" => options.solid_background = true,
+                "--dmabuf-info" => options.dmabuf_info = true,
+                "--explicit-sync" => options.explicit_sync = true,
+                "--render-thread" => options.render_thread = true,
+                "--force-software" => options.force_software = true,
+                "--export" => options.export = true,
+                "--self-check" => options.self_check = true,
+                "--maximized" => options.maximized = true,
+                // Only after an equals sign, the output is optional
+                "--fullscreen" => {
+                    options.fullscreen = true;
+                    options.fullscreen_output = inline.take();
+                }
+                "--srgb" => options.srgb = true,
+                "--no-srgb" => options.srgb = false,
+                "--msaa" => match value().and_then(|samples| samples.parse().ok()) {
+                    Some(samples @ (0 | 2 | 4 | 8)) => options.msaa = samples,
+                    _ => return Err(needs(&arg, "0, 2, 4 or 8 samples")),
+                },
+                "--max-frames-in-flight" => match value().and_then(|n| n.parse().ok()) {
+                    Some(frames @ 1..) => options.max_frames_in_flight = Some(frames),
+                    _ => return Err(needs(&arg, "a number of frames, at least 1")),
+                },
+                "--max-fps" => match value().and_then(|n| n.parse().ok()) {
+                    Some(fps @ 1..) => options.max_fps = Some(fps),
+                    _ => return Err(needs(&arg, "a number of frames a second, at least 1")),
+                },
+                "--offscreen" => match value().and_then(|n| n.parse().ok()) {
+                    Some(frames @ 1..) => options.offscreen = Some(frames),
+                    _ => return Err(needs(&arg, "a number of frames, at least 1")),
+                },
+                "--trace" => match value() {
+                    Some(path) => options.trace = Some(PathBuf::from(path)),
+                    None => return Err(needs(&arg, "a file to write the events to")),
+                },
+                "--device" => match value() {
+                    Some(node) => options.gpu = Some(GpuChoice::Node(node)),
+                    None => return Err(needs(&arg, "a DRM node, like /dev/dri/renderD128")),
+                },
+                "--gpu" => match value().and_then(|index| index.parse().ok()) {
+                    Some(index) => options.gpu = Some(GpuChoice::Index(index)),
+                    None => return Err(needs(&arg, "the number of an EGL device, from 0")),
+                },
+                "--api" => match value().as_deref() {
+                    Some("gles2") => options.gl_api = Some(GlApi::Gles2),
+                    Some("gles3") => options.gl_api = Some(GlApi::Gles3),
+                    Some("gl") => options.gl_api = Some(GlApi::Gl),
+                    _ => return Err(needs(&arg, "one of gles2, gles3, gl")),
+                },
+                "--scene" => match value().as_deref() {
+                    Some("triangle") => options.scene = BuiltinScene::Triangle,
+                    Some("blue-clear") => options.scene = BuiltinScene::BlueClear,
+                    _ => return Err(needs(&arg, "one of triangle, blue-clear")),
+                },
+                "--redraw-interval" => match value().and_then(|ms| ms.parse().ok()) {
+                    Some(ms) if ms > 0 => options.redraw_interval = Duration::from_millis(ms),
+                    _ => return Err(needs(&arg, "a positive number of milliseconds")),
+                },
+                "--activate-token" => match value() {
+                    Some(token) => options.activate_token = Some(token),
+                    None => return Err(needs(&arg, "a token")),
+                },
+                "--import" => match value() {
+                    Some(handle) => options.import = Some(handle),
+                    None => return Err(needs(&arg, "the handle another instance exported")),
+                },
+                "--size" => match value().and_then(|size| parse_pair(&size, 'x')) {
+                    Some(size) => options.size = size,
+                    None => return Err(needs(&arg, "a size like 640x480")),
+                },
+                "--min-size" => match value().and_then(|size| parse_pair(&size, 'x')) {
+                    Some(size) => min_size = Some(size),
+                    None => return Err(needs(&arg, "a size like 320x240")),
+                },
+                "--max-size" => match value().and_then(|size| parse_pair(&size, 'x')) {
+                    Some(size) => options.max_size = Some(size),
+                    None => return Err(needs(&arg, "a size like 1920x1080")),
+                },
+                "--fixed-aspect" => match value().and_then(|ratio| parse_pair(&ratio, ':')) {
+                    Some(ratio) => options.fixed_aspect = Some(ratio),
+                    None => return Err(needs(&arg, "a ratio like 16:9")),
+                },
+                "--title" => match value() {
+                    Some(title) => options.title = Some(title),
+                    None => return Err(needs(&arg, "a title")),
+                },
+                "--app-id" => match value() {
+                    Some(app_id) => options.app_id = Some(app_id),
+                    None => return Err(needs(&arg, "an id, like org.example.App")),
+                },
+                "--content-type" => match value().and_then(|value| parse_content_type(&value)) {
+                    Some(content_type) => options.content_type = Some(content_type),
+                    None => return Err(needs(&arg, &format!("one of {CONTENT_TYPES}"))),
+                },
+                _ => return Err(ArgsError::Invalid(format!("unknown argument {arg:?}"))),
+            }
+            if inline.is_some() {
+                return Err(ArgsError::Invalid(format!("{arg} takes no value")));
+            }
+        }
+        let BasicOptions { log_level, exit_after_frames, exit_after_seconds, reconnect } = common;
+        options.log_level = log_level;
+        options.exit_after_frames = exit_after_frames;
+        options.exit_after_seconds = exit_after_seconds;
+        options.reconnect = reconnect;
+
+        // Unless given the windows can't get smaller than the default size, or than they start
+        let (width, height) = options.size;
+        options.min_size =
+            min_size.unwrap_or((DEFAULT_SIZE.min(width), DEFAULT_SIZE.min(height)));
+        let (min_width, min_height) = options.min_size;
+        let (max_width, max_height) = options.max_size.unwrap_or((u32::MAX, u32::MAX));
+        if max_width < min_width || max_height < min_height {
+            return Err(ArgsError::Invalid(format!(
+                "the --max-size {max_width}x{max_height} is smaller than the --min-size \
+                 {min_width}x{min_height}"
+            )));
+        }
+        if !(min_width..=max_width).contains(&width) || !(min_height..=max_height).contains(&height)
+        {
+            return Err(ArgsError::Invalid(format!(
+                "the --size {width}x{height} is outside of the --min-size and the --max-size"
+            )));
+        }
+        Ok(options)
+    }
+}
+
+/// The options of the examples that don't open their windows through this crate, the
+/// layer-shell bar and the session lock: `--help`, `--log-level`, `--exit-after-frames`,
+/// `--exit-after-seconds` and `--reconnect`, parsed like the same options of [`Options`]. The
+/// example adds its own.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BasicOptions {
+    /// See [`Options::log_level`].
+    pub log_level: Option<log::LevelFilter>,
+    /// See [`Options::exit_after_frames`].
+    pub exit_after_frames: Option<u64>,
+    /// See [`Options::exit_after_seconds`].
+    pub exit_after_seconds: Option<Duration>,
+    /// See [`Options::reconnect`].
+    pub reconnect: u32,
+}
+
+/// Takes the value of the option being parsed, from after the equals sign or the next
+/// argument. `None` when there is none.
+pub type TakeValue<'a> = dyn FnMut() -> Option<String> + 'a;
+
+impl BasicOptions {
+    /// Parses the options from the command line like [`Options::from_args`], `usage` is what
+    /// `--help` prints. See [`Self::parse`] for `extra`.
+    pub fn from_args(
+        usage: &str,
+        extra: impl FnMut(&str, &mut TakeValue) -> Result<bool, ArgsError>,
+    ) -> BasicOptions {
+        match BasicOptions::parse(std::env::args().skip(1), extra) {
+            Ok(options) => options,
+            Err(err) => exit_with(err, usage),
+        }
+    }
+
+    /// Parses the options from the arguments, without the name of the binary in front. Every
+    /// option goes to `extra` first, with a way to take its value. It returns whether it knew
+    /// the option, so an example can add options, or refuse one of these with an error.
+    pub fn parse(
+        args: impl IntoIterator<Item = String>,
+        mut extra: impl FnMut(&str, &mut TakeValue) -> Result<bool, ArgsError>,
+    ) -> Result<BasicOptions, ArgsError> {
+        let mut options = BasicOptions::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (arg, mut inline) = split_inline(arg);
+            let mut value = || inline.take().or_else(|| args.next());
+            if arg == "-h" || arg == "--help" {
+                return Err(ArgsError::Help);
+            }
+            if !extra(&arg, &mut value)? && !options.parse_common(&arg, &mut value)? {
+                return Err(ArgsError::Invalid(format!("unknown argument {arg:?}")));
+            }
+            if inline.is_some() {
+                return Err(ArgsError::Invalid(format!("{arg} takes no value")));
+            }
+        }
+        Ok(options)
+    }
+
+    /// Parses one of the options shared with [`Options`], false when `arg` isn't one of them.
+    fn parse_common(&mut self, arg: &str, value: &mut TakeValue) -> Result<bool, ArgsError> {
+        match arg {
+            "--log-level" => match value().and_then(|level| level.parse().ok()) {
+                Some(level) => self.log_level = Some(level),
+                None => return Err(needs(arg, "one of off, error, warn, info, debug, trace")),
+            },
+            "--exit-after-frames" => match value().and_then(|n| n.parse().ok()) {
+                Some(frames @ 1..) => self.exit_after_frames = Some(frames),
+                _ => return Err(needs(arg, "a number of frames, at least 1")),
+            },
+            "--exit-after-seconds" => match value().and_then(|s| s.parse::<f64>().ok()) {
+                Some(seconds) if seconds > 0.0 && seconds.is_finite() => {
+                    self.exit_after_seconds = Some(Duration::from_secs_f64(seconds));
+                }
+                _ => return Err(needs(arg, "a positive number of seconds")),
+            },
+            "--reconnect" => match value().and_then(|n| n.parse().ok()) {
+                Some(attempts) => self.reconnect = attempts,
+                None => return Err(needs(arg, "a number of attempts")),
+            },
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// Splits off the value of an option given after an equals sign, like `--size=640x480`.
+fn split_inline(arg: String) -> (String, Option<String>) {
+    match arg.split_once('=') {
+        Some((name, value)) if name.starts_with("--") => {
+            (name.to_string(), Some(value.to_string()))
+        }
+        _ => (arg, None),
+    }
+}
+
+/// Prints the usage and exits for `--help`, otherwise exits with the error.
+fn exit_with(err: ArgsError, usage: &str) -> ! {
+    match err {
+        ArgsError::Help => {
+            print!("{usage}");
+            std::process::exit(0);
+        }
+        ArgsError::Invalid(message) => {
+            eprintln!("{message}, see --help");
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Why [`Options::parse`] or [`BasicOptions::parse`] gave no options.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgsError {
+    /// `--help` or `-h`, the caller prints [`USAGE`].
+    Help,
+    /// An argument that isn't known, or the value of one that doesn't fit.
+    Invalid(String),
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgsError::Help => write!(f, "the usage was asked for"),
+            ArgsError::Invalid(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+/// The error of an option without the value it needs.
+fn needs(arg: &str, value: &str) -> ArgsError {
+    ArgsError::Invalid(format!("{arg} needs {value}"))
+}
+
+/// A GL API with the version the GL examples ask for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GlApi {
+    Gles2,
+    Gles3,
+    /// Desktop OpenGL 3.3, core profile.
+    Gl,
+}
+
+impl GlApi {
+    /// The API followed by the ones to fall back to when the driver can't create a context of
+    /// it, down to GLES2 which every driver has.
+    pub fn fallbacks(self) -> &'static [GlApi] {
+        match self {
+            GlApi::Gl => &[GlApi::Gl, GlApi::Gles3, GlApi::Gles2],
+            GlApi::Gles3 => &[GlApi::Gles3, GlApi::Gles2],
+            GlApi::Gles2 => &[GlApi::Gles2],
+        }
+    }
+}
+
+impl fmt::Display for GlApi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GlApi::Gles2 => write!(f, "OpenGL ES 2.0"),
+            GlApi::Gles3 => write!(f, "OpenGL ES 3.0"),
+            GlApi::Gl => write!(f, "OpenGL 3.3 core"),
+        }
+    }
+}
+
+/// The scenes the GL examples come with, see `glow_common::Renderer` for drawing others.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BuiltinScene {
+    /// The rotating triangle the arrow keys move.
+    Triangle,
+    /// Only a blue content area.
+    BlueClear,
+}
+
+/// A GPU picked on the command line, by its DRM node or by its place among the EGL devices.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GpuChoice {
+    /// `--device`, the primary or the render node.
+    Node(String),
+    /// `--gpu`, counting from 0.
+    Index(usize),
+}
+
+impl fmt::Display for GpuChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GpuChoice::Node(node) => write!(f, "{node}"),
+            GpuChoice::Index(index) => write!(f, "GPU {index}"),
+        }
+    }
+}
+
+/// Parses two positive numbers with a separator between, like `640x480` or `16:9`.
+fn parse_pair(value: &str, separator: char) -> Option<(u32, u32)> {
+    let (first, second) = value.split_once(separator)?;
+    let pair = (first.trim().parse().ok()?, second.trim().parse().ok()?);
+    (pair.0 > 0 && pair.1 > 0).then_some(pair)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, ArgsError> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn parse_basic(args: &[&str]) -> Result<BasicOptions, ArgsError> {
+        BasicOptions::parse(args.iter().map(|arg| arg.to_string()), |_, _| Ok(false))
+    }
+
+    fn invalid(args: &[&str]) -> String {
+        match parse(args) {
+            Err(ArgsError::Invalid(message)) => message,
+            other => panic!("{args:?} should be invalid, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn defaults_without_arguments() {
+        let options = parse(&[]).unwrap();
+        assert_eq!(options.size, (DEFAULT_SIZE, DEFAULT_SIZE));
+        assert_eq!(options.min_size, (DEFAULT_SIZE, DEFAULT_SIZE));
+        assert_eq!(options.max_size, None);
+        assert!(!options.fullscreen);
+        assert_eq!(options.gl_api, None);
+    }
+
+    #[test]
+    fn help() {
+        assert_eq!(parse(&["--help"]).unwrap_err(), ArgsError::Help);
+        assert_eq!(parse(&["--stats", "-h", "--bogus"]).unwrap_err(), ArgsError::Help);
+    }
+
+    #[test]
+    fn value_after_equals_or_separate() {
+        for args in [&["--size=640x480"][..], &["--size", "640x480"]] {
+            assert_eq!(parse(args).unwrap().size, (640, 480));
+        }
+        let options = parse(&["--msaa=4", "--max-fps", "30"]).unwrap();
+        assert_eq!((options.msaa, options.max_fps), (4, Some(30)));
+        let options = parse(&["--trace=events.jsonl"]).unwrap();
+        assert_eq!(options.trace, Some(PathBuf::from("events.jsonl")));
+    }
+
+    #[test]
+    fn flags_take_no_value() {
+        assert_eq!(invalid(&["--stats=yes"]), "--stats takes no value");
+    }
+
+    #[test]
+    fn fullscreen_output_only_after_equals() {
+        let options = parse(&["--fullscreen"]).unwrap();
+        assert!(options.fullscreen);
+        assert_eq!(options.fullscreen_output, None);
+
+        let options = parse(&["--fullscreen=DP-1"]).unwrap();
+        assert!(options.fullscreen);
+        assert_eq!(options.fullscreen_output.as_deref(), Some("DP-1"));
+
+        assert!(invalid(&["--fullscreen", "DP-1"]).contains("unknown argument"));
+    }
+
+    #[test]
+    fn later_arguments_win() {
+        assert!(!parse(&["--vsync", "--no-vsync"]).unwrap().vsync);
+        assert_eq!(parse(&["--size=300x300", "--size=400x300"]).unwrap().size, (400, 300));
+    }
+
+    #[test]
+    fn bench_draws_full_frames() {
+        let options = parse(&["--bench"]).unwrap();
+        assert!(options.bench && options.full_damage);
+    }
+
+    #[test]
+    fn unknown_arguments() {
+        assert_eq!(invalid(&["--bogus"]), "unknown argument \"--bogus\"");
+        assert_eq!(invalid(&["stray"]), "unknown argument \"stray\"");
+    }
+
+    #[test]
+    fn missing_and_bad_values() {
+        assert_eq!(invalid(&["--size"]), "--size needs a size like 640x480");
+        assert_eq!(invalid(&["--size=640"]), "--size needs a size like 640x480");
+        assert_eq!(invalid(&["--size=0x480"]), "--size needs a size like 640x480");
+        assert_eq!(invalid(&["--msaa=3"]), "--msaa needs 0, 2, 4 or 8 samples");
+        assert!(invalid(&["--exit-after-frames=0"]).starts_with("--exit-after-frames needs"));
+        assert!(invalid(&["--exit-after-seconds=-1"]).starts_with("--exit-after-seconds needs"));
+        assert!(invalid(&["--exit-after-seconds=inf"]).starts_with("--exit-after-seconds needs"));
+        assert!(invalid(&["--api=vulkan"]).starts_with("--api needs"));
+        assert!(invalid(&["--content-type=movie"]).starts_with("--content-type needs"));
+    }
+
+    #[test]
+    fn min_size_defaults_to_the_smaller_of_size_and_default() {
+        let options = parse(&["--size=100x400"]).unwrap();
+        assert_eq!(options.min_size, (100, DEFAULT_SIZE));
+        let options = parse(&["--size=640x480", "--min-size=320x240"]).unwrap();
+        assert_eq!(options.min_size, (320, 240));
+    }
+
+    #[test]
+    fn size_limits() {
+        let options = parse(&["--size=800x600", "--max-size=1024x768"]).unwrap();
+        assert_eq!(options.max_size, Some((1024, 768)));
+
+        assert!(invalid(&["--min-size=500x500", "--max-size=400x400", "--size=450x450"])
+            .contains("is smaller than the --min-size"));
+        assert!(invalid(&["--size=1000x1000", "--max-size=800x800"])
+            .contains("is outside of the --min-size and the --max-size"));
+        assert!(invalid(&["--size=300x300", "--min-size=400x400"])
+            .contains("is outside of the --min-size and the --max-size"));
+    }
+
+    #[test]
+    fn gl_api_and_its_fallbacks() {
+        assert_eq!(parse(&["--api=gles2"]).unwrap().gl_api, Some(GlApi::Gles2));
+        assert_eq!(parse(&["--api", "gles3"]).unwrap().gl_api, Some(GlApi::Gles3));
+        assert_eq!(parse(&["--api=gl"]).unwrap().gl_api, Some(GlApi::Gl));
+
+        assert_eq!(GlApi::Gl.fallbacks(), [GlApi::Gl, GlApi::Gles3, GlApi::Gles2]);
+        assert_eq!(GlApi::Gles3.fallbacks(), [GlApi::Gles3, GlApi::Gles2]);
+        assert_eq!(GlApi::Gles2.fallbacks(), [GlApi::Gles2]);
+    }
+
+    #[test]
+    fn shared_with_basic_options() {
+        let args = ["--log-level=debug", "--exit-after-frames", "60", "--reconnect=2"];
+        let options = parse(&args).unwrap();
+        let basic = parse_basic(&args).unwrap();
+        assert_eq!(options.log_level, Some(log::LevelFilter::Debug));
+        assert_eq!((options.exit_after_frames, options.reconnect), (Some(60), 2));
+        assert_eq!(basic.log_level, options.log_level);
+        assert_eq!(basic.exit_after_frames, options.exit_after_frames);
+        assert_eq!(basic.reconnect, options.reconnect);
+
+        let basic = parse_basic(&["--exit-after-seconds=0.5"]).unwrap();
+        assert_eq!(basic.exit_after_seconds, Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn basic_options_errors() {
+        assert_eq!(parse_basic(&[]).unwrap(), BasicOptions::default());
+        assert_eq!(parse_basic(&["--help"]).unwrap_err(), ArgsError::Help);
+        assert_eq!(
+            parse_basic(&["--size=640x480"]).unwrap_err(),
+            ArgsError::Invalid("unknown argument \"--size\"".to_string())
+        );
+        assert_eq!(
+            parse_basic(&["--log-level=loud"]).unwrap_err(),
+            ArgsError::Invalid(
+                "--log-level needs one of off, error, warn, info, debug, trace".to_string()
+            )
+        );
+        assert_eq!(
+            parse_basic(&["--exit-after-frames"]).unwrap_err(),
+            ArgsError::Invalid("--exit-after-frames needs a number of frames, at least 1".into())
+        );
+    }
+
+    #[test]
+    fn basic_options_of_the_example() {
+        for args in [&["--output=DP-1"][..], &["--output", "DP-1", "--reconnect", "1"]] {
+            let mut output = None;
+            let basic = BasicOptions::parse(args.iter().map(|arg| arg.to_string()), |arg, value| {
+                if arg != "--output" {
+                    return Ok(false);
+                }
+                output = value();
+                Ok(true)
+            });
+            assert!(basic.is_ok());
+            assert_eq!(output.as_deref(), Some("DP-1"));
+        }
+
+        // The example goes first, so it can refuse one of the shared options
+        let refused = BasicOptions::parse(["--reconnect=1".to_string()], |arg, _| match arg {
+            "--reconnect" => Err(ArgsError::Invalid("no reconnecting".to_string())),
+            _ => Ok(false),
+        });
+        assert_eq!(refused.unwrap_err(), ArgsError::Invalid("no reconnecting".to_string()));
+
+        // A value the example didn't take is an error, like for the flags of `Options`
+        let flag = BasicOptions::parse(["--quiet=yes".to_string()], |arg, _| Ok(arg == "--quiet"));
+        assert_eq!(flag.unwrap_err(), ArgsError::Invalid("--quiet takes no value".to_string()));
+    }
+
+    #[test]
+    fn gpu_choice() {
+        let options = parse(&["--gpu=1"]).unwrap();
+        assert_eq!(options.gpu, Some(GpuChoice::Index(1)));
+        let options = parse(&["--device", "/dev/dri/renderD128"]).unwrap();
+        assert_eq!(options.gpu, Some(GpuChoice::Node("/dev/dri/renderD128".to_string())));
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt::Write;
+
+use smithay_client_toolkit::output::OutputInfo;
+use wayland_client::{protocol::wl_output, Proxy};
+
+use crate::{trace::Debugged, SharedState};
+
+impl SharedState {
+    /// Name of the output for logging, e.g. `DP-1`.
+    pub(crate) fn output_name(&self, output: &wl_output::WlOutput) -> String {
+        self.output_state
+            .info(output)
+            .and_then(|info| info.name)
+            .unwrap_or_else(|| "unnamed".to_string())
+    }
+
+    /// The output of `--fullscreen=<output>`, by its name, e.g. `DP-1`, or by its model.
+    pub(crate) fn find_output(&self, name: &str) -> Option<wl_output::WlOutput> {
+        self.output_state.outputs().find(|output| {
+            self.output_state
+                .info(output)
+                .is_some_and(|info| info.name.as_deref() == Some(name) || info.model == name)
+        })
+    }
+
+    /// The outputs by name and model, for telling which `--fullscreen` can go to.
+    pub(crate) fn output_names(&self) -> Vec<String> {
+        self.output_state
+            .outputs()
+            .filter_map(|output| self.output_state.info(&output))
+            .map(|info| {
+                let name = info.name.as_deref().unwrap_or("unnamed");
+                format!("{name} ({} {})", info.make, info.model)
+            })
+            .collect()
+    }
+
+    /// The output whose current mode has the highest refresh rate, e.g. for going fullscreen on
+    /// the smoothest display.
+    pub fn fastest_output(&self) -> Option<wl_output::WlOutput> {
+        self.output_state
+            .outputs()
+            .filter_map(|output| {
+                let info = self.output_state.info(&output)?;
+                let refresh = info.modes.iter().find(|mode| mode.current)?.refresh_rate;
+                Some((refresh, output))
+            })
+            .max_by_key(|(refresh, _)| *refresh)
+            .map(|(_, output)| output)
+    }
+
+    /// Records the event of the output in the `--trace`, with what matters for the scale.
+    pub(crate) fn trace_output(&self, event: &str, output: &wl_output::WlOutput) {
+        let Some(info) = self.output_state.info(output) else {
+            self.trace.record(event, &[("output", &output.id())]);
+            return;
+        };
+        let refresh = info.modes.iter().find(|mode| mode.current).map(|mode| mode.refresh_rate);
+        self.trace.record(
+            event,
+            &[
+                ("output", &output.id()),
+                ("name", &info.name),
+                ("logical_size", &info.logical_size),
+                ("scale", &info.scale_factor),
+                ("transform", &Debugged(info.transform)),
+                ("refresh", &refresh),
+            ],
+        );
+    }
+
+    /// Prints everything we know about the output, `event` says what happened to it.
+    pub(crate) fn print_output(&self, event: &str, output: &wl_output::WlOutput) {
+        match self.output_state.info(output) {
+            Some(info) => println!("output {event}:\n{}", describe_output(&info)),
+            None => println!("output {event}: no information yet"),
+        }
+    }
+}
+
+/// A multi-line description of the output, one property per line.
+fn describe_output(info: &OutputInfo) -> String {
+    let mut out = String::new();
+    // The name and description come from wl_output v4 or xdg-output, older compositors have
+    // neither
+    let name = info.name.as_deref().unwrap_or("unnamed");
+    let _ = writeln!(out, "  name: {name} (global {})", info.id);
+    if let Some(description) = &info.description {
+        let _ = writeln!(out, "  description: {description}");
+    }
+    let _ = writeln!(out, "  make/model: {} {}", info.make, info.model);
+    let _ = writeln!(out, "  physical size: {}x{} mm", info.physical_size.0, info.physical_size.1);
+    match (info.logical_position, info.logical_size) {
+        (Some((x, y)), Some((width, height))) => {
+            let _ = writeln!(out, "  logical: {width}x{height} at ({x}, {y})");
+        }
+        _ => {
+            let (x, y) = info.location;
+            let _ = writeln!(out, "  location: ({x}, {y})");
+        }
+    }
+
+    for mode in &info.modes {
+        let (width, height) = mode.dimensions;
+        let mut flags = String::new();
+        if mode.current {
+            flags.push_str(" current");
+        }
+        if mode.preferred {
+            flags.push_str(" preferred");
+        }
+        // The refresh rate is in mHz
+        let _ = writeln!(
+            out,
+            "  mode: {width}x{height} @ {:.3} Hz{flags}",
+            mode.refresh_rate as f64 / 1000.0
+        );
+    }
+
+    let _ = writeln!(out, "  scale: {}", info.scale_factor);
+    let _ = writeln!(out, "  transform: {:?}", info.transform);
+    let _ = write!(out, "  subpixel: {:?}", info.subpixel);
+    out
+}
@@ -0,0 +1,186 @@
+//! The `--overlay` subsurface, a graph of the latest frame times in the top right corner of the
+//! window. It has a `wl_surface` of its own the renderer draws into, see [`AppHandler::OVERLAY`].
+//! In sync mode its commits only show with the next commit of the window, in desync mode right
+//! away. O switches.
+
+use std::collections::VecDeque;
+
+use smithay_client_toolkit::{
+    compositor::{CompositorState, Region},
+    subcompositor::SubcompositorState,
+};
+use wayland_client::{
+    backend::ObjectId,
+    protocol::{wl_subsurface::WlSubsurface, wl_surface::WlSurface},
+    Proxy, QueueHandle,
+};
+
+use crate::{App, AppHandler, BaseApp};
+
+/// Size of the overlay in logical pixels.
+pub const OVERLAY_WIDTH: u32 = 128;
+pub const OVERLAY_HEIGHT: u32 = 48;
+/// Gap between the overlay and the edges of the content area, in logical pixels.
+const OVERLAY_MARGIN: u32 = 8;
+
+/// Frame intervals in the graph, one bar each.
+const BARS: usize = 32;
+/// A frame this long in milliseconds fills the height of the graph.
+const FULL_SCALE: u32 = 50;
+/// Frames up to this long in milliseconds are green, up to twice as long yellow, then red.
+const TARGET_INTERVAL: u32 = 17;
+
+const BACKGROUND_COLOR: [f32; 3] = [0.1, 0.1, 0.1];
+const TARGET_LINE_COLOR: [f32; 3] = [0.5, 0.5, 0.5];
+
+/// The overlay of a window and what the renderer needs to know to draw it.
+pub struct Overlay {
+    subsurface: WlSubsurface,
+    surface: WlSurface,
+    /// Integer buffer scale, taken over from the window like the popup menu does.
+    scale: i32,
+    /// Whether the commits of the overlay wait for the window, the default of subsurfaces.
+    sync: bool,
+    /// The position last sent, relative to the window surface.
+    position: Option<(i32, i32)>,
+    /// Timestamp of the last frame, see [`BaseApp::time`].
+    last_time: Option<u32>,
+    /// The latest frame intervals in milliseconds, newest last.
+    intervals: VecDeque<u32>,
+}
+
+impl Overlay {
+    /// Creates the subsurface on the window surface. It takes no input, clicks on it go to the
+    /// window below.
+    pub(crate) fn new<H: AppHandler>(
+        subcompositor: &SubcompositorState,
+        compositor: &CompositorState,
+        parent: &WlSurface,
+        qh: &QueueHandle<App<H>>,
+    ) -> Overlay {
+        let (subsurface, surface) = subcompositor.create_subsurface(parent.clone(), qh);
+        if let Ok(region) = Region::new(compositor) {
+            surface.set_input_region(Some(region.wl_region()));
+        }
+        Overlay {
+            subsurface,
+            surface,
+            scale: 1,
+            sync: true,
+            position: None,
+            last_time: None,
+            intervals: VecDeque::with_capacity(BARS),
+        }
+    }
+
+    /// The id of the overlay's `wl_surface`.
+    pub fn id(&self) -> ObjectId {
+        self.surface.id()
+    }
+
+    /// The `wl_surface` to render into.
+    pub fn wl_surface(&self) -> &WlSurface {
+        &self.surface
+    }
+
+    /// Size of the overlay in physical pixels.
+    pub fn buffer_size(&self) -> (u32, u32) {
+        let scale = self.scale as u32;
+        (OVERLAY_WIDTH * scale, OVERLAY_HEIGHT * scale)
+    }
+
+    /// The background, the bars and a line at the target frame time as `[x, y, width, height]`
+    /// in GL buffer coordinates (origin bottom left) with their colors, ready for `glScissor`
+    /// and `glClear`. The newest frame is on the right.
+    pub fn graph_rects(&self) -> Vec<([i32; 4], [f32; 3])> {
+        let (width, height) = self.buffer_size();
+        let (width, height) = (width as i32, height as i32);
+        let bar_width = width / BARS as i32;
+        let bar_height = |ms: u32| (ms.min(FULL_SCALE) as i32 * height) / FULL_SCALE as i32;
+
+        let mut rects = vec![([0, 0, width, height], BACKGROUND_COLOR)];
+        let first = BARS - self.intervals.len();
+        for (index, &interval) in self.intervals.iter().enumerate() {
+            let color = if interval <= TARGET_INTERVAL {
+                [0.2, 0.8, 0.2]
+            } else if interval <= 2 * TARGET_INTERVAL {
+                [0.9, 0.8, 0.1]
+            } else {
+                [0.9, 0.2, 0.1]
+            };
+            // A pixel of gap between the bars
+            let x = (first + index) as i32 * bar_width;
+            rects.push(([x, 0, bar_width - 1, bar_height(interval)], color));
+        }
+        rects.push(([0, bar_height(TARGET_INTERVAL), width, self.scale], TARGET_LINE_COLOR));
+        rects
+    }
+
+    /// Destroys the subsurface and its surface. The renderer has to be done with the surface,
+    /// and the window surface has to still be around.
+    pub(crate) fn destroy(&self) {
+        self.subsurface.destroy();
+        self.surface.destroy();
+    }
+}
+
+impl BaseApp {
+    /// The `--overlay` subsurface, for renderers with [`AppHandler::OVERLAY`].
+    pub fn overlay(&self) -> Option<&Overlay> {
+        self.overlay.as_ref()
+    }
+
+    /// Adds the frame to the graph and keeps the overlay in the corner and at the scale of the
+    /// window. The position goes with the next commit of the window, the scale with the next
+    /// commit of the overlay, which the renderer draws at the new size.
+    pub(crate) fn update_overlay(&mut self) {
+        let [x, y, width, _] = self.content_area();
+        let scale = self.scale;
+        let time = self.time;
+        let Some(overlay) = &mut self.overlay else {
+            return;
+        };
+
+        if let Some(last_time) = overlay.last_time {
+            // The time stands still over a pause of the animation, that's no frame to show
+            let interval = time.wrapping_sub(last_time);
+            if interval > 0 {
+                if overlay.intervals.len() == BARS {
+                    overlay.intervals.pop_front();
+                }
+                overlay.intervals.push_back(interval);
+            }
+        }
+        overlay.last_time = Some(time);
+
+        // In the top right corner, or the top left when the content is narrower than that
+        let right = (x + width).saturating_sub(OVERLAY_WIDTH + OVERLAY_MARGIN);
+        let position = (right.max(x) as i32, (y + OVERLAY_MARGIN) as i32);
+        if overlay.position != Some(position) {
+            overlay.subsurface.set_position(position.0, position.1);
+            overlay.position = Some(position);
+        }
+        if overlay.scale != scale {
+            overlay.surface.set_buffer_scale(scale);
+            overlay.scale = scale;
+        }
+    }
+}
+
+impl<H: AppHandler> App<H> {
+    /// Switches the overlay of the window between sync and desync mode.
+    pub(crate) fn toggle_overlay_sync(&mut self, qh: &QueueHandle<Self>, id: &ObjectId) {
+        let Some(overlay) = self.windows.get_mut(id).and_then(|base| base.overlay.as_mut()) else {
+            println!("no overlay, start with --overlay");
+            return;
+        };
+        overlay.sync = !overlay.sync;
+        if overlay.sync {
+            overlay.subsurface.set_sync();
+        } else {
+            overlay.subsurface.set_desync();
+        }
+        println!("overlay: {}", if overlay.sync { "sync" } else { "desync" });
+        self.redraw(qh, id);
+    }
+}
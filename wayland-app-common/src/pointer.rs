@@ -0,0 +1,224 @@
+use smithay_client_toolkit::{
+    reexports::csd_frame::WindowManagerCapabilities,
+    seat::pointer::{
+        PointerEvent, PointerEventKind, PointerHandler, BTN_LEFT, BTN_MIDDLE, BTN_RIGHT,
+    },
+};
+use wayland_client::{
+    backend::ObjectId,
+    protocol::{wl_pointer, wl_seat, wl_surface},
+    Connection, Proxy, QueueHandle,
+};
+
+use crate::{decorations::resize_cursor, App, AppHandler, BaseApp, SharedState, TitleBarHit};
+
+/// Longest gap between the clicks of a double click, in milliseconds.
+const DOUBLE_CLICK_TIME: u32 = 400;
+/// How far the pointer may move between the clicks of a double click, in logical pixels.
+const DOUBLE_CLICK_DISTANCE: f64 = 4.0;
+
+impl<H: AppHandler> PointerHandler for App<H> {
+    fn pointer_frame(
+        &mut self,
+        conn: &Connection,
+        qh: &QueueHandle<Self>,
+        pointer: &wl_pointer::WlPointer,
+        events: &[PointerEvent],
+    ) {
+        let Some((seat, _)) = self.shared.pointer_seat(pointer) else {
+            return;
+        };
+        for event in events {
+            let id = event.surface.id();
+            let (kind, button) = match event.kind {
+                PointerEventKind::Enter { .. } => ("enter", None),
+                PointerEventKind::Leave { .. } => ("leave", None),
+                PointerEventKind::Motion { .. } => ("motion", None),
+                PointerEventKind::Press { button, .. } => ("press", Some(button)),
+                PointerEventKind::Release { button, .. } => ("release", Some(button)),
+                PointerEventKind::Axis { .. } => ("axis", None),
+            };
+            self.shared.trace.record(
+                "pointer",
+                &[
+                    ("surface", &id),
+                    ("kind", &kind),
+                    ("position", &event.position),
+                    ("button", &button),
+                ],
+            );
+            if !matches!(event.kind, PointerEventKind::Leave { .. }) {
+                self.wake_inactive(qh, &id);
+            }
+            if let PointerEventKind::Press { .. } = event.kind
+                && self.popup_clicked(&id, event.position.1)
+            {
+                continue;
+            }
+
+            // Ignore events for other surfaces
+            let Some(base) = self.windows.get_mut(&id) else {
+                continue;
+            };
+
+            let Some(resources) = self.shared.seats.get_mut(&seat) else {
+                continue;
+            };
+            let (x, y) = event.position;
+            match event.kind {
+                PointerEventKind::Enter { .. } => {
+                    base.pointer_position = Some(event.position);
+                    resources.pointer_focus = Some(id.clone());
+                    let name = self.shared.seat_name(&seat);
+                    log::info!("pointer of seat {name} entered window {id} at ({x:.1}, {y:.1})");
+                    // The cursor has to be set again on every enter
+                    self.reload_seat_cursor(conn, &seat);
+                }
+                PointerEventKind::Leave { .. } => {
+                    base.pointer_position = None;
+                    if resources.pointer_focus.as_ref() == Some(&id) {
+                        resources.pointer_focus = None;
+                    }
+                    log::info!("pointer of seat {} left window {id}", self.shared.seat_name(&seat));
+                }
+                PointerEventKind::Motion { .. } => {
+                    base.pointer_position = Some(event.position);
+                    if base.drag_threshold_passed(x, y)
+                        && let Some((_, _, serial)) = base.drag_start.take()
+                    {
+                        // With Shift the drag selects the color instead of dragging it away
+                        if resources.modifiers.shift {
+                            self.select_color(qh, &id, &seat, serial);
+                        } else {
+                            self.start_drag(qh, &id, &seat, serial);
+                        }
+                        continue;
+                    }
+                    self.update_cursor(conn, &seat, &id, x, y);
+                }
+                PointerEventKind::Press {
+                    button,
+                    serial,
+                    time,
+                } => {
+                    println!("button {button:#x} pressed at ({x:.1}, {y:.1})");
+
+                    if button == BTN_LEFT {
+                        if let Some(edge) = base.resize_edge(x, y) {
+                            base.window.resize(&seat, serial, edge);
+                            continue;
+                        }
+
+                        match base.title_bar_hit(x, y) {
+                            // Only this window goes, the app exits with the last one
+                            Some(TitleBarHit::Close) => base.close = true,
+                            Some(TitleBarHit::Move) => base.window.move_(&seat, serial),
+                            None if base.is_double_click(time, x, y) => {
+                                self.toggle_fullscreen(&id);
+                            }
+                            None => base.drag_start = Some((x, y, serial)),
+                        }
+                    } else if button == BTN_MIDDLE {
+                        self.paste_primary(&id, &seat);
+                    } else if button == BTN_RIGHT && base.title_bar_hit(x, y).is_some() {
+                        // Server-side title bars have the menu already. show_window_menu takes
+                        // surface-local logical coordinates, which the pointer position already
+                        // is.
+                        if base.capabilities.contains(WindowManagerCapabilities::WINDOW_MENU) {
+                            base.window.show_window_menu(&seat, serial, (x as i32, y as i32));
+                        } else {
+                            println!("the compositor has no window menu");
+                        }
+                    } else if button == BTN_RIGHT && H::POPUP_MENU {
+                        self.open_popup(qh, &id, &seat, serial, event.position);
+                    }
+                }
+                PointerEventKind::Release { button, .. } => {
+                    if button == BTN_LEFT {
+                        base.drag_start = None;
+                    }
+                }
+                PointerEventKind::Axis { .. } => {}
+            }
+        }
+    }
+}
+
+impl BaseApp {
+    /// Whether this click completes a double click, i.e. it came soon after the last one and
+    /// close to it.
+    fn is_double_click(&mut self, time: u32, x: f64, y: f64) -> bool {
+        let double_click = self.last_click.is_some_and(|(last_time, last_x, last_y)| {
+            time.wrapping_sub(last_time) <= DOUBLE_CLICK_TIME
+                && (x - last_x).abs() <= DOUBLE_CLICK_DISTANCE
+                && (y - last_y).abs() <= DOUBLE_CLICK_DISTANCE
+        });
+
+        // A third click starts over instead of making another double click
+        self.last_click = if double_click { None } else { Some((time, x, y)) };
+        double_click
+    }
+}
+
+impl SharedState {
+    /// Whether the surface is one the themed pointers draw their cursors into.
+    pub(crate) fn is_cursor_surface(&self, surface: &wl_surface::WlSurface) -> bool {
+        self.seats
+            .values()
+            .filter_map(|seat| seat.pointer.as_ref())
+            .any(|pointer| pointer.surface() == surface)
+    }
+}
+
+impl<H: AppHandler> App<H> {
+    /// Sets the current cursors again, e.g. after a cursor surface changed its scale.
+    pub(crate) fn reload_cursor(&mut self, conn: &Connection) {
+        let seats: Vec<wl_seat::WlSeat> = self.shared.seats.keys().cloned().collect();
+        for seat in seats {
+            self.reload_seat_cursor(conn, &seat);
+        }
+    }
+
+    /// Sets the cursor of the pointer of the seat again.
+    pub(crate) fn reload_seat_cursor(&mut self, conn: &Connection, seat: &wl_seat::WlSeat) {
+        let Some(resources) = self.shared.seats.get_mut(seat) else {
+            return;
+        };
+        resources.cursor_icon = None;
+        if let Some(id) = resources.pointer_focus.clone()
+            && let Some((x, y)) = self.windows.get(&id).and_then(|base| base.pointer_position)
+        {
+            self.update_cursor(conn, seat, &id, x, y);
+        }
+    }
+
+    /// Shows the arrow cursor, or a resize cursor while the pointer is over a window edge.
+    fn update_cursor(
+        &mut self,
+        conn: &Connection,
+        seat: &wl_seat::WlSeat,
+        id: &ObjectId,
+        x: f64,
+        y: f64,
+    ) {
+        let (Some(base), Some(resources)) = (self.windows.get(id), self.shared.seats.get_mut(seat))
+        else {
+            return;
+        };
+        // Mouse look hides the cursor
+        if self.shared.pointer_lock.as_ref().is_some_and(|lock| lock.hides_cursor_of(seat)) {
+            return;
+        }
+        let icon = resize_cursor(base.resize_edge(x, y));
+        if resources.cursor_icon == Some(icon) {
+            return;
+        }
+
+        if let Some(pointer) = &resources.pointer
+            && let Err(err) = pointer.set_cursor(conn, icon)
+        {
+            log::warn!("failed to set cursor {icon:?}: {err}");
+        }
+        resources.cursor_icon = Some(icon);
+    }
+}
@@ -0,0 +1,366 @@
+//! Mouse look: M locks the pointer in place and its relative motion turns the triangle, like
+//! the camera of a first person game. The cursor is hidden while the lock is active.
+//!
+//! The lock is one-shot. Escape, losing the keyboard focus or the compositor deactivating the
+//! lock on its own end it, M locks again.
+//!
+//! C confines the pointer to the middle of the window instead, the examples outline the region.
+//! A pointer can only have one constraint at a time, so each mode ends the other.
+
+use smithay_client_toolkit::{
+    compositor::{CompositorState, Region},
+    reexports::protocols::wp::{
+        pointer_constraints::zv1::client::{
+            zwp_confined_pointer_v1::ZwpConfinedPointerV1,
+            zwp_locked_pointer_v1::ZwpLockedPointerV1, zwp_pointer_constraints_v1::Lifetime,
+        },
+        relative_pointer::zv1::client::zwp_relative_pointer_v1::ZwpRelativePointerV1,
+    },
+    seat::{
+        pointer_constraints::PointerConstraintsHandler,
+        relative_pointer::{RelativeMotionEvent, RelativePointerHandler},
+    },
+    shell::WaylandSurface,
+};
+use wayland_client::{
+    backend::ObjectId,
+    protocol::{wl_pointer, wl_seat, wl_surface},
+    Connection, Proxy, QueueHandle,
+};
+
+use crate::{App, AppHandler, BaseApp};
+
+/// Radians the triangle turns per logical pixel of pointer motion.
+const LOOK_SPEED: f32 = 0.005;
+/// Width of the outline around the confinement region, in logical pixels.
+const OUTLINE_WIDTH: u32 = 2;
+
+/// The pointer lock of mouse look.
+pub(crate) struct PointerLock {
+    /// The window the pointer is locked to.
+    window: ObjectId,
+    /// The seat of the locked pointer.
+    seat: wl_seat::WlSeat,
+    locked_pointer: ZwpLockedPointerV1,
+    /// Whether the compositor activated the lock. Until then the pointer moves as usual.
+    active: bool,
+}
+
+impl PointerLock {
+    /// Whether the lock is active on the pointer of the seat, its cursor is hidden then.
+    pub(crate) fn hides_cursor_of(&self, seat: &wl_seat::WlSeat) -> bool {
+        self.active && &self.seat == seat
+    }
+}
+
+/// The confinement of the pointer to the middle of a window.
+pub(crate) struct PointerConfinement {
+    window: ObjectId,
+    seat: wl_seat::WlSeat,
+    confined_pointer: ZwpConfinedPointerV1,
+}
+
+impl BaseApp {
+    /// The content area below our title bar, the lock activates once the pointer is inside.
+    fn content_region(&self, compositor: &CompositorState) -> Option<Region> {
+        let title_bar = self.title_bar_height();
+        region(compositor, [0, title_bar, self.width, self.height - title_bar])
+    }
+
+    /// The middle half of the content area in both directions, in logical coordinates.
+    fn confinement_rect(&self) -> [u32; 4] {
+        let top = self.title_bar_height();
+        let (width, height) = (self.width, self.height - top);
+        [width / 4, top + height / 4, width / 2, height / 2]
+    }
+
+    /// An outline around the region the pointer is confined to, or nothing while it isn't. In
+    /// buffer coordinates, see [`Self::buffer_rect`].
+    pub fn confinement_outline(&self) -> Vec<[i32; 4]> {
+        if !self.confined {
+            return Vec::new();
+        }
+        let [x, y, width, height] = self.confinement_rect();
+        let line = OUTLINE_WIDTH.min(width).min(height);
+        vec![
+            self.buffer_rect(x, y, width, line),
+            self.buffer_rect(x, y + height - line, width, line),
+            self.buffer_rect(x, y, line, height),
+            self.buffer_rect(x + width - line, y, line, height),
+        ]
+    }
+}
+
+/// A region of a single rectangle in logical coordinates.
+fn region(compositor: &CompositorState, [x, y, width, height]: [u32; 4]) -> Option<Region> {
+    let region = Region::new(compositor).ok()?;
+    region.add(x as i32, y as i32, width as i32, height as i32);
+    Some(region)
+}
+
+impl<H: AppHandler> App<H> {
+    /// Locks the pointer of the seat to the window for mouse look, or releases the lock.
+    pub(crate) fn toggle_mouse_look(
+        &mut self,
+        qh: &QueueHandle<Self>,
+        id: &ObjectId,
+        seat: &wl_seat::WlSeat,
+    ) {
+        if self.shared.pointer_lock.is_some() {
+            self.unlock_pointer();
+            return;
+        }
+        self.unconfine_pointer();
+
+        let Some(base) = self.windows.get(id) else {
+            return;
+        };
+        let Some(resources) = self.shared.seats.get(seat) else {
+            return;
+        };
+        let Some(pointer) = &resources.pointer else {
+            println!("mouse look needs a pointer on the seat of the keyboard");
+            return;
+        };
+        if resources.relative_pointer.is_none() {
+            println!("mouse look needs relative pointers, which the compositor lacks");
+            return;
+        }
+
+        let region = base.content_region(&self.shared.compositor_state);
+        let locked_pointer = match self.shared.pointer_constraints.lock_pointer(
+            base.window.wl_surface(),
+            pointer.pointer(),
+            region.as_ref().map(Region::wl_region),
+            Lifetime::Oneshot,
+            qh,
+        ) {
+            Ok(locked_pointer) => locked_pointer,
+            Err(_) => {
+                println!("mouse look needs zwp_pointer_constraints_v1, which the compositor lacks");
+                return;
+            }
+        };
+        println!("mouse look on, Escape or M to leave");
+        self.shared.pointer_lock = Some(PointerLock {
+            window: id.clone(),
+            seat: seat.clone(),
+            locked_pointer,
+            active: false,
+        });
+    }
+
+    /// Ends mouse look and brings the cursor back.
+    pub(crate) fn unlock_pointer(&mut self) {
+        let Some(lock) = self.shared.pointer_lock.take() else {
+            return;
+        };
+        lock.locked_pointer.destroy();
+        println!("mouse look off");
+
+        let conn = self.shared.conn.clone();
+        self.reload_cursor(&conn);
+    }
+
+    /// Confines the pointer of the seat to the middle of the window, or lets it go again.
+    pub(crate) fn toggle_confinement(
+        &mut self,
+        qh: &QueueHandle<Self>,
+        id: &ObjectId,
+        seat: &wl_seat::WlSeat,
+    ) {
+        if self.shared.pointer_confinement.is_some() {
+            self.unconfine_pointer();
+            return;
+        }
+        self.unlock_pointer();
+
+        let Some(base) = self.windows.get_mut(id) else {
+            return;
+        };
+        let Some(pointer) = self.shared.seats.get(seat).and_then(|seat| seat.pointer.as_ref())
+        else {
+            println!("confining needs a pointer on the seat of the keyboard");
+            return;
+        };
+
+        // Persistent, so the confinement comes back whenever the pointer enters the region again
+        let region = region(&self.shared.compositor_state, base.confinement_rect());
+        let confined_pointer = match self.shared.pointer_constraints.confine_pointer(
+            base.window.wl_surface(),
+            pointer.pointer(),
+            region.as_ref().map(Region::wl_region),
+            Lifetime::Persistent,
+            qh,
+        ) {
+            Ok(confined_pointer) => confined_pointer,
+            Err(_) => {
+                println!("confining needs zwp_pointer_constraints_v1, which the compositor lacks");
+                return;
+            }
+        };
+        println!("pointer confined to the outlined region, C to release");
+        base.confined = true;
+        self.shared.pointer_confinement = Some(PointerConfinement {
+            window: id.clone(),
+            seat: seat.clone(),
+            confined_pointer,
+        });
+    }
+
+    /// Releases the pointer from the confinement region.
+    pub(crate) fn unconfine_pointer(&mut self) {
+        let Some(confinement) = self.shared.pointer_confinement.take() else {
+            return;
+        };
+        confinement.confined_pointer.destroy();
+        if let Some(base) = self.windows.get_mut(&confinement.window) {
+            base.confined = false;
+        }
+        println!("pointer released");
+    }
+
+    /// Ends mouse look when it is the pointer of the seat that is locked.
+    pub(crate) fn unlock_pointer_of(&mut self, seat: &wl_seat::WlSeat) {
+        if self.shared.pointer_lock.as_ref().is_some_and(|lock| &lock.seat == seat) {
+            self.unlock_pointer();
+        }
+    }
+
+    /// Releases the pointer of the seat when it is the one confined.
+    pub(crate) fn unconfine_pointer_of(&mut self, seat: &wl_seat::WlSeat) {
+        if self.shared.pointer_confinement.as_ref().is_some_and(|c| &c.seat == seat) {
+            self.unconfine_pointer();
+        }
+    }
+
+    /// Ends the constraints on the window, e.g. because it is closing.
+    pub(crate) fn release_pointer_of(&mut self, id: &ObjectId) {
+        if self.shared.pointer_lock.as_ref().is_some_and(|lock| &lock.window == id) {
+            self.unlock_pointer();
+        }
+        if self.shared.pointer_confinement.as_ref().is_some_and(|c| &c.window == id) {
+            self.unconfine_pointer();
+        }
+    }
+
+    /// Follows a new size of the window with the constraint regions, the next commit applies
+    /// them.
+    pub(crate) fn update_pointer_constraint_regions(&self, id: &ObjectId) {
+        let Some(base) = self.windows.get(id) else {
+            return;
+        };
+        let compositor = &self.shared.compositor_state;
+
+        if let Some(lock) = &self.shared.pointer_lock
+            && &lock.window == id
+        {
+            let region = base.content_region(compositor);
+            lock.locked_pointer.set_region(region.as_ref().map(Region::wl_region));
+        }
+        if let Some(confinement) = &self.shared.pointer_confinement
+            && &confinement.window == id
+        {
+            let region = region(compositor, base.confinement_rect());
+            confinement.confined_pointer.set_region(region.as_ref().map(Region::wl_region));
+        }
+    }
+}
+
+impl<H: AppHandler> PointerConstraintsHandler for App<H> {
+    fn confined(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _confined_pointer: &ZwpConfinedPointerV1,
+        surface: &wl_surface::WlSurface,
+        _pointer: &wl_pointer::WlPointer,
+    ) {
+        log::info!("pointer confined to window {}", surface.id());
+    }
+
+    fn unconfined(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _confined_pointer: &ZwpConfinedPointerV1,
+        surface: &wl_surface::WlSurface,
+        _pointer: &wl_pointer::WlPointer,
+    ) {
+        // A persistent confinement only waits for the pointer to enter the region again
+        log::info!("pointer no longer confined to window {}", surface.id());
+    }
+
+    fn locked(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        locked_pointer: &ZwpLockedPointerV1,
+        _surface: &wl_surface::WlSurface,
+        _pointer: &wl_pointer::WlPointer,
+    ) {
+        let Some(lock) = &mut self.shared.pointer_lock else {
+            return;
+        };
+        if &lock.locked_pointer != locked_pointer {
+            return;
+        }
+        log::info!("pointer locked to window {}", lock.window);
+        lock.active = true;
+
+        let Some(resources) = self.shared.seats.get_mut(&lock.seat) else {
+            return;
+        };
+        if let Some(pointer) = &resources.pointer
+            && let Err(err) = pointer.hide_cursor()
+        {
+            log::warn!("failed to hide the cursor: {err}");
+        }
+        resources.cursor_icon = None;
+    }
+
+    fn unlocked(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        locked_pointer: &ZwpLockedPointerV1,
+        _surface: &wl_surface::WlSurface,
+        _pointer: &wl_pointer::WlPointer,
+    ) {
+        // Our own unlocks destroy the lock right away, so this is the compositor ending it,
+        // e.g. for a shortcut of its own. A one-shot lock is dead now.
+        if self
+            .shared
+            .pointer_lock
+            .as_ref()
+            .is_some_and(|lock| &lock.locked_pointer == locked_pointer)
+        {
+            log::info!("the compositor released the pointer lock");
+            self.unlock_pointer();
+        }
+    }
+}
+
+impl<H: AppHandler> RelativePointerHandler for App<H> {
+    fn relative_pointer_motion(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        relative_pointer: &ZwpRelativePointerV1,
+        _pointer: &wl_pointer::WlPointer,
+        event: RelativeMotionEvent,
+    ) {
+        // Relative motion comes all the time, only the locked pointer looks around
+        let Some(lock) = &self.shared.pointer_lock else {
+            return;
+        };
+        let seat = self.shared.seats.get(&lock.seat);
+        let locked = seat.and_then(|seat| seat.relative_pointer.as_ref());
+        if !lock.active || locked != Some(relative_pointer) {
+            return;
+        }
+        if let Some(base) = self.windows.get_mut(&lock.window) {
+            base.look_angle += event.delta.0 as f32 * LOOK_SPEED;
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//! Touchpad gestures with pointer-gestures. Swiping three fingers moves the triangle like the
+//! arrow keys, pinching scales it like two fingers on a touch screen, and resting fingers on the
+//! touchpad holds the animation until they lift.
+//!
+//! Each seat's pointer has gestures of its own, and a seat does one gesture at a time. The
+//! deltas are in logical pixels like `triangle_offset`, the renderers scale that to the buffer
+//! along with the rest of the view. A cancelled gesture undoes what it did.
+
+use smithay_client_toolkit::reexports::protocols::wp::pointer_gestures::zv1::client::{
+    zwp_pointer_gesture_hold_v1::{self, ZwpPointerGestureHoldV1},
+    zwp_pointer_gesture_pinch_v1::{self, ZwpPointerGesturePinchV1},
+    zwp_pointer_gesture_swipe_v1::{self, ZwpPointerGestureSwipeV1},
+    zwp_pointer_gestures_v1::ZwpPointerGesturesV1,
+};
+use wayland_client::{
+    backend::ObjectId,
+    delegate_noop,
+    globals::GlobalList,
+    protocol::{wl_pointer, wl_seat, wl_surface},
+    Connection, Dispatch, Proxy, QueueHandle,
+};
+
+use crate::{App, AppHandler, BaseApp};
+
+/// The fingers of a swipe that moves the triangle, compositors keep fewer for scrolling.
+const SWIPE_FINGERS: u32 = 3;
+
+/// Binds the pointer gestures manager, or nothing when the compositor lacks it. Hold gestures
+/// need version 3.
+pub(crate) fn bind_manager<H: AppHandler>(
+    globals: &GlobalList,
+    qh: &QueueHandle<App<H>>,
+) -> Option<ZwpPointerGesturesV1> {
+    let manager = globals.bind::<ZwpPointerGesturesV1, _, _>(qh, 1..=3, ()).ok();
+    if manager.is_none() {
+        log::info!("zwp_pointer_gestures_v1 not available, no touchpad gestures");
+    }
+    manager
+}
+
+/// The gesture objects of a seat's pointer, created along with it.
+pub(crate) struct Gestures {
+    swipe: ZwpPointerGestureSwipeV1,
+    pinch: ZwpPointerGesturePinchV1,
+    hold: Option<ZwpPointerGestureHoldV1>,
+    /// The window of the gesture under way and what it started from.
+    active: Option<(ObjectId, Gesture)>,
+}
+
+/// A gesture under way, with what it changes as it was at the start.
+pub(crate) enum Gesture {
+    Swipe { start_offset: (f32, f32) },
+    Pinch { start_zoom: f32 },
+    /// Whether the hold paused the animation, a paused one stays paused.
+    Hold { paused: bool },
+}
+
+impl Gestures {
+    pub(crate) fn new<H: AppHandler>(
+        manager: &ZwpPointerGesturesV1,
+        pointer: &wl_pointer::WlPointer,
+        seat: &wl_seat::WlSeat,
+        qh: &QueueHandle<App<H>>,
+    ) -> Gestures {
+        Gestures {
+            swipe: manager.get_swipe_gesture(pointer, qh, seat.clone()),
+            pinch: manager.get_pinch_gesture(pointer, qh, seat.clone()),
+            hold: (manager.version() >= 3)
+                .then(|| manager.get_hold_gesture(pointer, qh, seat.clone())),
+            active: None,
+        }
+    }
+
+    /// Destroys the gesture objects. Returns the gesture that was under way, no end comes for
+    /// it anymore.
+    pub(crate) fn destroy(self) -> Option<(ObjectId, Gesture)> {
+        self.swipe.destroy();
+        self.pinch.destroy();
+        if let Some(hold) = self.hold {
+            hold.destroy();
+        }
+        self.active
+    }
+}
+
+impl Gesture {
+    fn name(&self) -> &'static str {
+        match self {
+            Gesture::Swipe { .. } => "swipe",
+            Gesture::Pinch { .. } => "pinch",
+            Gesture::Hold { .. } => "hold",
+        }
+    }
+}
+
+impl<H: AppHandler> App<H> {
+    /// Starts the gesture `start` makes of the window the fingers went down on. A gesture
+    /// `start` has no use for, e.g. a swipe with the wrong number of fingers, is ignored.
+    fn begin_gesture(
+        &mut self,
+        qh: &QueueHandle<Self>,
+        seat: &wl_seat::WlSeat,
+        surface: &wl_surface::WlSurface,
+        fingers: u32,
+        start: impl FnOnce(&BaseApp) -> Option<Gesture>,
+    ) {
+        let id = surface.id();
+        let gesture = self.windows.get(&id).and_then(start);
+        let kind = gesture.as_ref().map_or("ignored", Gesture::name);
+        self.shared.trace.record(
+            "gesture_begin",
+            &[("seat", &seat.id()), ("surface", &id), ("kind", &kind), ("fingers", &fingers)],
+        );
+        let Some(gesture) = gesture else {
+            return;
+        };
+        log::info!(
+            "seat {} began a {fingers} finger {kind} on window {id}",
+            self.shared.seat_name(seat)
+        );
+        self.wake_inactive(qh, &id);
+        if let Gesture::Hold { paused: true } = gesture {
+            self.toggle_animation(qh, &id);
+        }
+
+        let Some(gestures) = self.shared.seats.get_mut(seat).and_then(|s| s.gestures.as_mut())
+        else {
+            return;
+        };
+        // The compositor ends a gesture before it begins the next, should it not the old one
+        // is undone
+        if let Some((id, gesture)) = gestures.active.replace((id, gesture)) {
+            self.end_gesture(qh, &id, gesture, true);
+        }
+    }
+
+    /// The window of the gesture under way on the seat, and the gesture.
+    fn gesture_window(&mut self, seat: &wl_seat::WlSeat) -> Option<(&mut BaseApp, &Gesture)> {
+        let (id, gesture) = self.shared.seats.get(seat)?.gestures.as_ref()?.active.as_ref()?;
+        Some((self.windows.get_mut(id)?, gesture))
+    }
+
+    /// Ends the gesture under way on the seat, the fingers lifted or the compositor took over.
+    fn finish_gesture(&mut self, qh: &QueueHandle<Self>, seat: &wl_seat::WlSeat, cancelled: bool) {
+        let resources = self.shared.seats.get_mut(seat);
+        let Some((id, gesture)) = resources
+            .and_then(|resources| resources.gestures.as_mut())
+            .and_then(|gestures| gestures.active.take())
+        else {
+            return;
+        };
+        self.shared.trace.record(
+            "gesture_end",
+            &[("seat", &seat.id()), ("surface", &id), ("cancelled", &cancelled)],
+        );
+        let how = if cancelled { "cancelled" } else { "ended" };
+        log::info!("{} of seat {} {how}", gesture.name(), self.shared.seat_name(seat));
+        self.end_gesture(qh, &id, gesture, cancelled);
+    }
+
+    /// Undoes a cancelled swipe or pinch, and resumes the animation a hold paused.
+    pub(crate) fn end_gesture(
+        &mut self,
+        qh: &QueueHandle<Self>,
+        id: &ObjectId,
+        gesture: Gesture,
+        cancelled: bool,
+    ) {
+        let Some(base) = self.windows.get_mut(id) else {
+            return;
+        };
+        match gesture {
+            Gesture::Swipe { start_offset } if cancelled => base.triangle_offset = start_offset,
+            Gesture::Pinch { start_zoom } if cancelled => base.zoom = start_zoom,
+            // Unless Space resumed it in the meantime
+            Gesture::Hold { paused: true } if base.paused => self.toggle_animation(qh, id),
+            _ => {}
+        }
+    }
+}
+
+impl<H: AppHandler> Dispatch<ZwpPointerGestureSwipeV1, wl_seat::WlSeat> for App<H> {
+    fn event(
+        app: &mut Self,
+        _swipe: &ZwpPointerGestureSwipeV1,
+        event: zwp_pointer_gesture_swipe_v1::Event,
+        seat: &wl_seat::WlSeat,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        use zwp_pointer_gesture_swipe_v1::Event;
+
+        match event {
+            Event::Begin { surface, fingers, .. } => {
+                app.begin_gesture(qh, seat, &surface, fingers, |base| {
+                    (fingers == SWIPE_FINGERS)
+                        .then_some(Gesture::Swipe { start_offset: base.triangle_offset })
+                });
+            }
+            Event::Update { dx, dy, .. } => {
+                if let Some((base, Gesture::Swipe { .. })) = app.gesture_window(seat) {
+                    base.move_triangle(dx as f32, dy as f32);
+                }
+            }
+            Event::End { cancelled, .. } => app.finish_gesture(qh, seat, cancelled != 0),
+            _ => {}
+        }
+    }
+}
+
+impl<H: AppHandler> Dispatch<ZwpPointerGesturePinchV1, wl_seat::WlSeat> for App<H> {
+    fn event(
+        app: &mut Self,
+        _pinch: &ZwpPointerGesturePinchV1,
+        event: zwp_pointer_gesture_pinch_v1::Event,
+        seat: &wl_seat::WlSeat,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        use zwp_pointer_gesture_pinch_v1::Event;
+
+        match event {
+            Event::Begin { surface, fingers, .. } => {
+                app.begin_gesture(qh, seat, &surface, fingers, |base| {
+                    Some(Gesture::Pinch { start_zoom: base.zoom })
+                });
+            }
+            Event::Update { scale, .. } => {
+                // The scale is relative to the start of the pinch, not to the last update
+                if let Some((base, &Gesture::Pinch { start_zoom })) = app.gesture_window(seat) {
+                    base.set_zoom(start_zoom * scale as f32);
+                }
+            }
+            Event::End { cancelled, .. } => app.finish_gesture(qh, seat, cancelled != 0),
+            _ => {}
+        }
+    }
+}
+
+impl<H: AppHandler> Dispatch<ZwpPointerGestureHoldV1, wl_seat::WlSeat> for App<H> {
+    fn event(
+        app: &mut Self,
+        _hold: &ZwpPointerGestureHoldV1,
+        event: zwp_pointer_gesture_hold_v1::Event,
+        seat: &wl_seat::WlSeat,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        use zwp_pointer_gesture_hold_v1::Event;
+
+        match event {
+            Event::Begin { surface, fingers, .. } => {
+                app.begin_gesture(qh, seat, &surface, fingers, |base| {
+                    Some(Gesture::Hold { paused: base.animating() })
+                });
+            }
+            // Fingers that start moving cancel the hold, the animation goes on either way
+            Event::End { cancelled, .. } => app.finish_gesture(qh, seat, cancelled != 0),
+            _ => {}
+        }
+    }
+}
+
+delegate_noop!(@<H: AppHandler> App<H>: ignore ZwpPointerGesturesV1);
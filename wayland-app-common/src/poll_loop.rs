@@ -0,0 +1,124 @@
+//! Running the Wayland queue from a plain `poll(2)` loop, the way an engine that owns its main
+//! loop would integrate it. Nothing in here blocks except `poll` itself.
+
+use std::{
+    io::{ErrorKind, Read, Write},
+    os::fd::AsRawFd,
+    thread,
+    time::{Duration, Instant},
+};
+
+use wayland_client::{backend::WaylandError, EventQueue};
+
+use crate::{App, AppHandler, ConnectionError};
+
+impl<H: AppHandler> App<H> {
+    /// Dispatches events from a `poll(2)` loop instead of calloop, see `--poll-loop`.
+    ///
+    /// A helper thread wakes the loop through a pipe every redraw interval, standing in for
+    /// whatever other thread of the embedding application needs the loop's attention.
+    pub(crate) fn run_poll_loop(
+        &mut self,
+        mut event_queue: EventQueue<Self>,
+    ) -> Result<(), ConnectionError> {
+        let qh = event_queue.handle();
+        let conn = self.shared.conn.clone();
+
+        let (mut wake_reader, wake_writer) = std::io::pipe().expect("Failed to create wake pipe");
+        spawn_waker(wake_writer, self.shared.options.redraw_interval);
+        let exit_at = self.shared.options.exit_after_seconds.map(|after| Instant::now() + after);
+
+        loop {
+            if exit_at.is_some_and(|at| Instant::now() >= at) {
+                self.shared.exit = true;
+            }
+            // Events read on an earlier round may still be queued, they must be dispatched
+            // before going to sleep or they'd wait for the next unrelated wake up.
+            if let Err(err) = event_queue.dispatch_pending(self) {
+                return Err(ConnectionError::dispatch(&self.shared.conn, err));
+            }
+            self.after_dispatch(&qh);
+            if self.shared.exit {
+                println!("exiting example");
+                return Ok(());
+            }
+
+            // Send our requests. With a full socket buffer the rest is sent once the socket is
+            // writable again, so wait for that too.
+            let mut wait_for_writable = false;
+            match conn.flush() {
+                Ok(()) => {}
+                Err(WaylandError::Io(err)) if err.kind() == ErrorKind::WouldBlock => {
+                    wait_for_writable = true;
+                }
+                Err(err) => return Err(ConnectionError::wayland(&self.shared.conn, err)),
+            }
+
+            // Announces that we are about to read. None means events arrived in the meantime,
+            // go back and dispatch them first.
+            let Some(guard) = event_queue.prepare_read() else {
+                continue;
+            };
+
+            let mut fds = [
+                libc::pollfd {
+                    fd: guard.connection_fd().as_raw_fd(),
+                    events: libc::POLLIN | if wait_for_writable { libc::POLLOUT } else { 0 },
+                    revents: 0,
+                },
+                libc::pollfd {
+                    fd: wake_reader.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                },
+            ];
+            // Without --exit-after-seconds only the socket and the waker end the wait
+            let timeout = exit_at.map_or(-1, |at| {
+                let left = at.saturating_duration_since(Instant::now()).as_millis();
+                left.min(i32::MAX as u128) as i32
+            });
+            let nfds = fds.len() as libc::nfds_t;
+            let result = unsafe { libc::poll(fds.as_mut_ptr(), nfds, timeout) };
+            if result < 0 {
+                let err = std::io::Error::last_os_error();
+                if err.kind() == ErrorKind::Interrupted {
+                    // Dropping the guard cancels the read
+                    continue;
+                }
+                panic!("poll failed: {err}");
+            }
+
+            if fds[0].revents & (libc::POLLIN | libc::POLLERR | libc::POLLHUP) != 0 {
+                // Reads whatever is on the socket. A message that only partially arrived stays
+                // buffered in the backend until the rest of it comes, so nothing is lost.
+                match guard.read() {
+                    Ok(_) => {}
+                    // Another reader got to the events first, or the wake up was spurious
+                    Err(WaylandError::Io(err)) if err.kind() == ErrorKind::WouldBlock => {}
+                    Err(err) => return Err(ConnectionError::wayland(&self.shared.conn, err)),
+                }
+            } else {
+                drop(guard);
+            }
+
+            if fds[1].revents & libc::POLLIN != 0 {
+                let mut buffer = [0; 64];
+                let _ = wake_reader.read(&mut buffer);
+                self.redraw_idle_windows(&qh);
+            }
+        }
+    }
+}
+
+/// Wakes the loop at the given interval from another thread. The thread ends once the loop is
+/// gone and the read end of the pipe with it.
+fn spawn_waker(mut writer: std::io::PipeWriter, interval: Duration) {
+    thread::spawn(move || {
+        loop {
+            thread::sleep(interval);
+            if writer.write_all(&[1]).is_err() {
+                break;
+            }
+        }
+    });
+}
@@ -0,0 +1,220 @@
+//! A small popup menu opened with a right click, to exercise xdg_popup. The menu is a column of
+//! items the renderer draws, see [`AppHandler::draw_popup`].
+
+use smithay_client_toolkit::{
+    reexports::protocols::xdg::shell::client::xdg_positioner::{
+        Anchor, ConstraintAdjustment, Gravity,
+    },
+    shell::xdg::{
+        popup::{Popup, PopupConfigure, PopupHandler},
+        XdgPositioner, XdgSurface,
+    },
+};
+use wayland_client::{backend::ObjectId, protocol::wl_seat, Connection, Proxy, QueueHandle};
+
+use crate::{App, AppHandler};
+
+/// How many items the menu has.
+pub const MENU_ITEMS: u32 = 3;
+/// Size of a menu item in logical pixels.
+pub const MENU_ITEM_WIDTH: u32 = 160;
+pub const MENU_ITEM_HEIGHT: u32 = 32;
+
+const MENU_COLOR: [f32; 3] = [0.15, 0.15, 0.15];
+const MENU_ITEM_COLOR: [f32; 3] = [0.35, 0.35, 0.4];
+/// Gap around the items in logical pixels, the menu color shows through.
+const MENU_PADDING: u32 = 4;
+
+/// The popup menu and what the renderer needs to know to draw it.
+pub struct MenuPopup {
+    /// Logical size from the latest configure, the compositor may shrink the menu to keep it
+    /// on screen.
+    pub width: u32,
+    pub height: u32,
+    /// Integer buffer scale, taken over from the parent window.
+    pub scale: i32,
+    popup: Popup,
+    /// The window the menu was opened on.
+    parent: ObjectId,
+}
+
+impl MenuPopup {
+    /// The id of the popup's `wl_surface`, e.g. for keying the renderer's state.
+    pub fn id(&self) -> ObjectId {
+        self.popup.wl_surface().id()
+    }
+
+    /// The popup, e.g. for getting at the `wl_surface` to render into.
+    pub fn popup(&self) -> &Popup {
+        &self.popup
+    }
+
+    /// Size of the menu in physical pixels.
+    pub fn buffer_size(&self) -> (u32, u32) {
+        let scale = self.scale as u32;
+        (self.width * scale, self.height * scale)
+    }
+
+    /// The background and the items as `[x, y, width, height]` in GL buffer coordinates (origin
+    /// bottom left) with their colors, ready for `glScissor` and `glClear`.
+    pub fn menu_rects(&self) -> Vec<([i32; 4], [f32; 3])> {
+        let scale = self.scale;
+        let (width, height) = self.buffer_size();
+        let mut rects = vec![([0, 0, width as i32, height as i32], MENU_COLOR)];
+        for item in 0..MENU_ITEMS {
+            let top = (MENU_PADDING + item * MENU_ITEM_HEIGHT) as i32 * scale;
+            let item_height = (MENU_ITEM_HEIGHT - MENU_PADDING) as i32 * scale;
+            rects.push((
+                [
+                    MENU_PADDING as i32 * scale,
+                    height as i32 - top - item_height,
+                    width as i32 - 2 * MENU_PADDING as i32 * scale,
+                    item_height,
+                ],
+                MENU_ITEM_COLOR,
+            ));
+        }
+        rects
+    }
+
+    /// The item at the surface-local position, if any.
+    fn item_at(&self, y: f64) -> Option<u32> {
+        let item = (y as u32).checked_sub(MENU_PADDING)? / MENU_ITEM_HEIGHT;
+        (item < MENU_ITEMS).then_some(item)
+    }
+}
+
+impl<H: AppHandler> App<H> {
+    /// Opens the menu on the window at the surface-local position of a click. The popup grabs
+    /// the pointer and keyboard of the seat, so a click anywhere else dismisses it.
+    pub(crate) fn open_popup(
+        &mut self,
+        qh: &QueueHandle<Self>,
+        id: &ObjectId,
+        seat: &wl_seat::WlSeat,
+        serial: u32,
+        (x, y): (f64, f64),
+    ) {
+        // Only one menu at a time, opening another one closes the old
+        self.close_popup();
+        let Some(base) = self.windows.get(id) else {
+            return;
+        };
+
+        let positioner = match XdgPositioner::new(&self.shared.xdg_shell) {
+            Ok(positioner) => positioner,
+            Err(err) => {
+                log::warn!("failed to create a positioner: {err}");
+                return;
+            }
+        };
+        let (width, height) = (MENU_ITEM_WIDTH, MENU_ITEMS * MENU_ITEM_HEIGHT + MENU_PADDING);
+        positioner.set_size(width as i32, height as i32);
+        // Anchored to the pixel that was clicked, opening towards the bottom right unless that
+        // doesn't fit on the output
+        positioner.set_anchor_rect(x as i32, y as i32, 1, 1);
+        positioner.set_anchor(Anchor::BottomRight);
+        positioner.set_gravity(Gravity::BottomRight);
+        positioner.set_constraint_adjustment(
+            ConstraintAdjustment::FlipX
+                | ConstraintAdjustment::FlipY
+                | ConstraintAdjustment::SlideX
+                | ConstraintAdjustment::SlideY,
+        );
+
+        let popup = match Popup::new(
+            base.window.xdg_surface(),
+            &positioner,
+            qh,
+            &self.shared.compositor_state,
+            &self.shared.xdg_shell,
+        ) {
+            Ok(popup) => popup,
+            Err(err) => {
+                log::warn!("failed to create the popup: {err}");
+                return;
+            }
+        };
+
+        // The grab has to come before the first commit, with the serial of the click
+        popup.xdg_popup().grab(seat, serial);
+        popup.wl_surface().set_buffer_scale(base.scale);
+        popup.wl_surface().commit();
+
+        let popup = MenuPopup {
+            width,
+            height,
+            scale: base.scale,
+            popup,
+            parent: id.clone(),
+        };
+        log::info!("opened popup {} on window {id}", popup.id());
+        self.popup = Some(popup);
+    }
+
+    /// Lets the renderer free its state for the menu and destroys it.
+    pub(crate) fn close_popup(&mut self) {
+        if let Some(mut popup) = self.popup.take() {
+            log::info!("closed popup {}", popup.id());
+            self.handler.popup_closed(&mut popup);
+        }
+    }
+
+    /// Closes the menu when it belongs to the window, popups have to go before their parent.
+    pub(crate) fn close_popup_of(&mut self, id: &ObjectId) {
+        if self.popup.as_ref().is_some_and(|popup| &popup.parent == id) {
+            self.close_popup();
+        }
+    }
+
+    /// Handles a click on the menu, returns false when the click wasn't on the menu.
+    pub(crate) fn popup_clicked(&mut self, surface: &ObjectId, y: f64) -> bool {
+        let Some(popup) = &self.popup else {
+            return false;
+        };
+        if &popup.id() != surface {
+            return false;
+        }
+
+        match popup.item_at(y) {
+            Some(item) => println!("menu item {item} clicked"),
+            None => println!("menu clicked between the items"),
+        }
+        self.close_popup();
+        true
+    }
+}
+
+impl<H: AppHandler> PopupHandler for App<H> {
+    fn configure(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        popup: &Popup,
+        config: PopupConfigure,
+    ) {
+        let Some(menu) = self.popup.as_mut().filter(|menu| &menu.popup == popup) else {
+            return;
+        };
+
+        // The configure has already been acked, the buffer that goes with it follows here
+        if config.width > 0 && config.height > 0 {
+            menu.width = config.width as u32;
+            menu.height = config.height as u32;
+        }
+        log::debug!(
+            "popup configured at {:?}, {}x{}",
+            config.position,
+            menu.width,
+            menu.height
+        );
+        self.handler.draw_popup(menu);
+    }
+
+    fn done(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, popup: &Popup) {
+        // Dismissed by the compositor, e.g. because of a click outside the menu
+        if self.popup.as_ref().is_some_and(|menu| &menu.popup == popup) {
+            self.close_popup();
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use smithay_client_toolkit::{
+    reexports::protocols::wp::presentation_time::client::{
+        wp_presentation::{self, WpPresentation},
+        wp_presentation_feedback::{self, Kind, WpPresentationFeedback},
+    },
+    shell::WaylandSurface,
+};
+use wayland_client::{
+    backend::ObjectId, globals::GlobalList, protocol::wl_output, Connection, Dispatch, QueueHandle,
+    WEnum,
+};
+
+use crate::{App, AppHandler, BaseApp};
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+/// Tracks when the frames of a window actually reach the screen through wp_presentation.
+#[derive(Default)]
+pub(crate) struct Presentation {
+    next_frame: u64,
+    /// Frames waiting for their feedback, with the time their buffer was swapped.
+    swapped: VecDeque<(u64, u64)>,
+    /// Presentation time of the previous frame, for spotting missed vblanks. Cleared when a frame
+    /// is discarded so a stretch of hidden frames doesn't count as missed.
+    last_presented: Option<u64>,
+    /// Refresh cycle of the output the last frame was presented on, 0 when unknown.
+    refresh: u64,
+    stats: PresentationStats,
+}
+
+/// Statistics over the current one second period.
+#[derive(Default)]
+struct PresentationStats {
+    start: Option<u64>,
+    presented: u64,
+    discarded: u64,
+    vsynced: u64,
+    zero_copy: u64,
+    missed_vblanks: u64,
+    total_latency: u64,
+}
+
+/// User data of a feedback object.
+pub(crate) struct FeedbackData {
+    /// The window the frame was drawn for.
+    window: ObjectId,
+    frame: u64,
+    /// The output the presentation was synchronized to, sent before `presented`.
+    output: Mutex<Option<wl_output::WlOutput>>,
+}
+
+/// Binds wp_presentation, or nothing when the compositor doesn't support it.
+pub(crate) fn bind<H: AppHandler>(
+    globals: &GlobalList,
+    qh: &QueueHandle<App<H>>,
+) -> Option<WpPresentation> {
+    globals.bind::<WpPresentation, _, _>(qh, 1..=1, ()).ok()
+}
+
+/// Current time of the presentation clock in nanoseconds.
+fn now(clock_id: u32) -> Option<u64> {
+    let mut time = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    let result = unsafe { libc::clock_gettime(clock_id as libc::clockid_t, &mut time) };
+    (result == 0).then(|| time.tv_sec as u64 * NANOS_PER_SEC + time.tv_nsec as u64)
+}
+
+impl Presentation {
+    /// Takes the swap time of the frame out of the queue. Feedback arrives in commit order, so
+    /// anything older was never answered and is dropped along the way.
+    fn take_swapped(&mut self, frame: u64) -> Option<u64> {
+        while let Some((queued, swapped)) = self.swapped.pop_front() {
+            if queued == frame {
+                return Some(swapped);
+            }
+        }
+        None
+    }
+
+    fn presented(&mut self, frame: u64, time: u64, refresh: u64, kind: Kind, output: Option<&str>) {
+        let latency = self.take_swapped(frame).map(|swapped| time.saturating_sub(swapped));
+
+        // A gap of more than one and a half refresh cycles since the last frame means vblanks
+        // went by without a new frame from us.
+        let mut missed = 0;
+        if let Some(last) = self.last_presented
+            && refresh > 0
+        {
+            let gap = time.saturating_sub(last);
+            if gap > refresh * 3 / 2 {
+                missed = (gap + refresh / 2) / refresh - 1;
+            }
+        }
+        self.last_presented = Some(time);
+        self.refresh = refresh;
+
+        log::debug!(
+            "frame {frame} presented on {}, latency {:.2} ms, refresh {:.2} ms, {kind:?}",
+            output.unwrap_or("unknown output"),
+            latency.unwrap_or(0) as f64 / 1e6,
+            refresh as f64 / 1e6,
+        );
+
+        let stats = &mut self.stats;
+        stats.presented += 1;
+        stats.missed_vblanks += missed;
+        stats.total_latency += latency.unwrap_or(0);
+        if kind.contains(Kind::Vsync) {
+            stats.vsynced += 1;
+        }
+        if kind.contains(Kind::ZeroCopy) {
+            stats.zero_copy += 1;
+        }
+        self.report(time);
+    }
+
+    fn discarded(&mut self, frame: u64) {
+        self.take_swapped(frame);
+        self.last_presented = None;
+        self.stats.discarded += 1;
+        log::debug!("frame {frame} discarded");
+    }
+
+    /// Logs a summary once the current period is a second long.
+    fn report(&mut self, time: u64) {
+        let start = *self.stats.start.get_or_insert(time);
+        if time - start < NANOS_PER_SEC {
+            return;
+        }
+
+        let stats = std::mem::take(&mut self.stats);
+        log::info!(
+            "presented {} frames ({} vsynced, {} zero-copy), {} discarded, {} missed vblanks, \
+             average latency {:.2} ms",
+            stats.presented,
+            stats.vsynced,
+            stats.zero_copy,
+            stats.discarded,
+            stats.missed_vblanks,
+            stats.total_latency as f64 / stats.presented.max(1) as f64 / 1e6,
+        );
+        self.stats.start = Some(time);
+    }
+}
+
+impl BaseApp {
+    /// Asks for presentation feedback on the next commit. Returns the frame number to pass to
+    /// [`Self::frame_swapped`] once the renderer has committed.
+    pub(crate) fn request_presentation_feedback<H: AppHandler>(
+        &mut self,
+        presentation: Option<&WpPresentation>,
+        qh: &QueueHandle<App<H>>,
+    ) -> Option<u64> {
+        let presentation = presentation?;
+        let frame = self.presentation.next_frame;
+        self.presentation.next_frame += 1;
+
+        let data = FeedbackData {
+            window: self.id(),
+            frame,
+            output: Mutex::new(None),
+        };
+        presentation.feedback(self.window.wl_surface(), qh, data);
+        Some(frame)
+    }
+
+    /// How long to wait before drawing so the frame isn't presented sooner than `interval` after
+    /// the last one, going by the presentation feedback. `None` without feedback to go by, e.g.
+    /// after a frame was discarded.
+    pub(crate) fn presentation_delay(
+        &self,
+        interval: Duration,
+        clock_id: Option<u32>,
+    ) -> Option<Duration> {
+        let last = self.presentation.last_presented?;
+        let now = now(clock_id?)?;
+        // A frame drawn now is shown with the next refresh at the earliest
+        let earliest = now + self.presentation.refresh;
+        let due = last + interval.as_nanos() as u64;
+        Some(Duration::from_nanos(due.saturating_sub(earliest)))
+    }
+
+    /// Records when the buffer of the frame was swapped, the latency is measured from here.
+    pub(crate) fn frame_swapped(&mut self, frame: Option<u64>, clock_id: Option<u32>) {
+        if let (Some(frame), Some(clock_id)) = (frame, clock_id)
+            && let Some(now) = now(clock_id)
+        {
+            self.presentation.swapped.push_back((frame, now));
+        }
+    }
+}
+
+impl<H: AppHandler> Dispatch<WpPresentation, ()> for App<H> {
+    fn event(
+        app: &mut Self,
+        _proxy: &WpPresentation,
+        event: wp_presentation::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let wp_presentation::Event::ClockId { clk_id } = event {
+            app.shared.presentation_clock = Some(clk_id);
+        }
+    }
+}
+
+impl<H: AppHandler> Dispatch<WpPresentationFeedback, FeedbackData> for App<H> {
+    fn event(
+        app: &mut Self,
+        _proxy: &WpPresentationFeedback,
+        event: wp_presentation_feedback::Event,
+        data: &FeedbackData,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            wp_presentation_feedback::Event::SyncOutput { output } => {
+                *data.output.lock().unwrap() = Some(output);
+            }
+            wp_presentation_feedback::Event::Presented {
+                tv_sec_hi,
+                tv_sec_lo,
+                tv_nsec,
+                refresh,
+                flags,
+                ..
+            } => {
+                let seconds = ((tv_sec_hi as u64) << 32) | tv_sec_lo as u64;
+                let time = seconds * NANOS_PER_SEC + tv_nsec as u64;
+                let kind = match flags {
+                    WEnum::Value(kind) => kind,
+                    WEnum::Unknown(bits) => Kind::from_bits_truncate(bits),
+                };
+                let output = data
+                    .output
+                    .lock()
+                    .unwrap()
+                    .as_ref()
+                    .and_then(|output| app.shared.output_state.info(output))
+                    .and_then(|info| info.name);
+
+                // The window may have been closed in the meantime
+                if let Some(base) = app.windows.get_mut(&data.window) {
+                    let presentation = &mut base.presentation;
+                    presentation.presented(data.frame, time, refresh as u64, kind, output.as_deref());
+                }
+            }
+            wp_presentation_feedback::Event::Discarded => {
+                if let Some(base) = app.windows.get_mut(&data.window) {
+                    base.presentation.discarded(data.frame);
+                }
+            }
+            _ => {}
+        }
+    }
+}
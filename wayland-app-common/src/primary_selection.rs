@@ -0,0 +1,137 @@
+//! The primary selection, the middle-click clipboard. Dragging with Shift held selects the
+//! clear color of the window, a middle click pastes the primary selection into the window. It
+//! is independent of the clipboard of Ctrl+C and Ctrl+V.
+
+use std::io::Write;
+
+use smithay_client_toolkit::{
+    data_device_manager::WritePipe,
+    primary_selection::{
+        device::PrimarySelectionDeviceHandler, selection::PrimarySelectionSourceHandler,
+    },
+    reexports::protocols::wp::primary_selection::zv1::client::{
+        zwp_primary_selection_device_v1::ZwpPrimarySelectionDeviceV1,
+        zwp_primary_selection_source_v1::ZwpPrimarySelectionSourceV1,
+    },
+};
+use wayland_client::{backend::ObjectId, protocol::wl_seat, Connection, QueueHandle};
+
+use crate::{
+    clipboard::{format_color, text_mime_type, TEXT_MIME_TYPES},
+    App, AppHandler,
+};
+
+impl<H: AppHandler> App<H> {
+    /// Makes the clear color of the window the primary selection of the seat, `serial` is that
+    /// of the button press that started selecting.
+    pub(crate) fn select_color(
+        &mut self,
+        qh: &QueueHandle<Self>,
+        id: &ObjectId,
+        seat: &wl_seat::WlSeat,
+        serial: u32,
+    ) {
+        let shared = &mut self.shared;
+        let device = shared.seats.get(seat).and_then(|seat| seat.primary_selection_device.as_ref());
+        let (Some(manager), Some(device)) = (&shared.primary_selection_manager, device) else {
+            println!(
+                "selecting needs zwp_primary_selection_device_manager_v1, which the compositor lacks"
+            );
+            return;
+        };
+        let Some(base) = self.windows.get(id) else {
+            return;
+        };
+
+        let text = format_color(base.clear_color());
+        let source = manager.create_selection_source(qh, TEXT_MIME_TYPES);
+        source.set_selection(device, serial);
+        println!("selected {text}");
+        shared.primary_source = Some((source, text));
+    }
+
+    /// Pastes the primary selection of the seat into the window when it holds a color.
+    pub(crate) fn paste_primary(&mut self, id: &ObjectId, seat: &wl_seat::WlSeat) {
+        let resources = self.shared.seats.get(seat);
+        let Some(device) = resources.and_then(|seat| seat.primary_selection_device.as_ref()) else {
+            println!(
+                "middle-click paste needs zwp_primary_selection_device_manager_v1, which the \
+                 compositor lacks"
+            );
+            return;
+        };
+        let Some(offer) = device.data().selection_offer() else {
+            println!("nothing to paste, the primary selection is empty");
+            return;
+        };
+        let Some(mime_type) = offer.with_mime_types(text_mime_type) else {
+            println!("nothing to paste, the primary selection holds no text");
+            return;
+        };
+        if !self.can_read_pipes() {
+            return;
+        }
+
+        match offer.receive(mime_type.to_string()) {
+            Ok(read_pipe) => self.read_pasted_color(id, read_pipe, "primary selection"),
+            Err(err) => log::warn!("failed to receive the primary selection: {err}"),
+        }
+    }
+}
+
+impl<H: AppHandler> PrimarySelectionDeviceHandler for App<H> {
+    fn selection(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _device: &ZwpPrimarySelectionDeviceV1,
+    ) {
+        // The offer is kept by the device, it's looked at when pasting
+        log::debug!("the primary selection changed");
+    }
+}
+
+impl<H: AppHandler> PrimarySelectionSourceHandler for App<H> {
+    fn send_request(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        source: &ZwpPrimarySelectionSourceV1,
+        mime: String,
+        mut write_pipe: WritePipe,
+    ) {
+        let Some((_, text)) = self
+            .shared
+            .primary_source
+            .as_ref()
+            .filter(|(selected, _)| selected.inner() == source)
+        else {
+            return;
+        };
+        if !TEXT_MIME_TYPES.contains(&mime.as_str()) {
+            return;
+        }
+
+        // Small enough for the pipe buffer, see the clipboard
+        if let Err(err) = write_pipe.write_all(text.as_bytes()) {
+            log::warn!("failed to send the primary selection: {err}");
+        }
+    }
+
+    fn cancelled(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        source: &ZwpPrimarySelectionSourceV1,
+    ) {
+        // Something else got selected
+        if self
+            .shared
+            .primary_source
+            .as_ref()
+            .is_some_and(|(selected, _)| selected.inner() == source)
+        {
+            self.shared.primary_source = None;
+        }
+    }
+}
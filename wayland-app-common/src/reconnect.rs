@@ -0,0 +1,94 @@
+//! What happens when the connection fails. A protocol error ends the app with the error the
+//! compositor sent, a connection that broke, e.g. because the compositor restarted, is made
+//! again with `--reconnect <n>`.
+//!
+//! Nothing of the old connection carries over: the globals are bound again and the windows and
+//! renderers are made from scratch. The renderers have to free what belongs to the old display
+//! first, EGL may hand the same display out again for a new `wl_display` at the same address.
+
+use std::{
+    io, thread,
+    time::{Duration, Instant},
+};
+
+use wayland_client::{backend::WaylandError, Connection, DispatchError, EventQueue};
+
+use crate::{App, AppHandler, ConnectionError, Options};
+
+/// How long `--reconnect` waits for the compositor to come back.
+const RECONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Runs the app made by `start` until it exits, for the `main` of an example. `teardown` frees
+/// what the renderer holds for the windows before they go, after an exit and after a broken
+/// connection alike.
+///
+/// A protocol error exits with status 1. So does a broken connection, unless `--reconnect`
+/// allows another attempt and the compositor comes back within 10 seconds.
+pub fn run_app<H: AppHandler>(
+    options: Options,
+    mut start: impl FnMut(Options) -> (App<H>, EventQueue<App<H>>),
+    mut teardown: impl FnMut(App<H>),
+) {
+    let mut reconnects = 0;
+    loop {
+        let (mut app, event_queue) = start(options.clone());
+        let result = app.run(event_queue);
+        teardown(app);
+        let Err(err) = result else {
+            return;
+        };
+
+        eprintln!("{err}");
+        if !err.is_broken() || reconnects == options.reconnect {
+            std::process::exit(1);
+        }
+        reconnects += 1;
+        println!("reconnecting, attempt {reconnects} of {}", options.reconnect);
+        if !wait_for_compositor() {
+            eprintln!("no compositor to reconnect to within {RECONNECT_TIMEOUT:?}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Waits up to 10 seconds for a compositor to accept connections on `WAYLAND_DISPLAY` again.
+pub fn wait_for_compositor() -> bool {
+    let start = Instant::now();
+    loop {
+        match Connection::connect_to_env() {
+            Ok(_) => return true,
+            Err(err) if start.elapsed() >= RECONNECT_TIMEOUT => {
+                log::warn!("can't connect: {err}");
+                return false;
+            }
+            Err(_) => thread::sleep(Duration::from_millis(200)),
+        }
+    }
+}
+
+impl ConnectionError {
+    /// The error to report for a failure of the connection, the protocol error the compositor
+    /// sent if there was one.
+    pub(crate) fn io(conn: &Connection, err: io::Error) -> ConnectionError {
+        match conn.protocol_error() {
+            Some(protocol_error) => ConnectionError::Protocol(protocol_error),
+            None => ConnectionError::Io(err),
+        }
+    }
+
+    /// [`Self::io`] for a failed read or flush.
+    pub fn wayland(conn: &Connection, err: WaylandError) -> ConnectionError {
+        match err {
+            WaylandError::Io(err) => ConnectionError::io(conn, err),
+            WaylandError::Protocol(protocol_error) => ConnectionError::Protocol(protocol_error),
+        }
+    }
+
+    /// [`Self::io`] for a failed dispatch, e.g. of `EventQueue::blocking_dispatch`.
+    pub fn dispatch(conn: &Connection, err: DispatchError) -> ConnectionError {
+        match err {
+            DispatchError::Backend(err) => ConnectionError::wayland(conn, err),
+            err => ConnectionError::io(conn, io::Error::other(err.to_string())),
+        }
+    }
+}
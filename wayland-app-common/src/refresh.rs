@@ -0,0 +1,130 @@
+//! The refresh rate of the outputs a window is on, from their current modes. The fastest of
+//! them sets the interval the frame callbacks should come at, a window spanning a 60 Hz and a
+//! 144 Hz output is drawn at 144 Hz. Frames the redraw timer draws while no callback comes move
+//! the animation on by the intervals that passed, and a warning tells when most callbacks come
+//! later than an interval after the one before.
+
+use std::time::Duration;
+
+use wayland_client::backend::ObjectId;
+
+use crate::{App, AppHandler, BaseApp};
+
+/// Callbacks this many intervals after the one before missed a refresh.
+const LATE_FACTOR: f64 = 1.5;
+
+/// Gaps between callbacks this long are a window that stopped drawing for a while, e.g. paused
+/// or hidden, not a missed refresh.
+const MAX_GAP_MS: u32 = 1000;
+
+/// Callbacks counted for a verdict, more than half of them late warns.
+const VERDICT_FRAMES: u32 = 120;
+
+/// The refresh of a window and how well its frames keep up.
+#[derive(Default)]
+pub(crate) struct Refresh {
+    /// In mHz, of the fastest output the window is on with a known rate.
+    rate: Option<i32>,
+    /// The time of the last callback that was counted.
+    last_callback: Option<u32>,
+    frames: u32,
+    late: u32,
+    /// Whether the last verdict warned, the next only tells when that changes.
+    warned: bool,
+}
+
+impl Refresh {
+    /// The time between two refreshes of the fastest output.
+    fn interval(&self) -> Option<Duration> {
+        self.rate.map(|rate| Duration::from_secs_f64(1000.0 / f64::from(rate)))
+    }
+}
+
+impl BaseApp {
+    /// Advances the animation by the whole frame intervals since the last frame callback, for
+    /// a frame drawn while none came. Without a known refresh rate by the time that passed.
+    pub(crate) fn extrapolate_time(&mut self) {
+        let Some((time, at)) = self.last_frame_done else {
+            return;
+        };
+        let elapsed = at.elapsed();
+        let elapsed = match self.refresh.interval() {
+            Some(interval) => interval * (elapsed.as_secs_f64() / interval.as_secs_f64()) as u32,
+            None => elapsed,
+        };
+        self.advance_time(time.wrapping_add(elapsed.as_millis() as u32));
+    }
+
+    /// Counts the frame callback as on time or late, given the refresh of the window or the
+    /// `--max-fps` interval when that is longer. Warns once most of a run of callbacks came
+    /// late, and tells when they keep up again.
+    pub(crate) fn check_refresh(&mut self, time: u32, max_fps: Option<u32>) {
+        let Some(interval) = self.refresh.interval() else {
+            return;
+        };
+        let capped = max_fps.map(|fps| Duration::from_secs(1) / fps);
+        let interval = capped.map_or(interval, |capped| capped.max(interval));
+        let id = self.id();
+        let refresh = &mut self.refresh;
+        let last = refresh.last_callback.replace(time);
+        let Some(gap) = last.map(|last| time.wrapping_sub(last)).filter(|&gap| gap < MAX_GAP_MS)
+        else {
+            return;
+        };
+
+        refresh.frames += 1;
+        if f64::from(gap) > interval.as_secs_f64() * 1000.0 * LATE_FACTOR {
+            refresh.late += 1;
+        }
+        if refresh.frames < VERDICT_FRAMES {
+            return;
+        }
+        let (frames, late) = (refresh.frames, refresh.late);
+        (refresh.frames, refresh.late) = (0, 0);
+        let missing = late * 2 > frames;
+        if missing && !refresh.warned {
+            log::warn!(
+                "window {id} misses its frame interval of {:.2} ms, {late} of the last {frames} \
+                 frames came late",
+                interval.as_secs_f64() * 1000.0
+            );
+        } else if !missing && refresh.warned {
+            log::info!("window {id} keeps up with its frame interval again");
+        }
+        refresh.warned = missing;
+    }
+}
+
+impl<H: AppHandler> App<H> {
+    /// Takes the refresh rate of the window from the fastest output it is on, after it entered
+    /// or left one or an output changed its mode.
+    pub(crate) fn update_refresh(&mut self, id: &ObjectId) {
+        let Some(base) = self.windows.get_mut(id) else {
+            return;
+        };
+        let output_state = &self.shared.output_state;
+        let rate = base
+            .outputs
+            .iter()
+            .filter_map(|output| {
+                let info = output_state.info(output)?;
+                Some(info.modes.iter().find(|mode| mode.current)?.refresh_rate)
+            })
+            // Virtual outputs may have no rate
+            .filter(|&rate| rate > 0)
+            .max();
+        if rate == base.refresh.rate {
+            return;
+        }
+
+        base.refresh = Refresh { rate, ..Default::default() };
+        match rate {
+            Some(rate) => log::info!(
+                "window {id} refreshes at {:.2} Hz, a frame every {:.2} ms",
+                f64::from(rate) / 1000.0,
+                1_000_000.0 / f64::from(rate)
+            ),
+            None => log::info!("window {id} is on no output with a known refresh rate"),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! U unmaps the focused window and maps it again, as a new xdg_toplevel with a new wl_surface
+//! two seconds later. The renderer tears down the surfaces of the old one like for any closed
+//! window, and sets up the ones of the new window with its first configure. A renderer with a
+//! context shared by all windows keeps it through this, along with the shaders and buffers in
+//! it. The app doesn't exit while a window is away, even when it was the only one.
+
+use std::time::Duration;
+
+use calloop::timer::{TimeoutAction, Timer};
+use wayland_client::{backend::ObjectId, QueueHandle};
+
+use crate::{App, AppHandler};
+
+/// How long a window stays unmapped.
+const REMAP_DELAY: Duration = Duration::from_secs(2);
+
+impl<H: AppHandler> App<H> {
+    /// Closes the window after this dispatch and opens a new one in its place after
+    /// [`REMAP_DELAY`].
+    pub(crate) fn remap_window(&mut self, qh: &QueueHandle<Self>, id: &ObjectId) {
+        let Some(handle) = &self.loop_handle else {
+            println!("remapping needs the timers of the event loop, not with --poll-loop");
+            return;
+        };
+        let Some(base) = self.windows.get_mut(id) else {
+            return;
+        };
+
+        let qh = qh.clone();
+        let inserted = handle.insert_source(Timer::from_duration(REMAP_DELAY), move |_, _, app| {
+            app.shared.unmapped -= 1;
+            let id = app.open_window(&qh);
+            println!("mapped the window again as {id}");
+            TimeoutAction::Drop
+        });
+        if let Err(err) = inserted {
+            log::warn!("failed to schedule mapping the window again: {err}");
+            return;
+        }
+        self.shared.unmapped += 1;
+        println!("unmapping window {id}, mapping it again in {} s", REMAP_DELAY.as_secs());
+        base.close = true;
+    }
+}
@@ -0,0 +1,282 @@
+//! The input devices of each seat. Compositors may offer several seats, e.g. a second one that
+//! wayvnc adds for a remote user, each with its own keyboard, pointer and touch, and its own
+//! focus. Keys act on the window the keyboard of their seat is focused on, the clipboard and
+//! the pointer constraints go through the seat the input came from. The log names the seats.
+
+use std::collections::HashMap;
+
+use smithay_client_toolkit::{
+    data_device_manager::data_device::DataDevice,
+    primary_selection::device::PrimarySelectionDevice,
+    reexports::protocols::wp::relative_pointer::zv1::client::{
+        zwp_relative_pointer_v1::ZwpRelativePointerV1,
+    },
+    seat::{
+        keyboard::{KeyboardData, Modifiers},
+        pointer::{CursorIcon, PointerData, ThemeSpec, ThemedPointer},
+        touch::TouchData,
+        Capability, SeatHandler, SeatState,
+    },
+};
+use wayland_client::{
+    backend::ObjectId,
+    protocol::{wl_keyboard, wl_pointer, wl_seat, wl_touch},
+    Connection, Proxy, QueueHandle,
+};
+
+use crate::{pointer_gestures, tablet, text_input, trace, App, AppHandler, SharedState};
+
+/// The input objects of one seat and where its input is focused.
+#[derive(Default)]
+pub(crate) struct SeatResources {
+    pub(crate) keyboard: Option<wl_keyboard::WlKeyboard>,
+    /// The window with the keyboard focus of the seat, its key presses act on it.
+    pub(crate) keyboard_focus: Option<ObjectId>,
+    pub(crate) modifiers: Modifiers,
+    pub(crate) pointer: Option<ThemedPointer>,
+    /// The window the pointer of the seat is over.
+    pub(crate) pointer_focus: Option<ObjectId>,
+    /// Created along with the pointer when the compositor has relative pointers.
+    pub(crate) relative_pointer: Option<ZwpRelativePointerV1>,
+    /// Created along with the pointer when the compositor has pointer gestures.
+    pub(crate) gestures: Option<pointer_gestures::Gestures>,
+    /// The cursor we last set, so motion only touches the cursor when it has to change.
+    pub(crate) cursor_icon: Option<CursorIcon>,
+    pub(crate) touch: Option<wl_touch::WlTouch>,
+    /// The window each touch point went down on, by touch id.
+    pub(crate) touch_points: HashMap<i32, ObjectId>,
+    /// Created along with the keyboard, the clipboard belongs to the seat's keyboard focus.
+    pub(crate) data_device: Option<DataDevice>,
+    /// Created along with the keyboard like the data device.
+    pub(crate) primary_selection_device: Option<PrimarySelectionDevice>,
+    /// Created along with the keyboard when the compositor has text-input-v3.
+    pub(crate) text_input: Option<text_input::TextInput>,
+    /// Created along with the seat when the compositor has tablets, they are no capability.
+    pub(crate) tablet_seat: Option<tablet::TabletSeat>,
+}
+
+impl SharedState {
+    /// The name of the seat for the log, e.g. "seat0". Its id until the compositor named it.
+    pub(crate) fn seat_name(&self, seat: &wl_seat::WlSeat) -> String {
+        self.seat_state
+            .info(seat)
+            .and_then(|info| info.name)
+            .unwrap_or_else(|| seat.id().to_string())
+    }
+
+    /// The seat a keyboard, pointer or touch belongs to, and its resources.
+    pub(crate) fn keyboard_seat<H: AppHandler>(
+        &mut self,
+        keyboard: &wl_keyboard::WlKeyboard,
+    ) -> Option<(wl_seat::WlSeat, &mut SeatResources)> {
+        let seat = keyboard.data::<KeyboardData<App<H>>>()?.seat().clone();
+        self.seats.get_mut(&seat).map(|resources| (seat, resources))
+    }
+
+    pub(crate) fn pointer_seat(
+        &mut self,
+        pointer: &wl_pointer::WlPointer,
+    ) -> Option<(wl_seat::WlSeat, &mut SeatResources)> {
+        let seat = pointer.data::<PointerData>()?.seat().clone();
+        self.seats.get_mut(&seat).map(|resources| (seat, resources))
+    }
+
+    pub(crate) fn touch_seat(
+        &mut self,
+        touch: &wl_touch::WlTouch,
+    ) -> Option<(wl_seat::WlSeat, &mut SeatResources)> {
+        let seat = touch.data::<TouchData>()?.seat().clone();
+        self.seats.get_mut(&seat).map(|resources| (seat, resources))
+    }
+
+    /// Adds the resources of the seat unless it has them already, along with its tablet seat.
+    fn add_seat<H: AppHandler>(&mut self, qh: &QueueHandle<App<H>>, seat: &wl_seat::WlSeat) {
+        let resources = self.seats.entry(seat.clone()).or_default();
+        if resources.tablet_seat.is_none()
+            && let Some(manager) = &self.tablet_manager
+        {
+            resources.tablet_seat = Some(tablet::TabletSeat::new(manager, seat, qh));
+        }
+    }
+
+    /// Whether the keyboard of any seat is focused on the window.
+    pub(crate) fn has_keyboard_focus(&self, id: &ObjectId) -> bool {
+        self.seats.values().any(|seat| seat.keyboard_focus.as_ref() == Some(id))
+    }
+}
+
+impl<H: AppHandler> App<H> {
+    /// Lets go of the keyboard of the seat and of what came with it.
+    fn release_keyboard(&mut self, qh: &QueueHandle<Self>, seat: &wl_seat::WlSeat) {
+        let Some(resources) = self.shared.seats.get_mut(seat) else {
+            return;
+        };
+        if let Some(keyboard) = resources.keyboard.take() {
+            keyboard.release();
+        }
+        let focus = resources.keyboard_focus.take();
+        resources.modifiers = Modifiers::default();
+        resources.data_device = None;
+        resources.primary_selection_device = None;
+        self.release_text_input(seat);
+        if let Some(id) = focus {
+            self.suspend_shortcuts_inhibit(qh, &id);
+        }
+        self.stop_key_repeat();
+    }
+
+    /// Lets go of the pointer of the seat, and of the constraints and gestures on it.
+    fn release_pointer(&mut self, qh: &QueueHandle<Self>, seat: &wl_seat::WlSeat) {
+        self.unlock_pointer_of(seat);
+        self.unconfine_pointer_of(seat);
+        let Some(resources) = self.shared.seats.get_mut(seat) else {
+            return;
+        };
+        if let Some(relative_pointer) = resources.relative_pointer.take() {
+            relative_pointer.destroy();
+        }
+        let gesture = resources.gestures.take().and_then(|gestures| gestures.destroy());
+        if let Some(pointer) = resources.pointer.take() {
+            pointer.pointer().release();
+        }
+        resources.cursor_icon = None;
+        let focus = resources.pointer_focus.take();
+        if let Some(base) = focus.and_then(|id| self.windows.get_mut(&id)) {
+            base.pointer_position = None;
+        }
+        // No end comes for a gesture under way anymore
+        if let Some((id, gesture)) = gesture {
+            self.end_gesture(qh, &id, gesture, true);
+        }
+    }
+
+    /// Lets go of the touch of the seat, its touch points are gone.
+    fn release_touch(&mut self, seat: &wl_seat::WlSeat) {
+        let Some(resources) = self.shared.seats.get_mut(seat) else {
+            return;
+        };
+        if let Some(touch) = resources.touch.take() {
+            touch.release();
+        }
+        for (id, window) in resources.touch_points.drain() {
+            if let Some(base) = self.windows.get_mut(&window) {
+                base.end_touch_point(id);
+            }
+        }
+    }
+}
+
+impl<H: AppHandler> SeatHandler for App<H> {
+    fn seat_state(&mut self) -> &mut SeatState {
+        &mut self.shared.seat_state
+    }
+
+    fn new_seat(&mut self, _: &Connection, qh: &QueueHandle<Self>, seat: wl_seat::WlSeat) {
+        self.shared.trace.record("new_seat", &[("seat", &seat.id())]);
+        log::info!("new seat {}", self.shared.seat_name(&seat));
+        self.shared.add_seat(qh, &seat);
+    }
+
+    fn new_capability(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        seat: wl_seat::WlSeat,
+        capability: Capability,
+    ) {
+        let name = self.shared.seat_name(&seat);
+        let shared = &mut self.shared;
+        shared.trace.record(
+            "new_capability",
+            &[("seat", &seat.id()), ("capability", &trace::Debugged(capability))],
+        );
+        log::info!("seat {name} gained its {capability}");
+        shared.add_seat(qh, &seat);
+        let Some(resources) = shared.seats.get_mut(&seat) else {
+            return;
+        };
+        if capability == Capability::Keyboard && resources.keyboard.is_none() {
+            let keyboard = shared
+                .seat_state
+                .get_keyboard(qh, &seat, None)
+                .expect("Failed to create keyboard");
+            resources.keyboard = Some(keyboard);
+
+            if let Some(manager) = &shared.data_device_manager {
+                resources.data_device = Some(manager.get_data_device(qh, &seat));
+            }
+            if let Some(manager) = &shared.primary_selection_manager {
+                resources.primary_selection_device = Some(manager.get_selection_device(qh, &seat));
+            }
+            if let Some(manager) = &shared.text_input_manager {
+                resources.text_input = Some(text_input::TextInput::new(manager, &seat, qh));
+            }
+        }
+
+        if capability == Capability::Pointer && resources.pointer.is_none() {
+            // The themed pointer uses the cursor shape protocol when available and falls back to
+            // drawing the cursor theme into a surface of its own.
+            let cursor_surface = shared.compositor_state.create_surface(qh);
+            let pointer = shared
+                .seat_state
+                .get_pointer_with_theme(
+                    qh,
+                    &seat,
+                    shared.shm.wl_shm(),
+                    cursor_surface,
+                    // Follows XCURSOR_THEME and XCURSOR_SIZE
+                    ThemeSpec::System,
+                )
+                .expect("Failed to create pointer");
+            resources.relative_pointer = shared
+                .relative_pointer_state
+                .get_relative_pointer(pointer.pointer(), qh)
+                .ok();
+            resources.gestures = shared.pointer_gestures.as_ref().map(|manager| {
+                pointer_gestures::Gestures::new(manager, pointer.pointer(), &seat, qh)
+            });
+            resources.pointer = Some(pointer);
+        }
+
+        if capability == Capability::Touch && resources.touch.is_none() {
+            let touch = shared
+                .seat_state
+                .get_touch(qh, &seat)
+                .expect("Failed to create touch");
+            resources.touch = Some(touch);
+        }
+    }
+
+    fn remove_capability(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        seat: wl_seat::WlSeat,
+        capability: Capability,
+    ) {
+        let removed = trace::Debugged(capability);
+        self.shared.trace.record(
+            "remove_capability",
+            &[("seat", &seat.id()), ("capability", &removed)],
+        );
+        log::info!("seat {} lost its {capability}", self.shared.seat_name(&seat));
+        match capability {
+            Capability::Keyboard => self.release_keyboard(qh, &seat),
+            Capability::Pointer => self.release_pointer(qh, &seat),
+            Capability::Touch => self.release_touch(&seat),
+            _ => {}
+        }
+    }
+
+    fn remove_seat(&mut self, _: &Connection, qh: &QueueHandle<Self>, seat: wl_seat::WlSeat) {
+        self.shared.trace.record("remove_seat", &[("seat", &seat.id())]);
+        log::info!("seat {} removed", self.shared.seat_name(&seat));
+        self.release_keyboard(qh, &seat);
+        self.release_pointer(qh, &seat);
+        self.release_touch(&seat);
+        let resources = self.shared.seats.remove(&seat);
+        if let Some(tablet_seat) = resources.and_then(|resources| resources.tablet_seat) {
+            tablet_seat.destroy();
+        }
+    }
+}
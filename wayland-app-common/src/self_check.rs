@@ -0,0 +1,519 @@
+//! `--self-check`, the examples check that what they draw reaches the screen. The windows fill
+//! with [`CHECK_COLOR`] instead of their scene, and a few frames in the output the first window
+//! is on is captured, with ext-image-copy-capture or else wlr-screencopy. Where the compositor
+//! put the window is for it to know, so we look for a solid rectangle of the color about the
+//! size the window has on that output and print PASS or FAIL.
+//!
+//! Without either protocol, or when the compositor refuses the capture, the check is skipped.
+//! The window should be opaque and in plain view, `--transparent` and overlapping windows fail.
+
+use smithay_client_toolkit::{
+    reexports::{
+        protocols::ext::{
+            image_capture_source::v1::client::{
+                ext_image_capture_source_v1::ExtImageCaptureSourceV1,
+                ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1,
+            },
+            image_copy_capture::v1::client::{
+                ext_image_copy_capture_frame_v1::{self, ExtImageCopyCaptureFrameV1},
+                ext_image_copy_capture_manager_v1::{self, ExtImageCopyCaptureManagerV1},
+                ext_image_copy_capture_session_v1::{self, ExtImageCopyCaptureSessionV1},
+            },
+        },
+        protocols_wlr::screencopy::v1::client::{
+            zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
+            zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1,
+        },
+    },
+    shm::{raw::RawPool, Shm},
+};
+use wayland_client::{
+    backend::ObjectId,
+    delegate_noop,
+    globals::GlobalList,
+    protocol::{
+        wl_buffer::{self, WlBuffer},
+        wl_shm,
+    },
+    Connection, Dispatch, Proxy, QueueHandle, WEnum,
+};
+
+use crate::{transform_swaps_axes, App, AppHandler, BaseApp, SharedState};
+
+/// The color the windows fill with. Each channel is fully on or off, so it reads the same from
+/// sRGB and linear framebuffers.
+pub(crate) const CHECK_COLOR: [f32; 3] = [1.0, 0.0, 1.0];
+
+/// Frame callbacks of the first window before the capture, the window has settled by then.
+const CHECK_AFTER_FRAMES: u64 = 10;
+
+/// How far each channel of a pixel may be off, for dithering and color management.
+const TOLERANCE: u8 = 24;
+
+/// The share of the rectangle found that must have the color, the cursor may be over it.
+const MIN_COVERAGE: f64 = 0.95;
+
+/// User data of the capture buffer.
+pub(crate) struct CaptureBuffer;
+
+/// The capture globals, whichever the compositor has.
+enum Capturer {
+    ImageCopy {
+        sources: ExtOutputImageCaptureSourceManagerV1,
+        manager: ExtImageCopyCaptureManagerV1,
+    },
+    Screencopy(ZwlrScreencopyManagerV1),
+}
+
+/// The state of `--self-check`.
+pub(crate) struct SelfCheck {
+    capturer: Capturer,
+    /// The capture underway, until the result is printed.
+    capture: Option<Capture>,
+    /// The check runs once.
+    started: bool,
+}
+
+/// The capture of the output the window is on.
+struct Capture {
+    window: ObjectId,
+    output_name: String,
+    /// Logical size of the content area of the window.
+    content_size: (u32, u32),
+    /// Logical size of the output, `None` without xdg-output or wl_output version 4.
+    output_size: Option<(u32, u32)>,
+    output_scale: u32,
+    /// Whether the output is rotated by 90 or 270 degrees, its buffer is then taller than wide
+    /// for a wide output.
+    swaps_axes: bool,
+    objects: CaptureObjects,
+    /// The first shm format offered that we can read.
+    format: Option<wl_shm::Format>,
+    /// Size of the image in pixels, the size of the output's buffer.
+    size: (u32, u32),
+    stride: u32,
+    buffer: Option<(RawPool, WlBuffer)>,
+    /// Whether the image is upside down, wlr-screencopy only.
+    y_invert: bool,
+}
+
+enum CaptureObjects {
+    ImageCopy {
+        source: ExtImageCaptureSourceV1,
+        session: ExtImageCopyCaptureSessionV1,
+        /// Created once the session told the buffer constraints.
+        frame: Option<ExtImageCopyCaptureFrameV1>,
+    },
+    Screencopy(ZwlrScreencopyFrameV1),
+}
+
+/// Binds the capture globals with `--self-check`. When the compositor has neither the check is
+/// skipped right away and the examples draw as usual.
+pub(crate) fn bind<H: AppHandler>(
+    globals: &GlobalList,
+    qh: &QueueHandle<App<H>>,
+    self_check: bool,
+) -> Option<SelfCheck> {
+    if !self_check {
+        return None;
+    }
+    let sources = globals.bind::<ExtOutputImageCaptureSourceManagerV1, _, _>(qh, 1..=1, ());
+    let manager = globals.bind::<ExtImageCopyCaptureManagerV1, _, _>(qh, 1..=1, ());
+    let capturer = match (sources, manager) {
+        (Ok(sources), Ok(manager)) => Capturer::ImageCopy { sources, manager },
+        (sources, manager) => {
+            // Only one of the pair is of no use
+            if let Ok(sources) = sources {
+                sources.destroy();
+            }
+            if let Ok(manager) = manager {
+                manager.destroy();
+            }
+            match globals.bind::<ZwlrScreencopyManagerV1, _, _>(qh, 1..=3, ()) {
+                Ok(manager) => Capturer::Screencopy(manager),
+                Err(_) => {
+                    println!(
+                        "self-check: SKIP, the compositor has neither \
+                         ext_image_copy_capture_manager_v1 nor zwlr_screencopy_manager_v1"
+                    );
+                    return None;
+                }
+            }
+        }
+    };
+    Some(SelfCheck { capturer, capture: None, started: false })
+}
+
+impl BaseApp {
+    /// Whether the window shows its clear color and no scene: dialogs, and every window with
+    /// `--self-check`.
+    pub fn plain(&self) -> bool {
+        self.is_dialog() || self.self_check
+    }
+}
+
+impl SharedState {
+    /// Captures the output the window is on, once it has shown a few frames of the color.
+    pub(crate) fn start_self_check<H: AppHandler>(
+        &mut self,
+        qh: &QueueHandle<App<H>>,
+        base: &BaseApp,
+    ) {
+        let pending = self.self_check.as_ref().is_some_and(|check| !check.started);
+        if !pending || base.frame_callbacks < CHECK_AFTER_FRAMES {
+            return;
+        }
+        // Tried again with the next frame until the compositor says where the window is
+        let Some(output) = base.outputs().last() else {
+            return;
+        };
+        let info = self.output_state.info(output);
+        let output_size = info.as_ref().and_then(|info| info.logical_size);
+        let output_name = self.output_name(output);
+        let [_, _, content_width, content_height] = base.content_area();
+
+        let Some(check) = &mut self.self_check else {
+            return;
+        };
+        check.started = true;
+        let objects = match &check.capturer {
+            Capturer::ImageCopy { sources, manager } => {
+                let source = sources.create_source(output, qh, ());
+                let options = ext_image_copy_capture_manager_v1::Options::empty();
+                let session = manager.create_session(&source, options, qh, ());
+                CaptureObjects::ImageCopy { source, session, frame: None }
+            }
+            // The cursor would only be in the way
+            Capturer::Screencopy(manager) => {
+                CaptureObjects::Screencopy(manager.capture_output(0, output, qh, ()))
+            }
+        };
+        println!("self-check: capturing output {output_name}");
+        check.capture = Some(Capture {
+            window: base.id(),
+            output_name,
+            content_size: (content_width, content_height),
+            output_size: output_size.map(|(width, height)| (width as u32, height as u32)),
+            output_scale: info.as_ref().map_or(1, |info| info.scale_factor.max(1) as u32),
+            swaps_axes: info.is_some_and(|info| transform_swaps_axes(info.transform)),
+            objects,
+            format: None,
+            size: (0, 0),
+            stride: 0,
+            buffer: None,
+            y_invert: false,
+        });
+    }
+}
+
+impl SelfCheck {
+    /// Prints the verdict and lets go of the capture.
+    fn finish(&mut self, verdict: &str, message: &str) {
+        println!("self-check: {verdict}, {message}");
+        if let Some(capture) = self.capture.take() {
+            capture.destroy();
+        }
+    }
+
+    /// Compares the capture with what the window drew, once the compositor filled the buffer.
+    fn evaluate(&mut self) {
+        let Some(capture) = &mut self.capture else {
+            return;
+        };
+        match capture.find_window() {
+            Ok(message) => self.finish("PASS", &message),
+            Err(message) => self.finish("FAIL", &message),
+        }
+    }
+}
+
+impl Capture {
+    /// Takes the format if we can read it and none was offered before. Returns whether it was
+    /// taken.
+    fn offer_format(&mut self, format: WEnum<wl_shm::Format>) -> bool {
+        if self.format.is_some() {
+            return false;
+        }
+        let WEnum::Value(format) = format else {
+            return false;
+        };
+        if channel_offsets(format).is_none() {
+            return false;
+        }
+        self.format = Some(format);
+        true
+    }
+
+    /// Creates the buffer the compositor copies into, in the format it offered. `None` when it
+    /// offered none we can read.
+    fn create_buffer<H: AppHandler>(
+        &mut self,
+        shm: &Shm,
+        qh: &QueueHandle<App<H>>,
+    ) -> Option<&WlBuffer> {
+        let format = self.format?;
+        let ((width, height), stride) = (self.size, self.stride);
+        let mut pool = RawPool::new(stride as usize * height as usize, shm).ok()?;
+        let buffer = pool.create_buffer(
+            0,
+            width as i32,
+            height as i32,
+            stride as i32,
+            format,
+            CaptureBuffer,
+            qh,
+        );
+        self.buffer = Some((pool, buffer));
+        self.buffer.as_ref().map(|(_, buffer)| buffer)
+    }
+
+    /// Looks for the window in the image, a solid rectangle of the color the size of its
+    /// content area on the output. Ok with what was found, or the reason it wasn't.
+    fn find_window(&mut self) -> Result<String, String> {
+        let (Some(format), Some((pool, _))) = (self.format, &mut self.buffer) else {
+            return Err("the capture has no image".to_string());
+        };
+        let ((width, height), stride) = (self.size, self.stride);
+        let offsets = channel_offsets(format).unwrap();
+        let color = CHECK_COLOR.map(|c| (c * 255.0).round() as u8);
+        let pixels = &pool.mmap()[..];
+
+        // The bounding box of the pixels of the color, and how many there are
+        let mut count = 0u64;
+        let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+        for y in 0..height {
+            let row = &pixels[(y * stride) as usize..][..(width * 4) as usize];
+            for (x, pixel) in row.chunks_exact(4).enumerate() {
+                let matches = offsets
+                    .iter()
+                    .zip(color)
+                    .all(|(&offset, c)| pixel[offset].abs_diff(c) <= TOLERANCE);
+                if matches {
+                    count += 1;
+                    (left, right) = (left.min(x as u32), right.max(x as u32));
+                    (top, bottom) = (top.min(y), bottom.max(y));
+                }
+            }
+        }
+        if count == 0 {
+            return Err(format!(
+                "no pixel of window {} on output {}, is it covered?",
+                self.window, self.output_name
+            ));
+        }
+        if self.y_invert {
+            (top, bottom) = (height - 1 - bottom, height - 1 - top);
+        }
+
+        let found = (right - left + 1, bottom - top + 1);
+        let expected = self.expected_size((width, height));
+        let coverage = count as f64 / (found.0 as f64 * found.1 as f64);
+        let description = format!(
+            "{}x{} pixels at ({left}, {top}) of output {}, {}x{} expected for window {}, \
+             {:.1}% of them the color",
+            found.0,
+            found.1,
+            self.output_name,
+            expected.0,
+            expected.1,
+            self.window,
+            coverage * 100.0
+        );
+        // A couple of pixels for rounding fractional scales, and borders the compositor draws
+        let close = |found: u32, expected: u32| found.abs_diff(expected) <= (expected / 50).max(4);
+        if close(found.0, expected.0) && close(found.1, expected.1) && coverage >= MIN_COVERAGE {
+            Ok(description)
+        } else {
+            Err(description)
+        }
+    }
+
+    /// The size of the content area in the image, which has the size of the output's buffer.
+    fn expected_size(&self, (image_width, image_height): (u32, u32)) -> (u32, u32) {
+        let (mut width, mut height) = self.content_size;
+        if self.swaps_axes {
+            (width, height) = (height, width);
+        }
+        let Some((mut output_width, mut output_height)) =
+            self.output_size.filter(|&(width, height)| width > 0 && height > 0)
+        else {
+            return (width * self.output_scale, height * self.output_scale);
+        };
+        if self.swaps_axes {
+            (output_width, output_height) = (output_height, output_width);
+        }
+        // Fractional scales included, the image is as large as the output's mode
+        (
+            (width as u64 * image_width as u64 / output_width as u64) as u32,
+            (height as u64 * image_height as u64 / output_height as u64) as u32,
+        )
+    }
+
+    fn destroy(self) {
+        match self.objects {
+            CaptureObjects::ImageCopy { source, session, frame } => {
+                if let Some(frame) = frame {
+                    frame.destroy();
+                }
+                session.destroy();
+                source.destroy();
+            }
+            CaptureObjects::Screencopy(frame) => frame.destroy(),
+        }
+        if let Some((_, buffer)) = self.buffer {
+            buffer.destroy();
+        }
+    }
+}
+
+/// Where red, green and blue are within a pixel of the format, for the formats we can read.
+fn channel_offsets(format: wl_shm::Format) -> Option<[usize; 3]> {
+    use wl_shm::Format;
+
+    // Little endian, the bytes come in reverse order of the name
+    match format {
+        Format::Argb8888 | Format::Xrgb8888 => Some([2, 1, 0]),
+        Format::Abgr8888 | Format::Xbgr8888 => Some([0, 1, 2]),
+        _ => None,
+    }
+}
+
+impl<H: AppHandler> Dispatch<ExtImageCopyCaptureSessionV1, ()> for App<H> {
+    fn event(
+        app: &mut Self,
+        session: &ExtImageCopyCaptureSessionV1,
+        event: ext_image_copy_capture_session_v1::Event,
+        _: &(),
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        use ext_image_copy_capture_session_v1::Event;
+
+        let Some(check) = &mut app.shared.self_check else {
+            return;
+        };
+        let Some(capture) = &mut check.capture else {
+            return;
+        };
+        match event {
+            Event::BufferSize { width, height } => {
+                capture.size = (width, height);
+                capture.stride = width * 4;
+            }
+            Event::ShmFormat { format } => {
+                capture.offer_format(format);
+            }
+            Event::Done => {
+                // Sent again when the constraints change, one frame is all we take
+                if !matches!(capture.objects, CaptureObjects::ImageCopy { frame: None, .. }) {
+                    return;
+                }
+                let (width, height) = capture.size;
+                let Some(buffer) = capture.create_buffer(&app.shared.shm, qh) else {
+                    check.finish("SKIP", "none of the formats offered for the capture is RGB");
+                    return;
+                };
+                let frame = session.create_frame(qh, ());
+                frame.attach_buffer(buffer);
+                frame.damage_buffer(0, 0, width as i32, height as i32);
+                frame.capture();
+                if let CaptureObjects::ImageCopy { frame: slot, .. } = &mut capture.objects {
+                    *slot = Some(frame);
+                }
+            }
+            Event::Stopped => check.finish("SKIP", "the output went away during the capture"),
+            _ => {}
+        }
+    }
+}
+
+impl<H: AppHandler> Dispatch<ExtImageCopyCaptureFrameV1, ()> for App<H> {
+    fn event(
+        app: &mut Self,
+        _frame: &ExtImageCopyCaptureFrameV1,
+        event: ext_image_copy_capture_frame_v1::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        use ext_image_copy_capture_frame_v1::Event;
+
+        let Some(check) = &mut app.shared.self_check else {
+            return;
+        };
+        match event {
+            Event::Ready => check.evaluate(),
+            Event::Failed { reason } => {
+                check.finish("SKIP", &format!("the compositor failed the capture: {reason:?}"));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<H: AppHandler> Dispatch<ZwlrScreencopyFrameV1, ()> for App<H> {
+    fn event(
+        app: &mut Self,
+        frame: &ZwlrScreencopyFrameV1,
+        event: zwlr_screencopy_frame_v1::Event,
+        _: &(),
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        use zwlr_screencopy_frame_v1::{Event, Flags};
+
+        let Some(check) = &mut app.shared.self_check else {
+            return;
+        };
+        match event {
+            Event::Ready { .. } => return check.evaluate(),
+            Event::Failed => return check.finish("SKIP", "the compositor failed the capture"),
+            _ => {}
+        }
+        let Some(capture) = &mut check.capture else {
+            return;
+        };
+        // Before version 3 there is no buffer_done, the shm buffer is all there is
+        let copy = match event {
+            Event::Buffer { format, width, height, stride } => {
+                if capture.offer_format(format) {
+                    capture.size = (width, height);
+                    capture.stride = stride;
+                }
+                frame.version() < 3
+            }
+            Event::BufferDone => true,
+            Event::Flags { flags } => {
+                capture.y_invert =
+                    matches!(flags, WEnum::Value(flags) if flags.contains(Flags::YInvert));
+                false
+            }
+            _ => false,
+        };
+        if !copy {
+            return;
+        }
+        match capture.create_buffer(&app.shared.shm, qh) {
+            Some(buffer) => frame.copy(buffer),
+            None => check.finish("SKIP", "none of the formats offered for the capture is RGB"),
+        }
+    }
+}
+
+impl<H: AppHandler> Dispatch<WlBuffer, CaptureBuffer> for App<H> {
+    fn event(
+        _app: &mut Self,
+        _buffer: &WlBuffer,
+        _event: wl_buffer::Event,
+        _: &CaptureBuffer,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        // Released right after the copy, it is destroyed along with the capture
+    }
+}
+
+delegate_noop!(@<H: AppHandler> App<H>: ignore ExtOutputImageCaptureSourceManagerV1);
+delegate_noop!(@<H: AppHandler> App<H>: ignore ExtImageCaptureSourceV1);
+delegate_noop!(@<H: AppHandler> App<H>: ignore ExtImageCopyCaptureManagerV1);
+delegate_noop!(@<H: AppHandler> App<H>: ignore ZwlrScreencopyManagerV1);
@@ -0,0 +1,94 @@
+//! The wl_shm buffers of a window drawn with the CPU. A buffer the compositor still holds can't
+//! be drawn into, so a window keeps a few and draws into one the compositor is done with.
+//! Running out of memory for a buffer, e.g. after a resize to a huge size, is an error for the
+//! caller to log, not a panic.
+
+use std::io;
+
+use smithay_client_toolkit::shm::{
+    slot::{Buffer, CreateBufferError, SlotPool},
+    CreatePoolError,
+};
+use wayland_client::protocol::wl_shm;
+
+use crate::BaseApp;
+
+/// How many buffers a window keeps, more are only needed when the compositor holds on to them.
+const MAX_BUFFERS: usize = 3;
+
+/// The buffers of a window and the pool they live in. Each buffer comes with a `T` of the
+/// caller, e.g. what it last drew into it.
+pub struct ShmBuffers<T = ()> {
+    // Declared before the pool, the buffers live in it
+    buffers: Vec<(Buffer, T)>,
+    /// The size of the buffers, they are made anew when the window has another.
+    size: (u32, u32),
+    pool: SlotPool,
+}
+
+/// A buffer to draw the next frame into, see [`ShmBuffers::acquire`].
+pub struct ShmFrame<'a, T> {
+    /// Attach it once drawn.
+    pub buffer: &'a Buffer,
+    /// Argb8888, stored little endian so the bytes are in BGRA order, the top row first.
+    pub canvas: &'a mut [u8],
+    /// What the caller keeps with the buffer, `T::default()` for a new one.
+    pub data: &'a mut T,
+    /// Whether the buffer was just created, with all of its bytes zero.
+    pub fresh: bool,
+}
+
+impl<T: Default> ShmBuffers<T> {
+    /// Creates the pool with room for a buffer of the current size of the window, it grows
+    /// when needed.
+    pub fn new(base: &BaseApp) -> Result<ShmBuffers<T>, CreatePoolError> {
+        let (width, height) = base.buffer_size();
+        let len = (width as usize * height as usize * 4).max(4);
+        Ok(ShmBuffers {
+            buffers: Vec::new(),
+            size: (width, height),
+            pool: SlotPool::new(len, &base.shm)?,
+        })
+    }
+
+    /// Finds a buffer of the size the compositor is done with, or creates one. Buffers of
+    /// another size are dropped first, their slots go back to the pool.
+    pub fn acquire(
+        &mut self,
+        width: u32,
+        height: u32,
+    ) -> Result<ShmFrame<'_, T>, CreateBufferError> {
+        if self.size != (width, height) {
+            self.buffers.clear();
+            self.size = (width, height);
+        }
+        let pool = &mut self.pool;
+        let free = self.buffers.iter().position(|(buffer, _)| buffer.canvas(pool).is_some());
+        let (index, fresh) = match free {
+            Some(index) => (index, false),
+            None => {
+                if self.buffers.len() >= MAX_BUFFERS {
+                    // All buffers are busy, drop the oldest, its slot goes back to the pool
+                    // once the compositor releases it
+                    self.buffers.remove(0);
+                }
+                let stride = width as i32 * 4;
+                let (buffer, _) = pool.create_buffer(
+                    width as i32,
+                    height as i32,
+                    stride,
+                    wl_shm::Format::Argb8888,
+                )?;
+                self.buffers.push((buffer, T::default()));
+                (self.buffers.len() - 1, true)
+            }
+        };
+
+        let (buffer, data) = &mut self.buffers[index];
+        // Free a moment ago, nothing attached it since
+        let canvas = buffer.canvas(&mut self.pool).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::ResourceBusy, "the compositor holds the buffer")
+        })?;
+        Ok(ShmFrame { buffer, canvas, data, fresh })
+    }
+}
@@ -0,0 +1,183 @@
+//! Capturing the compositor's keyboard shortcuts, like a game or a remote desktop would. F10
+//! asks the compositor to send keys such as Super to the window instead of acting on them, and
+//! F10 again gives them back. The compositor decides whether and when that happens, the window
+//! shows a badge while it does.
+//!
+//! An inhibitor only lasts while the window has keyboard focus. It goes when the focus leaves
+//! and comes back with it, for as long as the window wants the shortcuts.
+
+use smithay_client_toolkit::{
+    reexports::protocols::wp::keyboard_shortcuts_inhibit::zv1::client::{
+        zwp_keyboard_shortcuts_inhibit_manager_v1::ZwpKeyboardShortcutsInhibitManagerV1,
+        zwp_keyboard_shortcuts_inhibitor_v1::{self, ZwpKeyboardShortcutsInhibitorV1},
+    },
+    shell::WaylandSurface,
+};
+use wayland_client::{
+    backend::ObjectId, delegate_noop, globals::GlobalList, protocol::wl_seat, Connection,
+    Dispatch, QueueHandle,
+};
+
+use crate::{font, App, AppHandler, BaseApp};
+
+/// Shown while the compositor keeps its shortcuts to itself.
+const BADGE_TEXT: &str = "shortcuts captured";
+const BADGE_COLOR: [f32; 3] = [0.15, 0.15, 0.15];
+const BADGE_TEXT_COLOR: [f32; 3] = [1.0, 0.8, 0.0];
+/// Space around the text of the badge and between the badge and the top of the content area,
+/// in logical pixels.
+const BADGE_PADDING: u32 = 4;
+
+/// Binds the keyboard shortcuts inhibit manager, or nothing when the compositor lacks it.
+pub(crate) fn bind_manager<H: AppHandler>(
+    globals: &GlobalList,
+    qh: &QueueHandle<App<H>>,
+) -> Option<ZwpKeyboardShortcutsInhibitManagerV1> {
+    let manager = globals.bind::<ZwpKeyboardShortcutsInhibitManagerV1, _, _>(qh, 1..=1, ()).ok();
+    if manager.is_none() {
+        log::info!("zwp_keyboard_shortcuts_inhibit_manager_v1 not available, F10 does nothing");
+    }
+    manager
+}
+
+impl BaseApp {
+    /// The badge telling that the window gets the compositor's shortcuts, centered at the top
+    /// of the content area, with its colors in GL buffer coordinates. Empty while the
+    /// compositor hasn't activated the inhibitor, or when the badge doesn't fit.
+    pub fn shortcuts_badge_rects(&self) -> Vec<([i32; 4], [f32; 3])> {
+        if !self.shortcuts_inhibited || self.plain() {
+            return Vec::new();
+        }
+        let [x, y, width, height] = self.content_area();
+        let (text, text_width) = font::line_rects(BADGE_TEXT);
+        let badge_width = text_width + 2 * BADGE_PADDING;
+        let badge_height = font::GLYPH_HEIGHT + 2 * BADGE_PADDING;
+        if badge_width > width || badge_height + BADGE_PADDING > height {
+            return Vec::new();
+        }
+
+        let left = x + (width - badge_width) / 2;
+        let top = y + BADGE_PADDING;
+        let mut rects = vec![(self.buffer_rect(left, top, badge_width, badge_height), BADGE_COLOR)];
+        let (left, top) = (left + BADGE_PADDING, top + BADGE_PADDING);
+        rects.extend(text.into_iter().map(|[x, y, width, height]| {
+            (self.buffer_rect(left + x, top + y, width, height), BADGE_TEXT_COLOR)
+        }));
+        rects
+    }
+
+    /// Asks the compositor for its shortcuts, unless an inhibitor of the window is already
+    /// there. At most one is allowed per surface and seat.
+    fn inhibit_shortcuts<H: AppHandler>(
+        &mut self,
+        manager: &ZwpKeyboardShortcutsInhibitManagerV1,
+        seat: &wl_seat::WlSeat,
+        qh: &QueueHandle<App<H>>,
+    ) {
+        if self.shortcuts_inhibitor.is_none() {
+            let surface = self.window.wl_surface();
+            let inhibitor = manager.inhibit_shortcuts(surface, seat, qh, self.id());
+            self.shortcuts_inhibitor = Some(inhibitor);
+        }
+    }
+
+    /// Destroys the inhibitor, the shortcuts go back to the compositor.
+    pub(crate) fn release_shortcuts(&mut self) {
+        if let Some(inhibitor) = self.shortcuts_inhibitor.take() {
+            inhibitor.destroy();
+        }
+        self.shortcuts_inhibited = false;
+    }
+}
+
+impl<H: AppHandler> App<H> {
+    /// Captures the compositor's shortcuts for the window, or gives them back.
+    pub(crate) fn toggle_shortcuts_inhibit(
+        &mut self,
+        qh: &QueueHandle<Self>,
+        id: &ObjectId,
+        seat: &wl_seat::WlSeat,
+    ) {
+        let Some(manager) = &self.shared.shortcuts_inhibit_manager else {
+            println!("capturing shortcuts needs zwp_keyboard_shortcuts_inhibit_manager_v1");
+            return;
+        };
+        let Some(base) = self.windows.get_mut(id) else {
+            return;
+        };
+        base.capture_shortcuts = !base.capture_shortcuts;
+        if base.capture_shortcuts {
+            println!("capturing shortcuts, F10 to give them back");
+            base.inhibit_shortcuts(manager, seat, qh);
+        } else {
+            println!("shortcuts given back");
+            base.release_shortcuts();
+            self.redraw(qh, id);
+        }
+    }
+
+    /// Asks for the shortcuts again when the window that wanted them gets the keyboard focus
+    /// back.
+    pub(crate) fn restore_shortcuts_inhibit(
+        &mut self,
+        qh: &QueueHandle<Self>,
+        id: &ObjectId,
+        seat: &wl_seat::WlSeat,
+    ) {
+        let (Some(manager), Some(base)) =
+            (&self.shared.shortcuts_inhibit_manager, self.windows.get_mut(id))
+        else {
+            return;
+        };
+        if base.capture_shortcuts {
+            base.inhibit_shortcuts(manager, seat, qh);
+        }
+    }
+
+    /// Lets go of the inhibitor of the window along with the keyboard focus. The window keeps
+    /// wanting the shortcuts, see [`Self::restore_shortcuts_inhibit`].
+    pub(crate) fn suspend_shortcuts_inhibit(&mut self, qh: &QueueHandle<Self>, id: &ObjectId) {
+        let Some(base) = self.windows.get_mut(id) else {
+            return;
+        };
+        if base.shortcuts_inhibitor.is_some() {
+            base.release_shortcuts();
+            self.redraw(qh, id);
+        }
+    }
+}
+
+impl<H: AppHandler> Dispatch<ZwpKeyboardShortcutsInhibitorV1, ObjectId> for App<H> {
+    fn event(
+        app: &mut Self,
+        inhibitor: &ZwpKeyboardShortcutsInhibitorV1,
+        event: zwp_keyboard_shortcuts_inhibitor_v1::Event,
+        id: &ObjectId,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        use zwp_keyboard_shortcuts_inhibitor_v1::Event;
+
+        let active = match event {
+            Event::Active => true,
+            Event::Inactive => false,
+            _ => return,
+        };
+        app.shared.trace.record("shortcuts_inhibitor", &[("window", id), ("active", &active)]);
+        let Some(base) = app.windows.get_mut(id) else {
+            return;
+        };
+        if base.shortcuts_inhibitor.as_ref() != Some(inhibitor) {
+            // For an inhibitor destroyed since
+            return;
+        }
+        // The compositor may also deactivate the inhibitor on its own, e.g. for its emergency
+        // key combination, and activate it again later. The badge follows it, not F10.
+        let state = if active { "active" } else { "inactive" };
+        log::info!("shortcuts inhibitor of window {id} {state}");
+        base.shortcuts_inhibited = active;
+        app.redraw(qh, id);
+    }
+}
+
+delegate_noop!(@<H: AppHandler> App<H>: ignore ZwpKeyboardShortcutsInhibitManagerV1);
@@ -0,0 +1,310 @@
+//! `--solid-background`, the window surface shows a single-pixel buffer its viewport stretches
+//! over the whole window, and the renderer only draws the square the triangle turns in, into a
+//! subsurface of its own. The compositor fills the rest without a buffer of ours to sample. The
+//! client-side title bar is made of single-pixel subsurfaces too, see
+//! [`AppHandler::SOLID_BACKGROUND`].
+//!
+//! The color is [`BaseApp::background`], so it changes at runtime like the clear color does,
+//! with the pointer and with a color pasted with Ctrl+V.
+
+use smithay_client_toolkit::{
+    compositor::{CompositorState, Region},
+    reexports::protocols::wp::{
+        single_pixel_buffer::v1::client::wp_single_pixel_buffer_manager_v1::{
+            WpSinglePixelBufferManagerV1,
+        },
+        viewporter::client::{wp_viewport::WpViewport, wp_viewporter::WpViewporter},
+    },
+    shell::WaylandSurface,
+    subcompositor::SubcompositorState,
+};
+use wayland_client::{
+    delegate_noop,
+    globals::GlobalList,
+    protocol::{
+        wl_buffer::{self, WlBuffer},
+        wl_output,
+        wl_subsurface::WlSubsurface,
+        wl_surface::WlSurface,
+    },
+    Connection, Dispatch, Proxy, QueueHandle,
+};
+
+use crate::{
+    decorations::{CLOSE_BUTTON_COLOR, TITLE_BAR_COLOR},
+    App, AppHandler, BaseApp,
+};
+
+/// User data of the single-pixel buffers, they are destroyed once the compositor releases them.
+pub(crate) struct SinglePixelBuffer;
+
+/// Binds wp_single_pixel_buffer_manager_v1, or nothing when the compositor lacks it.
+pub(crate) fn bind_manager<H: AppHandler>(
+    globals: &GlobalList,
+    qh: &QueueHandle<App<H>>,
+) -> Option<WpSinglePixelBufferManagerV1> {
+    globals.bind::<WpSinglePixelBufferManagerV1, _, _>(qh, 1..=1, ()).ok()
+}
+
+/// A subsurface the viewport stretches over a rectangle of the window. It takes no input,
+/// clicks on it go to the window below.
+struct Stretched {
+    subsurface: WlSubsurface,
+    surface: WlSurface,
+    viewport: WpViewport,
+    /// The rectangle last sent, in logical coordinates of the window.
+    rect: Option<[u32; 4]>,
+}
+
+impl Stretched {
+    fn new<H: AppHandler>(
+        subcompositor: &SubcompositorState,
+        compositor: &CompositorState,
+        viewporter: &WpViewporter,
+        parent: &WlSurface,
+        qh: &QueueHandle<App<H>>,
+    ) -> Stretched {
+        let (subsurface, surface) = subcompositor.create_subsurface(parent.clone(), qh);
+        if let Ok(region) = Region::new(compositor) {
+            surface.set_input_region(Some(region.wl_region()));
+        }
+        let viewport = viewporter.get_viewport(&surface, qh, ());
+        Stretched {
+            subsurface,
+            surface,
+            viewport,
+            rect: None,
+        }
+    }
+
+    /// Moves the subsurface over the rectangle, which must not be empty. The position goes with
+    /// the next commit of the window, the size with the next commit of the subsurface. Returns
+    /// whether anything changed.
+    fn place(&mut self, rect: [u32; 4]) -> bool {
+        if self.rect == Some(rect) {
+            return false;
+        }
+        let [x, y, width, height] = rect;
+        self.subsurface.set_position(x as i32, y as i32);
+        self.viewport.set_destination(width as i32, height as i32);
+        self.rect = Some(rect);
+        true
+    }
+
+    fn destroy(&self) {
+        self.viewport.destroy();
+        self.subsurface.destroy();
+        self.surface.destroy();
+    }
+}
+
+/// A subsurface showing a single-pixel buffer, one part of the title bar.
+struct Fill {
+    stretched: Stretched,
+    /// The buffer attached last and its color, `None` while hidden.
+    buffer: Option<(WlBuffer, [f32; 4])>,
+}
+
+impl Fill {
+    /// Shows the color over the rectangle, or hides the subsurface while it is empty. Commits
+    /// the subsurface when it changed, it still waits for the next commit of the window.
+    fn show<H: AppHandler>(
+        &mut self,
+        manager: &WpSinglePixelBufferManagerV1,
+        qh: &QueueHandle<App<H>>,
+        rect: [u32; 4],
+        color: [f32; 4],
+    ) {
+        let [_, _, width, height] = rect;
+        if width == 0 || height == 0 {
+            if self.buffer.take().is_some() {
+                self.stretched.surface.attach(None, 0, 0);
+                self.stretched.surface.commit();
+            }
+            return;
+        }
+
+        let mut changed = self.stretched.place(rect);
+        if self.buffer.as_ref().map(|&(_, shown)| shown) != Some(color) {
+            let buffer = create_buffer(manager, qh, color);
+            attach(&self.stretched.surface, &buffer);
+            self.buffer = Some((buffer, color));
+            changed = true;
+        }
+        if changed {
+            self.stretched.surface.commit();
+        }
+    }
+
+    fn destroy(&self) {
+        self.stretched.destroy();
+        if let Some((buffer, _)) = &self.buffer {
+            buffer.destroy();
+        }
+    }
+}
+
+/// The surfaces of `--solid-background` for one window.
+pub(crate) struct SolidBackground {
+    manager: WpSinglePixelBufferManagerV1,
+    /// The buffer on the window surface and its color.
+    buffer: Option<(WlBuffer, [f32; 4])>,
+    /// The subsurface the renderer draws the content into.
+    content: Stretched,
+    /// The buffer transform last set on the content, it follows the window like its buffer.
+    content_transform: wl_output::Transform,
+    title_bar: Fill,
+    close_button: Fill,
+}
+
+impl SolidBackground {
+    /// Creates the subsurfaces on the window surface, the content first so the title bar and
+    /// anything created later stacks above it.
+    pub(crate) fn new<H: AppHandler>(
+        manager: &WpSinglePixelBufferManagerV1,
+        subcompositor: &SubcompositorState,
+        compositor: &CompositorState,
+        viewporter: &WpViewporter,
+        parent: &WlSurface,
+        qh: &QueueHandle<App<H>>,
+    ) -> SolidBackground {
+        let stretched = || Stretched::new(subcompositor, compositor, viewporter, parent, qh);
+        SolidBackground {
+            manager: manager.clone(),
+            buffer: None,
+            content: stretched(),
+            content_transform: wl_output::Transform::Normal,
+            title_bar: Fill {
+                stretched: stretched(),
+                buffer: None,
+            },
+            close_button: Fill {
+                stretched: stretched(),
+                buffer: None,
+            },
+        }
+    }
+
+    /// Destroys the subsurfaces and the buffers. The renderer has to be done with the content
+    /// surface, and the window surface has to still be around.
+    pub(crate) fn destroy(&self) {
+        self.close_button.destroy();
+        self.title_bar.destroy();
+        self.content.destroy();
+        if let Some((buffer, _)) = &self.buffer {
+            buffer.destroy();
+        }
+    }
+}
+
+impl BaseApp {
+    /// The subsurface to render into with `--solid-background`, for renderers with
+    /// [`AppHandler::SOLID_BACKGROUND`]. `None` draws into the window surface as usual.
+    pub fn solid_content_surface(&self) -> Option<&WlSurface> {
+        self.solid_background.as_ref().map(|solid| &solid.content.surface)
+    }
+
+    /// Where the content surface is in the buffer of the whole window, as `[x, y, width,
+    /// height]` in GL buffer coordinates like [`Self::content_rect`]. Its size is the size of
+    /// the content surface in physical pixels, never zero. `None` without a solid background.
+    pub fn solid_content_rect(&self) -> Option<[i32; 4]> {
+        self.solid_background.as_ref()?;
+        let [x, y, width, height] = self.solid_content_area();
+        let [x, y, width, height] = self.buffer_rect(x, y, width, height);
+        Some([x, y, width.max(1), height.max(1)])
+    }
+
+    /// The largest square in the middle of the content area, in logical coordinates. The
+    /// triangle and the quads stay within it unless moved or zoomed.
+    fn solid_content_area(&self) -> [u32; 4] {
+        let [x, y, width, height] = self.content_area();
+        let side = width.min(height).max(1);
+        [
+            x + width.saturating_sub(side) / 2,
+            y + height.saturating_sub(side) / 2,
+            side,
+            side,
+        ]
+    }
+
+    /// Attaches a buffer of the current background color to the window surface when it changed,
+    /// and brings the subsurfaces up to date with the window size. The window surface is
+    /// committed after the renderer committed the content, see [`Self::commit_solid_background`].
+    pub(crate) fn update_solid_background<H: AppHandler>(&mut self, qh: &QueueHandle<App<H>>) {
+        let background = self.background();
+        let content = self.solid_content_area();
+        let transform = self.transform;
+        let (width, bar) = (self.width, self.title_bar_height());
+        let close = bar.min(width);
+        let Some(solid) = &mut self.solid_background else {
+            return;
+        };
+
+        if solid.buffer.as_ref().map(|&(_, shown)| shown) != Some(background) {
+            let buffer = create_buffer(&solid.manager, qh, background);
+            attach(self.window.wl_surface(), &buffer);
+            solid.buffer = Some((buffer, background));
+        }
+
+        // Both go with the commit the renderer does when swapping
+        solid.content.place(content);
+        if solid.content_transform != transform {
+            solid.content.surface.set_buffer_transform(transform);
+            solid.content_transform = transform;
+        }
+
+        let [red, green, blue] = TITLE_BAR_COLOR;
+        solid.title_bar.show(&solid.manager, qh, [0, 0, width, bar], [red, green, blue, 1.0]);
+        let [red, green, blue] = CLOSE_BUTTON_COLOR;
+        let rect = [width - close, 0, close, bar];
+        solid.close_button.show(&solid.manager, qh, rect, [red, green, blue, 1.0]);
+    }
+
+    /// Commits the window surface with a solid background. The renderer only committed the
+    /// content subsurface, which the compositor holds back until the window commits too.
+    pub(crate) fn commit_solid_background(&self) {
+        if self.solid_background.is_some() {
+            self.window.wl_surface().commit();
+        }
+    }
+}
+
+/// Creates a single-pixel buffer of the color, RGBA with the alpha premultiplied.
+fn create_buffer<H: AppHandler>(
+    manager: &WpSinglePixelBufferManagerV1,
+    qh: &QueueHandle<App<H>>,
+    color: [f32; 4],
+) -> WlBuffer {
+    let [red, green, blue, alpha] =
+        color.map(|value| (value.clamp(0.0, 1.0) as f64 * u32::MAX as f64) as u32);
+    manager.create_u32_rgba_buffer(red, green, blue, alpha, qh, SinglePixelBuffer)
+}
+
+/// Attaches the buffer and damages all of its one pixel.
+fn attach(surface: &WlSurface, buffer: &WlBuffer) {
+    surface.attach(Some(buffer), 0, 0);
+    if surface.version() >= 4 {
+        surface.damage_buffer(0, 0, 1, 1);
+    } else {
+        surface.damage(0, 0, i32::MAX, i32::MAX);
+    }
+}
+
+impl<H: AppHandler> Dispatch<WlBuffer, SinglePixelBuffer> for App<H> {
+    fn event(
+        _app: &mut Self,
+        buffer: &WlBuffer,
+        event: wl_buffer::Event,
+        _data: &SinglePixelBuffer,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        // The compositor keeps showing the color after releasing the buffer, and a new color
+        // gets a new buffer anyway
+        if let wl_buffer::Event::Release = event {
+            buffer.destroy();
+        }
+    }
+}
+
+delegate_noop!(@<H: AppHandler> App<H>: ignore WpSinglePixelBufferManagerV1);
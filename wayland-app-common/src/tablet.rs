@@ -0,0 +1,339 @@
+//! Drawing on the windows with a graphics tablet through tablet-v2. While the tip of a tool
+//! touches the tablet its motion leaves a stroke, wider the harder it presses, and a button of
+//! the tool clears the window. The strokes collect here until the renderer draws them into a
+//! canvas of its own, which keeps them from frame to frame.
+//!
+//! Each seat has a tablet seat announcing its tablets, tools and pads. Only the tools draw,
+//! the tablets are logged by name and the pads are kept just long enough to be destroyed.
+
+use std::collections::HashMap;
+
+use smithay_client_toolkit::reexports::protocols::wp::tablet::zv2::client::{
+    zwp_tablet_manager_v2::ZwpTabletManagerV2,
+    zwp_tablet_pad_group_v2::{self, ZwpTabletPadGroupV2},
+    zwp_tablet_pad_ring_v2::ZwpTabletPadRingV2,
+    zwp_tablet_pad_strip_v2::ZwpTabletPadStripV2,
+    zwp_tablet_pad_v2::{self, ZwpTabletPadV2},
+    zwp_tablet_seat_v2::{self, ZwpTabletSeatV2},
+    zwp_tablet_tool_v2::{self, ZwpTabletToolV2},
+    zwp_tablet_v2::{self, ZwpTabletV2},
+};
+use wayland_client::{
+    backend::ObjectId, delegate_noop, event_created_child, globals::GlobalList, protocol::wl_seat,
+    Connection, Dispatch, Proxy, QueueHandle, WEnum,
+};
+
+use crate::{App, AppHandler, BaseApp, SharedState};
+
+/// Width of a stroke without pressure and at full pressure, in logical pixels.
+const STROKE_WIDTH: (f32, f32) = (1.0, 8.0);
+/// Segments kept for a renderer that doesn't take them, e.g. one without a canvas. The oldest
+/// are dropped beyond that.
+const MAX_PENDING_SEGMENTS: usize = 4096;
+
+/// Binds the tablet manager, or nothing when the compositor lacks it.
+pub(crate) fn bind_manager<H: AppHandler>(
+    globals: &GlobalList,
+    qh: &QueueHandle<App<H>>,
+) -> Option<ZwpTabletManagerV2> {
+    let manager = globals.bind::<ZwpTabletManagerV2, _, _>(qh, 1..=1, ()).ok();
+    if manager.is_none() {
+        log::info!("zwp_tablet_manager_v2 not available, no drawing with a tablet");
+    }
+    manager
+}
+
+/// A piece of a stroke, from one frame of the tool to the next. In logical pixels from the top
+/// left of the content area.
+#[derive(Debug, Clone, Copy)]
+pub struct StrokeSegment {
+    pub from: (f32, f32),
+    pub to: (f32, f32),
+    /// Width of the stroke at `from` and `to`, following the pressure.
+    pub from_width: f32,
+    pub to_width: f32,
+}
+
+/// What the tablet drew on a window since the renderer last looked.
+#[derive(Default)]
+pub(crate) struct Sketch {
+    segments: Vec<StrokeSegment>,
+    /// Whether a button cleared the window, before `segments` were drawn.
+    clear: bool,
+    /// Counts the changes, see [`BaseApp::sketch_version`].
+    version: u64,
+}
+
+/// The tablet seat of a seat and the tools it announced.
+pub(crate) struct TabletSeat {
+    tablet_seat: ZwpTabletSeatV2,
+    tools: HashMap<ZwpTabletToolV2, Tool>,
+}
+
+/// A tool, e.g. a pen or its eraser end, and where it is.
+#[derive(Default)]
+struct Tool {
+    /// The window the tool is in proximity of.
+    focus: Option<ObjectId>,
+    /// Surface-local position and pressure from 0 to 1, as the events of the frame left them.
+    position: (f64, f64),
+    pressure: f64,
+    tilt: (f64, f64),
+    /// Whether the tip touches the tablet.
+    down: bool,
+    /// Where the stroke got to with the previous frame, the next segment starts there.
+    stroke: Option<((f64, f64), f64)>,
+}
+
+impl TabletSeat {
+    pub(crate) fn new<H: AppHandler>(
+        manager: &ZwpTabletManagerV2,
+        seat: &wl_seat::WlSeat,
+        qh: &QueueHandle<App<H>>,
+    ) -> TabletSeat {
+        TabletSeat {
+            tablet_seat: manager.get_tablet_seat(seat, qh, seat.clone()),
+            tools: HashMap::new(),
+        }
+    }
+
+    /// Destroys the tablet seat and the tools along with the seat.
+    pub(crate) fn destroy(self) {
+        for tool in self.tools.into_keys() {
+            tool.destroy();
+        }
+        self.tablet_seat.destroy();
+    }
+}
+
+impl BaseApp {
+    /// Takes the stroke segments drawn since the last call, and whether the canvas is to be
+    /// cleared before drawing them. Renderers with a canvas call it every frame.
+    pub fn take_strokes(&mut self) -> (bool, Vec<StrokeSegment>) {
+        let clear = std::mem::take(&mut self.sketch.clear);
+        (clear, std::mem::take(&mut self.sketch.segments))
+    }
+
+    /// Changes whenever the tablet draws on the window or clears it, so the damage tracking
+    /// can tell.
+    pub fn sketch_version(&self) -> u64 {
+        self.sketch.version
+    }
+
+    /// Adds a segment from one surface-local position to the next, with the pressure at both.
+    fn add_stroke(&mut self, from: ((f64, f64), f64), to: ((f64, f64), f64)) {
+        let [x, y, _, _] = self.content_area();
+        let point = |((px, py), _): ((f64, f64), f64)| (px as f32 - x as f32, py as f32 - y as f32);
+        let width = |(_, pressure): ((f64, f64), f64)| {
+            STROKE_WIDTH.0 + (STROKE_WIDTH.1 - STROKE_WIDTH.0) * pressure as f32
+        };
+        let segments = &mut self.sketch.segments;
+        if segments.len() >= MAX_PENDING_SEGMENTS {
+            segments.remove(0);
+        }
+        segments.push(StrokeSegment {
+            from: point(from),
+            to: point(to),
+            from_width: width(from),
+            to_width: width(to),
+        });
+        self.sketch.version += 1;
+    }
+
+    /// Throws away the strokes, those drawn already and those still pending.
+    fn clear_strokes(&mut self) {
+        self.sketch.segments.clear();
+        self.sketch.clear = true;
+        self.sketch.version += 1;
+    }
+}
+
+impl SharedState {
+    /// The tool wherever a tablet seat announced it, and the seat.
+    fn tablet_tool(&mut self, tool: &ZwpTabletToolV2) -> Option<(&wl_seat::WlSeat, &mut Tool)> {
+        self.seats.iter_mut().find_map(|(seat, resources)| {
+            Some((seat, resources.tablet_seat.as_mut()?.tools.get_mut(tool)?))
+        })
+    }
+}
+
+impl<H: AppHandler> Dispatch<ZwpTabletSeatV2, wl_seat::WlSeat> for App<H> {
+    fn event(
+        app: &mut Self,
+        _tablet_seat: &ZwpTabletSeatV2,
+        event: zwp_tablet_seat_v2::Event,
+        seat: &wl_seat::WlSeat,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        // The tablets describe themselves and the pads are of no use here
+        let zwp_tablet_seat_v2::Event::ToolAdded { id } = event else {
+            return;
+        };
+        log::info!("seat {} has a new tablet tool", app.shared.seat_name(seat));
+        let resources = app.shared.seats.get_mut(seat);
+        match resources.and_then(|resources| resources.tablet_seat.as_mut()) {
+            Some(tablet_seat) => {
+                tablet_seat.tools.insert(id, Tool::default());
+            }
+            None => id.destroy(),
+        }
+    }
+
+    event_created_child!(@<H: AppHandler> App<H>, ZwpTabletSeatV2, [
+        zwp_tablet_seat_v2::EVT_TABLET_ADDED_OPCODE => (ZwpTabletV2, ()),
+        zwp_tablet_seat_v2::EVT_TOOL_ADDED_OPCODE => (ZwpTabletToolV2, ()),
+        zwp_tablet_seat_v2::EVT_PAD_ADDED_OPCODE => (ZwpTabletPadV2, ()),
+    ]);
+}
+
+impl<H: AppHandler> Dispatch<ZwpTabletV2, ()> for App<H> {
+    fn event(
+        _app: &mut Self,
+        tablet: &ZwpTabletV2,
+        event: zwp_tablet_v2::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        use zwp_tablet_v2::Event;
+
+        match event {
+            Event::Name { name } => log::info!("tablet {}: {name}", tablet.id()),
+            Event::Removed => {
+                log::info!("tablet {} removed", tablet.id());
+                tablet.destroy();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<H: AppHandler> Dispatch<ZwpTabletToolV2, ()> for App<H> {
+    fn event(
+        app: &mut Self,
+        proxy: &ZwpTabletToolV2,
+        event: zwp_tablet_tool_v2::Event,
+        _: &(),
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        use zwp_tablet_tool_v2::{ButtonState, Event};
+
+        let Some((seat, tool)) = app.shared.tablet_tool(proxy) else {
+            return;
+        };
+        match event {
+            Event::Type { tool_type: WEnum::Value(tool_type) } => {
+                log::info!("tablet tool {} is a {tool_type:?}", proxy.id());
+            }
+            Event::ProximityIn { surface, .. } => {
+                log::info!("tablet tool {} in proximity of window {}", proxy.id(), surface.id());
+                tool.focus = Some(surface.id());
+            }
+            Event::ProximityOut => {
+                // Ends the stroke, should the tip still be down
+                tool.focus = None;
+                tool.down = false;
+                tool.stroke = None;
+            }
+            Event::Down { .. } => tool.down = true,
+            Event::Up => {
+                tool.down = false;
+                tool.stroke = None;
+            }
+            Event::Motion { x, y } => tool.position = (x, y),
+            Event::Pressure { pressure } => tool.pressure = f64::from(pressure) / 65535.0,
+            Event::Tilt { tilt_x, tilt_y } => {
+                if tool.tilt != (tilt_x, tilt_y) {
+                    log::debug!("tablet tool {} tilted {tilt_x:.1}°, {tilt_y:.1}°", proxy.id());
+                }
+                tool.tilt = (tilt_x, tilt_y);
+            }
+            Event::Button { button, state: WEnum::Value(ButtonState::Pressed), .. } => {
+                let Some(id) = tool.focus.clone() else {
+                    return;
+                };
+                println!("tablet button {button:#x}, cleared the window");
+                if let Some(base) = app.windows.get_mut(&id) {
+                    base.clear_strokes();
+                    app.redraw(qh, &id);
+                }
+            }
+            Event::Frame { .. } => {
+                let Some(id) = tool.focus.clone() else {
+                    return;
+                };
+                let current = (tool.position, tool.pressure);
+                let previous = if tool.down { tool.stroke.replace(current) } else { None };
+                let seat = seat.id();
+                let (down, pressure) = (tool.down, tool.pressure);
+                app.shared.trace.record(
+                    "tablet_tool",
+                    &[
+                        ("seat", &seat),
+                        ("surface", &id),
+                        ("position", &current.0),
+                        ("pressure", &pressure),
+                        ("down", &down),
+                    ],
+                );
+                // The first frame of a stroke leaves a dot
+                if down && let Some(base) = app.windows.get_mut(&id) {
+                    base.add_stroke(previous.unwrap_or(current), current);
+                    app.redraw(qh, &id);
+                }
+            }
+            Event::Removed => {
+                log::info!("tablet tool {} removed", proxy.id());
+                for resources in app.shared.seats.values_mut() {
+                    if let Some(tablet_seat) = &mut resources.tablet_seat {
+                        tablet_seat.tools.remove(proxy);
+                    }
+                }
+                proxy.destroy();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<H: AppHandler> Dispatch<ZwpTabletPadV2, ()> for App<H> {
+    fn event(
+        _app: &mut Self,
+        pad: &ZwpTabletPadV2,
+        event: zwp_tablet_pad_v2::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let zwp_tablet_pad_v2::Event::Removed = event {
+            pad.destroy();
+        }
+    }
+
+    event_created_child!(@<H: AppHandler> App<H>, ZwpTabletPadV2, [
+        zwp_tablet_pad_v2::EVT_GROUP_OPCODE => (ZwpTabletPadGroupV2, ()),
+    ]);
+}
+
+impl<H: AppHandler> Dispatch<ZwpTabletPadGroupV2, ()> for App<H> {
+    fn event(
+        _app: &mut Self,
+        _group: &ZwpTabletPadGroupV2,
+        _event: zwp_tablet_pad_group_v2::Event,
+        _: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+
+    event_created_child!(@<H: AppHandler> App<H>, ZwpTabletPadGroupV2, [
+        zwp_tablet_pad_group_v2::EVT_RING_OPCODE => (ZwpTabletPadRingV2, ()),
+        zwp_tablet_pad_group_v2::EVT_STRIP_OPCODE => (ZwpTabletPadStripV2, ()),
+    ]);
+}
+
+delegate_noop!(@<H: AppHandler> App<H>: ignore ZwpTabletManagerV2);
+delegate_noop!(@<H: AppHandler> App<H>: ignore ZwpTabletPadRingV2);
+delegate_noop!(@<H: AppHandler> App<H>: ignore ZwpTabletPadStripV2);
@@ -0,0 +1,78 @@
+//! Switching between vsync and tearing with T, for latency testing. The window hints the
+//! compositor with `wp_tearing_control_v1` and the renderer sets its swap interval to match,
+//! 1 for vsync and 0 for async presentation.
+
+use smithay_client_toolkit::{
+    reexports::protocols::wp::tearing_control::v1::client::{
+        wp_tearing_control_manager_v1::WpTearingControlManagerV1,
+        wp_tearing_control_v1::{PresentationHint, WpTearingControlV1},
+    },
+    shell::WaylandSurface,
+};
+use wayland_client::{backend::ObjectId, delegate_noop, globals::GlobalList, QueueHandle};
+
+use crate::{App, AppHandler, BaseApp};
+
+/// Binds the tearing control manager, or nothing when the compositor lacks it. Without it
+/// only the swap interval changes.
+pub(crate) fn bind_manager<H: AppHandler>(
+    globals: &GlobalList,
+    qh: &QueueHandle<App<H>>,
+) -> Option<WpTearingControlManagerV1> {
+    let manager = globals.bind::<WpTearingControlManagerV1, _, _>(qh, 1..=1, ()).ok();
+    if manager.is_none() {
+        log::info!("wp_tearing_control_manager_v1 not available, T only sets the swap interval");
+    }
+    manager
+}
+
+/// The tearing control of a window surface. The compositor assumes vsync, so only async needs
+/// a hint, which goes with the next commit.
+pub(crate) fn get_tearing_control<H: AppHandler>(
+    manager: &WpTearingControlManagerV1,
+    qh: &QueueHandle<App<H>>,
+    surface: &impl WaylandSurface,
+    vsync: bool,
+) -> WpTearingControlV1 {
+    let tearing_control = manager.get_tearing_control(surface.wl_surface(), qh, ());
+    if !vsync {
+        tearing_control.set_presentation_hint(presentation_hint(vsync));
+    }
+    tearing_control
+}
+
+fn presentation_hint(vsync: bool) -> PresentationHint {
+    if vsync { PresentationHint::Vsync } else { PresentationHint::Async }
+}
+
+impl BaseApp {
+    /// The swap interval the renderer should use: 1 waits for vsync, 0 presents right away.
+    pub fn swap_interval(&self) -> u32 {
+        if self.vsync { 1 } else { 0 }
+    }
+}
+
+impl<H: AppHandler> App<H> {
+    /// Switches the window between vsync and async presentation.
+    pub(crate) fn toggle_vsync(&mut self, qh: &QueueHandle<Self>, id: &ObjectId) {
+        let Some(base) = self.windows.get_mut(id) else {
+            return;
+        };
+        base.vsync = !base.vsync;
+
+        // Double buffered, the hint goes with the next frame
+        if let Some(tearing_control) = &base.tearing_control {
+            tearing_control.set_presentation_hint(presentation_hint(base.vsync));
+        }
+        self.handler.vsync_changed(base);
+        println!(
+            "presentation: {}, swap interval {}",
+            if base.vsync { "vsync" } else { "async" },
+            base.swap_interval()
+        );
+        self.redraw(qh, id);
+    }
+}
+
+delegate_noop!(@<H: AppHandler> App<H>: ignore WpTearingControlManagerV1);
+delegate_noop!(@<H: AppHandler> App<H>: ignore WpTearingControlV1);
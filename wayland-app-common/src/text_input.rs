@@ -0,0 +1,314 @@
+//! Typing through an input method with text-input-v3. The text input is enabled on the window
+//! with keyboard focus, what the input method commits collects along the bottom of the content
+//! area, and the preedit, the text it is still composing, follows it underlined. The cursor
+//! rectangle tells the input method where the caret is, so its candidate popup opens next to it.
+//!
+//! Without the protocol, or without an input method running, the keys work as before.
+
+use smithay_client_toolkit::reexports::protocols::wp::text_input::zv3::client::{
+    zwp_text_input_manager_v3::ZwpTextInputManagerV3,
+    zwp_text_input_v3::{self, ZwpTextInputV3},
+};
+use wayland_client::{
+    backend::ObjectId, delegate_noop, globals::GlobalList, protocol::wl_seat, Connection,
+    Dispatch, Proxy, QueueHandle,
+};
+
+use crate::{
+    font::{self, CELL_WIDTH, GLYPH_HEIGHT},
+    App, AppHandler, BaseApp,
+};
+
+/// How many characters of committed text a window keeps, the oldest drop off the front.
+const MAX_TEXT: usize = 256;
+/// Logical pixels per font pixel.
+const TEXT_SCALE: u32 = 2;
+/// Distance of the text from the edges of the content area, in logical pixels.
+const TEXT_MARGIN: u32 = 8;
+/// The row of the preedit underline, in font pixels from the top of the glyphs.
+const UNDERLINE_ROW: u32 = GLYPH_HEIGHT + 1;
+const COMMITTED_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+const PREEDIT_COLOR: [f32; 3] = [0.6, 0.8, 1.0];
+
+/// A character to show, with its color and whether it is underlined.
+type ShownChar = (char, [f32; 3], bool);
+
+/// Binds the text input manager, or nothing when the compositor lacks it.
+pub(crate) fn bind_manager<H: AppHandler>(
+    globals: &GlobalList,
+    qh: &QueueHandle<App<H>>,
+) -> Option<ZwpTextInputManagerV3> {
+    let manager = globals.bind::<ZwpTextInputManagerV3, _, _>(qh, 1..=1, ()).ok();
+    if manager.is_none() {
+        log::info!("zwp_text_input_manager_v3 not available, no input methods");
+    }
+    manager
+}
+
+/// The text input of the seat, created along with the keyboard.
+pub(crate) struct TextInput {
+    text_input: ZwpTextInputV3,
+    /// The window the text input is enabled on.
+    focus: Option<ObjectId>,
+    /// What the input method sent since its last `done`.
+    pending: Pending,
+    /// The commit requests sent so far, a `done` tells which of them it answers.
+    commits: u32,
+}
+
+/// The changes a `done` applies all at once.
+#[derive(Default)]
+struct Pending {
+    preedit: Option<Preedit>,
+    commit: Option<String>,
+    /// Bytes to delete before the cursor. The cursor is at the end, nothing comes after it.
+    delete_before: usize,
+}
+
+/// The text typed into a window.
+#[derive(Default)]
+pub(crate) struct TextField {
+    committed: String,
+    preedit: Option<Preedit>,
+    /// Whether the text input is enabled on the window, the caret shows then.
+    active: bool,
+}
+
+/// Text the input method is still composing.
+struct Preedit {
+    text: String,
+    /// Byte offset of the cursor in the text, `None` when the input method hides it.
+    cursor: Option<usize>,
+}
+
+impl TextInput {
+    pub(crate) fn new<H: AppHandler>(
+        manager: &ZwpTextInputManagerV3,
+        seat: &wl_seat::WlSeat,
+        qh: &QueueHandle<App<H>>,
+    ) -> TextInput {
+        TextInput {
+            text_input: manager.get_text_input(seat, qh, seat.clone()),
+            focus: None,
+            pending: Pending::default(),
+            commits: 0,
+        }
+    }
+
+    /// Tells the input method about the text around the cursor and where the caret is, and
+    /// commits that along with whatever was requested before.
+    fn commit_state(&mut self, base: &BaseApp) {
+        let committed = &base.text_field.committed;
+        let end = committed.len() as i32;
+        self.text_input.set_surrounding_text(committed.clone(), end, end);
+        if let Some([x, y, width, height]) = base.caret_area() {
+            self.text_input.set_cursor_rectangle(x as i32, y as i32, width as i32, height as i32);
+        }
+        self.text_input.commit();
+        self.commits = self.commits.wrapping_add(1);
+    }
+}
+
+impl TextField {
+    /// Applies what the input method sent, in the order the protocol asks for. The preedit is
+    /// replaced, so the old one goes whether or not a new one came.
+    fn apply(&mut self, pending: Pending) {
+        let mut end = self.committed.len().saturating_sub(pending.delete_before);
+        while !self.committed.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.committed.truncate(end);
+
+        if let Some(commit) = pending.commit {
+            self.committed.extend(commit.chars().filter(|c| !c.is_control()));
+            let excess = self.committed.chars().count().saturating_sub(MAX_TEXT);
+            if let Some((start, _)) = self.committed.char_indices().nth(excess) {
+                self.committed.drain(..start);
+            }
+        }
+
+        self.preedit = pending.preedit;
+    }
+
+    /// The characters to show, and the index of the character the caret is in front of.
+    fn chars(&self) -> (Vec<ShownChar>, Option<usize>) {
+        let mut chars: Vec<_> =
+            self.committed.chars().map(|c| (c, COMMITTED_COLOR, false)).collect();
+        let caret = match &self.preedit {
+            Some(preedit) => {
+                let caret = preedit
+                    .cursor
+                    .and_then(|cursor| preedit.text.get(..cursor))
+                    .map(|before| chars.len() + before.chars().count());
+                chars.extend(preedit.text.chars().map(|c| (c, PREEDIT_COLOR, true)));
+                caret
+            }
+            None => Some(chars.len()),
+        };
+        (chars, caret)
+    }
+}
+
+impl BaseApp {
+    /// The text typed through the input method, a rectangle per run of lit font pixels with
+    /// its color, in GL buffer coordinates. The latest characters show when they don't all
+    /// fit, and the caret while the text input is enabled. Empty for [`Self::plain`] windows.
+    pub fn text_rects(&self) -> Vec<([i32; 4], [f32; 3])> {
+        let Some((x, y, fit)) = self.text_layout() else {
+            return Vec::new();
+        };
+        if self.plain() {
+            return Vec::new();
+        }
+
+        let (chars, _) = self.text_field.chars();
+        let skip = chars.len().saturating_sub(fit);
+        let mut rects = Vec::new();
+        for (column, &(c, color, underlined)) in (0..).zip(&chars[skip..]) {
+            let left = x + cell(column);
+            for [glyph_x, glyph_y, width, height] in font::glyph_rects(c) {
+                let rect = self.buffer_rect(
+                    left + glyph_x * TEXT_SCALE,
+                    y + glyph_y * TEXT_SCALE,
+                    width * TEXT_SCALE,
+                    height * TEXT_SCALE,
+                );
+                rects.push((rect, color));
+            }
+            if underlined {
+                let underline = UNDERLINE_ROW * TEXT_SCALE;
+                rects.push((self.buffer_rect(left, y + underline, cell(1), TEXT_SCALE), color));
+            }
+        }
+
+        if self.text_field.active
+            && let Some([x, y, width, height]) = self.caret_area()
+        {
+            rects.push((self.buffer_rect(x, y, width, height), COMMITTED_COLOR));
+        }
+        rects
+    }
+
+    /// Where the text starts in logical coordinates and how many characters fit, `None` when
+    /// the content area is too small for even one.
+    fn text_layout(&self) -> Option<(u32, u32, usize)> {
+        let [x, y, width, height] = self.content_area();
+        let line_height = (UNDERLINE_ROW + 1) * TEXT_SCALE;
+        let fit = width.checked_sub(2 * TEXT_MARGIN)? / cell(1);
+        let top = (y + height).checked_sub(TEXT_MARGIN + line_height)?;
+        (fit > 0 && top >= y + TEXT_MARGIN).then_some((x + TEXT_MARGIN, top, fit as usize))
+    }
+
+    /// The caret in logical coordinates, sent as the cursor rectangle. `None` while the input
+    /// method hides the cursor in the preedit.
+    fn caret_area(&self) -> Option<[u32; 4]> {
+        let (x, y, fit) = self.text_layout()?;
+        let (chars, caret) = self.text_field.chars();
+        let skip = chars.len().saturating_sub(fit);
+        let column = caret?.saturating_sub(skip).min(fit);
+        let left = (x + cell(column as u32)).saturating_sub(TEXT_SCALE / 2);
+        Some([left, y, TEXT_SCALE / 2, UNDERLINE_ROW * TEXT_SCALE])
+    }
+}
+
+/// Width of that many characters in logical pixels.
+fn cell(count: u32) -> u32 {
+    count * CELL_WIDTH * TEXT_SCALE
+}
+
+impl<H: AppHandler> App<H> {
+    /// Lets go of the text input of the seat along with its keyboard. The window it was enabled
+    /// on drops its preedit, no `leave` comes for it anymore.
+    pub(crate) fn release_text_input(&mut self, seat: &wl_seat::WlSeat) {
+        let resources = self.shared.seats.get_mut(seat);
+        let Some(text_input) = resources.and_then(|resources| resources.text_input.take()) else {
+            return;
+        };
+        text_input.text_input.destroy();
+        if let Some(base) = text_input.focus.and_then(|id| self.windows.get_mut(&id)) {
+            base.text_field.preedit = None;
+            base.text_field.active = false;
+        }
+    }
+}
+
+impl<H: AppHandler> Dispatch<ZwpTextInputV3, wl_seat::WlSeat> for App<H> {
+    fn event(
+        app: &mut Self,
+        _proxy: &ZwpTextInputV3,
+        event: zwp_text_input_v3::Event,
+        seat: &wl_seat::WlSeat,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        use zwp_text_input_v3::{ContentHint, ContentPurpose, Event};
+
+        let resources = app.shared.seats.get_mut(seat);
+        let Some(text_input) = resources.and_then(|resources| resources.text_input.as_mut()) else {
+            return;
+        };
+        match event {
+            Event::Enter { surface } => {
+                let id = surface.id();
+                app.shared.trace.record("text_input_enter", &[("surface", &id)]);
+                let Some(base) = app.windows.get_mut(&id) else {
+                    return;
+                };
+                log::info!("text input enabled on window {id}");
+                base.text_field.active = true;
+                text_input.pending = Pending::default();
+                text_input.text_input.enable();
+                text_input.text_input.set_content_type(ContentHint::None, ContentPurpose::Normal);
+                text_input.commit_state(base);
+                text_input.focus = Some(id.clone());
+                app.redraw(qh, &id);
+            }
+            Event::Leave { surface } => {
+                let id = surface.id();
+                app.shared.trace.record("text_input_leave", &[("surface", &id)]);
+                // Whatever the input method had pending is for the window it leaves
+                text_input.pending = Pending::default();
+                text_input.text_input.disable();
+                text_input.text_input.commit();
+                text_input.commits = text_input.commits.wrapping_add(1);
+                text_input.focus = None;
+                let Some(base) = app.windows.get_mut(&id) else {
+                    return;
+                };
+                base.text_field.preedit = None;
+                base.text_field.active = false;
+                app.redraw(qh, &id);
+            }
+            Event::PreeditString { text, cursor_begin, cursor_end: _ } => {
+                text_input.pending.preedit = text.map(|text| Preedit {
+                    cursor: usize::try_from(cursor_begin).ok().filter(|&c| c <= text.len()),
+                    text,
+                });
+            }
+            Event::CommitString { text } => text_input.pending.commit = text,
+            Event::DeleteSurroundingText { before_length, after_length: _ } => {
+                text_input.pending.delete_before = before_length as usize;
+            }
+            Event::Done { serial } => {
+                app.shared.trace.record("text_input_done", &[("serial", &serial)]);
+                let pending = std::mem::take(&mut text_input.pending);
+                let Some(id) = text_input.focus.clone() else {
+                    return;
+                };
+                let Some(base) = app.windows.get_mut(&id) else {
+                    return;
+                };
+                base.text_field.apply(pending);
+                // An outdated `done` changes the text, but our state waits for the input method
+                // to catch up with the requests already on their way
+                if serial == text_input.commits {
+                    text_input.commit_state(base);
+                }
+                app.redraw(qh, &id);
+            }
+            _ => {}
+        }
+    }
+}
+
+delegate_noop!(@<H: AppHandler> App<H>: ignore ZwpTextInputManagerV3);
@@ -0,0 +1,43 @@
+//! The window title, which shows the size and frame rate of the window after the name of the
+//! example.
+
+use crate::BaseApp;
+
+/// Longest title we send, in bytes. A Wayland message must fit in 4096 bytes, a longer
+/// `--title` would otherwise take the connection down.
+const MAX_TITLE_LEN: usize = 1024;
+
+/// Cuts the title to [`MAX_TITLE_LEN`], at a character boundary so non-ASCII titles stay valid
+/// UTF-8.
+pub(crate) fn limit_title(mut title: String) -> String {
+    if title.len() > MAX_TITLE_LEN {
+        let end = title.floor_char_boundary(MAX_TITLE_LEN);
+        title.truncate(end);
+    }
+    title
+}
+
+impl BaseApp {
+    /// Puts the current size and frame rate into the title, at most once a second. Nothing is
+    /// sent when the title stays the same.
+    pub(crate) fn update_title(&mut self, name: &str, time: u32) {
+        if let Some(updated) = self.title_updated
+            && time.wrapping_sub(updated) < 1000
+        {
+            return;
+        }
+        self.title_updated = Some(time);
+
+        let dialog = if self.is_dialog() { " dialog" } else { "" };
+        let title = limit_title(format!(
+            "{name}{dialog} ({}x{}, {} fps)",
+            self.width,
+            self.height,
+            self.frame_stats.fps()
+        ));
+        if title != self.title {
+            self.window.set_title(title.clone());
+            self.title = title;
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! Touch input. Every contact shows as a square under the finger, and two fingers pinch the
+//! triangle larger or smaller, as does a pinch on the touchpad.
+//!
+//! The toolkit holds the events back until the `wl_touch.frame` that ends them, so by the time
+//! the handlers run all changes of a frame arrive together, e.g. both fingers of a pinch.
+
+use std::collections::BTreeMap;
+
+use smithay_client_toolkit::seat::touch::TouchHandler;
+use wayland_client::{
+    Connection, Proxy, QueueHandle,
+    protocol::{wl_surface::WlSurface, wl_touch::WlTouch},
+};
+
+use crate::{App, AppHandler, BaseApp};
+
+/// Side of the square drawn under a contact, in logical pixels.
+const TOUCH_POINT_SIZE: u32 = 40;
+/// Cycled through by touch id, so a finger keeps its color while it is down.
+const TOUCH_POINT_COLORS: [[f32; 3]; 5] = [
+    [1.0, 0.8, 0.0],
+    [0.0, 0.8, 1.0],
+    [1.0, 0.3, 0.6],
+    [0.4, 1.0, 0.4],
+    [0.8, 0.5, 1.0],
+];
+/// How far the pinch can scale the triangle in either direction.
+const ZOOM_RANGE: (f32, f32) = (0.25, 4.0);
+
+/// The touch points on a window and the pinch they may be doing.
+#[derive(Default)]
+pub(crate) struct Touches {
+    /// Surface-local logical position of each contact, by touch id.
+    points: BTreeMap<i32, (f64, f64)>,
+    /// Distance of the fingers and the zoom when the pinch started.
+    pinch: Option<(f64, f32)>,
+}
+
+impl BaseApp {
+    /// A square under each touch point with its color, in GL buffer coordinates.
+    pub fn touch_rects(&self) -> Vec<([i32; 4], [f32; 3])> {
+        let size = TOUCH_POINT_SIZE.min(self.width).min(self.height);
+        self.touches
+            .points
+            .iter()
+            .map(|(&id, &(x, y))| {
+                // Centered on the contact, kept inside the window near the edges
+                let x = (x.max(0.0) as u32)
+                    .saturating_sub(size / 2)
+                    .min(self.width - size);
+                let y = (y.max(0.0) as u32)
+                    .saturating_sub(size / 2)
+                    .min(self.height - size);
+                let color =
+                    TOUCH_POINT_COLORS[id.rem_euclid(TOUCH_POINT_COLORS.len() as i32) as usize];
+                (self.buffer_rect(x, y, size, size), color)
+            })
+            .collect()
+    }
+
+    /// Removes the touch point of the id, it went up or its seat lost it.
+    pub(crate) fn end_touch_point(&mut self, id: i32) {
+        self.touches.points.remove(&id);
+        self.update_pinch();
+    }
+
+    /// Scales the triangle, within [`ZOOM_RANGE`].
+    pub(crate) fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom.clamp(ZOOM_RANGE.0, ZOOM_RANGE.1);
+    }
+
+    /// Starts, follows or ends the pinch, whichever the touch points call for. It takes exactly
+    /// two fingers.
+    fn update_pinch(&mut self) {
+        let mut points = self.touches.points.values();
+        let (Some(&(x0, y0)), Some(&(x1, y1)), None) =
+            (points.next(), points.next(), points.next())
+        else {
+            self.touches.pinch = None;
+            return;
+        };
+
+        let distance = (x1 - x0).hypot(y1 - y0).max(1.0);
+        match self.touches.pinch {
+            Some((start_distance, start_zoom)) => {
+                self.set_zoom(start_zoom * (distance / start_distance) as f32);
+            }
+            None => self.touches.pinch = Some((distance, self.zoom)),
+        }
+    }
+}
+
+impl<H: AppHandler> TouchHandler for App<H> {
+    fn down(
+        &mut self,
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+        touch: &WlTouch,
+        _serial: u32,
+        _time: u32,
+        surface: WlSurface,
+        id: i32,
+        position: (f64, f64),
+    ) {
+        // The later events of the touch point don't say which surface it is on
+        let window = surface.id();
+        self.shared.trace.record(
+            "touch_down",
+            &[("surface", &window), ("id", &id), ("position", &position)],
+        );
+        self.wake_inactive(qh, &window);
+        let Some(base) = self.windows.get_mut(&window) else {
+            return;
+        };
+        log::info!(
+            "touch {id} down on window {window} at ({:.1}, {:.1})",
+            position.0,
+            position.1
+        );
+        base.touches.points.insert(id, position);
+        base.update_pinch();
+        if let Some((_, resources)) = self.shared.touch_seat(touch) {
+            resources.touch_points.insert(id, window);
+        }
+    }
+
+    fn up(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        touch: &WlTouch,
+        _serial: u32,
+        _time: u32,
+        id: i32,
+    ) {
+        self.shared.trace.record("touch_up", &[("id", &id)]);
+        let Some((_, resources)) = self.shared.touch_seat(touch) else {
+            return;
+        };
+        let Some(window) = resources.touch_points.remove(&id) else {
+            return;
+        };
+        log::info!("touch {id} up");
+        if let Some(base) = self.windows.get_mut(&window) {
+            base.end_touch_point(id);
+        }
+    }
+
+    fn motion(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        touch: &WlTouch,
+        _time: u32,
+        id: i32,
+        position: (f64, f64),
+    ) {
+        let Some((_, resources)) = self.shared.touch_seat(touch) else {
+            return;
+        };
+        let Some(base) = resources
+            .touch_points
+            .get(&id)
+            .and_then(|window| self.windows.get_mut(window))
+        else {
+            return;
+        };
+        base.touches.points.insert(id, position);
+        base.update_pinch();
+    }
+
+    fn shape(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &WlTouch,
+        _id: i32,
+        _major: f64,
+        _minor: f64,
+    ) {
+    }
+
+    fn orientation(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _touch: &WlTouch,
+        _id: i32,
+        _orientation: f64,
+    ) {
+    }
+
+    fn cancel(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, touch: &WlTouch) {
+        // The compositor took the touches of the seat over, e.g. for a gesture of its own. The
+        // zoom stays where the pinch left it.
+        log::info!("touch cancelled");
+        self.shared.trace.record("touch_cancel", &[]);
+        let Some((_, resources)) = self.shared.touch_seat(touch) else {
+            return;
+        };
+        for (id, window) in resources.touch_points.drain() {
+            if let Some(base) = self.windows.get_mut(&window) {
+                base.end_touch_point(id);
+            }
+        }
+    }
+}
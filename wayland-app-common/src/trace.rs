@@ -0,0 +1,191 @@
+//! `--trace <file>`, a JSON line for every Wayland event the app handles, for untangling in
+//! which order configures, scales and frames arrived. `trace-dump` pretty-prints and diffs
+//! the files.
+//!
+//! Each line is an object with the seconds since start in `time`, what happened in `event` and
+//! the fields of the event. A thread of its own writes the lines, so a slow disk never holds
+//! up the dispatch.
+
+use std::{
+    fmt::{self, Write as _},
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::mpsc::{self, Sender},
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+use wayland_client::backend::ObjectId;
+
+/// Writes the trace, or does nothing without `--trace`.
+pub(crate) struct Trace {
+    sender: Option<Sender<String>>,
+    writer: Option<JoinHandle<()>>,
+    start: Instant,
+}
+
+impl Trace {
+    /// Starts writing the trace to the file, a trace that can't be written is reported and off.
+    pub(crate) fn open(path: Option<&Path>) -> Trace {
+        let mut trace = Trace { sender: None, writer: None, start: Instant::now() };
+        let Some(path) = path else {
+            return trace;
+        };
+        let file = match File::create(path) {
+            Ok(file) => file,
+            Err(err) => {
+                eprintln!("can't write the trace to {}: {err}", path.display());
+                return trace;
+            }
+        };
+
+        let (sender, receiver) = mpsc::channel::<String>();
+        let writer = thread::Builder::new().name("trace".to_string()).spawn(move || {
+            let mut file = BufWriter::new(file);
+            // Ends once the sender is gone
+            for line in receiver {
+                if let Err(err) = writeln!(file, "{line}") {
+                    log::warn!("failed to write the trace: {err}");
+                    return;
+                }
+            }
+            if let Err(err) = file.flush() {
+                log::warn!("failed to write the trace: {err}");
+            }
+        });
+        match writer {
+            Ok(writer) => {
+                println!("tracing the Wayland events to {}", path.display());
+                trace.sender = Some(sender);
+                trace.writer = Some(writer);
+            }
+            Err(err) => eprintln!("can't start the trace writer: {err}"),
+        }
+        trace
+    }
+
+    /// Appends a line for the event with the fields, in the order given.
+    pub(crate) fn record(&self, event: &str, fields: &[(&str, &dyn TraceField)]) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        let mut line = format!("{{\"time\":{:.6},\"event\":", self.start.elapsed().as_secs_f64());
+        write_string(&mut line, event);
+        for (name, value) in fields {
+            line.push(',');
+            write_string(&mut line, name);
+            line.push(':');
+            value.write_json(&mut line);
+        }
+        line.push('}');
+        // The writer only goes away along with the sender
+        let _ = sender.send(line);
+    }
+
+    /// Writes out what is left and closes the file.
+    pub(crate) fn finish(&mut self) {
+        self.sender = None;
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+impl Drop for Trace {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// A value of a trace line, written as JSON.
+pub(crate) trait TraceField {
+    fn write_json(&self, out: &mut String);
+}
+
+macro_rules! number_fields {
+    ($($type:ty),*) => {
+        $(impl TraceField for $type {
+            fn write_json(&self, out: &mut String) {
+                let _ = write!(out, "{self}");
+            }
+        })*
+    };
+}
+
+number_fields!(i32, u32, u64, usize, bool);
+
+impl TraceField for f64 {
+    fn write_json(&self, out: &mut String) {
+        // JSON has no infinities or NaN
+        if self.is_finite() {
+            let _ = write!(out, "{self}");
+        } else {
+            out.push_str("null");
+        }
+    }
+}
+
+impl TraceField for &str {
+    fn write_json(&self, out: &mut String) {
+        write_string(out, self);
+    }
+}
+
+impl TraceField for String {
+    fn write_json(&self, out: &mut String) {
+        write_string(out, self);
+    }
+}
+
+impl TraceField for ObjectId {
+    fn write_json(&self, out: &mut String) {
+        write_string(out, &self.to_string());
+    }
+}
+
+impl<T: TraceField> TraceField for Option<T> {
+    fn write_json(&self, out: &mut String) {
+        match self {
+            Some(value) => value.write_json(out),
+            None => out.push_str("null"),
+        }
+    }
+}
+
+impl<T: TraceField> TraceField for (T, T) {
+    fn write_json(&self, out: &mut String) {
+        out.push('[');
+        self.0.write_json(out);
+        out.push(',');
+        self.1.write_json(out);
+        out.push(']');
+    }
+}
+
+/// A field written with its `Debug` form as a string, e.g. an enum of the protocol.
+pub(crate) struct Debugged<T>(pub T);
+
+impl<T: fmt::Debug> TraceField for Debugged<T> {
+    fn write_json(&self, out: &mut String) {
+        write_string(out, &format!("{:?}", self.0));
+    }
+}
+
+/// Writes the string as a JSON string, with quotes and escapes.
+fn write_string(out: &mut String, value: &str) {
+    out.push('"');
+    for char in value.chars() {
+        match char {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            char if char < ' ' => {
+                let _ = write!(out, "\\u{:04x}", char as u32);
+            }
+            char => out.push(char),
+        }
+    }
+    out.push('"');
+}
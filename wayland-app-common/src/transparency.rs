@@ -0,0 +1,80 @@
+//! `--transparent`, the GL examples clear to half transparent black and only the title bar and
+//! the triangle are opaque. The opaque region tells the compositor, so it can skip drawing what
+//! is behind those parts.
+
+use smithay_client_toolkit::{
+    compositor::{CompositorState, Region},
+    shell::WaylandSurface,
+};
+
+use crate::BaseApp;
+
+/// Alpha of the background with `--transparent`.
+const BACKGROUND_ALPHA: f32 = 0.5;
+
+/// Radius of the circle the triangle covers whatever its angle, relative to half the smaller
+/// side of the content area like its size. Its edges are about 0.4 away from its center.
+const TRIANGLE_INNER_RADIUS: f32 = 0.4;
+
+impl BaseApp {
+    /// What the GL examples clear the background to, RGBA with the alpha premultiplied. That is
+    /// [`Self::clear_color`], or half transparent black with `--transparent`.
+    pub fn background(&self) -> [f32; 4] {
+        if self.transparent {
+            [0.0, 0.0, 0.0, BACKGROUND_ALPHA]
+        } else {
+            let [red, green, blue] = self.clear_color();
+            [red, green, blue, 1.0]
+        }
+    }
+
+    /// The opaque parts of the window with `--transparent`, in logical coordinates. That is the
+    /// client-side title bar and the square inside the triangle at any angle. The corners of
+    /// the square around the triangle show the background, the region may leave out opaque
+    /// parts but must not claim translucent ones.
+    fn opaque_rects(&self) -> Vec<[i32; 4]> {
+        let mut rects = Vec::new();
+        let bar = self.title_bar_height();
+        if bar > 0 {
+            rects.push([0, 0, self.width as i32, bar as i32]);
+        }
+
+        let [x, y, width, height] = self.content_area();
+        let side = width.min(height) as f32;
+        let half = TRIANGLE_INNER_RADIUS * self.zoom * side / 2.0 / std::f32::consts::SQRT_2;
+        let (offset_x, offset_y) = self.triangle_offset;
+        let center_x = x as f32 + width as f32 / 2.0 + offset_x;
+        let center_y = y as f32 + height as f32 / 2.0 + offset_y;
+
+        // Rounded inwards, and cut to the content area the triangle is clipped to
+        let clamp_x = |v: f32| v.clamp(x as f32, (x + width) as f32) as i32;
+        let clamp_y = |v: f32| v.clamp(y as f32, (y + height) as f32) as i32;
+        let (left, right) = (clamp_x((center_x - half).ceil()), clamp_x(center_x + half));
+        let (top, bottom) = (clamp_y((center_y - half).ceil()), clamp_y(center_y + half));
+        if right > left && bottom > top {
+            rects.push([left, top, right - left, bottom - top]);
+        }
+        rects
+    }
+
+    /// Sets the opaque region for the next commit when it changed. Only with `--transparent`,
+    /// other windows keep having none.
+    pub(crate) fn update_opaque_region(&mut self, compositor: &CompositorState) {
+        if !self.transparent {
+            return;
+        }
+        let rects = self.opaque_rects();
+        if rects == self.opaque_region {
+            return;
+        }
+        let Ok(region) = Region::new(compositor) else {
+            return;
+        };
+        for &[x, y, width, height] in &rects {
+            region.add(x, y, width, height);
+        }
+        // The surface keeps a copy, the region can go
+        self.window.wl_surface().set_opaque_region(Some(region.wl_region()));
+        self.opaque_region = rects;
+    }
+}
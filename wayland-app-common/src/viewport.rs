@@ -0,0 +1,176 @@
+use std::time::{Duration, Instant};
+
+use smithay_client_toolkit::{
+    reexports::{
+        csd_frame::WindowState,
+        protocols::wp::viewporter::client::{wp_viewport::WpViewport, wp_viewporter::WpViewporter},
+    },
+    shell::WaylandSurface,
+};
+use wayland_client::{delegate_noop, globals::GlobalList, QueueHandle};
+
+use crate::{App, AppHandler, BaseApp};
+
+/// Internal resolution of the fixed resolution mode.
+pub const FIXED_RESOLUTION: (u32, u32) = (640, 360);
+
+/// How long an interactive resize keeps the buffer size before the buffers catch up.
+const RESIZE_HOLD: Duration = Duration::from_millis(100);
+
+/// The wp_viewport of the window surface, lets the compositor do the scaling of our buffers.
+pub(crate) struct Viewport {
+    viewport: WpViewport,
+    /// The state last sent, so it is only updated when it changes.
+    source: Option<[u32; 4]>,
+    destination: Option<(u32, u32)>,
+}
+
+/// Binds wp_viewporter, or nothing when the compositor lacks it.
+pub(crate) fn bind_viewporter<H: AppHandler>(
+    globals: &GlobalList,
+    qh: &QueueHandle<App<H>>,
+) -> Option<WpViewporter> {
+    globals.bind::<WpViewporter, _, _>(qh, 1..=1, ()).ok()
+}
+
+impl Viewport {
+    /// Creates the viewport for a window surface.
+    pub(crate) fn new<H: AppHandler>(
+        viewporter: &WpViewporter,
+        qh: &QueueHandle<App<H>>,
+        surface: &impl WaylandSurface,
+    ) -> Viewport {
+        Viewport {
+            viewport: viewporter.get_viewport(surface.wl_surface(), qh, ()),
+            source: None,
+            destination: None,
+        }
+    }
+
+    pub(crate) fn destroy(&self) {
+        self.viewport.destroy();
+    }
+}
+
+impl BaseApp {
+    /// The internal resolution while rendering at a fixed resolution, see
+    /// [`Self::set_fixed_resolution`].
+    pub fn fixed_resolution(&self) -> Option<(u32, u32)> {
+        self.fixed_resolution
+    }
+
+    /// Renders at the given resolution regardless of the window size and lets the compositor
+    /// scale it to the window, `None` goes back to rendering at the window size. Resizing the
+    /// window then only touches the viewport, the renderer keeps its buffers.
+    ///
+    /// Returns false when the compositor has no wp_viewporter, or with `--solid-background`
+    /// where the viewport stretches the single pixel instead.
+    pub fn set_fixed_resolution(&mut self, resolution: Option<(u32, u32)>) -> bool {
+        if self.viewport.is_none() || self.solid_background.is_some() {
+            return false;
+        }
+
+        self.fixed_resolution = resolution;
+        self.update_buffer_scale();
+        true
+    }
+
+    /// Whether the viewport rather than the buffer scale maps our buffers to the surface.
+    pub(crate) fn uses_viewport(&self) -> bool {
+        self.fixed_resolution.is_some()
+            || self.resize_hold.is_some()
+            || self.fractional_scale.is_some()
+            || self.solid_background.is_some()
+    }
+
+    /// Keeps the buffers at `buffer_size` while the window is resized interactively, the
+    /// viewport stretches them over the window until they catch up every [`RESIZE_HOLD`]. Once
+    /// the resize is over the buffers get the exact size of the window again. Slow GPUs then
+    /// don't reallocate their buffers for every configure of a drag.
+    ///
+    /// `buffer_size` is the size before the transform, from before the window size changed.
+    pub(crate) fn update_resize_hold(&mut self, buffer_size: (u32, u32)) {
+        let held = self.resize_hold.is_some();
+        let can_hold = self.resize_throttle
+            && self.viewport.is_some()
+            && self.fixed_resolution.is_none()
+            && self.solid_background.is_none();
+        if !can_hold || !self.window_state.contains(WindowState::RESIZING) {
+            self.resize_hold = None;
+        } else {
+            match self.resize_hold {
+                Some((_, since)) if since.elapsed() < RESIZE_HOLD => {}
+                // Catch up with the window and hold that for a while
+                Some(_) => {
+                    let size = (self.to_physical(self.width), self.to_physical(self.height));
+                    self.resize_hold = Some((size, Instant::now()));
+                }
+                None => self.resize_hold = Some((buffer_size, Instant::now())),
+            }
+        }
+        if self.resize_hold.is_some() != held {
+            log::debug!("resize hold {}", if held { "released" } else { "started" });
+            self.update_buffer_scale();
+        }
+    }
+
+    /// Sets the integer buffer scale, or 1 when the viewport does the scaling. Takes effect with
+    /// the next commit, which carries the buffer rendered at the matching size.
+    pub(crate) fn update_buffer_scale(&self) {
+        let scale = if self.uses_viewport() { 1 } else { self.scale };
+        self.window.wl_surface().set_buffer_scale(scale);
+    }
+
+    /// The part of the fixed resolution buffer that is shown, as `[x, y, width, height]`. It is
+    /// cropped to the aspect ratio of the window so the image is never stretched.
+    pub(crate) fn fixed_source(&self) -> Option<[u32; 4]> {
+        let (width, height) = self.fixed_resolution?;
+        let (window_width, window_height) = (self.width.max(1), self.height.max(1));
+
+        let [x, y, w, h] = if window_width * height > width * window_height {
+            // The window is wider, crop the top and bottom
+            let h = (width * window_height / window_width).max(1);
+            [0, (height - h) / 2, width, h]
+        } else {
+            let w = (height * window_width / window_height).max(1);
+            [(width - w) / 2, 0, w, height]
+        };
+        Some([x, y, w, h])
+    }
+
+    /// Brings the viewport up to date with the window size. Like the buffer scale this is
+    /// double-buffered, so it goes out with the commit of the buffer rendered for that size.
+    pub(crate) fn update_viewport(&mut self) {
+        let uses_viewport = self.uses_viewport();
+        let source = self.fixed_source();
+        let size = (self.width, self.height);
+        let Some(viewport) = &mut self.viewport else {
+            return;
+        };
+
+        if viewport.source != source {
+            match source {
+                Some([x, y, width, height]) => {
+                    viewport.viewport.set_source(x as f64, y as f64, width as f64, height as f64)
+                }
+                // Unset, the whole buffer is shown
+                None => viewport.viewport.set_source(-1.0, -1.0, -1.0, -1.0),
+            }
+            viewport.source = source;
+        }
+
+        let destination = uses_viewport.then_some(size);
+        if viewport.destination != destination {
+            match destination {
+                Some((width, height)) => {
+                    viewport.viewport.set_destination(width as i32, height as i32)
+                }
+                None => viewport.viewport.set_destination(-1, -1),
+            }
+            viewport.destination = destination;
+        }
+    }
+}
+
+delegate_noop!(@<H: AppHandler> App<H>: ignore WpViewporter);
+delegate_noop!(@<H: AppHandler> App<H>: ignore WpViewport);
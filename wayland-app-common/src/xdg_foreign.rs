@@ -0,0 +1,120 @@
+//! xdg-foreign, so that a window of another client can be the parent of ours, the way portals
+//! parent their dialogs to the window that asked for them. `--export` prints a handle for every
+//! window, a new instance started with one opens its first window as a dialog of that window:
+//!
+//! ```text
+//! cargo run --bin glow-with-wayland-egl -- --export
+//! cargo run --bin shm-example -- --import <handle>
+//! ```
+
+use smithay_client_toolkit::{
+    reexports::protocols::xdg::foreign::zv2::client::{
+        zxdg_exported_v2::{self, ZxdgExportedV2},
+        zxdg_exporter_v2::ZxdgExporterV2,
+        zxdg_imported_v2::{self, ZxdgImportedV2},
+        zxdg_importer_v2::ZxdgImporterV2,
+    },
+    shell::WaylandSurface,
+};
+use wayland_client::{
+    backend::ObjectId, delegate_noop, globals::GlobalList, Connection, Dispatch, Proxy,
+    QueueHandle,
+};
+
+use crate::{App, AppHandler};
+
+/// Binds the exporter with `--export`, when the compositor has it.
+pub(crate) fn bind_exporter<H: AppHandler>(
+    globals: &GlobalList,
+    qh: &QueueHandle<App<H>>,
+    export: bool,
+) -> Option<ZxdgExporterV2> {
+    if !export {
+        return None;
+    }
+    let exporter = globals.bind::<ZxdgExporterV2, _, _>(qh, 1..=1, ()).ok();
+    if exporter.is_none() {
+        println!("--export needs zxdg_exporter_v2, which the compositor lacks");
+    }
+    exporter
+}
+
+/// Binds the importer with `--import`, when the compositor has it. Without it the window opens
+/// on its own.
+pub(crate) fn bind_importer<H: AppHandler>(
+    globals: &GlobalList,
+    qh: &QueueHandle<App<H>>,
+    import: Option<&str>,
+) -> Option<ZxdgImporterV2> {
+    import?;
+    let importer = globals.bind::<ZxdgImporterV2, _, _>(qh, 1..=1, ()).ok();
+    if importer.is_none() {
+        println!("--import needs zxdg_importer_v2, which the compositor lacks");
+    }
+    importer
+}
+
+/// Exports the window, the handle comes with an event. It stays valid until the window closes.
+pub(crate) fn export<H: AppHandler>(
+    exporter: &ZxdgExporterV2,
+    qh: &QueueHandle<App<H>>,
+    surface: &impl WaylandSurface,
+) -> ZxdgExportedV2 {
+    // The surface id tells the handles of the windows apart
+    exporter.export_toplevel(surface.wl_surface(), qh, surface.wl_surface().id())
+}
+
+/// Makes the window a child of the exported window of another client, like a dialog it stays
+/// above that window. A stale handle is only found out with the `destroyed` event.
+pub(crate) fn import<H: AppHandler>(
+    importer: &ZxdgImporterV2,
+    qh: &QueueHandle<App<H>>,
+    surface: &impl WaylandSurface,
+    handle: String,
+) -> ZxdgImportedV2 {
+    let imported = importer.import_toplevel(handle, qh, surface.wl_surface().id());
+    imported.set_parent_of(surface.wl_surface());
+    imported
+}
+
+impl<H: AppHandler> Dispatch<ZxdgExportedV2, ObjectId> for App<H> {
+    fn event(
+        _app: &mut Self,
+        _proxy: &ZxdgExportedV2,
+        event: zxdg_exported_v2::Event,
+        id: &ObjectId,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        let zxdg_exported_v2::Event::Handle { handle } = event else {
+            return;
+        };
+        println!("exported window {id} as {handle}");
+        println!("start another instance with --import {handle} to open a dialog of it");
+    }
+}
+
+impl<H: AppHandler> Dispatch<ZxdgImportedV2, ObjectId> for App<H> {
+    fn event(
+        app: &mut Self,
+        proxy: &ZxdgImportedV2,
+        event: zxdg_imported_v2::Event,
+        id: &ObjectId,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        let zxdg_imported_v2::Event::Destroyed = event else {
+            return;
+        };
+        // The handle was stale from the start, or the parent window closed since. Either way
+        // the window is an ordinary toplevel from now on.
+        println!("the parent of window {id} is gone, it stays open on its own");
+        if let Some(base) = app.windows.get_mut(id) {
+            base.imported = None;
+        }
+        proxy.destroy();
+    }
+}
+
+delegate_noop!(@<H: AppHandler> App<H>: ignore ZxdgExporterV2);
+delegate_noop!(@<H: AppHandler> App<H>: ignore ZxdgImporterV2);
@@ -1,270 +1,336 @@
-// This is nearly copy-pasted from the wgpu.rs example, but updated to use latest WGPU 27 APIs.
-// 
+// This started out nearly copy-pasted from the wgpu.rs example, updated to use latest WGPU 27
+// APIs, and now shares the Wayland side with the glow examples.
+//
 // https://github.com/Smithay/client-toolkit/blob/master/examples/wgpu.rs
 
 use raw_window_handle::{
     RawDisplayHandle, RawWindowHandle, WaylandDisplayHandle, WaylandWindowHandle,
 };
-use smithay_client_toolkit::{
-    compositor::{CompositorHandler, CompositorState},
-    delegate_compositor, delegate_output, delegate_registry, delegate_seat, delegate_xdg_shell,
-    delegate_xdg_window,
-    output::{OutputHandler, OutputState},
-    registry::{ProvidesRegistryState, RegistryState},
-    registry_handlers,
-    seat::{Capability, SeatHandler, SeatState},
-    shell::{
-        xdg::{
-            window::{Window, WindowConfigure, WindowDecorations, WindowHandler},
-            XdgShell,
-        },
-        WaylandSurface,
-    },
-};
-use wgpu::wgt::DeviceDescriptor;
-use std::ptr::NonNull;
-use wayland_client::{
-    globals::registry_queue_init,
-    protocol::{wl_output, wl_seat, wl_surface},
-    Connection, Proxy, QueueHandle,
+use smithay_client_toolkit::shell::{xdg::window::WindowConfigure, WaylandSurface};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    ptr::NonNull,
 };
+use wayland_app_common::{App, AppHandler, BaseApp, Options};
+use wayland_client::{backend::ObjectId, Proxy, QueueHandle};
+use wgpu::{util::DeviceExt, wgt::DeviceDescriptor};
 
-fn main() {
-    env_logger::init();
-
-    let conn = Connection::connect_to_env().unwrap();
-    let (globals, mut event_queue) = registry_queue_init(&conn).unwrap();
-    let qh = event_queue.handle();
-
-    // Initialize xdg_shell handlers so we can select the correct adapter
-    let compositor_state =
-        CompositorState::bind(&globals, &qh).expect("wl_compositor not available");
-    let xdg_shell_state = XdgShell::bind(&globals, &qh).expect("xdg shell not available");
-
-    let surface = compositor_state.create_surface(&qh);
-    // Create the window for adapter selection
-    let window = xdg_shell_state.create_window(surface, WindowDecorations::ServerDefault, &qh);
-    window.set_title("wgpu wayland window");
-    // GitHub does not let projects use the `org.github` domain but the `io.github` domain is fine.
-    window.set_app_id("io.github.smithay.client-toolkit.WgpuExample");
-    window.set_min_size(Some((256, 256)));
-    window.commit();
-
-    // Initialize wgpu
-    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-        backends: wgpu::Backends::all(),
-        ..Default::default()
-    });
+/// Size of the checkerboard texture drawn on the quad.
+const TEXTURE_SIZE: u32 = 8;
 
-    // Create the raw window handle for the surface.
-    let raw_display_handle = RawDisplayHandle::Wayland(WaylandDisplayHandle::new(
-        NonNull::new(conn.backend().display_ptr() as *mut _).unwrap(),
-    ));
-    let raw_window_handle = RawWindowHandle::Wayland(WaylandWindowHandle::new(
-        NonNull::new(window.wl_surface().id().as_ptr() as *mut _).unwrap(),
-    ));
-
-    let surface = unsafe {
-        instance
-            .create_surface_unsafe(wgpu::SurfaceTargetUnsafe::RawHandle {
-                raw_display_handle,
-                raw_window_handle,
-            })
-            .unwrap()
-    };
-
-    // Pick a supported adapter
-    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-        compatible_surface: Some(&surface),
-        ..Default::default()
-    }))
-    .expect("Failed to find suitable adapter");
-
-    let (device, queue) = pollster::block_on(adapter.request_device(&DeviceDescriptor {
-        memory_hints: wgpu::MemoryHints::MemoryUsage,
-        ..Default::default()
-    }))
-        .expect("Failed to request device");
+const SHADER: &str = r#"
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
 
-    let mut wgpu = Wgpu {
-        registry_state: RegistryState::new(&globals),
-        seat_state: SeatState::new(&globals, &qh),
-        output_state: OutputState::new(&globals, &qh),
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // A quad covering the middle half of the window, drawn as a triangle strip
+    let uv = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+    var out: VertexOutput;
+    out.position = vec4<f32>((uv.x - 0.5), (0.5 - uv.y), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
 
-        exit: false,
-        width: 256,
-        height: 256,
-        window,
-        device,
-        surface,
-        adapter,
-        queue,
-    };
+@group(0) @binding(0) var quad_texture: texture_2d<f32>;
+@group(0) @binding(1) var quad_sampler: sampler;
 
-    // We don't draw immediately, the configure will notify us when to first draw.
-    loop {
-        event_queue.blocking_dispatch(&mut wgpu).unwrap();
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(quad_texture, quad_sampler, in.uv);
+}
+"#;
 
-        if wgpu.exit {
-            println!("exiting example");
-            break;
-        }
+fn main() {
+    let options = Options::from_args();
+    let mut logger = env_logger::Builder::from_default_env();
+    if let Some(level) = options.log_level {
+        logger.filter_level(level);
     }
-
-    // On exit we must destroy the surface before the window is destroyed.
-    drop(wgpu.surface);
-    drop(wgpu.window);
+    logger.init();
+
+    wayland_app_common::run_app(
+        options,
+        |options| {
+            // GitHub does not let projects use the `org.github` domain but the `io.github`
+            // domain is fine.
+            wayland_app_common::init_with_options(
+                "wgpu wayland window",
+                "io.github.smithay.client-toolkit.WgpuExample",
+                Wgpu {
+                    windows: HashMap::new(),
+                },
+                options,
+            )
+        },
+        |app| {
+            // On exit we must destroy the surfaces before the windows are destroyed.
+            drop(app.handler);
+            drop(app.windows);
+        },
+    );
 }
 
 struct Wgpu {
-    registry_state: RegistryState,
-    seat_state: SeatState,
-    output_state: OutputState,
-
-    exit: bool,
-    width: u32,
-    height: u32,
-    window: Window,
+    /// The GPU state of each window, keyed by [`BaseApp::id`].
+    windows: HashMap<ObjectId, Gpu>,
+}
 
-    adapter: wgpu::Adapter,
+struct Gpu {
     device: wgpu::Device,
     queue: wgpu::Queue,
     surface: wgpu::Surface<'static>,
+    surface_config: wgpu::SurfaceConfiguration,
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
 }
 
-impl CompositorHandler for Wgpu {
-    fn scale_factor_changed(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _new_factor: i32,
-    ) {
-        // Not needed for this example.
-    }
-
-    fn transform_changed(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _new_transform: wl_output::Transform,
-    ) {
-        // Not needed for this example.
-    }
+impl Gpu {
+    fn new(base: &BaseApp) -> Gpu {
+        // Initialize wgpu
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+
+        // Create the raw window handle for the surface.
+        let raw_display_handle = RawDisplayHandle::Wayland(WaylandDisplayHandle::new(
+            NonNull::new(base.connection().backend().display_ptr() as *mut _).unwrap(),
+        ));
+        let raw_window_handle = RawWindowHandle::Wayland(WaylandWindowHandle::new(
+            NonNull::new(base.window().wl_surface().id().as_ptr() as *mut _).unwrap(),
+        ));
+
+        let surface = unsafe {
+            instance
+                .create_surface_unsafe(wgpu::SurfaceTargetUnsafe::RawHandle {
+                    raw_display_handle,
+                    raw_window_handle,
+                })
+                .unwrap()
+        };
 
-    fn frame(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _time: u32,
-    ) {
-    }
+        // Pick a supported adapter
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            compatible_surface: Some(&surface),
+            ..Default::default()
+        }))
+        .expect("Failed to find suitable adapter");
+
+        let (device, queue) = pollster::block_on(adapter.request_device(&DeviceDescriptor {
+            memory_hints: wgpu::MemoryHints::MemoryUsage,
+            ..Default::default()
+        }))
+        .expect("Failed to request device");
 
-    fn surface_enter(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _output: &wl_output::WlOutput,
-    ) {
-        // Not needed for this example.
+        let cap = surface.get_capabilities(&adapter);
+        let (width, height) = base.buffer_size();
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: cap.formats[0],
+            view_formats: vec![cap.formats[0]],
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            width,
+            height,
+            desired_maximum_frame_latency: 2,
+            // Wayland is inherently a mailbox system.
+            present_mode: wgpu::PresentMode::Mailbox,
+        };
+        surface.configure(&device, &surface_config);
+
+        // A small checkerboard, sampled with nearest filtering so the texels stay sharp
+        let texels: Vec<u8> = (0..TEXTURE_SIZE * TEXTURE_SIZE)
+            .flat_map(|i| {
+                let (x, y) = (i % TEXTURE_SIZE, i / TEXTURE_SIZE);
+                if (x + y) % 2 == 0 {
+                    [255, 255, 255, 255]
+                } else {
+                    [40, 40, 40, 255]
+                }
+            })
+            .collect();
+        let texture = device.create_texture_with_data(
+            &queue,
+            &wgpu::TextureDescriptor {
+                label: Some("checkerboard"),
+                size: wgpu::Extent3d {
+                    width: TEXTURE_SIZE,
+                    height: TEXTURE_SIZE,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            &texels,
+        );
+        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: Default::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(surface_config.format.into())],
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        Gpu {
+            device,
+            queue,
+            surface,
+            surface_config,
+            pipeline,
+            bind_group,
+        }
     }
 
-    fn surface_leave(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _output: &wl_output::WlOutput,
-    ) {
-        // Not needed for this example.
+    /// Acquires the next swapchain texture, reconfiguring the surface once if it went stale.
+    fn current_texture(&self) -> Option<wgpu::SurfaceTexture> {
+        match self.surface.get_current_texture() {
+            Ok(texture) => Some(texture),
+            Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+                log::info!("surface outdated, reconfiguring");
+                self.surface.configure(&self.device, &self.surface_config);
+                self.surface.get_current_texture().ok()
+            }
+            Err(err) => {
+                log::warn!("failed to acquire next swapchain texture: {err}");
+                None
+            }
+        }
     }
 }
 
-impl OutputHandler for Wgpu {
-    fn output_state(&mut self) -> &mut OutputState {
-        &mut self.output_state
-    }
-
-    fn new_output(
+impl AppHandler for Wgpu {
+    fn configure(
         &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _output: wl_output::WlOutput,
+        base: &mut BaseApp,
+        _qh: &QueueHandle<App<Self>>,
+        configure: WindowConfigure,
+        _serial: u32,
     ) {
+        let (width, height) = base.configured_size(&configure);
+
+        // Initialize wgpu on the first configure of the window
+        if let Entry::Vacant(entry) = self.windows.entry(base.id()) {
+            base.width = width;
+            base.height = height;
+            entry.insert(Gpu::new(base));
+        } else if base.set_size(width, height) {
+            self.resize(base);
+        }
     }
 
-    fn update_output(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _output: wl_output::WlOutput,
-    ) {
-    }
+    fn resize(&mut self, base: &mut BaseApp) {
+        let Some(gpu) = self.windows.get_mut(&base.id()) else {
+            return;
+        };
 
-    fn output_destroyed(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _output: wl_output::WlOutput,
-    ) {
-    }
-}
+        let (width, height) = base.buffer_size();
+        if width == 0 || height == 0 {
+            return;
+        }
 
-impl WindowHandler for Wgpu {
-    fn request_close(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &Window) {
-        self.exit = true;
+        gpu.surface_config.width = width;
+        gpu.surface_config.height = height;
+        gpu.surface.configure(&gpu.device, &gpu.surface_config);
     }
 
-    fn configure(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _window: &Window,
-        configure: WindowConfigure,
-        _serial: u32,
-    ) {
-        let (new_width, new_height) = configure.new_size;
-        self.width = new_width.map_or(256, |v| v.get());
-        self.height = new_height.map_or(256, |v| v.get());
-
-        let adapter = &self.adapter;
-        let surface = &self.surface;
-        let device = &self.device;
-        let queue = &self.queue;
+    fn draw(&mut self, base: &mut BaseApp) {
+        let gpu = &self.windows[&base.id()];
 
-        let cap = surface.get_capabilities(&adapter);
-        let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: cap.formats[0],
-            view_formats: vec![cap.formats[0]],
-            alpha_mode: wgpu::CompositeAlphaMode::Auto,
-            width: self.width,
-            height: self.height,
-            desired_maximum_frame_latency: 2,
-            // Wayland is inherently a mailbox system.
-            present_mode: wgpu::PresentMode::Mailbox,
+        let Some(surface_texture) = gpu.current_texture() else {
+            // Nothing gets presented, commit anyway so the frame callback request goes out.
+            base.window().wl_surface().commit();
+            return;
         };
-
-        surface.configure(&self.device, &surface_config);
-
-        // We don't plan to render much in this example, just clear the surface.
-        let surface_texture =
-            surface.get_current_texture().expect("failed to acquire next swapchain texture");
         let texture_view =
             surface_texture.texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-        let mut encoder = device.create_command_encoder(&Default::default());
+        let [red, green, blue] = base.clear_color();
+        let mut encoder = gpu.device.create_command_encoder(&Default::default());
         {
-            let _renderpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            let mut renderpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: None,
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                     view: &texture_view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLUE),
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: red as f64,
+                            g: green as f64,
+                            b: blue as f64,
+                            a: 1.0,
+                        }),
                         store: wgpu::StoreOp::Store,
                     },
                     depth_slice: None,
@@ -273,55 +339,21 @@ impl WindowHandler for Wgpu {
                 timestamp_writes: None,
                 occlusion_query_set: None,
             });
+            // Dialogs and `--self-check` show only the clear color
+            if !base.plain() {
+                renderpass.set_pipeline(&gpu.pipeline);
+                renderpass.set_bind_group(0, &gpu.bind_group, &[]);
+                renderpass.draw(0..4, 0..1);
+            }
         }
 
-        // Submit the command in the queue to execute
-        queue.submit(Some(encoder.finish()));
+        // Submit the command in the queue to execute, presenting commits the surface
+        gpu.queue.submit(Some(encoder.finish()));
         surface_texture.present();
     }
-}
-
-impl SeatHandler for Wgpu {
-    fn seat_state(&mut self) -> &mut SeatState {
-        &mut self.seat_state
-    }
-
-    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
-
-    fn new_capability(
-        &mut self,
-        _conn: &Connection,
-        _qh: &QueueHandle<Self>,
-        _seat: wl_seat::WlSeat,
-        _capability: Capability,
-    ) {
-    }
-
-    fn remove_capability(
-        &mut self,
-        _conn: &Connection,
-        _: &QueueHandle<Self>,
-        _: wl_seat::WlSeat,
-        _capability: Capability,
-    ) {
-    }
-
-    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
-}
-
-delegate_compositor!(Wgpu);
-delegate_output!(Wgpu);
-
-delegate_seat!(Wgpu);
-
-delegate_xdg_shell!(Wgpu);
-delegate_xdg_window!(Wgpu);
-
-delegate_registry!(Wgpu);
 
-impl ProvidesRegistryState for Wgpu {
-    fn registry(&mut self) -> &mut RegistryState {
-        &mut self.registry_state
+    fn window_closed(&mut self, base: &mut BaseApp) {
+        // The surface has to go while the window still exists
+        self.windows.remove(&base.id());
     }
-    registry_handlers![OutputState];
 }